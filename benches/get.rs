@@ -0,0 +1,65 @@
+//! Benchmark reading entire stored entries with `get`, which reads each value in a single request,
+//! against `get_partial` with a byte range spanning the value, which validates, coalesces, and splits byte ranges.
+//!
+//! Run with `cargo bench --bench get`.
+#![allow(missing_docs)]
+
+use std::{io::Cursor, io::Write};
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use zip::write::SimpleFileOptions;
+
+use zarrs_storage::{ReadableStorageTraits, StoreKey, byte_range::ByteRange};
+use zarrs_zip::{ZipBytes, ZipStorageAdapter};
+
+const NUM_ENTRIES: usize = 64;
+const ENTRY_SIZES: [usize; 3] = [64, 4096, 262_144];
+
+/// Write a zip file of `NUM_ENTRIES` stored entries of `entry_size` bytes, and open it in memory.
+fn open_zip_bytes(entry_size: usize) -> (ZipStorageAdapter<ZipBytes>, Vec<StoreKey>) {
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    let mut keys = Vec::with_capacity(NUM_ENTRIES);
+    for i in 0..NUM_ENTRIES {
+        let name = format!("c/{i}");
+        zip.start_file(name.as_str(), options).unwrap();
+        #[allow(clippy::cast_possible_truncation)]
+        let data: Vec<u8> = (0..entry_size).map(|j| ((j + i) % 251) as u8).collect();
+        zip.write_all(&data).unwrap();
+        keys.push(StoreKey::new(name).unwrap());
+    }
+    let zip_bytes = zip.finish().unwrap().into_inner();
+    let zip_store = ZipStorageAdapter::from_bytes(zip_bytes).unwrap();
+    (zip_store, keys)
+}
+
+fn bench_get(c: &mut Criterion) {
+    let mut group = c.benchmark_group("get");
+    for entry_size in ENTRY_SIZES {
+        let (zip_store, keys) = open_zip_bytes(entry_size);
+        group.throughput(Throughput::Elements(NUM_ENTRIES as u64));
+        group.bench_with_input(BenchmarkId::new("get", entry_size), &keys, |b, keys| {
+            b.iter(|| {
+                for key in keys {
+                    zip_store.get(key).unwrap();
+                }
+            });
+        });
+        group.bench_with_input(
+            BenchmarkId::new("get_partial", entry_size),
+            &keys,
+            |b, keys| {
+                let byte_range = ByteRange::FromStart(0, Some(entry_size as u64));
+                b.iter(|| {
+                    for key in keys {
+                        zip_store.get_partial(key, byte_range).unwrap();
+                    }
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_get);
+criterion_main!(benches);
@@ -0,0 +1,67 @@
+//! Benchmark reading many deflated entries with `ZipStorageAdapter::get_many` against
+//! calling `get` for each key, across a range of entry sizes.
+//!
+//! Run with `cargo bench --features rayon --bench get_many`.
+#![allow(missing_docs)]
+
+use std::{io::Cursor, io::Write, sync::Arc};
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use zip::write::SimpleFileOptions;
+
+use zarrs_storage::{ReadableStorageTraits, StoreKey, WritableStorageTraits, store::MemoryStore};
+use zarrs_zip::ZipStorageAdapter;
+
+const NUM_ENTRIES: usize = 16;
+const ENTRY_SIZES: [usize; 5] = [1024, 4096, 16384, 65536, 262_144];
+
+/// Write a zip file of `NUM_ENTRIES` deflated entries of `entry_size` bytes into a `MemoryStore`.
+fn open_zip_memory(entry_size: usize) -> (ZipStorageAdapter<MemoryStore>, Vec<StoreKey>) {
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    let mut keys = Vec::with_capacity(NUM_ENTRIES);
+    for i in 0..NUM_ENTRIES {
+        let name = format!("c/{i}");
+        zip.start_file(name.as_str(), options).unwrap();
+        #[allow(clippy::cast_possible_truncation)]
+        let data: Vec<u8> = (0..entry_size)
+            .map(|j| ((j * 31 + i) % 251) as u8 ^ (j / 7) as u8)
+            .collect();
+        zip.write_all(&data).unwrap();
+        keys.push(StoreKey::new(name).unwrap());
+    }
+    let zip_bytes = zip.finish().unwrap().into_inner();
+
+    let memory_store = Arc::new(MemoryStore::default());
+    memory_store
+        .set(&StoreKey::root(), zip_bytes.into())
+        .unwrap();
+    let zip_store = ZipStorageAdapter::new(memory_store, StoreKey::root()).unwrap();
+    (zip_store, keys)
+}
+
+fn bench_get_many(c: &mut Criterion) {
+    let mut group = c.benchmark_group("get_many");
+    for entry_size in ENTRY_SIZES {
+        let (zip_store, keys) = open_zip_memory(entry_size);
+        group.throughput(Throughput::Bytes((entry_size * NUM_ENTRIES) as u64));
+        group.bench_with_input(BenchmarkId::new("get", entry_size), &keys, |b, keys| {
+            b.iter(|| {
+                for key in keys {
+                    zip_store.get(key).unwrap();
+                }
+            });
+        });
+        group.bench_with_input(
+            BenchmarkId::new("get_many", entry_size),
+            &keys,
+            |b, keys| {
+                b.iter(|| zip_store.get_many(keys).unwrap());
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_get_many);
+criterion_main!(benches);
@@ -0,0 +1,51 @@
+//! Benchmark opening a zip file of 1M entries, with and without listing its keys.
+//!
+//! The sorted listing of the entries is built on the first listing, so opening a zip file to read known keys does not sort its entries.
+//!
+//! Run with `cargo bench --bench open`.
+#![allow(missing_docs)]
+
+use std::io::Cursor;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use zip::write::SimpleFileOptions;
+
+use zarrs_storage::{Bytes, ListableStorageTraits, ReadableStorageTraits, StoreKey};
+use zarrs_zip::ZipStorageAdapter;
+
+const NUM_ENTRIES: usize = 1_000_000;
+
+/// Write a zip file of `NUM_ENTRIES` empty stored entries.
+fn write_zip() -> Bytes {
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    for i in 0..NUM_ENTRIES {
+        // Write the entries out of order, as the chunks of an array written in parallel may be
+        zip.start_file(format!("c/{}/{}", i % 1000, i / 1000), options)
+            .unwrap();
+    }
+    zip.finish().unwrap().into_inner().into()
+}
+
+fn bench_open(c: &mut Criterion) {
+    let zip_bytes = write_zip();
+    let key = StoreKey::new("c/999/999").unwrap();
+    let mut group = c.benchmark_group("open");
+    group.sample_size(10);
+    group.bench_function("open_get", |b| {
+        b.iter(|| {
+            let zip_store = ZipStorageAdapter::from_bytes(zip_bytes.clone()).unwrap();
+            zip_store.get(&key).unwrap().unwrap();
+        });
+    });
+    group.bench_function("open_list", |b| {
+        b.iter(|| {
+            let zip_store = ZipStorageAdapter::from_bytes(zip_bytes.clone()).unwrap();
+            zip_store.list().unwrap();
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_open);
+criterion_main!(benches);
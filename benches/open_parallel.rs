@@ -0,0 +1,62 @@
+//! Benchmark opening and listing a zip file of 2M entries, with the index built on a single thread and on the default `rayon` thread pool.
+//!
+//! With the `rayon` feature, the names of the entries of a zip file with many entries are indexed in parallel, and its sorted listing is sorted in parallel.
+//!
+//! Run with `cargo bench --features rayon --bench open_parallel`.
+#![allow(missing_docs)]
+
+use std::io::Cursor;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use zip::write::SimpleFileOptions;
+
+use zarrs_storage::{Bytes, ListableStorageTraits};
+use zarrs_zip::ZipStorageAdapter;
+
+const NUM_ENTRIES: usize = 2_000_000;
+
+/// Write a zip file of `NUM_ENTRIES` empty stored entries.
+fn write_zip() -> Bytes {
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    for i in 0..NUM_ENTRIES {
+        // Write the entries out of order, as the chunks of an array written in parallel may be
+        zip.start_file(
+            format!("c/{}/{}/{}", i % 100, (i / 100) % 100, i / 10000),
+            options,
+        )
+        .unwrap();
+    }
+    zip.finish().unwrap().into_inner().into()
+}
+
+fn bench_open_parallel(c: &mut Criterion) {
+    let zip_bytes = write_zip();
+    let single_thread = rayon::ThreadPoolBuilder::new()
+        .num_threads(1)
+        .build()
+        .unwrap();
+    let mut group = c.benchmark_group("open_parallel");
+    group.sample_size(10);
+    group.bench_function("open_list_single_thread", |b| {
+        b.iter(|| {
+            single_thread.install(|| {
+                let zip_store = ZipStorageAdapter::from_bytes(zip_bytes.clone()).unwrap();
+                zip_store.list().unwrap();
+            });
+        });
+    });
+    group.bench_function(
+        format!("open_list_{}_threads", rayon::current_num_threads()),
+        |b| {
+            b.iter(|| {
+                let zip_store = ZipStorageAdapter::from_bytes(zip_bytes.clone()).unwrap();
+                zip_store.list().unwrap();
+            });
+        },
+    );
+    group.finish();
+}
+
+criterion_group!(benches, bench_open_parallel);
+criterion_main!(benches);
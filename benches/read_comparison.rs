@@ -1,6 +1,6 @@
 //! Benchmark full-array reads across four store configurations, at two chunk sizes:
 //! `FilesystemStore`, `ZipStorageAdapter<FilesystemStore>`, `MemoryStore`, and
-//! `ZipStorageAdapter<MemoryStore>`.
+//! `ZipStorageAdapter<MemoryStore>`, and `ZipStorageAdapter<ZipBytes>` with the `mmap` feature.
 #![allow(missing_docs)]
 
 use std::{
@@ -22,6 +22,8 @@ use zarrs::{
 };
 use zarrs_filesystem::FilesystemStore;
 use zarrs_storage::{StoreKey, WritableStorageTraits, store::MemoryStore};
+#[cfg(feature = "mmap")]
+use zarrs_zip::ZipBytes;
 use zarrs_zip::ZipStorageAdapter;
 
 const ARRAY_SHAPE: [u64; 3] = [512, 512, 512];
@@ -140,6 +142,19 @@ fn open_zip_filesystem(chunk_shape: [u64; 3]) -> Fixture<ZipStorageAdapter<Files
     }
 }
 
+#[cfg(feature = "mmap")]
+fn open_zip_mmap(chunk_shape: [u64; 3]) -> Fixture<ZipStorageAdapter<ZipBytes>> {
+    let (data_dir, path) = populate_filesystem_array(chunk_shape);
+    let (zip_dir, zip_path) = zip_to_disk(&path);
+    // SAFETY: The zip file is not modified while it is mapped.
+    let zip_store = Arc::new(unsafe { ZipStorageAdapter::new_mmap(&zip_path) }.unwrap());
+    let array = Array::open(zip_store, "/").unwrap();
+    Fixture {
+        array,
+        _guards: vec![data_dir, zip_dir],
+    }
+}
+
 fn open_memory(chunk_shape: [u64; 3]) -> Fixture<MemoryStore> {
     let storage = Arc::new(MemoryStore::default());
     let array = ArrayBuilder::new(
@@ -180,6 +195,8 @@ fn open_zip_memory(chunk_shape: [u64; 3]) -> Fixture<ZipStorageAdapter<MemorySto
 fn bench_chunk_size(c: &mut Criterion, group_name: &str, chunk_shape: [u64; 3]) {
     let filesystem = open_filesystem(chunk_shape);
     let zip_filesystem = open_zip_filesystem(chunk_shape);
+    #[cfg(feature = "mmap")]
+    let zip_mmap = open_zip_mmap(chunk_shape);
     let memory = open_memory(chunk_shape);
     let zip_memory = open_zip_memory(chunk_shape);
 
@@ -200,6 +217,13 @@ fn bench_chunk_size(c: &mut Criterion, group_name: &str, chunk_shape: [u64; 3])
                 zip_filesystem.array.retrieve_array_subset(&subset).unwrap();
         });
     });
+    #[cfg(feature = "mmap")]
+    group.bench_function("zip_mmap", |b| {
+        b.iter(|| {
+            let _: zarrs::array::ArrayBytes =
+                zip_mmap.array.retrieve_array_subset(&subset).unwrap();
+        });
+    });
     group.bench_function("memory", |b| {
         b.iter(|| {
             let _: zarrs::array::ArrayBytes = memory.array.retrieve_array_subset(&subset).unwrap();
@@ -1,12 +1,6 @@
 #![allow(missing_docs)]
 
-use std::{
-    fs::File,
-    io::{Read, Write},
-    path::Path,
-    sync::Arc,
-};
-use walkdir::WalkDir;
+use std::sync::Arc;
 
 use zarrs::{
     array::{Array, DataType, ZARR_NAN_F32, codec},
@@ -112,43 +106,19 @@ fn read_array_from_store<TStorage: ReadableStorageTraits + 'static>(
     Ok(())
 }
 
-// https://github.com/zip-rs/zip/blob/master/examples/write_dir.rs
-fn zip_dir<I: Iterator<Item = walkdir::DirEntry>>(
-    it: I,
-    prefix: &str,
-    writer: File,
-    method: zip::CompressionMethod,
-) -> zip::result::ZipResult<()> {
-    let mut zip = zip::ZipWriter::new(writer);
-    let options = zip::write::SimpleFileOptions::default().compression_method(method);
-    let mut buffer = Vec::new();
-    for entry in it {
-        let path = entry.path();
-        let name = path.strip_prefix(Path::new(prefix)).unwrap();
-        if path.is_file() {
-            println!("Storing file {name:?} <- {path:?}");
-            #[allow(deprecated)]
-            zip.start_file_from_path(name, options)?;
-            let mut f = File::open(path)?;
-            f.read_to_end(&mut buffer)?;
-            zip.write_all(&buffer)?;
-            buffer.clear();
-        } else if !name.as_os_str().is_empty() {
-            println!("Storing dir {name:?} <- {path:?}");
-            #[allow(deprecated)]
-            zip.add_directory_from_path(name, options)?;
-        }
-    }
-    zip.finish()?;
-    Result::Ok(())
-}
-
 fn zip_array_write_read() -> Result<(), Box<dyn std::error::Error>> {
-    // Create a store
+    // Create a zip storage adapter for a new zip file
     let path = tempfile::TempDir::new()?;
-    let mut zarr_dir = path.path().to_path_buf();
-    zarr_dir.push("hierarchy.zarr");
-    let mut store: ReadableWritableListableStorage = Arc::new(FilesystemStore::new(&zarr_dir)?);
+    let mut path_zip = path.path().to_path_buf();
+    path_zip.push("zarr_array.zip");
+    let zip_key = StoreKey::new("zarr_array.zip")?;
+    println!(
+        "Create a ZipStorageAdapter for store at {} with {zip_key}",
+        path.path().display()
+    );
+    let fs_store = Arc::new(FilesystemStore::new(path.path())?);
+    let zip_store = Arc::new(ZipStorageAdapter::create(fs_store, zip_key, false)?);
+    let mut store: ReadableWritableListableStorage = zip_store.clone();
     if let Some(arg1) = std::env::args().collect::<Vec<_>>().get(1) {
         if arg1 == "--usage-log" {
             let log_writer = Arc::new(std::sync::Mutex::new(
@@ -162,45 +132,16 @@ fn zip_array_write_read() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
-    // Write the array to the store
+    // Write the array directly into the zip file
     write_array_to_storage(store.clone())?;
+    zip_store.finish()?;
+    println!("Created zip {}\n", path_zip.display());
 
-    // Write the store to zip
-    let mut path_zip = path.path().to_path_buf();
-    path_zip.push("zarr_array.zip");
-    let file = File::create(&path_zip).unwrap();
-    zip_dir(
-        &mut WalkDir::new(&zarr_dir).into_iter().filter_map(|e| e.ok()),
-        zarr_dir.to_str().unwrap(),
-        file,
-        zip::CompressionMethod::Stored,
-    )?;
-    println!("Created zip {path_zip:?} containing {:?}\n", zarr_dir);
-
-    // // Also write to tests/example.zip for async example testing
-    // let example_zip_path = Path::new("tests/example.zip");
-    // let file = File::create(example_zip_path).unwrap();
-    // zip_dir(
-    //     &mut WalkDir::new(&zarr_dir).into_iter().filter_map(|e| e.ok()),
-    //     zarr_dir.to_str().unwrap(),
-    //     file,
-    //     zip::CompressionMethod::Stored,
-    // )?;
-    // println!("Also created {example_zip_path:?}\n");
-
-    let zip_key = StoreKey::new("zarr_array.zip")?;
-    println!(
-        "Create a ZipStorageAdapter for store at {:?} with {}",
-        path.path(),
-        zip_key
-    );
-    let store = Arc::new(FilesystemStore::new(&path.path())?);
-    let store = Arc::new(ZipStorageAdapter::new(store, zip_key)?);
-    let array = Array::open(store.clone(), ARRAY_PATH)?;
+    let array = Array::open(zip_store.clone(), ARRAY_PATH)?;
     read_array_from_store(array)?;
 
     // Show the hierarchy
-    let node = Node::open(&store, "/").unwrap();
+    let node = Node::open(&zip_store, "/").unwrap();
     let tree = node.hierarchy_tree();
     println!("The Zarr hierarchy tree is:\n{}", tree);
 
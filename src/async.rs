@@ -1,20 +1,55 @@
-use std::{collections::HashMap, path::PathBuf, sync::Arc};
+#[cfg(feature = "write")]
+use std::collections::HashMap;
+use std::{ops::Range, path::PathBuf, sync::Arc};
 
-use crate::ZipEntry;
+use futures::{StreamExt, TryStreamExt};
 
-use super::{ZipStorageAdapter, ZipStorageAdapterCreateError};
+#[cfg(any(feature = "lzma", feature = "zstd"))]
+use crate::decoder::EntryDecoder;
+use crate::{
+    index::{IndexEntry, IndexOptions, StoredEntry},
+    preload::PreloadRun,
+    progress::report_read_progress,
+    seek::{SEEK_INITIAL_READ_SIZE, SEEK_MAX_READ_SIZE, SeekIndexes},
+};
+
+#[cfg(feature = "write")]
+use super::RepackSummary;
+use super::{
+    ArchivePrefetch, ArchiveTail, DATA_DESCRIPTOR_MAX_SIZE, END_OF_CENTRAL_DIRECTORY_SEARCH_SIZE,
+    ValidatedByteRanges, ZipParsedArchive, ZipStorageAdapter, ZipStorageAdapterBuilder,
+    ZipStorageAdapterCreateError, blank_comment, has_end_of_central_directory, is_entire_value,
+    missing_end_of_central_directory_error,
+};
 use rc_zip::{
-    Entry, EntryKind,
     fsm::{ArchiveFsm, EntryFsm, FsmResult},
     parse::Method,
 };
 use zarrs_storage::{
     AsyncListableStorageTraits, AsyncMaybeBytesIterator, AsyncReadableStorageTraits, Bytes,
-    StorageError, StoreKey, StorePrefix, StorePrefixes,
-    byte_range::{ByteRange, ByteRangeIterator, InvalidByteRangeError},
+    MaybeBytes, StorageError, StoreKey, StorePrefix,
+    byte_range::{ByteRange, ByteRangeIterator},
 };
+#[cfg(feature = "write")]
+use zarrs_storage::{AsyncWritableStorageTraits, OffsetBytesIterator};
 use zarrs_storage::{StoreKeys, StoreKeysPrefixes};
 
+#[cfg(feature = "write")]
+use crate::{
+    index::{LocalRecord, PendingChanges, ZipIndex},
+    write::{
+        ArchiveWriter, append_archive, apply_partial_writes, central_directory_offset,
+        crc32_data_len, crc32_updates, dead_bytes, pending_changes_error, repack_archive,
+        rewrite_archive, staged_copy_error, temporary_key, zip_error,
+    },
+};
+
+/// The size of the first read of the compressed data of a partially decompressed entry.
+const ASYNC_INITIAL_READ_SIZE: u64 = 64 * 1024;
+
+/// The maximum size of subsequent reads of the compressed data of a partially decompressed entry.
+const ASYNC_MAX_READ_SIZE: u64 = 8 * 1024 * 1024;
+
 impl<TStorage: ?Sized + AsyncReadableStorageTraits> ZipStorageAdapter<TStorage> {
     /// Create a new zip storage adapter asynchronously.
     ///
@@ -30,8 +65,61 @@ impl<TStorage: ?Sized + AsyncReadableStorageTraits> ZipStorageAdapter<TStorage>
         Self::new_with_path_async(storage, key, "").await
     }
 
+    /// Create a new zip storage adapter with a cache of decompressed entries asynchronously.
+    ///
+    /// See [`with_cache`](ZipStorageAdapter::with_cache).
+    ///
+    /// # Errors
+    /// Returns a [`ZipStorageAdapterCreateError`] if the store value at `key` is not a valid zip file.
+    pub async fn new_with_cache_async(
+        storage: Arc<TStorage>,
+        key: StoreKey,
+        cache_bytes: usize,
+    ) -> Result<Self, ZipStorageAdapterCreateError> {
+        ZipStorageAdapterBuilder::new(storage, key)
+            .cache_bytes(cache_bytes)
+            .build_async()
+            .await
+    }
+
+    /// Create a new zip storage adapter that verifies the CRC-32 of stored entries read in full asynchronously.
+    ///
+    /// See [`with_crc32_verification`](ZipStorageAdapter::with_crc32_verification).
+    ///
+    /// # Errors
+    /// Returns a [`ZipStorageAdapterCreateError`] if the store value at `key` is not a valid zip file.
+    pub async fn new_verified_async(
+        storage: Arc<TStorage>,
+        key: StoreKey,
+    ) -> Result<Self, ZipStorageAdapterCreateError> {
+        ZipStorageAdapterBuilder::new(storage, key)
+            .verify_crc(true)
+            .build_async()
+            .await
+    }
+
+    /// Create a new zip storage adapter that decrypts encrypted entries with `password` asynchronously.
+    ///
+    /// See [`with_password`](ZipStorageAdapter::with_password).
+    ///
+    /// # Errors
+    /// Returns a [`ZipStorageAdapterCreateError`] if the store value at `key` is not a valid zip file.
+    #[cfg(feature = "encryption")]
+    pub async fn new_encrypted_async(
+        storage: Arc<TStorage>,
+        key: StoreKey,
+        password: impl Into<Vec<u8>>,
+    ) -> Result<Self, ZipStorageAdapterCreateError> {
+        ZipStorageAdapterBuilder::new(storage, key)
+            .password(password)
+            .build_async()
+            .await
+    }
+
     /// Create a new zip storage adapter to `path` within the zip file asynchronously.
     ///
+    /// `path` is treated as a directory, see [`ZipStorageAdapterBuilder::path`].
+    ///
     /// Pass [`StoreKey::root()`] to treat the entire `storage` as the zip file
     /// (e.g., a `FilesystemStore` rooted directly at a zip file).
     ///
@@ -42,79 +130,154 @@ impl<TStorage: ?Sized + AsyncReadableStorageTraits> ZipStorageAdapter<TStorage>
         key: StoreKey,
         path: T,
     ) -> Result<Self, ZipStorageAdapterCreateError> {
-        let zip_path = path.into();
+        ZipStorageAdapterBuilder::new(storage, key)
+            .path(path)
+            .build_async()
+            .await
+    }
+
+    /// Parse the central directory of the zip file at `key` of `storage` asynchronously, so that adapters rooted at different paths within it can be created without parsing it again.
+    ///
+    /// See [`from_archive`](ZipStorageAdapter::from_archive).
+    ///
+    /// # Errors
+    /// Returns a [`ZipStorageAdapterCreateError`] if the store value at `key` is not a valid zip file.
+    pub async fn parse_async(
+        storage: &Arc<TStorage>,
+        key: &StoreKey,
+    ) -> Result<Arc<ZipParsedArchive>, ZipStorageAdapterCreateError> {
+        Self::parse_with_prefetch_async(storage, key, 0)
+            .await
+            .map(Arc::new)
+    }
 
+    /// Parse the central directory of the zip file at `key` of `storage` asynchronously, reading `eocd_prefetch_bytes` trailing bytes of the zip file in a single request first.
+    async fn parse_with_prefetch_async(
+        storage: &Arc<TStorage>,
+        key: &StoreKey,
+        eocd_prefetch_bytes: u64,
+    ) -> Result<ZipParsedArchive, ZipStorageAdapterCreateError> {
         // Get zip file size
         let size = storage
-            .size_key(&key)
+            .size_key(key)
             .await?
             .ok_or_else(|| StorageError::UnknownKeySize(key.clone()))?;
 
         // Parse the archive using ArchiveFsm
-        let archive = Self::parse_archive_async(&storage, &key, size).await?;
-
-        // Build entries map and sorted entries list
-        let mut entries: HashMap<StoreKey, Entry> = HashMap::new();
-        let mut sorted_entries: Vec<ZipEntry> = Vec::new();
-        for entry in archive.entries() {
-            if let Some(stripped) = Self::strip_zip_path_prefix(&entry.name, &zip_path) {
-                match entry.kind() {
-                    EntryKind::File => {
-                        let store_key = StoreKey::try_from(stripped)?;
-                        entries.insert(store_key.clone(), entry.clone());
-                        sorted_entries.push(ZipEntry::Key(store_key));
-                    }
-                    EntryKind::Directory => {
-                        let store_prefix = StorePrefix::try_from(stripped)?;
-                        sorted_entries.push(ZipEntry::Prefix(store_prefix));
-                    }
-                    EntryKind::Symlink => {
-                        // Ignore symlinks
-                    }
-                }
-            }
-        }
-        sorted_entries.sort_by(|a, b| a.as_str().cmp(b.as_str()));
-
-        Ok(Self {
+        let (archive, comment) =
+            Self::parse_archive_async(storage, key, size, eocd_prefetch_bytes).await?;
+        Ok(ZipParsedArchive {
+            archive,
+            comment,
             size,
+        })
+    }
+
+    /// Open the zip file at `key` of `storage`, rooted at `zip_path` within the zip file.
+    pub(crate) async fn open_async(
+        storage: Arc<TStorage>,
+        key: StoreKey,
+        zip_path: String,
+        index_options: IndexOptions,
+        eocd_prefetch_bytes: u64,
+    ) -> Result<Self, ZipStorageAdapterCreateError> {
+        let archive = Self::parse_with_prefetch_async(&storage, &key, eocd_prefetch_bytes).await?;
+        Self::from_parsed(
             storage,
             key,
-            entries,
-            sorted_entries,
-        })
+            zip_path,
+            &archive,
+            index_options,
+            eocd_prefetch_bytes,
+        )
     }
 
     /// Parse the zip archive using `ArchiveFsm` asynchronously.
+    ///
+    /// Also returns the raw bytes of the archive comment.
     async fn parse_archive_async(
         storage: &Arc<TStorage>,
         key: &StoreKey,
         size: u64,
-    ) -> Result<rc_zip::parse::Archive, ZipStorageAdapterCreateError> {
+        eocd_prefetch_bytes: u64,
+    ) -> Result<(rc_zip::parse::Archive, Vec<u8>), ZipStorageAdapterCreateError> {
         let mut fsm = ArchiveFsm::new(size);
+        let mut tail = ArchiveTail::new(size);
+        // The byte range of the archive comment, if it is blanked as the zip file is parsed again
+        let mut comment = None;
+        // The end of the last byte range requested by the FSM
+        let mut requested_end = 0;
+
+        // Read the trailing bytes of the zip file upfront, so that requests of the FSM within them are served without reading storage
+        let prefetch = if eocd_prefetch_bytes > 0 && size > 0 {
+            let len = eocd_prefetch_bytes.min(size);
+            let bytes = storage
+                .get_partial(key, ByteRange::Suffix(len))
+                .await?
+                .ok_or_else(|| {
+                    ZipStorageAdapterCreateError::ZipError("Cannot read zip data".to_string())
+                })?;
+            Some(ArchivePrefetch {
+                offset: size - len,
+                bytes,
+            })
+        } else {
+            None
+        };
 
         loop {
             // Check if FSM needs more data
             if let Some(offset) = fsm.wants_read() {
                 let space = fsm.space();
+                requested_end = offset + space.len() as u64;
                 // Don't request more than what's left in the file
                 let remaining = size.saturating_sub(offset);
                 let to_read = (space.len() as u64).min(remaining);
 
                 if to_read > 0 {
-                    // Read from storage at the requested offset
-                    let byte_range = ByteRange::FromStart(offset, Some(to_read));
-                    let data = storage.get_partial(key, byte_range).await?.ok_or_else(|| {
-                        ZipStorageAdapterCreateError::ZipError("Cannot read zip data".to_string())
-                    })?;
+                    let read;
+                    let data = if let Some(data) = prefetch
+                        .as_ref()
+                        .and_then(|prefetch| prefetch.get(offset, to_read))
+                    {
+                        data
+                    } else {
+                        // Read from storage at the requested offset
+                        let byte_range = ByteRange::FromStart(offset, Some(to_read));
+                        read = storage.get_partial(key, byte_range).await?.ok_or_else(|| {
+                            ZipStorageAdapterCreateError::ZipError(
+                                "Cannot read zip data".to_string(),
+                            )
+                        })?;
+                        &read[..]
+                    };
 
                     // Copy data into FSM buffer
                     let copy_len = data.len().min(space.len());
                     space[..copy_len].copy_from_slice(&data[..copy_len]);
+                    tail.push(offset, &data[..copy_len]);
+                    blank_comment(&mut space[..copy_len], offset, comment.as_ref());
                     fsm.fill(copy_len);
                 } else {
-                    // No more data to read, signal EOF by filling 0 bytes
-                    fsm.fill(0);
+                    // The FSM wants data past the end of the file, which it would otherwise wait for indefinitely
+                    // An empty value, or one with neither an end of central directory record nor a local file header, is not a zip file rather than truncated
+                    return Err(
+                        match Self::parse_archive_error_async(
+                            storage,
+                            key,
+                            size,
+                            requested_end,
+                            "unexpected end of file",
+                        )
+                        .await
+                        {
+                            err @ ZipStorageAdapterCreateError::NotAZipFile(_) => err,
+                            _ => ZipStorageAdapterCreateError::TruncatedArchive {
+                                expected: requested_end,
+                                available: size,
+                            },
+                        },
+                    );
                 }
             }
 
@@ -124,44 +287,173 @@ impl<TStorage: ?Sized + AsyncReadableStorageTraits> ZipStorageAdapter<TStorage>
                     fsm = next_fsm;
                 }
                 Ok(FsmResult::Done(archive)) => {
-                    return Ok(archive);
+                    return Ok((archive, tail.comment()));
+                }
+                Err(rc_zip::error::Error::Encoding(_))
+                    if comment.is_none() && tail.comment_range().is_some() =>
+                {
+                    // The archive comment may not be text in the encoding of the entry names, parse again with it blanked
+                    comment = tail.comment_range();
+                    fsm = ArchiveFsm::new(size);
+                    tail = ArchiveTail::new(size);
                 }
                 Err(e) => {
-                    return Err(ZipStorageAdapterCreateError::ZipError(e.to_string()));
+                    return Err(Self::parse_archive_error_async(
+                        storage,
+                        key,
+                        size,
+                        requested_end,
+                        e,
+                    )
+                    .await);
                 }
             }
         }
     }
 
+    /// The error of a zip file at `key` of `storage` of `size` bytes that failed to parse with `err`, after requesting bytes up to `requested_end`.
+    ///
+    /// Returns [`ZipStorageAdapterCreateError::NotAZipFile`] if the value has no end of central directory record,
+    /// or [`ZipStorageAdapterCreateError::TruncatedArchive`] if it also starts with a local file header.
+    async fn parse_archive_error_async(
+        storage: &Arc<TStorage>,
+        key: &StoreKey,
+        size: u64,
+        requested_end: u64,
+        err: impl std::fmt::Display,
+    ) -> ZipStorageAdapterCreateError {
+        let window_size = size.min(END_OF_CENTRAL_DIRECTORY_SEARCH_SIZE as u64);
+        let tail = if window_size == 0 {
+            Some(Bytes::new())
+        } else {
+            storage
+                .get_partial(key, ByteRange::Suffix(window_size))
+                .await
+                .ok()
+                .flatten()
+        };
+        match tail {
+            Some(tail) if !has_end_of_central_directory(&tail) => {
+                let head = if size >= 4 {
+                    storage
+                        .get_partial(key, ByteRange::FromStart(0, Some(4)))
+                        .await
+                        .ok()
+                        .flatten()
+                } else {
+                    None
+                };
+                missing_end_of_central_directory_error(key, size, requested_end, head.as_deref())
+            }
+            _ => ZipStorageAdapterCreateError::ZipError(err.to_string()),
+        }
+    }
+
     async fn get_impl_async(
         &self,
         key: &StoreKey,
         byte_ranges: Vec<ByteRange>,
     ) -> Result<AsyncMaybeBytesIterator<'_>, StorageError> {
-        let Some(entry) = self.get_entry(key) else {
+        // Clone the entry so that the index is not locked across await points
+        let Some(entry) = self.index.read().get(key).cloned() else {
             return Ok(None);
         };
 
-        // Validate that all byte ranges are within bounds
-        for range in &byte_ranges {
-            let end = match range {
-                ByteRange::FromStart(start, Some(len)) => start.saturating_add(*len),
-                ByteRange::FromStart(start, None) => *start,
-                ByteRange::Suffix(_) => 0,
+        if is_entire_value(&byte_ranges, entry.size()) {
+            // Fast path: a single byte range spanning the entire value
+            let value = self.get_value_async(key, entry).await?;
+            return Ok(value.map(|value| futures::stream::once(async { Ok(value) }).boxed()));
+        }
+
+        // Validate the byte ranges before reading or decompressing the entry, an out of bounds byte range yields an error in place of its value
+        let byte_ranges = ValidatedByteRanges::new(byte_ranges, entry.size());
+        if byte_ranges.all_valid() {
+            return self.get_entry_async(key, entry, &byte_ranges.valid).await;
+        }
+        let values = if byte_ranges.valid.is_empty() {
+            Some(futures::stream::empty().boxed())
+        } else {
+            self.get_entry_async(key, entry, &byte_ranges.valid).await?
+        };
+        Ok(values.map(|values| byte_ranges.merge_async(values)))
+    }
+
+    /// Read the entire value of `entry`, the value of `key`, asynchronously.
+    ///
+    /// The value of a stored entry is read in a single request of the zip file, without validating, coalescing, and splitting byte ranges.
+    async fn get_value_async(
+        &self,
+        key: &StoreKey,
+        entry: IndexEntry,
+    ) -> Result<MaybeBytes, StorageError> {
+        let byte_ranges = [ByteRange::FromStart(0, None)];
+        let Some(stored) = self.stored_value_entry(key, &entry) else {
+            let Some(mut values) = self.get_entry_async(key, entry, &byte_ranges).await? else {
+                return Ok(None);
             };
-            if end > entry.uncompressed_size {
-                return Err(InvalidByteRangeError::new(*range, entry.uncompressed_size).into());
-            }
+            return values.next().await.transpose();
+        };
+        let data_offset = self
+            .calculate_data_offset_async(stored.header_offset)
+            .await
+            .map_err(|e| StorageError::Other(e.to_string()))?;
+        let value = self
+            .storage
+            .get_partial(
+                &self.key,
+                ByteRange::FromStart(data_offset, Some(stored.uncompressed_size)),
+            )
+            .await?
+            .ok_or_else(|| StorageError::Other("Entry data not found".to_string()))?;
+        if self.verifies_crc32(stored, &byte_ranges) {
+            Self::verify_stored_crc32(key, stored, &byte_ranges, std::slice::from_ref(&value))?;
         }
+        Ok(Some(value))
+    }
 
-        match entry.method {
-            Method::Store => {
-                // Fast path: read directly from storage
-                self.get_stored_entry_async(entry, &byte_ranges).await
+    /// Read `byte_ranges` of `entry`, the value of `key`, which are within its bounds asynchronously.
+    async fn get_entry_async(
+        &self,
+        key: &StoreKey,
+        entry: IndexEntry,
+        byte_ranges: &[ByteRange],
+    ) -> Result<AsyncMaybeBytesIterator<'_>, StorageError> {
+        #[cfg_attr(not(feature = "write"), allow(irrefutable_let_patterns))]
+        if let IndexEntry::Archived(entry) = &entry {
+            if let Some(value) = self.held_value(key, entry) {
+                // Serve the byte ranges from the value read when the adapter was built or prefetched
+                let values: Vec<_> = byte_ranges
+                    .iter()
+                    .map(|range| Ok(value.slice(range.to_range_usize(value.len() as u64))))
+                    .collect();
+                return Ok(Some(Box::pin(futures::stream::iter(values))));
             }
-            _ => {
-                // Decompress the entry using EntryFsm
-                self.get_compressed_entry_async(entry, &byte_ranges).await
+        }
+        match entry {
+            IndexEntry::Archived(entry) => match entry.method {
+                #[cfg(feature = "encryption")]
+                _ if entry.is_encrypted() => {
+                    // Decrypt and decompress the entire entry
+                    self.get_encrypted_entry_async(key, &entry, byte_ranges)
+                        .await
+                }
+                Method::Store => {
+                    // Fast path: read directly from storage
+                    self.get_stored_entry_async(key, &entry, byte_ranges).await
+                }
+                _ => {
+                    // Decompress the entry using EntryFsm
+                    self.get_compressed_entry_async(key, &entry, byte_ranges)
+                        .await
+                }
+            },
+            #[cfg(feature = "write")]
+            IndexEntry::Pending(value, _) => {
+                let results: Vec<_> = byte_ranges
+                    .iter()
+                    .map(|range| Ok(value.slice(range.to_range_usize(value.len() as u64))))
+                    .collect();
+                Ok(Some(Box::pin(futures::stream::iter(results))))
             }
         }
     }
@@ -169,7 +461,8 @@ impl<TStorage: ?Sized + AsyncReadableStorageTraits> ZipStorageAdapter<TStorage>
     /// Fast path for stored (uncompressed) entries asynchronously.
     async fn get_stored_entry_async(
         &self,
-        entry: &Entry,
+        key: &StoreKey,
+        entry: &StoredEntry,
         byte_ranges: &[ByteRange],
     ) -> Result<AsyncMaybeBytesIterator<'_>, StorageError> {
         // Calculate data offset by reading local file header
@@ -178,111 +471,277 @@ impl<TStorage: ?Sized + AsyncReadableStorageTraits> ZipStorageAdapter<TStorage>
             .await
             .map_err(|e| StorageError::Other(e.to_string()))?;
 
-        // Translate relative byte ranges to absolute zip file offsets
-        let translated: Vec<ByteRange> = byte_ranges
+        // Coalesce nearby byte ranges into fewer requests of the zip file, which are issued concurrently
+        let coalesced = self.coalesce_stored_ranges(entry, data_offset, byte_ranges);
+        let Some(values) = self.get_partial_concurrent(coalesced.requests()).await? else {
+            return Ok(None);
+        };
+        let values = coalesced.split(&values)?;
+        if self.verifies_crc32(entry, byte_ranges) {
+            Self::verify_stored_crc32(key, entry, byte_ranges, &values)?;
+        }
+        Ok(Some(Box::pin(futures::stream::iter(
+            values.into_iter().map(Ok),
+        ))))
+    }
+
+    /// Path for encrypted entries asynchronously, which are read, decrypted, and decompressed in full.
+    ///
+    /// The decrypted entry is cached if it fits in the cache.
+    #[cfg(feature = "encryption")]
+    async fn get_encrypted_entry_async(
+        &self,
+        key: &StoreKey,
+        entry: &StoredEntry,
+        byte_ranges: &[ByteRange],
+    ) -> Result<AsyncMaybeBytesIterator<'_>, StorageError> {
+        let cached = self
+            .cache
+            .as_ref()
+            .filter(|cache| cache.fits(entry.uncompressed_size))
+            .and_then(|cache| cache.get(key, entry.header_offset));
+        let value = if let Some(value) = cached {
+            value
+        } else {
+            // Read the local file header with the data, it has the AES extra field and the flags
+            let data_offset = self
+                .calculate_data_offset_async(entry.header_offset)
+                .await
+                .map_err(|e| StorageError::Other(e.to_string()))?;
+            let len = data_offset - entry.header_offset + entry.compressed_size;
+            let local = self
+                .storage
+                .get_partial(
+                    &self.key,
+                    ByteRange::FromStart(entry.header_offset, Some(len)),
+                )
+                .await?
+                .ok_or_else(|| StorageError::Other("Entry data not found".to_string()))?;
+            let value = self.decrypt_entry(key, entry, &local)?;
+            if let Some(cache) = &self.cache {
+                cache.insert(key.clone(), entry.header_offset, value.clone());
+            }
+            value
+        };
+
+        let values: Vec<_> = byte_ranges
             .iter()
-            .map(|range| match range {
-                ByteRange::FromStart(start, len) => {
-                    let actual_len = len.unwrap_or(entry.uncompressed_size.saturating_sub(*start));
-                    ByteRange::FromStart(data_offset + start, Some(actual_len))
-                }
-                ByteRange::Suffix(len) => {
-                    let start = data_offset + entry.uncompressed_size.saturating_sub(*len);
-                    ByteRange::FromStart(start, Some((*len).min(entry.uncompressed_size)))
-                }
-            })
+            .map(|range| Ok(value.slice(range.to_range_usize(entry.uncompressed_size))))
             .collect();
-
-        // Retrieve the bytes
-        Ok(self
-            .storage
-            .get_partial_many(&self.key, Box::new(translated.into_iter()))
-            .await?)
+        Ok(Some(Box::pin(futures::stream::iter(values))))
     }
 
     /// Slower path for compressed entries using `EntryFsm` asynchronously.
-    #[allow(clippy::cast_possible_truncation)]
     async fn get_compressed_entry_async(
         &self,
-        entry: &Entry,
+        key: &StoreKey,
+        entry: &StoredEntry,
         byte_ranges: &[ByteRange],
     ) -> Result<AsyncMaybeBytesIterator<'_>, StorageError> {
-        let decompressed = self.decompress_entry_async(entry).await?;
-
-        let mut results = Vec::with_capacity(byte_ranges.len());
-        for range in byte_ranges {
-            let range = range.to_range_usize(entry.uncompressed_size);
-            results.push(Ok(Bytes::copy_from_slice(&decompressed[range])));
+        if let Some(seek_indexes) = self.seek_indexes(entry) {
+            let values = self
+                .get_seek_indexed_entry_async(key, entry, byte_ranges, seek_indexes)
+                .await?;
+            return Ok(Some(Box::pin(futures::stream::iter(
+                values.into_iter().map(Ok),
+            ))));
         }
 
+        let decompressed = match &self.cache {
+            Some(cache) if cache.fits(entry.uncompressed_size) => {
+                if let Some(decompressed) = cache.get(key, entry.header_offset) {
+                    decompressed
+                } else {
+                    // Decompress the entire entry so that it can be cached
+                    let decompressed = Bytes::from(
+                        self.decompress_entry_async(key, entry, &[ByteRange::FromStart(0, None)])
+                            .await?,
+                    );
+                    cache.insert(key.clone(), entry.header_offset, decompressed.clone());
+                    decompressed
+                }
+            }
+            _ => Bytes::from(self.decompress_entry_async(key, entry, byte_ranges).await?),
+        };
+
+        // The byte ranges share the allocation of the decompressed entry, rather than being copied out of it
+        let results: Vec<_> = byte_ranges
+            .iter()
+            .map(|range| Ok(decompressed.slice(range.to_range_usize(entry.uncompressed_size))))
+            .collect();
+
         Ok(Some(Box::pin(futures::stream::iter(results))))
     }
 
-    /// Decompress an entry using `EntryFsm` asynchronously.
+    /// Decompress the byte ranges of a deflated entry asynchronously, resuming from the last checkpoint of its seek index before them.
+    ///
+    /// Checkpoints are recorded in the seek index as the entry is decompressed.
+    async fn get_seek_indexed_entry_async(
+        &self,
+        key: &StoreKey,
+        entry: &StoredEntry,
+        byte_ranges: &[ByteRange],
+        seek_indexes: &SeekIndexes,
+    ) -> Result<Vec<Bytes>, StorageError> {
+        let span = Self::seek_span(entry, byte_ranges);
+        let data_offset = self
+            .calculate_data_offset_async(entry.header_offset)
+            .await
+            .map_err(|e| StorageError::Other(e.to_string()))?;
+
+        let mut inflater = seek_indexes.inflater(key, entry.header_offset, span.start);
+        let out_start = inflater.out_offset();
+        let mut decompressed = Vec::new();
+        let mut read_size = SEEK_INITIAL_READ_SIZE;
+        let mut bytes_read = 0;
+        while !inflater.is_finished(span.end) {
+            let offset = inflater.in_offset();
+            let len = entry
+                .compressed_size
+                .saturating_sub(offset)
+                .min(read_size)
+                .min(self.read_buffer_size);
+            let input = if len == 0 {
+                Bytes::new()
+            } else {
+                self.storage
+                    .get_partial(
+                        &self.key,
+                        ByteRange::FromStart(data_offset + offset, Some(len)),
+                    )
+                    .await?
+                    .ok_or_else(|| StorageError::Other("Cannot read compressed data".to_string()))?
+            };
+            inflater.inflate(
+                &input,
+                offset + len < entry.compressed_size,
+                &span,
+                &mut decompressed,
+            )?;
+            bytes_read += len;
+            report_read_progress(
+                self.read_progress.as_ref(),
+                key,
+                entry,
+                bytes_read,
+                inflater.out_offset() - out_start,
+            );
+            read_size = (read_size * 2).min(SEEK_MAX_READ_SIZE);
+        }
+
+        let values = Self::seek_indexed_values(key, entry, byte_ranges, &span, decompressed)?;
+        seek_indexes.insert(key, entry.header_offset, inflater);
+        Ok(values)
+    }
+
+    /// Decompress an entry using `EntryFsm` asynchronously, stopping once the end of `byte_ranges` has been decompressed.
+    ///
+    /// Zstandard and LZMA compressed entries are decoded with an `EntryDecoder` instead.
+    /// If the entire entry is required, its compressed data is read in a single request.
+    /// Otherwise, it is read in requests that start at [`ASYNC_INITIAL_READ_SIZE`] bytes, or the minimum read size if larger, and double in size up to [`ASYNC_MAX_READ_SIZE`] bytes,
+    /// so that small byte ranges read little more than they need, and larger byte ranges need few requests on high-latency storage.
     #[allow(clippy::cast_possible_truncation)]
-    async fn decompress_entry_async(&self, entry: &Entry) -> Result<Vec<u8>, StorageError> {
+    async fn decompress_entry_async(
+        &self,
+        key: &StoreKey,
+        entry: &StoredEntry,
+        byte_ranges: &[ByteRange],
+    ) -> Result<Vec<u8>, StorageError> {
+        // Pre-allocate output buffer, the tail of the entry beyond the byte ranges is not needed
+        let expected_size = Self::decompressed_size_required(entry, byte_ranges) as usize;
+
+        #[cfg(any(feature = "lzma", feature = "zstd"))]
+        if let Some(decoder) = EntryDecoder::new(key, entry, expected_size)? {
+            return self
+                .decode_entry_async(key, entry, byte_ranges, decoder)
+                .await;
+        }
+
         // Create EntryFsm with the entry
-        let mut fsm = EntryFsm::new(Some(entry.clone()), None);
+        let mut fsm = EntryFsm::new(Some(entry.to_entry(key.as_str())), None);
 
         // Read position starts at header_offset (EntryFsm will parse local header first)
         let mut read_offset = entry.header_offset;
 
-        // Pre-allocate output buffer
-        let expected_size = entry.uncompressed_size as usize;
         let mut decompressed: Vec<u8> = Vec::with_capacity(expected_size);
         let mut write_offset = 0usize;
 
+        let (end, mut read_size) =
+            if let Some(single_read_end) = self.single_read_end(key, entry, byte_ranges) {
+                // A small entry read in full is read in a single request with its local file header
+                let read_size = single_read_end - entry.header_offset;
+                (self.index.read().size, read_size.max(self.min_read_size))
+            } else {
+                // The end of the local data of the entry, including a data descriptor
+                let data_offset = self
+                    .calculate_data_offset_async(entry.header_offset)
+                    .await
+                    .map_err(|err| StorageError::Other(err.to_string()))?;
+                let end = (data_offset + entry.compressed_size + DATA_DESCRIPTOR_MAX_SIZE)
+                    .min(self.index.read().size);
+                (end, self.incremental_read_size(ASYNC_INITIAL_READ_SIZE))
+            };
+        // Data that has been read but not yet fed to the FSM
+        let mut buffered = Bytes::new();
+
         loop {
+            if write_offset == expected_size && expected_size < entry.uncompressed_size as usize {
+                // Stop decompressing early
+                break;
+            }
+
             // Feed data to FSM if it wants to read
             if fsm.wants_read() {
-                let space = fsm.space();
-                // Don't request more than what's left in the file
-                let remaining = self.size.saturating_sub(read_offset);
-                let to_read = (space.len() as u64).min(remaining);
-
-                if to_read > 0 {
+                if buffered.is_empty() && read_offset < end {
+                    let to_read = read_size.min(end - read_offset);
                     let byte_range = ByteRange::FromStart(read_offset, Some(to_read));
-
-                    let data = self
+                    buffered = self
                         .storage
                         .get_partial(&self.key, byte_range)
                         .await?
                         .ok_or_else(|| {
                             StorageError::Other("Cannot read compressed data".to_string())
                         })?;
-
-                    let copy_len = data.len().min(space.len());
-                    space[..copy_len].copy_from_slice(&data[..copy_len]);
-                    let filled = fsm.fill(copy_len);
-                    read_offset += filled as u64;
-                } else {
-                    // No more data to read, signal EOF
-                    fsm.fill(0);
+                    read_offset += buffered.len() as u64;
+                    read_size = self.incremental_read_size(
+                        read_size.saturating_mul(2).min(ASYNC_MAX_READ_SIZE),
+                    );
                 }
+
+                let space = fsm.space();
+                // Signals EOF if nothing is buffered
+                let copy_len = buffered.len().min(space.len());
+                space[..copy_len].copy_from_slice(&buffered[..copy_len]);
+                let filled = fsm.fill(copy_len);
+                buffered = buffered.slice(filled..);
             }
 
             // Write directly into the spare capacity
             // SAFETY: We pass uninitialized memory to fsm.process, which will write
             // `outcome.bytes_written` bytes, and won't read.
-            let spare = decompressed.spare_capacity_mut();
+            let spare = &mut decompressed.spare_capacity_mut()[write_offset..];
             let out_slice = unsafe {
-                std::slice::from_raw_parts_mut(
-                    spare.as_mut_ptr().cast::<u8>(),
-                    expected_size.saturating_sub(write_offset),
-                )
+                std::slice::from_raw_parts_mut(spare.as_mut_ptr().cast::<u8>(), spare.len())
             };
 
             match fsm.process(out_slice) {
                 Ok(FsmResult::Continue((next_fsm, outcome))) => {
                     write_offset += outcome.bytes_written;
                     fsm = next_fsm;
+                    report_read_progress(
+                        self.read_progress.as_ref(),
+                        key,
+                        entry,
+                        read_offset - entry.header_offset,
+                        write_offset as u64,
+                    );
                 }
                 Ok(FsmResult::Done(_buffer)) => {
                     // Decompression complete
                     break;
                 }
                 Err(e) => {
-                    return Err(StorageError::Other(format!("Decompression error: {e}")));
+                    return Err(Self::decompression_error(key, entry, e));
                 }
             }
         }
@@ -303,11 +762,207 @@ impl<TStorage: ?Sized + AsyncReadableStorageTraits> ZipStorageAdapter<TStorage>
         Ok(decompressed)
     }
 
+    /// Decode `entry`, the value of `key`, with `decoder` asynchronously, stopping once the end of `byte_ranges` has been decoded.
+    ///
+    /// The compressed data is read in requests like those of [`decompress_entry_async`](Self::decompress_entry_async), starting at the data offset of the entry.
+    #[cfg(any(feature = "lzma", feature = "zstd"))]
+    async fn decode_entry_async(
+        &self,
+        key: &StoreKey,
+        entry: &StoredEntry,
+        byte_ranges: &[ByteRange],
+        mut decoder: EntryDecoder,
+    ) -> Result<Vec<u8>, StorageError> {
+        let data_offset = self
+            .calculate_data_offset_async(entry.header_offset)
+            .await
+            .map_err(|err| StorageError::Other(err.to_string()))?;
+        let end = (data_offset + entry.compressed_size).min(self.index.read().size);
+        let mut read_size = if self.single_read_end(key, entry, byte_ranges).is_some() {
+            // A small entry read in full is read in a single request
+            entry.compressed_size.max(self.min_read_size)
+        } else {
+            self.incremental_read_size(ASYNC_INITIAL_READ_SIZE)
+        };
+
+        let mut read_offset = data_offset;
+        while decoder.wants_read() && read_offset < end {
+            let to_read = read_size.min(end - read_offset);
+            let byte_range = ByteRange::FromStart(read_offset, Some(to_read));
+            let data = self
+                .storage
+                .get_partial(&self.key, byte_range)
+                .await?
+                .ok_or_else(|| StorageError::Other("Cannot read compressed data".to_string()))?;
+            if data.is_empty() {
+                break;
+            }
+            read_offset += data.len() as u64;
+            read_size =
+                self.incremental_read_size(read_size.saturating_mul(2).min(ASYNC_MAX_READ_SIZE));
+            decoder.decode(key, &data)?;
+            report_read_progress(
+                self.read_progress.as_ref(),
+                key,
+                entry,
+                read_offset - entry.header_offset,
+                decoder.decoded_len(),
+            );
+        }
+
+        Self::finish_decoding(key, entry, decoder)
+    }
+
+    /// Read the local file headers of all entries asynchronously, coalescing nearby headers into larger requests, and memoize their data offsets.
+    ///
+    /// See [`ZipStorageAdapterBuilder::prefetch_headers`](crate::ZipStorageAdapterBuilder::prefetch_headers).
+    ///
+    /// # Errors
+    /// Returns a [`ZipStorageAdapterCreateError`] if the headers cannot be read or are invalid.
+    pub(crate) async fn prefetch_headers_async(&self) -> Result<(), ZipStorageAdapterCreateError> {
+        let runs = self.header_prefetch_runs(self.index.read().archived_header_offsets());
+        self.read_header_runs_async(&runs).await
+    }
+
+    /// Read the local file headers in `runs` asynchronously, one request per run, and memoize their data offsets.
+    ///
+    /// # Errors
+    /// Returns a [`ZipStorageAdapterCreateError`] if the headers cannot be read or are invalid.
+    async fn read_header_runs_async(
+        &self,
+        runs: &[(Range<u64>, Vec<u64>)],
+    ) -> Result<(), ZipStorageAdapterCreateError> {
+        if runs.is_empty() {
+            return Ok(());
+        }
+        let byte_ranges = runs
+            .iter()
+            .map(|(range, _)| ByteRange::FromStart(range.start, Some(range.end - range.start)))
+            .collect();
+        let values = self
+            .get_partial_concurrent(byte_ranges)
+            .await?
+            .ok_or_else(|| {
+                ZipStorageAdapterCreateError::ZipError("Cannot read local file headers".to_string())
+            })?;
+        for ((range, header_offsets), bytes) in runs.iter().zip(&values) {
+            self.data_offsets_from_headers(range.start, bytes, header_offsets)?;
+        }
+        Ok(())
+    }
+
+    /// Read the values of all entries of at most `max_size` bytes asynchronously, coalescing nearby entries into larger requests, and hold them for later reads.
+    ///
+    /// See [`ZipStorageAdapterBuilder::preload_small_entries`](crate::ZipStorageAdapterBuilder::preload_small_entries).
+    ///
+    /// # Errors
+    /// Returns a [`ZipStorageAdapterCreateError`] if the entries cannot be read or decompressed.
+    pub(crate) async fn preload_small_entries_async(
+        &mut self,
+        max_size: u64,
+    ) -> Result<(), ZipStorageAdapterCreateError> {
+        let runs = self.preload_runs(max_size);
+        self.preload_async(&runs, true).await
+    }
+
+    /// Read the values of all entries asynchronously, coalescing nearby entries into larger requests, and hold them for later reads.
+    ///
+    /// See [`ZipStorageAdapterBuilder::preload_all`](crate::ZipStorageAdapterBuilder::preload_all).
+    ///
+    /// # Errors
+    /// Returns a [`ZipStorageAdapterCreateError`] if the total size of the values exceeds `max_total_size` bytes, or the entries cannot be read or decompressed.
+    pub(crate) async fn preload_all_async(
+        &mut self,
+        max_total_size: u64,
+    ) -> Result<(), ZipStorageAdapterCreateError> {
+        let runs = self.preload_all_runs(max_total_size)?;
+        self.preload_async(&runs, false).await
+    }
+
+    /// Read the entries of `runs` asynchronously, a request per run, and hold their values for later reads.
+    ///
+    /// # Errors
+    /// Returns a [`ZipStorageAdapterCreateError`] if the entries cannot be read or decompressed.
+    async fn preload_async(
+        &mut self,
+        runs: &[PreloadRun],
+        within_cache: bool,
+    ) -> Result<(), ZipStorageAdapterCreateError> {
+        if runs.is_empty() {
+            return Ok(());
+        }
+        let byte_ranges = runs
+            .iter()
+            .map(|(range, _)| ByteRange::FromStart(range.start, Some(range.end - range.start)))
+            .collect();
+        let values = self
+            .get_partial_concurrent(byte_ranges)
+            .await?
+            .ok_or_else(|| {
+                ZipStorageAdapterCreateError::ZipError("Cannot read preloaded entries".to_string())
+            })?;
+        for ((range, entries), bytes) in runs.iter().zip(&values) {
+            self.preload_run(range.start, bytes, entries, within_cache)?;
+        }
+        Ok(())
+    }
+
+    /// Read the values of `keys` asynchronously ahead of access, such as the chunks that a viewer will read next.
+    ///
+    /// See [`prefetch`](ZipStorageAdapter::prefetch).
+    /// The requests of runs of nearby entries are issued concurrently, see [`with_concurrency`](ZipStorageAdapter::with_concurrency).
+    ///
+    /// # Errors
+    /// Returns a [`StorageError`] if the entries cannot be read or decompressed.
+    pub async fn prefetch_async(&self, keys: &[StoreKey]) -> Result<(), StorageError> {
+        let (runs, header_runs) = self.prefetch_runs(keys);
+        self.read_header_runs_async(&header_runs)
+            .await
+            .map_err(|err| StorageError::Other(err.to_string()))?;
+        if runs.is_empty() {
+            return Ok(());
+        }
+        let byte_ranges = runs
+            .iter()
+            .map(|(range, _)| ByteRange::FromStart(range.start, Some(range.end - range.start)))
+            .collect();
+        let values = self
+            .get_partial_concurrent(byte_ranges)
+            .await?
+            .ok_or_else(|| StorageError::Other("Cannot read prefetched entries".to_string()))?;
+        for ((range, entries), bytes) in runs.iter().zip(&values) {
+            self.prefetch_run(range.start, bytes, entries)?;
+        }
+        Ok(())
+    }
+
+    /// Read `byte_ranges` of the zip file asynchronously, with at most [`concurrency`](ZipStorageAdapter::with_concurrency) requests in flight.
+    ///
+    /// The values are in the order of `byte_ranges`.
+    /// Returns [`None`] if the zip file does not exist.
+    async fn get_partial_concurrent(
+        &self,
+        byte_ranges: Vec<ByteRange>,
+    ) -> Result<Option<Vec<Bytes>>, StorageError> {
+        let values: Vec<Option<Bytes>> = futures::stream::iter(byte_ranges)
+            .map(|byte_range| self.storage.get_partial(&self.key, byte_range))
+            .buffered(self.concurrency)
+            .try_collect()
+            .await?;
+        Ok(values.into_iter().collect())
+    }
+
     /// Calculate the data offset by reading the local file header asynchronously.
+    ///
+    /// The header of each entry is read at most once, subsequent calls return the memoized data offset.
     async fn calculate_data_offset_async(
         &self,
         header_offset: u64,
     ) -> Result<u64, ZipStorageAdapterCreateError> {
+        if let Some(data_offset) = self.cached_data_offset(header_offset) {
+            return Ok(data_offset);
+        }
+
         // Read 30-byte local file header
         let byte_range = ByteRange::FromStart(header_offset, Some(30));
         let header = self
@@ -318,19 +973,7 @@ impl<TStorage: ?Sized + AsyncReadableStorageTraits> ZipStorageAdapter<TStorage>
                 ZipStorageAdapterCreateError::ZipError("Cannot read local file header".to_string())
             })?;
 
-        if header.len() < 30 {
-            return Err(ZipStorageAdapterCreateError::ZipError(
-                "Local file header too short".to_string(),
-            ));
-        }
-
-        // Local file header structure:
-        // Offset 26: filename length (2 bytes, little-endian)
-        // Offset 28: extra field length (2 bytes, little-endian)
-        let filename_len = u64::from(u16::from_le_bytes([header[26], header[27]]));
-        let extra_len = u64::from(u16::from_le_bytes([header[28], header[29]]));
-
-        Ok(header_offset + 30 + filename_len + extra_len)
+        self.data_offset_from_header(header_offset, &header)
     }
 }
 
@@ -338,34 +981,25 @@ impl<TStorage: ?Sized + AsyncReadableStorageTraits> ZipStorageAdapter<TStorage>
 impl<TStorage: ?Sized + AsyncReadableStorageTraits> AsyncReadableStorageTraits
     for ZipStorageAdapter<TStorage>
 {
+    async fn get(&self, key: &StoreKey) -> Result<MaybeBytes, StorageError> {
+        // Clone the entry so that the index is not locked across await points
+        let Some(entry) = self.index.read().get(key).cloned() else {
+            return Ok(None);
+        };
+        self.get_value_async(key, entry).await
+    }
+
     async fn get_partial_many<'a>(
         &'a self,
         key: &StoreKey,
         byte_ranges: ByteRangeIterator<'a>,
     ) -> Result<AsyncMaybeBytesIterator<'a>, StorageError> {
         let byte_ranges: Vec<ByteRange> = byte_ranges.collect();
-
-        let Some(entry) = self.get_entry(key) else {
-            return Ok(None);
-        };
-
-        // Validate that all byte ranges are within bounds
-        for range in &byte_ranges {
-            let end = match range {
-                ByteRange::FromStart(start, Some(len)) => start.saturating_add(*len),
-                ByteRange::FromStart(start, None) => *start,
-                ByteRange::Suffix(_) => 0,
-            };
-            if end > entry.uncompressed_size {
-                return Err(InvalidByteRangeError::new(*range, entry.uncompressed_size).into());
-            }
-        }
-
         self.get_impl_async(key, byte_ranges).await
     }
 
     async fn size_key(&self, key: &StoreKey) -> Result<Option<u64>, StorageError> {
-        Ok(self.get_entry(key).map(|e| e.uncompressed_size))
+        Ok(self.index.read().get(key).map(IndexEntry::size))
     }
 
     fn supports_get_partial(&self) -> bool {
@@ -378,83 +1012,385 @@ impl<TStorage: ?Sized + AsyncReadableStorageTraits> AsyncListableStorageTraits
     for ZipStorageAdapter<TStorage>
 {
     async fn list(&self) -> Result<StoreKeys, StorageError> {
-        // Filter to only keys, already sorted
-        Ok(self
-            .sorted_entries
-            .iter()
-            .filter_map(|e| match e {
-                ZipEntry::Key(k) => Some(k.clone()),
-                ZipEntry::Prefix(_) => None,
-            })
-            .collect())
+        Ok(self.index.read().list())
     }
 
     async fn list_prefix(&self, prefix: &StorePrefix) -> Result<StoreKeys, StorageError> {
-        // Use binary search to find matching range, filter to keys only
-        Ok(self
-            .entries_with_prefix(prefix)
-            .iter()
-            .filter_map(|e| match e {
-                ZipEntry::Key(k) => Some(k.clone()),
-                ZipEntry::Prefix(_) => None,
-            })
-            .collect())
+        Ok(self.index.read().list_prefix(prefix))
     }
 
     async fn list_dir(&self, prefix: &StorePrefix) -> Result<StoreKeysPrefixes, StorageError> {
-        let mut keys: StoreKeys = vec![];
-        let mut prefixes: StorePrefixes = vec![];
-
-        // Use binary search to find matching range
-        for entry in self.entries_with_prefix(prefix) {
-            match entry {
-                ZipEntry::Key(key) => {
-                    let parent = key.parent();
-                    if &parent == prefix {
-                        keys.push(key.clone());
-                    } else if let Some(child_prefix) = Self::immediate_child_prefix(key, prefix) {
-                        if prefixes.last() != Some(&child_prefix) {
-                            prefixes.push(child_prefix);
-                        }
-                    }
-                }
-                ZipEntry::Prefix(p) => {
-                    // Check if this prefix is an immediate child of the search prefix
-                    let p_str = p.as_str();
-                    let prefix_str = prefix.as_str();
-                    if let Some(suffix) = p_str.strip_prefix(prefix_str) {
-                        // Skip if suffix is empty (the prefix itself)
-                        if suffix.is_empty() {
-                            continue;
-                        }
-                        // Check if it's an immediate child (no additional '/' before the trailing one)
-                        let trimmed = suffix.trim_end_matches('/');
-                        if !trimmed.contains('/') && prefixes.last() != Some(&p) {
-                            prefixes.push(p.clone());
-                        }
-                    }
-                }
+        Ok(self.index.read().list_dir(prefix))
+    }
+
+    async fn size(&self) -> Result<u64, StorageError> {
+        Ok(self.index.read().size)
+    }
+
+    async fn size_prefix(&self, prefix: &StorePrefix) -> Result<u64, StorageError> {
+        Ok(self.index.read().size_prefix(prefix))
+    }
+}
+
+#[cfg(feature = "write")]
+impl<TStorage: ?Sized + AsyncReadableStorageTraits + AsyncWritableStorageTraits>
+    ZipStorageAdapter<TStorage>
+{
+    /// Create a new zip storage adapter for an empty zip file asynchronously.
+    ///
+    /// An empty zip file is written to `key` in `storage`.
+    /// Pass [`StoreKey::root()`] to treat the entire `storage` as the zip file.
+    ///
+    /// # Errors
+    /// Returns a [`ZipStorageAdapterCreateError::ExistingKey`] if `key` already exists in `storage` and `overwrite` is false.
+    /// Returns a [`ZipStorageAdapterCreateError`] if the empty zip file cannot be written.
+    pub async fn create_async(
+        storage: Arc<TStorage>,
+        key: StoreKey,
+        overwrite: bool,
+    ) -> Result<Self, ZipStorageAdapterCreateError> {
+        if !overwrite && storage.size_key(&key).await?.is_some() {
+            return Err(ZipStorageAdapterCreateError::ExistingKey(key));
+        }
+        let archive = ArchiveWriter::new().finish();
+        storage.set(&key, archive.into()).await?;
+        Self::new_async(storage, key).await
+    }
+
+    /// Create a new zip storage adapter that appends to an existing zip file asynchronously.
+    ///
+    /// See [Appending](ZipStorageAdapter#appending).
+    ///
+    /// # Errors
+    /// Returns a [`ZipStorageAdapterCreateError`] if the store value at `key` is not a valid zip file.
+    pub async fn open_append_async(
+        storage: Arc<TStorage>,
+        key: StoreKey,
+    ) -> Result<Self, ZipStorageAdapterCreateError> {
+        let mut adapter = Self::new_async(storage, key).await?;
+        adapter.append = true;
+        Ok(adapter)
+    }
+
+    /// Write pending changes to the zip file asynchronously.
+    ///
+    /// This rewrites the entire zip file, see [Writing](ZipStorageAdapter#writing), unless the adapter was opened with [`open_append_async`](ZipStorageAdapter::open_append_async).
+    /// The CRC-32s of stored entries that have been partially written in place are also updated, see [Partial writes](ZipStorageAdapter#partial-writes).
+    /// This is a no-op if there are no pending changes.
+    ///
+    /// Unlike [`finish`](ZipStorageAdapter::finish), the index is not locked while the zip file is rewritten.
+    /// Writes made while this is in progress remain pending and are written by the next call.
+    ///
+    /// Pending values are compressed on the calling task, blocking the async executor, unless a blocking spawner is set with [`with_blocking_spawner`](ZipStorageAdapter::with_blocking_spawner).
+    ///
+    /// # Errors
+    /// Returns a [`StorageError`] if the zip file cannot be read, rewritten, or written to the underlying storage.
+    pub async fn finish_async(&self) -> Result<(), StorageError> {
+        let _finish_guard = self.finish_lock.lock().await;
+
+        let (size, modified, changes) = {
+            let index = self.index.read();
+            if !index.has_changes() {
+                return Ok(());
             }
+            (
+                index.size,
+                index.modified,
+                Arc::new(index.pending_changes(self.write_options.explicit_directories)),
+            )
+        };
+
+        self.update_crc32s_async(size, &changes.stale_crc32s)
+            .await?;
+        let size = if !modified {
+            size
+        } else if self.append && self.storage.supports_set_partial() {
+            self.append_changes_async(size, &changes).await?
+        } else {
+            self.rewrite_changes_async(&changes).await?
+        };
+
+        // Rebuild the index from the new central directory
+        let (archive, comment) =
+            Self::parse_archive_async(&self.storage, &self.key, size, self.eocd_prefetch_bytes)
+                .await
+                .map_err(|err| StorageError::Other(err.to_string()))?;
+        let mut new_index =
+            ZipIndex::new(&archive, comment, size, &self.zip_path, self.index_options)
+                .map_err(|err| StorageError::Other(err.to_string()))?;
+
+        // Carry over writes made since the changes were snapshotted
+        let mut index = self.index.write();
+        new_index.reapply_changes(&index, &changes);
+        *index = new_index;
+        self.retain_data_offsets(&index);
+        Ok(())
+    }
+
+    /// Find the offset of the central directory of the zip file of `size` bytes asynchronously.
+    ///
+    /// Also returns the trailing bytes of the zip file that were read to find it, and their offset.
+    async fn find_central_directory_async(
+        &self,
+        size: u64,
+    ) -> Result<(u64, Bytes, u64), StorageError> {
+        let window_size = size.min(END_OF_CENTRAL_DIRECTORY_SEARCH_SIZE as u64);
+        let window_offset = size - window_size;
+        let window = self
+            .storage
+            .get_partial(&self.key, ByteRange::Suffix(window_size))
+            .await?
+            .ok_or_else(|| self.zip_not_found())?;
+        let directory_offset = central_directory_offset(&window, window_offset)?;
+        Ok((directory_offset, window, window_offset))
+    }
+
+    /// The number of bytes in the zip file that are not referenced by its central directory asynchronously.
+    ///
+    /// These are the local data of erased and overwritten entries, which is not reclaimed when the zip file is written, see [Writing](ZipStorageAdapter#writing).
+    /// Entries that have been erased or overwritten but not yet finished are counted, as are entries shadowed by a later entry with the same name.
+    /// The local file header of each entry is read the first time this is called, and data descriptors are assumed to have a signature and 32-bit sizes.
+    ///
+    /// # Errors
+    /// Returns a [`StorageError`] if the zip file cannot be read.
+    pub async fn dead_bytes_async(&self) -> Result<u64, StorageError> {
+        let (size, live) = {
+            let index = self.index.read();
+            (index.size, index.live_records())
+        };
+        let (directory_offset, _, _) = self.find_central_directory_async(size).await?;
+        let mut data_offsets = HashMap::with_capacity(live.len());
+        for record in &live {
+            let data_offset = self
+                .calculate_data_offset_async(record.header_offset)
+                .await
+                .map_err(|err| StorageError::Other(err.to_string()))?;
+            data_offsets.insert(record.header_offset, data_offset);
         }
+        dead_bytes(size, directory_offset, &live, |header_offset| {
+            Ok(data_offsets[&header_offset])
+        })
+    }
 
-        // Keys and prefixes are already sorted since sorted_entries is sorted
-        Ok(StoreKeysPrefixes::new(keys, prefixes))
+    /// Repack the zip file to `dst_key` of `dst_storage` asynchronously, without the unreferenced bytes of erased and overwritten entries.
+    ///
+    /// See [`repack`](ZipStorageAdapter::repack).
+    ///
+    /// # Errors
+    /// Returns a [`StorageError`] if there are pending changes, or the zip file cannot be read or the repacked zip file cannot be written.
+    pub async fn repack_async<TDst: ?Sized + AsyncWritableStorageTraits>(
+        &self,
+        dst_storage: &TDst,
+        dst_key: &StoreKey,
+    ) -> Result<RepackSummary, StorageError> {
+        if self.index.read().has_changes() {
+            return Err(pending_changes_error());
+        }
+        let archive = self
+            .storage
+            .get(&self.key)
+            .await?
+            .ok_or_else(|| self.zip_not_found())?;
+        let (repacked, summary) = repack_archive(&archive)?;
+        set_staged_async(dst_storage, dst_key, repacked.into(), true).await?;
+        Ok(summary)
     }
 
-    async fn size(&self) -> Result<u64, StorageError> {
-        Ok(self.size)
+    /// Run `task` with the [blocking spawner](ZipStorageAdapter::with_blocking_spawner) of the adapter, or on the current task if there is none.
+    ///
+    /// # Errors
+    /// Returns a [`StorageError`] if the blocking spawner drops `task` without running it.
+    async fn run_blocking<T: Send + 'static>(
+        &self,
+        task: impl FnOnce() -> T + Send + 'static,
+    ) -> Result<T, StorageError> {
+        let Some(blocking_spawner) = &self.blocking_spawner else {
+            return Ok(task());
+        };
+        let (sender, receiver) = futures::channel::oneshot::channel();
+        blocking_spawner(Box::new(move || {
+            // The receiver is only dropped if `finish_async` is cancelled
+            sender.send(task()).ok();
+        }));
+        receiver
+            .await
+            .map_err(|_| zip_error("the blocking task was dropped before it ran"))
     }
 
-    async fn size_prefix(&self, prefix: &StorePrefix) -> Result<u64, StorageError> {
-        // Use binary search to find matching range, then lookup in HashMap for keys only
-        Ok(self
-            .entries_with_prefix(prefix)
-            .iter()
-            .filter_map(|e| match e {
-                ZipEntry::Key(k) => self.entries.get(&k),
-                ZipEntry::Prefix(_) => None,
+    /// Rewrite the entire zip file with `changes` asynchronously, returning its new size.
+    async fn rewrite_changes_async(
+        &self,
+        changes: &Arc<PendingChanges>,
+    ) -> Result<u64, StorageError> {
+        let archive = self
+            .storage
+            .get(&self.key)
+            .await?
+            .ok_or_else(|| self.zip_not_found())?;
+        let zip_path = self.zip_path.clone();
+        let changes = changes.clone();
+        let archive = self
+            .run_blocking(move || rewrite_archive(&archive, &zip_path, &changes))
+            .await??;
+        let size = archive.len() as u64;
+        set_staged_async(
+            &*self.storage,
+            &self.key,
+            archive.into(),
+            self.write_options.staged,
+        )
+        .await?;
+        Ok(size)
+    }
+
+    /// Read the trailing bytes of the zip file of `size` bytes from its central directory asynchronously, returning their offset.
+    async fn central_directory_tail_async(&self, size: u64) -> Result<(u64, Bytes), StorageError> {
+        let (directory_offset, window, window_offset) =
+            self.find_central_directory_async(size).await?;
+        let tail = if let Some(start) = directory_offset.checked_sub(window_offset) {
+            window.slice(
+                usize::try_from(start)
+                    .unwrap_or(usize::MAX)
+                    .min(window.len())..,
+            )
+        } else {
+            self.storage
+                .get_partial(&self.key, ByteRange::FromStart(directory_offset, None))
+                .await?
+                .ok_or_else(|| self.zip_not_found())?
+        };
+        Ok((directory_offset, tail))
+    }
+
+    /// Update the CRC-32s of the `stale` stored entries of the zip file of `size` bytes asynchronously, which have been partially written in place.
+    async fn update_crc32s_async(
+        &self,
+        size: u64,
+        stale: &[LocalRecord],
+    ) -> Result<(), StorageError> {
+        if stale.is_empty() {
+            return Ok(());
+        }
+        let (directory_offset, tail) = self.central_directory_tail_async(size).await?;
+        let mut entries = Vec::with_capacity(stale.len());
+        for record in stale {
+            let data_offset = self
+                .calculate_data_offset_async(record.header_offset)
+                .await
+                .map_err(|err| StorageError::Other(err.to_string()))?;
+            let byte_range = ByteRange::FromStart(data_offset, Some(crc32_data_len(record)));
+            let data = self
+                .storage
+                .get_partial(&self.key, byte_range)
+                .await?
+                .ok_or_else(|| self.zip_not_found())?;
+            entries.push((*record, data_offset, data));
+        }
+        for (offset, crc32) in crc32_updates(&tail, directory_offset, &entries)? {
+            self.storage.set_partial(&self.key, offset, crc32).await?;
+        }
+        Ok(())
+    }
+
+    /// Append `changes` to the zip file of `size` bytes in place asynchronously, returning its new size.
+    async fn append_changes_async(
+        &self,
+        size: u64,
+        changes: &Arc<PendingChanges>,
+    ) -> Result<u64, StorageError> {
+        let (directory_offset, tail) = self.central_directory_tail_async(size).await?;
+        let zip_path = self.zip_path.clone();
+        let changes = changes.clone();
+        let (offset, bytes) = self
+            .run_blocking(move || {
+                append_archive(&tail, directory_offset, size, &zip_path, &changes)
             })
-            .map(|e| e.compressed_size)
-            .sum())
+            .await??;
+        let appended = bytes.len() as u64;
+        self.storage
+            .set_partial(&self.key, offset, bytes.into())
+            .await?;
+        Ok(offset + appended)
+    }
+}
+
+#[cfg(feature = "write")]
+#[async_trait::async_trait]
+impl<TStorage: ?Sized + AsyncReadableStorageTraits + AsyncWritableStorageTraits>
+    AsyncWritableStorageTraits for ZipStorageAdapter<TStorage>
+{
+    async fn set(&self, key: &StoreKey, value: Bytes) -> Result<(), StorageError> {
+        self.set_impl(key, value, &self.write_options)
+    }
+
+    async fn set_partial_many<'a>(
+        &'a self,
+        key: &StoreKey,
+        offset_values: OffsetBytesIterator<'a>,
+    ) -> Result<(), StorageError> {
+        let offset_values: Vec<(u64, Bytes)> = offset_values.collect();
+        let Some(header_offset) =
+            self.in_place_header_offset(key, &offset_values, self.storage.supports_set_partial())?
+        else {
+            // Read-modify-write, the new value is pending until the zip file is finished
+            let value = apply_partial_writes(self.get(key).await?, offset_values)?;
+            return self.set_impl(key, value, &self.write_options);
+        };
+
+        let data_offset = self
+            .calculate_data_offset_async(header_offset)
+            .await
+            .map_err(|err| StorageError::Other(err.to_string()))?;
+        for (offset, value) in offset_values {
+            self.storage
+                .set_partial(&self.key, data_offset + offset, value)
+                .await?;
+        }
+        self.written_in_place(key, header_offset);
+        Ok(())
+    }
+
+    async fn erase(&self, key: &StoreKey) -> Result<(), StorageError> {
+        self.erase_impl(key);
+        Ok(())
+    }
+
+    async fn erase_prefix(&self, prefix: &StorePrefix) -> Result<(), StorageError> {
+        self.erase_prefix_impl(prefix);
+        Ok(())
+    }
+
+    fn supports_set_partial(&self) -> bool {
+        self.storage.supports_set_partial()
+    }
+}
+
+/// Set `key` of `storage` to `value` asynchronously, staged under a temporary key first if `staged`.
+///
+/// See [`ZipWriteOptions::staged`](crate::ZipWriteOptions::staged).
+///
+/// # Errors
+/// Returns a [`StorageError`] if the temporary key or `key` cannot be written, or the temporary key cannot be erased.
+/// If `key` cannot be written, the temporary key is kept and named in the error.
+#[cfg(feature = "write")]
+async fn set_staged_async<TStorage: ?Sized + AsyncWritableStorageTraits>(
+    storage: &TStorage,
+    key: &StoreKey,
+    value: Bytes,
+    staged: bool,
+) -> Result<(), StorageError> {
+    let Some(temporary_key) = temporary_key(key).filter(|_| staged) else {
+        return storage.set(key, value).await;
+    };
+    if let Err(err) = storage.set(&temporary_key, value.clone()).await {
+        // The temporary key may be partially written, the error writing it takes precedence over an error erasing it
+        storage.erase(&temporary_key).await.ok();
+        return Err(err);
     }
+    // The staged zip file is complete, so it is kept if it cannot be written to `key`
+    storage
+        .set(key, value)
+        .await
+        .map_err(|err| staged_copy_error(key, &temporary_key, &err))?;
+    storage.erase(&temporary_key).await
 }
@@ -0,0 +1,317 @@
+//! Reading of values in blocks, without holding the entire value in memory.
+
+use rc_zip::{
+    fsm::{EntryFsm, FsmResult},
+    parse::Method,
+};
+use zarrs_storage::{Bytes, ReadableStorageTraits, StorageError, StoreKey, byte_range::ByteRange};
+
+use crate::{
+    ZipStorageAdapter,
+    index::{IndexEntry, StoredEntry},
+};
+
+/// An iterator over the value of a key in blocks, returned by [`ZipStorageAdapter::get_blocks`].
+///
+/// Blocks are read and decompressed as the iterator is advanced.
+/// The iterator ends after the last block or the first error.
+pub struct ZipEntryBlocks<'a, TStorage: ?Sized> {
+    /// The adapter that the value is read from.
+    adapter: &'a ZipStorageAdapter<TStorage>,
+    /// The key of the value.
+    key: StoreKey,
+    /// The size of each block, other than the last.
+    block_size: usize,
+    /// The state of the iterator.
+    state: BlocksState,
+}
+
+/// The state of a [`ZipEntryBlocks`].
+enum BlocksState {
+    /// A value that is already in memory, sliced into blocks from `offset`.
+    InMemory { value: Bytes, offset: usize },
+    /// A stored entry with its data at `data_offset` of the zip file, read in blocks from `offset` of the value.
+    Stored {
+        entry: StoredEntry,
+        data_offset: u64,
+        offset: u64,
+        hasher: Option<crc32fast::Hasher>,
+    },
+    /// A compressed entry that is decompressed by `fsm`, which has read the zip file up to `read_offset`.
+    Compressed {
+        entry: StoredEntry,
+        fsm: Option<Box<EntryFsm>>,
+        read_offset: u64,
+        end_offset: u64,
+        written: u64,
+    },
+    /// The iterator has ended.
+    Done,
+}
+
+impl<TStorage: ?Sized> core::fmt::Debug for ZipEntryBlocks<'_, TStorage> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ZipEntryBlocks")
+            .field("key", &self.key)
+            .field("block_size", &self.block_size)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<TStorage: ?Sized + ReadableStorageTraits> ZipStorageAdapter<TStorage> {
+    /// Get the value of `key` as an iterator of blocks of `block_size` bytes, or [`None`] if the key does not exist.
+    ///
+    /// Unlike [`get`](ReadableStorageTraits::get), the value is read and decompressed as the blocks are consumed,
+    /// so memory usage is bounded by `block_size` and the buffers of the decompressor rather than the size of the value.
+    /// This suits very large values, such as a compressed entry of many GiB.
+    ///
+    /// The value is the concatenation of the blocks, and every block other than the last has exactly `block_size` bytes.
+    /// The value of an empty entry has no blocks.
+    /// Once a compressed entry has been decompressed, its size is checked against the central directory and its CRC-32 is verified, and the iterator returns an error rather than ending if they do not match.
+    /// The CRC-32 of a stored entry is verified in the same way if enabled by [`with_crc32_verification`](ZipStorageAdapter::with_crc32_verification).
+    /// Encrypted, Zstandard compressed, and LZMA compressed entries are read in full before the first block is returned.
+    ///
+    /// # Errors
+    /// Returns a [`StorageError`] if `block_size` is zero or the local file header of the entry cannot be read.
+    pub fn get_blocks(
+        &self,
+        key: &StoreKey,
+        block_size: usize,
+    ) -> Result<Option<ZipEntryBlocks<'_, TStorage>>, StorageError> {
+        if block_size == 0 {
+            return Err(StorageError::Other(
+                "the block size must be greater than zero".to_string(),
+            ));
+        }
+        // Clone the entry so that the index is not locked while reading
+        let Some(entry) = self.index.read().get(key).cloned() else {
+            return Ok(None);
+        };
+
+        // Encrypted entries are decrypted in full, AES encrypted entries are authenticated before they are decrypted
+        // Zstandard and LZMA compressed entries are decoded in full, as they are not decompressed with `EntryFsm`
+        let in_full = matches!(&entry, IndexEntry::Archived(entry)
+            if (cfg!(feature = "encryption") && entry.is_encrypted())
+                || (cfg!(feature = "zstd") && entry.method == Method::Zstd)
+                || (cfg!(feature = "lzma") && entry.method == Method::Lzma));
+        if in_full {
+            let Some(value) = self.get(key)? else {
+                return Ok(None);
+            };
+            return Ok(Some(ZipEntryBlocks {
+                adapter: self,
+                key: key.clone(),
+                block_size,
+                state: BlocksState::InMemory { value, offset: 0 },
+            }));
+        }
+
+        let state = match entry {
+            IndexEntry::Archived(entry) => {
+                let cached = self
+                    .cache
+                    .as_ref()
+                    .and_then(|cache| cache.get(key, entry.header_offset));
+                if let Some(value) = cached {
+                    BlocksState::InMemory { value, offset: 0 }
+                } else if entry.method == Method::Store {
+                    let data_offset = self
+                        .calculate_data_offset(entry.header_offset)
+                        .map_err(|err| StorageError::Other(err.to_string()))?;
+                    let hasher = self
+                        .verifies_crc32(&entry, &[ByteRange::FromStart(0, None)])
+                        .then(crc32fast::Hasher::new);
+                    BlocksState::Stored {
+                        entry,
+                        data_offset,
+                        offset: 0,
+                        hasher,
+                    }
+                } else {
+                    BlocksState::Compressed {
+                        fsm: Some(Box::new(EntryFsm::new(
+                            Some(entry.to_entry(key.as_str())),
+                            None,
+                        ))),
+                        read_offset: entry.header_offset,
+                        end_offset: self.index.read().size,
+                        written: 0,
+                        entry,
+                    }
+                }
+            }
+            #[cfg(feature = "write")]
+            IndexEntry::Pending(value, _) => BlocksState::InMemory { value, offset: 0 },
+        };
+
+        Ok(Some(ZipEntryBlocks {
+            adapter: self,
+            key: key.clone(),
+            block_size,
+            state,
+        }))
+    }
+}
+
+impl<TStorage: ?Sized + ReadableStorageTraits> ZipEntryBlocks<'_, TStorage> {
+    /// Read the next block of a stored entry.
+    fn next_stored(&mut self) -> Result<Option<Bytes>, StorageError> {
+        let BlocksState::Stored {
+            entry,
+            data_offset,
+            offset,
+            hasher,
+        } = &mut self.state
+        else {
+            return Ok(None);
+        };
+        if *offset == entry.uncompressed_size {
+            return Ok(None);
+        }
+
+        let len = (entry.uncompressed_size - *offset).min(self.block_size as u64);
+        let block = self
+            .adapter
+            .storage
+            .get_partial(
+                &self.adapter.key,
+                ByteRange::FromStart(*data_offset + *offset, Some(len)),
+            )?
+            .ok_or_else(|| StorageError::Other("Entry data not found".to_string()))?;
+        if block.len() as u64 != len {
+            return Err(StorageError::Other(
+                "the underlying storage returned unexpected byte ranges".to_string(),
+            ));
+        }
+        *offset += len;
+        if let Some(hasher) = hasher {
+            hasher.update(&block);
+        }
+        if *offset == entry.uncompressed_size {
+            // Verify the CRC-32 before returning the last block
+            if let Some(hasher) = hasher.take() {
+                let actual = hasher.finalize();
+                if actual != entry.crc32 {
+                    return Err(ZipStorageAdapter::<TStorage>::checksum_error(
+                        &self.key,
+                        entry.crc32,
+                        actual,
+                    ));
+                }
+            }
+        }
+        Ok(Some(block))
+    }
+
+    /// Decompress the next block of a compressed entry.
+    fn next_compressed(&mut self) -> Result<Option<Bytes>, StorageError> {
+        let BlocksState::Compressed {
+            entry,
+            fsm,
+            read_offset,
+            end_offset,
+            written,
+        } = &mut self.state
+        else {
+            return Ok(None);
+        };
+
+        let mut block = vec![0; self.block_size];
+        let mut filled = 0;
+        let mut done = false;
+        while filled < block.len() {
+            // The FSM is consumed by processing, and is put back once it returns its next state
+            let Some(mut current) = fsm.take() else {
+                return Ok(None);
+            };
+
+            // Feed data to FSM if it wants to read
+            if current.wants_read() {
+                let space = current.space();
+                // Don't request more than what's left in the file
+                let remaining = end_offset.saturating_sub(*read_offset);
+                let to_read = (space.len() as u64)
+                    .min(remaining)
+                    .min(self.adapter.read_buffer_size);
+
+                if to_read > 0 {
+                    let data = self
+                        .adapter
+                        .storage
+                        .get_partial(
+                            &self.adapter.key,
+                            ByteRange::FromStart(*read_offset, Some(to_read)),
+                        )?
+                        .ok_or_else(|| {
+                            StorageError::Other("Cannot read compressed data".to_string())
+                        })?;
+                    let copy_len = data.len().min(space.len());
+                    space[..copy_len].copy_from_slice(&data[..copy_len]);
+                    let filled = current.fill(copy_len);
+                    *read_offset += filled as u64;
+                } else {
+                    // No more data to read, signal EOF
+                    current.fill(0);
+                }
+            }
+
+            match current.process(&mut block[filled..]) {
+                Ok(FsmResult::Continue((next_fsm, outcome))) => {
+                    filled += outcome.bytes_written;
+                    *fsm = Some(Box::new(next_fsm));
+                }
+                Ok(FsmResult::Done(_buffer)) => {
+                    done = true;
+                    break;
+                }
+                Err(err) => {
+                    return Err(ZipStorageAdapter::<TStorage>::decompression_error(
+                        &self.key, entry, err,
+                    ));
+                }
+            }
+        }
+        *written += filled as u64;
+
+        if done {
+            // Verify decompressed size matches expected
+            if *written != entry.uncompressed_size {
+                return Err(StorageError::Other(format!(
+                    "zip decompressed entry size mismatch: expected {}, got {written}",
+                    entry.uncompressed_size
+                )));
+            }
+            self.state = BlocksState::Done;
+        }
+        block.truncate(filled);
+        Ok((!block.is_empty()).then(|| Bytes::from(block)))
+    }
+}
+
+impl<TStorage: ?Sized + ReadableStorageTraits> Iterator for ZipEntryBlocks<'_, TStorage> {
+    type Item = Result<Bytes, StorageError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let block = match &mut self.state {
+            BlocksState::InMemory { value, offset } => {
+                let start = *offset;
+                *offset = value.len().min(start + self.block_size);
+                Ok((start < value.len()).then(|| value.slice(start..*offset)))
+            }
+            BlocksState::Stored { .. } => self.next_stored(),
+            BlocksState::Compressed { .. } => self.next_compressed(),
+            BlocksState::Done => Ok(None),
+        };
+        match block {
+            Ok(Some(block)) => Some(Ok(block)),
+            Ok(None) => {
+                self.state = BlocksState::Done;
+                None
+            }
+            Err(err) => {
+                self.state = BlocksState::Done;
+                Some(Err(err))
+            }
+        }
+    }
+}
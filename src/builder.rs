@@ -0,0 +1,452 @@
+use std::{path::PathBuf, sync::Arc};
+
+#[cfg(feature = "async")]
+use zarrs_storage::AsyncReadableStorageTraits;
+
+#[cfg(feature = "async")]
+use crate::DEFAULT_CONCURRENCY;
+use zarrs_storage::{ReadableStorageTraits, StoreKey};
+
+use crate::{
+    DEFAULT_COALESCE_GAP, DEFAULT_SINGLE_READ_THRESHOLD, SymlinkPolicy, UnsafeEntryNamePolicy,
+    ZipReadProgressCallback, ZipStorageAdapter, ZipStorageAdapterCreateError, index::IndexOptions,
+};
+
+/// A builder of a [`ZipStorageAdapter`].
+///
+/// ```
+/// # use std::sync::Arc;
+/// use zarrs_storage::StoreKey;
+/// use zarrs_filesystem::FilesystemStore;
+/// use zarrs_zip::ZipStorageAdapterBuilder;
+///
+/// # let fs_store = Arc::new(FilesystemStore::new("tests/zarr.zip")?);
+/// let zip_store = ZipStorageAdapterBuilder::new(fs_store, StoreKey::root())
+///     .path("foo/")
+///     .cache_bytes(64 << 20)
+///     .verify_crc(true)
+///     .build()?;
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+pub struct ZipStorageAdapterBuilder<TStorage: ?Sized> {
+    storage: Arc<TStorage>,
+    key: StoreKey,
+    path: PathBuf,
+    cache_bytes: Option<usize>,
+    verify_crc: bool,
+    index_options: IndexOptions,
+    prefetch_headers: bool,
+    preload_max_size: Option<u64>,
+    preload_all_max_size: Option<u64>,
+    coalesce_gap: u64,
+    min_read_size: u64,
+    read_buffer_size: u64,
+    single_read_threshold: u64,
+    #[cfg(feature = "async")]
+    concurrency: usize,
+    eocd_prefetch_bytes: u64,
+    seek_index_interval: Option<u64>,
+    seek_flush_points: bool,
+    read_progress: Option<ZipReadProgressCallback>,
+    #[cfg(feature = "encryption")]
+    password: Option<Vec<u8>>,
+}
+
+impl<TStorage: ?Sized> core::fmt::Debug for ZipStorageAdapterBuilder<TStorage> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut debug = f.debug_struct("ZipStorageAdapterBuilder");
+        debug
+            .field("key", &self.key)
+            .field("path", &self.path)
+            .field("cache_bytes", &self.cache_bytes)
+            .field("verify_crc", &self.verify_crc)
+            .field("symlink_policy", &self.index_options.symlink_policy)
+            .field("case_insensitive", &self.index_options.case_insensitive)
+            .field(
+                "normalize_separators",
+                &self.index_options.normalize_separators,
+            )
+            .field(
+                "unsafe_entry_name_policy",
+                &self.index_options.unsafe_entry_name_policy,
+            )
+            .field("prefetch_headers", &self.prefetch_headers)
+            .field("preload_max_size", &self.preload_max_size)
+            .field("preload_all_max_size", &self.preload_all_max_size)
+            .field("coalesce_gap", &self.coalesce_gap)
+            .field("min_read_size", &self.min_read_size)
+            .field("read_buffer_size", &self.read_buffer_size)
+            .field("single_read_threshold", &self.single_read_threshold);
+        #[cfg(feature = "async")]
+        debug.field("concurrency", &self.concurrency);
+        debug
+            .field("eocd_prefetch_bytes", &self.eocd_prefetch_bytes)
+            .field("seek_index_interval", &self.seek_index_interval)
+            .field("seek_flush_points", &self.seek_flush_points)
+            .field("read_progress", &self.read_progress.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<TStorage: ?Sized> ZipStorageAdapterBuilder<TStorage> {
+    /// Create a builder of a zip storage adapter for the zip file at `key` of `storage`.
+    ///
+    /// Pass [`StoreKey::root()`] to treat the entire `storage` as the zip file
+    /// (e.g., a `FilesystemStore` rooted directly at a zip file).
+    #[must_use]
+    pub fn new(storage: Arc<TStorage>, key: StoreKey) -> Self {
+        Self {
+            storage,
+            key,
+            path: PathBuf::new(),
+            cache_bytes: None,
+            verify_crc: false,
+            index_options: IndexOptions::default(),
+            prefetch_headers: false,
+            preload_max_size: None,
+            preload_all_max_size: None,
+            coalesce_gap: DEFAULT_COALESCE_GAP,
+            min_read_size: 0,
+            read_buffer_size: u64::MAX,
+            single_read_threshold: DEFAULT_SINGLE_READ_THRESHOLD,
+            #[cfg(feature = "async")]
+            concurrency: DEFAULT_CONCURRENCY,
+            eocd_prefetch_bytes: 0,
+            seek_index_interval: None,
+            seek_flush_points: false,
+            read_progress: None,
+            #[cfg(feature = "encryption")]
+            password: None,
+        }
+    }
+
+    /// Root the adapter at `path` within the zip file.
+    ///
+    /// `path` is a directory within the zip file, with or without a trailing `/`.
+    /// For example, `a` and `a/` both root the adapter at `a/`, excluding the entries of a sibling `ab/`.
+    ///
+    /// Defaults to the root of the zip file.
+    #[must_use]
+    pub fn path<T: Into<PathBuf>>(mut self, path: T) -> Self {
+        self.path = path.into();
+        self
+    }
+
+    /// Cache decompressed entries, holding at most `cache_bytes` bytes of decompressed data.
+    ///
+    /// See [`ZipStorageAdapter::with_cache`]. Decompressed entries are not cached by default.
+    #[must_use]
+    pub fn cache_bytes(mut self, cache_bytes: usize) -> Self {
+        self.cache_bytes = Some(cache_bytes);
+        self
+    }
+
+    /// Set whether the CRC-32 of stored entries is verified when they are read in full.
+    ///
+    /// See [`ZipStorageAdapter::with_crc32_verification`]. Defaults to `false`.
+    #[must_use]
+    pub fn verify_crc(mut self, verify_crc: bool) -> Self {
+        self.verify_crc = verify_crc;
+        self
+    }
+
+    /// Set the handling of symbolic link entries in the zip file.
+    ///
+    /// Defaults to [`SymlinkPolicy::Ignore`].
+    #[must_use]
+    pub fn symlink_policy(mut self, symlink_policy: SymlinkPolicy) -> Self {
+        self.index_options.symlink_policy = symlink_policy;
+        self
+    }
+
+    /// Set whether keys and prefixes are matched against the entries of the zip file ignoring ASCII case.
+    ///
+    /// This suits zip files created by tools that alter the case of entry names, such as some Windows tools.
+    /// Reads, writes, and listings of a key or prefix match entries that differ from it only by ASCII case, and entries are listed in the order of their lowercase names.
+    /// Entries with names that differ only by case are ambiguous, the last entry in the zip file wins and the others are not indexed.
+    ///
+    /// Defaults to `false`, keys are case-sensitive.
+    #[must_use]
+    pub fn case_insensitive(mut self, case_insensitive: bool) -> Self {
+        self.index_options.case_insensitive = case_insensitive;
+        self
+    }
+
+    /// Set whether `\` separators in the names of entries are replaced by `/`.
+    ///
+    /// Some Windows tools write zip files with `\` rather than `/` as the path separator, which the zip specification requires.
+    /// With normalized separators, such entries are indexed as keys and directories of the hierarchy they represent, and are matched by the path within the zip file.
+    ///
+    /// Defaults to `false`, as a `\` is a valid character of a key.
+    #[must_use]
+    pub fn normalize_separators(mut self, normalize_separators: bool) -> Self {
+        self.index_options.normalize_separators = normalize_separators;
+        self
+    }
+
+    /// Set the handling of entries in the zip file with unsafe names, which are absolute paths or have a `..` component.
+    ///
+    /// Defaults to [`UnsafeEntryNamePolicy::Error`].
+    #[must_use]
+    pub fn unsafe_entry_name_policy(
+        mut self,
+        unsafe_entry_name_policy: UnsafeEntryNamePolicy,
+    ) -> Self {
+        self.index_options.unsafe_entry_name_policy = unsafe_entry_name_policy;
+        self
+    }
+
+    /// Set whether the directory entries of the zip file are left out of the index.
+    ///
+    /// Some tools write a directory entry for every directory, such as every group of a Zarr hierarchy, which only matter for listing empty directories.
+    /// Ignoring them trims the memory of the index and the time to sort it for listing, for zip files with many directories.
+    /// Prefixes are still listed as the parent directories of keys, but empty directories are no longer listed by
+    /// [`list_dir`](zarrs_storage::ListableStorageTraits::list_dir) or [`entries`](ZipStorageAdapter::entries).
+    ///
+    /// Defaults to `false`.
+    #[must_use]
+    pub fn ignore_directory_entries(mut self, ignore_directory_entries: bool) -> Self {
+        self.index_options.ignore_directory_entries = ignore_directory_entries;
+        self
+    }
+
+    /// Set whether the local file headers of all entries are read when the adapter is built.
+    ///
+    /// The data offset of an entry is otherwise read from its local file header on first access, in a small request per entry.
+    /// Prefetching instead reads runs of nearby headers in a few large requests, reading some extra bytes between the headers upfront.
+    /// This is a win for zip files with many small entries, such as chunks, on storage with a high latency per request, such as HTTP object stores.
+    ///
+    /// Defaults to `false`.
+    #[must_use]
+    pub fn prefetch_headers(mut self, prefetch_headers: bool) -> Self {
+        self.prefetch_headers = prefetch_headers;
+        self
+    }
+
+    /// Read and hold the values of all entries of at most `max_size` bytes when the adapter is built.
+    ///
+    /// Opening a Zarr hierarchy reads many small metadata entries, such as `zarr.json`, scattered through the zip file, each in a request for its local file header and a request for its data.
+    /// Preloading instead reads runs of nearby small entries in a few large requests, and later reads of them are served without reading the zip file.
+    /// This cuts the time to open deep hierarchies on storage with a high latency per request, such as HTTP object stores.
+    ///
+    /// Preloaded values count against the capacity of the cache of decompressed entries, if enabled with [`cache_bytes`](Self::cache_bytes),
+    /// and entries are not preloaded once it is exhausted.
+    /// Encrypted entries are not preloaded.
+    ///
+    /// Entries are not preloaded by default.
+    #[must_use]
+    pub fn preload_small_entries(mut self, max_size: u64) -> Self {
+        self.preload_max_size = Some(max_size);
+        self
+    }
+
+    /// Read and hold the values of all entries when the adapter is built, if their total size is at most `max_total_size` bytes.
+    ///
+    /// Every entry is decompressed once, and all later reads are served from memory without reading the zip file.
+    /// This suits small to medium zip files that are read many times.
+    ///
+    /// Unlike [`preload_small_entries`](Self::preload_small_entries), the values do not count against the capacity of the cache of decompressed entries.
+    /// Encrypted entries and entries with an unsupported compression method are not preloaded.
+    ///
+    /// Building the adapter fails with [`ZipStorageAdapterCreateError::PreloadTooLarge`] if the total size exceeds `max_total_size`.
+    /// Entries are not preloaded by default.
+    #[must_use]
+    pub fn preload_all(mut self, max_total_size: u64) -> Self {
+        self.preload_all_max_size = Some(max_total_size);
+        self
+    }
+
+    /// Set the maximum gap in bytes between byte ranges of a stored entry that are read in the same request.
+    ///
+    /// See [`ZipStorageAdapter::with_coalesce_gap`]. Defaults to 4 KiB.
+    #[must_use]
+    pub fn coalesce_gap(mut self, coalesce_gap: u64) -> Self {
+        self.coalesce_gap = coalesce_gap;
+        self
+    }
+
+    /// Set the minimum size in bytes of the requests of the compressed data of an entry as it is decompressed.
+    ///
+    /// See [`ZipStorageAdapter::with_min_read_size`]. Defaults to `0`.
+    #[must_use]
+    pub fn min_read_size(mut self, min_read_size: u64) -> Self {
+        self.min_read_size = min_read_size;
+        self
+    }
+
+    /// Set the maximum size in bytes of the requests of the compressed data of an entry as it is decompressed.
+    ///
+    /// See [`ZipStorageAdapter::with_read_buffer_size`]. Defaults to [`u64::MAX`].
+    #[must_use]
+    pub fn read_buffer_size(mut self, read_buffer_size: u64) -> Self {
+        self.read_buffer_size = read_buffer_size;
+        self
+    }
+
+    /// Set the maximum compressed size in bytes of an entry that is read in a single request when it is decompressed in full.
+    ///
+    /// See [`ZipStorageAdapter::with_single_read_threshold`]. Defaults to 64 MiB.
+    #[must_use]
+    pub fn single_read_threshold(mut self, single_read_threshold: u64) -> Self {
+        self.single_read_threshold = single_read_threshold;
+        self
+    }
+
+    /// Set the maximum number of concurrent requests of the zip file by an asynchronous read.
+    ///
+    /// See [`ZipStorageAdapter::with_concurrency`]. Defaults to `8`.
+    #[cfg(feature = "async")]
+    #[must_use]
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    /// Set the number of trailing bytes of the zip file that are read in a single request before it is parsed.
+    ///
+    /// The central directory of a zip file is at its end, and is otherwise read in a sequence of requests of the sizes requested by the parser.
+    /// Reading a large suffix of the zip file upfront, such as 1 MiB, serves the end of central directory record and the central directory from a single request if they fit,
+    /// which reduces the time to open zip files with many entries on storage with a high latency per request, such as HTTP object stores.
+    /// Bytes outside of the suffix are read from storage as needed.
+    ///
+    /// Defaults to `0`, which disables the prefetch.
+    #[must_use]
+    pub fn eocd_prefetch_bytes(mut self, eocd_prefetch_bytes: u64) -> Self {
+        self.eocd_prefetch_bytes = eocd_prefetch_bytes;
+        self
+    }
+
+    /// Index deflated entries for random access, with checkpoints at least `interval_bytes` decompressed bytes apart.
+    ///
+    /// See [`ZipStorageAdapter::with_seek_index`]. Deflated entries are not indexed by default.
+    #[must_use]
+    pub fn with_seek_index(mut self, interval_bytes: u64) -> Self {
+        self.seek_index_interval = Some(interval_bytes);
+        self
+    }
+
+    /// Index deflated entries for random access at their full flush points.
+    ///
+    /// See [`ZipStorageAdapter::with_seek_flush_points`]. Flush points are not indexed by default.
+    #[must_use]
+    pub fn seek_flush_points(mut self, seek_flush_points: bool) -> Self {
+        self.seek_flush_points = seek_flush_points;
+        self
+    }
+
+    /// Decrypt entries encrypted with the traditional PKWARE encryption or the `WinZip` AES extension with `password`.
+    ///
+    /// See [`ZipStorageAdapter::with_password`]. Encrypted entries cannot be read by default.
+    #[cfg(feature = "encryption")]
+    #[must_use]
+    pub fn password(mut self, password: impl Into<Vec<u8>>) -> Self {
+        self.password = Some(password.into());
+        self
+    }
+
+    /// Call `read_progress` with the progress of reads of compressed entries as they are decompressed.
+    ///
+    /// See [`ZipStorageAdapter::with_read_progress`]. Progress is not reported by default.
+    #[must_use]
+    pub fn read_progress(mut self, read_progress: ZipReadProgressCallback) -> Self {
+        self.read_progress = Some(read_progress);
+        self
+    }
+
+    /// Apply the options of the builder to a newly opened `adapter`.
+    fn configure(self, mut adapter: ZipStorageAdapter<TStorage>) -> ZipStorageAdapter<TStorage> {
+        adapter = adapter
+            .with_coalesce_gap(self.coalesce_gap)
+            .with_min_read_size(self.min_read_size)
+            .with_read_buffer_size(self.read_buffer_size)
+            .with_single_read_threshold(self.single_read_threshold);
+        #[cfg(feature = "async")]
+        {
+            adapter = adapter.with_concurrency(self.concurrency);
+        }
+        if let Some(cache_bytes) = self.cache_bytes {
+            adapter = adapter.with_cache(cache_bytes);
+        }
+        if let Some(interval_bytes) = self.seek_index_interval {
+            adapter = adapter.with_seek_index(interval_bytes);
+        }
+        if self.seek_flush_points {
+            adapter = adapter.with_seek_flush_points();
+        }
+        if let Some(read_progress) = self.read_progress {
+            adapter = adapter.with_read_progress(read_progress);
+        }
+        if self.verify_crc {
+            adapter = adapter.with_crc32_verification();
+        }
+        #[cfg(feature = "encryption")]
+        if let Some(password) = self.password {
+            adapter = adapter.with_password(password);
+        }
+        adapter
+    }
+}
+
+impl<TStorage: ?Sized + ReadableStorageTraits> ZipStorageAdapterBuilder<TStorage> {
+    /// Build the zip storage adapter.
+    ///
+    /// # Errors
+    /// Returns a [`ZipStorageAdapterCreateError`] if the store value at `key` is not a valid zip file, it has a symbolic link with [`SymlinkPolicy::Error`] or an unsafe entry name with [`UnsafeEntryNamePolicy::Error`], its local file headers cannot be prefetched, or its entries cannot be preloaded.
+    pub fn build(self) -> Result<ZipStorageAdapter<TStorage>, ZipStorageAdapterCreateError> {
+        let zip_path = ZipStorageAdapter::<TStorage>::zip_path_str(&self.path);
+        let adapter = ZipStorageAdapter::open(
+            self.storage.clone(),
+            self.key.clone(),
+            zip_path,
+            self.index_options,
+            self.eocd_prefetch_bytes,
+        )?;
+        if self.prefetch_headers {
+            adapter.prefetch_headers()?;
+        }
+        let (preload_max_size, preload_all_max_size) =
+            (self.preload_max_size, self.preload_all_max_size);
+        let mut adapter = self.configure(adapter);
+        if let Some(max_total_size) = preload_all_max_size {
+            adapter.preload_all(max_total_size)?;
+        }
+        if let Some(max_size) = preload_max_size {
+            adapter.preload_small_entries(max_size)?;
+        }
+        Ok(adapter)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<TStorage: ?Sized + AsyncReadableStorageTraits> ZipStorageAdapterBuilder<TStorage> {
+    /// Build the zip storage adapter asynchronously.
+    ///
+    /// # Errors
+    /// Returns a [`ZipStorageAdapterCreateError`] if the store value at `key` is not a valid zip file, it has a symbolic link with [`SymlinkPolicy::Error`] or an unsafe entry name with [`UnsafeEntryNamePolicy::Error`], its local file headers cannot be prefetched, or its entries cannot be preloaded.
+    pub async fn build_async(
+        self,
+    ) -> Result<ZipStorageAdapter<TStorage>, ZipStorageAdapterCreateError> {
+        let zip_path = ZipStorageAdapter::<TStorage>::zip_path_str(&self.path);
+        let adapter = ZipStorageAdapter::open_async(
+            self.storage.clone(),
+            self.key.clone(),
+            zip_path,
+            self.index_options,
+            self.eocd_prefetch_bytes,
+        )
+        .await?;
+        if self.prefetch_headers {
+            adapter.prefetch_headers_async().await?;
+        }
+        let (preload_max_size, preload_all_max_size) =
+            (self.preload_max_size, self.preload_all_max_size);
+        let mut adapter = self.configure(adapter);
+        if let Some(max_total_size) = preload_all_max_size {
+            adapter.preload_all_async(max_total_size).await?;
+        }
+        if let Some(max_size) = preload_max_size {
+            adapter.preload_small_entries_async(max_size).await?;
+        }
+        Ok(adapter)
+    }
+}
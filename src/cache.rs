@@ -0,0 +1,192 @@
+use std::{
+    hash::{BuildHasher, RandomState},
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+};
+
+use lru::LruCache;
+use parking_lot::Mutex;
+use zarrs_storage::{Bytes, StoreKey};
+
+/// Statistics of the decompressed entry cache of a [`ZipStorageAdapter`](crate::ZipStorageAdapter).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ZipCacheStats {
+    /// The number of reads of compressed entries that were served from the cache.
+    pub hits: u64,
+    /// The number of reads of compressed entries that were not in the cache.
+    ///
+    /// Entries larger than the capacity of the cache are not counted.
+    pub misses: u64,
+    /// The number of decompressed entries in the cache.
+    pub entries: usize,
+    /// The total size in bytes of the decompressed entries in the cache.
+    pub size: usize,
+    /// The maximum total size in bytes of the decompressed entries in the cache.
+    ///
+    /// The capacity used by entries preloaded by [`ZipStorageAdapterBuilder::preload_small_entries`](crate::ZipStorageAdapterBuilder::preload_small_entries) is excluded.
+    pub capacity: usize,
+}
+
+/// The number of shards of the decompressed entry cache, each with its own lock.
+const CACHE_SHARDS: usize = 16;
+
+/// A least recently used cache of decompressed entries with a byte budget.
+///
+/// Values are stored with the local header offset of their entry, so that a value is not returned for a key that has since been rewritten.
+/// The entries are split into shards by the hash of their key, so that concurrent reads of different keys do not contend for a single lock.
+/// Each access of an entry is stamped with a counter shared by the shards, and the least recently used entry of all shards is evicted first.
+pub(crate) struct DecompressedCache {
+    capacity: usize,
+    shards: Box<[Mutex<LruCache<StoreKey, CachedValue>>]>,
+    hasher: RandomState,
+    /// The total size of the values in the cache.
+    size: AtomicUsize,
+    /// The counter of accesses of entries, the stamp of the next access.
+    accesses: AtomicU64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+/// A value in the cache.
+struct CachedValue {
+    /// The offset of the local file header of the entry of the value.
+    header_offset: u64,
+    value: Bytes,
+    /// The stamp of the last access of the value.
+    last_access: u64,
+}
+
+impl DecompressedCache {
+    /// Create a new cache holding at most `capacity` bytes of decompressed entries.
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            shards: (0..CACHE_SHARDS)
+                .map(|_| Mutex::new(LruCache::unbounded()))
+                .collect(),
+            hasher: RandomState::new(),
+            size: AtomicUsize::new(0),
+            accesses: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// The shard holding the value of `key`.
+    #[allow(clippy::cast_possible_truncation)]
+    fn shard(&self, key: &StoreKey) -> &Mutex<LruCache<StoreKey, CachedValue>> {
+        &self.shards[(self.hasher.hash_one(key) % CACHE_SHARDS as u64) as usize]
+    }
+
+    /// Reserve `size` bytes of the capacity of the cache for values held outside of it, returning false if the capacity is insufficient.
+    pub(crate) fn reserve(&mut self, size: u64) -> bool {
+        match usize::try_from(size) {
+            Ok(size) if size <= self.capacity => {
+                self.capacity -= size;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns true if a decompressed entry of `size` bytes can be cached.
+    pub(crate) fn fits(&self, size: u64) -> bool {
+        size <= self.capacity as u64
+    }
+
+    /// Get the value of `key` with the local header at `header_offset`, marking it as the most recently used.
+    fn lookup(&self, key: &StoreKey, header_offset: u64) -> Option<Bytes> {
+        let mut shard = self.shard(key).lock();
+        let cached = shard
+            .get_mut(key)
+            .filter(|cached| cached.header_offset == header_offset)?;
+        cached.last_access = self.accesses.fetch_add(1, Ordering::Relaxed);
+        Some(cached.value.clone())
+    }
+
+    /// Get the decompressed value of `key` with the local header at `header_offset`, recording a hit or miss.
+    pub(crate) fn get(&self, key: &StoreKey, header_offset: u64) -> Option<Bytes> {
+        let value = self.lookup(key, header_offset);
+        if value.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        }
+        value
+    }
+
+    /// Get the value of the stored entry of `key` with the local header at `header_offset`, if it was prefetched into the cache.
+    ///
+    /// Hits and misses are not recorded, the statistics are of compressed entries.
+    pub(crate) fn get_stored(&self, key: &StoreKey, header_offset: u64) -> Option<Bytes> {
+        self.lookup(key, header_offset)
+    }
+
+    /// Returns true if the value of `key` with the local header at `header_offset` is cached, without recording a hit or miss.
+    pub(crate) fn contains(&self, key: &StoreKey, header_offset: u64) -> bool {
+        self.shard(key)
+            .lock()
+            .peek(key)
+            .is_some_and(|cached| cached.header_offset == header_offset)
+    }
+
+    /// Insert the decompressed value of `key` with the local header at `header_offset`, evicting the least recently used entries as needed.
+    pub(crate) fn insert(&self, key: StoreKey, header_offset: u64, value: Bytes) {
+        if !self.fits(value.len() as u64) {
+            return;
+        }
+        let size = value.len();
+        let cached = CachedValue {
+            header_offset,
+            value,
+            last_access: self.accesses.fetch_add(1, Ordering::Relaxed),
+        };
+        let previous = self.shard(&key).lock().put(key, cached);
+        self.size.fetch_add(size, Ordering::Relaxed);
+        if let Some(previous) = previous {
+            self.size.fetch_sub(previous.value.len(), Ordering::Relaxed);
+        }
+        while self.size.load(Ordering::Relaxed) > self.capacity {
+            if !self.evict_least_recently_used() {
+                break;
+            }
+        }
+    }
+
+    /// Evict the least recently used entry of all shards, returning false if the cache is empty.
+    fn evict_least_recently_used(&self) -> bool {
+        let oldest = self
+            .shards
+            .iter()
+            .filter_map(|shard| {
+                let last_access = shard.lock().peek_lru()?.1.last_access;
+                Some((last_access, shard))
+            })
+            .min_by_key(|(last_access, _)| *last_access);
+        let Some((_, shard)) = oldest else {
+            return false;
+        };
+        if let Some((_, evicted)) = shard.lock().pop_lru() {
+            self.size.fetch_sub(evicted.value.len(), Ordering::Relaxed);
+        }
+        true
+    }
+
+    /// Remove the decompressed value of `key`.
+    #[cfg(feature = "write")]
+    pub(crate) fn remove(&self, key: &StoreKey) {
+        if let Some(previous) = self.shard(key).lock().pop(key) {
+            self.size.fetch_sub(previous.value.len(), Ordering::Relaxed);
+        }
+    }
+
+    /// The cache statistics.
+    pub(crate) fn stats(&self) -> ZipCacheStats {
+        ZipCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.shards.iter().map(|shard| shard.lock().len()).sum(),
+            size: self.size.load(Ordering::Relaxed),
+            capacity: self.capacity,
+        }
+    }
+}
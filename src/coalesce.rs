@@ -0,0 +1,71 @@
+use std::ops::Range;
+
+use zarrs_storage::{Bytes, StorageError, byte_range::ByteRange};
+
+/// Byte ranges of the zip file, coalesced into fewer requests to the underlying storage.
+///
+/// Ranges that overlap or are at most `gap` bytes apart are merged into a single request, and the requested ranges are sliced back out of the merged requests.
+pub(crate) struct CoalescedRanges {
+    /// The merged requests, in ascending order of offset.
+    requests: Vec<Range<u64>>,
+    /// The index of the request containing each of the requested ranges, and the range within that request.
+    slices: Vec<(usize, Range<usize>)>,
+}
+
+impl CoalescedRanges {
+    /// Coalesce `ranges` of the zip file, merging ranges that overlap or are at most `gap` bytes apart.
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn new(ranges: &[Range<u64>], gap: u64) -> Self {
+        let mut order: Vec<usize> = (0..ranges.len()).collect();
+        order.sort_unstable_by_key(|&i| ranges[i].start);
+
+        let mut requests: Vec<Range<u64>> = Vec::new();
+        let mut slices = vec![(0, 0..0); ranges.len()];
+        for i in order {
+            let range = &ranges[i];
+            match requests.last_mut() {
+                Some(request) if range.start <= request.end.saturating_add(gap) => {
+                    request.end = request.end.max(range.end);
+                }
+                _ => requests.push(range.clone()),
+            }
+            let request = requests.len() - 1;
+            let offset = requests[request].start;
+            slices[i] = (
+                request,
+                (range.start - offset) as usize..(range.end - offset) as usize,
+            );
+        }
+        Self { requests, slices }
+    }
+
+    /// The merged byte ranges to request from the underlying storage.
+    pub(crate) fn requests(&self) -> Vec<ByteRange> {
+        self.requests
+            .iter()
+            .map(|request| ByteRange::FromStart(request.start, Some(request.end - request.start)))
+            .collect()
+    }
+
+    /// Slice the requested ranges out of the `values` of the merged requests, in the order they were requested.
+    ///
+    /// # Errors
+    /// Returns a [`StorageError`] if there is not a value of the expected length for each merged request.
+    pub(crate) fn split(&self, values: &[Bytes]) -> Result<Vec<Bytes>, StorageError> {
+        let lengths_match = values.len() == self.requests.len()
+            && values
+                .iter()
+                .zip(&self.requests)
+                .all(|(value, request)| value.len() as u64 == request.end - request.start);
+        if !lengths_match {
+            return Err(StorageError::Other(
+                "the underlying storage returned unexpected byte ranges".to_string(),
+            ));
+        }
+        Ok(self
+            .slices
+            .iter()
+            .map(|(request, range)| values[*request].slice(range.clone()))
+            .collect())
+    }
+}
@@ -0,0 +1,384 @@
+//! Decoding of compressed entries by this crate as their data is read, rather than with `EntryFsm`.
+//!
+//! The Zstandard decoder of `rc_zip` fails unless the compressed data of an entry is fed to it in a single chunk,
+//! so Zstandard compressed entries are decoded with the streaming decoder of `zstd` instead.
+//! `rc_zip` only accepts LZMA compressed entries with version 2.0 of the LZMA SDK in their properties header, rather than the 9.4 written by Python and 7-Zip,
+//! so the properties header of LZMA compressed entries is parsed here and their data is decoded with `lzma_rs`.
+
+#[cfg(feature = "lzma")]
+use std::io::Write;
+
+#[cfg(feature = "lzma")]
+use lzma_rs::decompress::{Options, Stream, UnpackedSize};
+use rc_zip::parse::Method;
+use zarrs_storage::{Bytes, StorageError, StoreKey};
+#[cfg(feature = "zstd")]
+use zstd::stream::raw::{InBuffer, Operation, OutBuffer};
+
+use crate::{
+    ZipReadProgressCallback, ZipStorageAdapter, index::StoredEntry, progress::report_read_progress,
+};
+
+/// The maximum size of the output of a single step of a decoder.
+#[cfg(feature = "zstd")]
+const DECODE_STEP_SIZE: usize = 128 * 1024;
+
+/// The size of the LZMA properties header, the major and minor version of the LZMA SDK followed by the size of the LZMA properties.
+#[cfg(feature = "lzma")]
+const LZMA_HEADER_SIZE: usize = 4;
+
+/// The size of the LZMA properties, the `lc`, `lp`, and `pb` parameters in one byte followed by the dictionary size.
+#[cfg(feature = "lzma")]
+const LZMA_PROPERTIES_SIZE: usize = 5;
+
+/// The number of valid values of the byte of the `lc`, `lp`, and `pb` parameters of the LZMA properties, `(pb * 5 + lp) * 9 + lc`.
+#[cfg(feature = "lzma")]
+const LZMA_PARAMETERS_LIMIT: u8 = 9 * 5 * 5;
+
+/// The decoder of a compression method.
+enum Codec {
+    /// The streaming decoder of `zstd`.
+    #[cfg(feature = "zstd")]
+    Zstd(Box<zstd::stream::raw::Decoder<'static>>),
+    /// An LZMA decoder.
+    #[cfg(feature = "lzma")]
+    Lzma(Box<LzmaDecoder>),
+}
+
+/// The decoder of an LZMA compressed entry.
+#[cfg(feature = "lzma")]
+struct LzmaDecoder {
+    /// The size of the value.
+    uncompressed_size: u64,
+    /// The LZMA properties header and properties, until they are read in full.
+    header: Vec<u8>,
+    /// The decoder of the LZMA stream, once the properties are read.
+    stream: Option<Stream<Vec<u8>>>,
+}
+
+/// A decoder of the compressed data of an entry, which is fed to it as it is read.
+pub(crate) struct EntryDecoder {
+    /// The decoder of the compression method of the entry.
+    codec: Codec,
+    /// The length of the decoded prefix of the value that is required.
+    expected_size: usize,
+    /// The decoded prefix of the value.
+    decoded: Vec<u8>,
+    /// The size of the compressed data that has not been fed to the decoder.
+    remaining: u64,
+    /// The hasher of the value, if it is decoded in full.
+    hasher: Option<crc32fast::Hasher>,
+}
+
+impl EntryDecoder {
+    /// A decoder of the first `expected_size` bytes of `entry`, the value of `key`, or [`None`] if the entry is decompressed with `EntryFsm`.
+    ///
+    /// # Errors
+    /// Returns a [`StorageError`] if the decoder cannot be created.
+    #[cfg_attr(
+        not(feature = "zstd"),
+        allow(unused_variables, clippy::unnecessary_wraps)
+    )]
+    pub(crate) fn new(
+        key: &StoreKey,
+        entry: &StoredEntry,
+        expected_size: usize,
+    ) -> Result<Option<Self>, StorageError> {
+        let codec = match entry.method {
+            _ if entry.is_encrypted() => return Ok(None),
+            #[cfg(feature = "zstd")]
+            Method::Zstd => Codec::Zstd(Box::new(
+                zstd::stream::raw::Decoder::new().map_err(|err| decode_error(key, err))?,
+            )),
+            #[cfg(feature = "lzma")]
+            Method::Lzma => Codec::Lzma(Box::new(LzmaDecoder {
+                uncompressed_size: entry.uncompressed_size,
+                header: Vec::with_capacity(LZMA_HEADER_SIZE + LZMA_PROPERTIES_SIZE),
+                stream: None,
+            })),
+            _ => return Ok(None),
+        };
+        let hasher = (expected_size as u64 == entry.uncompressed_size).then(crc32fast::Hasher::new);
+        Ok(Some(Self {
+            codec,
+            expected_size,
+            decoded: Vec::with_capacity(expected_size),
+            remaining: entry.compressed_size,
+            hasher,
+        }))
+    }
+
+    /// The maximum length of the decoded prefix.
+    ///
+    /// A value decoded in full may be decoded to one byte more than its size, so that compressed data that decodes to more than the size in the central directory is detected.
+    fn limit(&self) -> usize {
+        self.expected_size + usize::from(self.hasher.is_some())
+    }
+
+    /// The size of the compressed data that has not been fed to the decoder.
+    pub(crate) fn remaining(&self) -> u64 {
+        self.remaining
+    }
+
+    /// The length of the decoded prefix of the value.
+    pub(crate) fn decoded_len(&self) -> u64 {
+        self.decoded.len() as u64
+    }
+
+    /// Whether the decoder needs more compressed data.
+    ///
+    /// It does not once the required prefix of the value is decoded, unless the value is decoded in full.
+    pub(crate) fn wants_read(&self) -> bool {
+        self.remaining > 0 && self.decoded.len() < self.limit()
+    }
+
+    /// Decode `data`, the next compressed data of the entry of `key`.
+    ///
+    /// Data beyond the compressed size of the entry, such as a data descriptor, is ignored.
+    ///
+    /// # Errors
+    /// Returns a [`StorageError`] if the compressed data is invalid.
+    pub(crate) fn decode(&mut self, key: &StoreKey, data: &[u8]) -> Result<(), StorageError> {
+        let len = data
+            .len()
+            .min(usize::try_from(self.remaining).unwrap_or(usize::MAX));
+        self.remaining -= len as u64;
+        let limit = self.limit();
+        let start = self.decoded.len();
+        match &mut self.codec {
+            #[cfg(feature = "zstd")]
+            Codec::Zstd(decoder) => decode_zstd(decoder, &data[..len], &mut self.decoded, limit)
+                .map_err(|err| decode_error(key, err))?,
+            #[cfg(feature = "lzma")]
+            Codec::Lzma(decoder) => decoder
+                .decode(&data[..len], self.remaining == 0, &mut self.decoded, limit)
+                .map_err(|err| decode_error(key, err))?,
+        }
+        if let Some(hasher) = &mut self.hasher {
+            hasher.update(&self.decoded[start..]);
+        }
+        Ok(())
+    }
+}
+
+/// Decode `data` with the Zstandard `decoder`, appending the output to `value` until it reaches `limit` bytes.
+///
+/// The output is drained from the decoder until it has consumed all of `data` and has no more output buffered, or `value` is full.
+#[cfg(feature = "zstd")]
+fn decode_zstd(
+    decoder: &mut zstd::stream::raw::Decoder<'static>,
+    data: &[u8],
+    value: &mut Vec<u8>,
+    limit: usize,
+) -> std::io::Result<()> {
+    let mut input = InBuffer::around(data);
+    loop {
+        let start = value.len();
+        let space = (limit - start).min(DECODE_STEP_SIZE);
+        if space == 0 {
+            return Ok(());
+        }
+        value.resize(start + space, 0);
+        let mut output = OutBuffer::around(&mut value[start..]);
+        let result = decoder.run(&mut input, &mut output);
+        let written = output.pos();
+        value.truncate(start + written);
+        result?;
+        if input.pos() == data.len() && written < space {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(feature = "lzma")]
+impl LzmaDecoder {
+    /// Decode `data`, appending the output to `value` until it reaches `limit` bytes.
+    ///
+    /// The LZMA stream is finished if `last`, when `data` ends the compressed data of the entry.
+    /// The stream is decoded up to the size of the value, so an end of stream marker that follows is ignored.
+    fn decode(
+        &mut self,
+        mut data: &[u8],
+        last: bool,
+        value: &mut Vec<u8>,
+        limit: usize,
+    ) -> Result<(), String> {
+        if self.stream.is_none() {
+            let len = (LZMA_HEADER_SIZE + LZMA_PROPERTIES_SIZE - self.header.len()).min(data.len());
+            self.header.extend_from_slice(&data[..len]);
+            data = &data[len..];
+            if self.header.len() < LZMA_HEADER_SIZE + LZMA_PROPERTIES_SIZE {
+                return if last {
+                    Err("truncated LZMA properties header".to_string())
+                } else {
+                    Ok(())
+                };
+            }
+            self.stream = Some(self.start()?);
+        }
+        let Some(stream) = self.stream.as_mut() else {
+            return Ok(());
+        };
+
+        // The stream stops accepting data once the value is decoded
+        while !data.is_empty() {
+            let len = stream.write(data).map_err(|err| err.to_string())?;
+            if len == 0 {
+                break;
+            }
+            data = &data[len..];
+        }
+        if let Some(output) = stream.get_output_mut() {
+            append_limited(value, output, limit);
+        }
+        if last {
+            if let Some(stream) = self.stream.take() {
+                let mut output = stream.finish().map_err(|err| err.to_string())?;
+                append_limited(value, &mut output, limit);
+            }
+        }
+        Ok(())
+    }
+
+    /// Parse the LZMA properties header and properties, and start decoding the LZMA stream that follows them.
+    ///
+    /// The version of the LZMA SDK in the header is not checked, the format of the properties has not changed between versions.
+    fn start(&self) -> Result<Stream<Vec<u8>>, String> {
+        let (header, properties) = self.header.split_at(LZMA_HEADER_SIZE);
+        let properties_size = u16::from_le_bytes([header[2], header[3]]);
+        if usize::from(properties_size) != LZMA_PROPERTIES_SIZE {
+            return Err(format!("invalid LZMA properties size {properties_size}"));
+        }
+        if properties[0] >= LZMA_PARAMETERS_LIMIT {
+            return Err(format!("invalid LZMA properties byte {}", properties[0]));
+        }
+        let options = Options {
+            unpacked_size: UnpackedSize::UseProvided(Some(self.uncompressed_size)),
+            ..Options::default()
+        };
+        // The properties are read again by the decoder, followed by the LZMA stream without its size
+        let mut stream = Stream::new_with_options(&options, Vec::new());
+        stream
+            .write_all(properties)
+            .map_err(|err| err.to_string())?;
+        Ok(stream)
+    }
+}
+
+/// Move `output` to the end of `value`, up to `limit` bytes of `value`.
+#[cfg(feature = "lzma")]
+fn append_limited(value: &mut Vec<u8>, output: &mut Vec<u8>, limit: usize) {
+    let len = output.len().min(limit.saturating_sub(value.len()));
+    value.extend_from_slice(&output[..len]);
+    output.clear();
+}
+
+/// An error decoding the compressed data of the entry of `key`.
+fn decode_error(key: &StoreKey, err: impl std::fmt::Display) -> StorageError {
+    StorageError::Other(format!("zip entry {key}: decompression error: {err}"))
+}
+
+impl<TStorage: ?Sized> ZipStorageAdapter<TStorage> {
+    /// Decode `entry`, the value of `key`, with `decoder`, reading the zip file with `read` as in [`decompress_entry_with`](Self::decompress_entry_with).
+    ///
+    /// The local file header of the entry is read first, then its compressed data is requested in full and fed to the decoder as it is returned.
+    ///
+    /// # Errors
+    /// Returns a [`StorageError`] if the zip file cannot be read, the local file header is invalid, or the entry cannot be decoded.
+    pub(crate) fn decode_entry_with(
+        key: &StoreKey,
+        entry: &StoredEntry,
+        mut decoder: EntryDecoder,
+        end_offset: u64,
+        progress: Option<&ZipReadProgressCallback>,
+        mut read: impl FnMut(u64, u64) -> Result<Bytes, StorageError>,
+    ) -> Result<Vec<u8>, StorageError> {
+        // `read` may return the compressed data that follows the local file header
+        let mut header = read(entry.header_offset, 30)?;
+        while header.len() < 30 {
+            let more = read(
+                entry.header_offset + header.len() as u64,
+                30 - header.len() as u64,
+            )?;
+            if more.is_empty() {
+                break;
+            }
+            header = Bytes::from([header, more].concat());
+        }
+        let data_offset = local_data_offset(key, entry.header_offset, &header)?;
+        let skip = usize::try_from(data_offset - entry.header_offset).unwrap_or(usize::MAX);
+        let mut buffered = header.slice(skip.min(header.len())..);
+        let mut read_offset = data_offset + buffered.len() as u64;
+
+        while decoder.wants_read() {
+            if buffered.is_empty() {
+                let to_read = decoder
+                    .remaining()
+                    .min(end_offset.saturating_sub(read_offset));
+                if to_read == 0 {
+                    break;
+                }
+                buffered = read(read_offset, to_read)?;
+                if buffered.is_empty() {
+                    break;
+                }
+                read_offset += buffered.len() as u64;
+            }
+            decoder.decode(key, &buffered)?;
+            buffered = Bytes::new();
+            report_read_progress(
+                progress,
+                key,
+                entry,
+                read_offset - entry.header_offset,
+                decoder.decoded_len(),
+            );
+        }
+
+        Self::finish_decoding(key, entry, decoder)
+    }
+
+    /// Finish decoding `entry`, the value of `key`, with `decoder`, returning the required prefix of the value.
+    ///
+    /// # Errors
+    /// Returns a [`StorageError`] if the size of the decoded prefix is not as expected, or the CRC-32 of a value decoded in full does not match.
+    pub(crate) fn finish_decoding(
+        key: &StoreKey,
+        entry: &StoredEntry,
+        decoder: EntryDecoder,
+    ) -> Result<Vec<u8>, StorageError> {
+        if decoder.decoded.len() != decoder.expected_size {
+            return Err(StorageError::Other(format!(
+                "zip decompressed entry size mismatch: expected {}, got {}",
+                decoder.expected_size,
+                decoder.decoded.len()
+            )));
+        }
+        if let Some(hasher) = decoder.hasher {
+            let actual = hasher.finalize();
+            if actual != entry.crc32 {
+                return Err(Self::checksum_error(key, entry.crc32, actual));
+            }
+        }
+        Ok(decoder.decoded)
+    }
+}
+
+/// The offset of the compressed data of the entry of `key` with its local file header at `header_offset`, given `header`, the bytes of the zip file from `header_offset`.
+///
+/// # Errors
+/// Returns a [`StorageError`] if `header` is too short or is not a local file header.
+fn local_data_offset(
+    key: &StoreKey,
+    header_offset: u64,
+    header: &[u8],
+) -> Result<u64, StorageError> {
+    if header.len() < 30 || !header.starts_with(b"PK\x03\x04") {
+        return Err(StorageError::Other(format!(
+            "zip entry {key}: invalid local file header at offset {header_offset}"
+        )));
+    }
+    let name_len = u64::from(u16::from_le_bytes([header[26], header[27]]));
+    let extra_len = u64::from(u16::from_le_bytes([header[28], header[29]]));
+    Ok(header_offset + 30 + name_len + extra_len)
+}
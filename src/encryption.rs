@@ -0,0 +1,301 @@
+//! Decryption of entries encrypted with the traditional PKWARE encryption (`ZipCrypto`) or the `WinZip` AES extension (AE-1 and AE-2).
+
+use aes::{
+    Aes128, Aes192, Aes256,
+    cipher::{BlockEncrypt, BlockSizeUser, KeyInit, consts::U16, generic_array::GenericArray},
+};
+use hmac::{Hmac, Mac};
+use rc_zip::parse::Method;
+use sha1::Sha1;
+use zarrs_storage::{Bytes, StorageError, StoreKey};
+
+use crate::{METHOD_AES, ZipReadError, ZipStorageAdapter, index::StoredEntry};
+
+/// The tag of the AES extra field.
+const AES_EXTRA_FIELD_TAG: u16 = 0x9901;
+/// The size of the data of the AES extra field.
+const AES_EXTRA_FIELD_SIZE: usize = 7;
+/// The vendor version of AE-1 entries, which have a CRC-32.
+const AES_VENDOR_VERSION_AE1: u16 = 1;
+/// The number of PBKDF2 iterations deriving the keys of an entry from the password.
+const AES_KEY_ITERATIONS: u32 = 1000;
+/// The size of the password verification value that follows the salt.
+const AES_PASSWORD_VERIFICATION_SIZE: usize = 2;
+/// The size of the authentication code that follows the encrypted data.
+const AES_AUTHENTICATION_CODE_SIZE: usize = 10;
+/// The size of the encryption header that precedes the encrypted data of a `ZipCrypto` entry.
+const ZIP_CRYPTO_HEADER_SIZE: usize = 12;
+/// General purpose flag bit 3: the compressed data is followed by a data descriptor.
+const FLAG_DATA_DESCRIPTOR: u16 = 1 << 3;
+
+/// The CRC-32 lookup table of the `ZipCrypto` key updates.
+#[allow(clippy::cast_possible_truncation)]
+const ZIP_CRYPTO_CRC32_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 0 {
+                crc >> 1
+            } else {
+                (crc >> 1) ^ 0xEDB8_8320
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// The keys of the traditional PKWARE encryption (`ZipCrypto`).
+struct ZipCryptoKeys([u32; 3]);
+
+impl ZipCryptoKeys {
+    /// The keys initialised with `password`.
+    fn new(password: &[u8]) -> Self {
+        let mut keys = Self([0x1234_5678, 0x2345_6789, 0x3456_7890]);
+        for &byte in password {
+            keys.update(byte);
+        }
+        keys
+    }
+
+    /// Update the keys with a byte of plain text.
+    fn update(&mut self, byte: u8) {
+        let crc32 = |crc: u32, byte: u8| {
+            ZIP_CRYPTO_CRC32_TABLE[((crc ^ u32::from(byte)) & 0xFF) as usize] ^ (crc >> 8)
+        };
+        self.0[0] = crc32(self.0[0], byte);
+        self.0[1] = self.0[1]
+            .wrapping_add(self.0[0] & 0xFF)
+            .wrapping_mul(134_775_813)
+            .wrapping_add(1);
+        self.0[2] = crc32(self.0[2], (self.0[1] >> 24) as u8);
+    }
+
+    /// Decrypt `data` in place.
+    fn decrypt(&mut self, data: &mut [u8]) {
+        for byte in data {
+            let temp = (self.0[2] | 2) & 0xFFFF;
+            *byte ^= (temp.wrapping_mul(temp ^ 1) >> 8) as u8;
+            self.update(*byte);
+        }
+    }
+}
+
+/// The AES extra field of an encrypted entry.
+struct AesExtraField {
+    /// The vendor version, 1 for AE-1 and 2 for AE-2.
+    vendor_version: u16,
+    /// The size in bytes of the AES key, from the encryption strength.
+    key_size: usize,
+    /// The compression method of the entry, before it was encrypted.
+    method: Method,
+}
+
+impl AesExtraField {
+    /// Find the AES extra field in `extra`, the extra fields of a local file header.
+    fn find(mut extra: &[u8]) -> Option<Self> {
+        while extra.len() >= 4 {
+            let tag = u16::from_le_bytes([extra[0], extra[1]]);
+            let len = usize::from(u16::from_le_bytes([extra[2], extra[3]]));
+            let field = extra.get(4..4 + len)?;
+            if tag == AES_EXTRA_FIELD_TAG && len >= AES_EXTRA_FIELD_SIZE {
+                let key_size = match field[4] {
+                    1 => 16,
+                    2 => 24,
+                    3 => 32,
+                    _ => return None,
+                };
+                return Some(Self {
+                    vendor_version: u16::from_le_bytes([field[0], field[1]]),
+                    key_size,
+                    method: Method::from(u16::from_le_bytes([field[5], field[6]])),
+                });
+            }
+            extra = &extra[4 + len..];
+        }
+        None
+    }
+}
+
+/// Decrypt `data` in place with the AES `key` in counter mode, with the little-endian counter starting at 1.
+fn apply_keystream<C: BlockEncrypt + BlockSizeUser<BlockSize = U16> + KeyInit>(
+    key: &[u8],
+    data: &mut [u8],
+) -> Result<(), StorageError> {
+    let cipher = C::new_from_slice(key).map_err(|err| StorageError::Other(err.to_string()))?;
+    for (counter, chunk) in (1u128..).zip(data.chunks_mut(16)) {
+        let mut block = GenericArray::from(counter.to_le_bytes());
+        cipher.encrypt_block(&mut block);
+        for (byte, key) in chunk.iter_mut().zip(block.iter()) {
+            *byte ^= key;
+        }
+    }
+    Ok(())
+}
+
+impl<TStorage: ?Sized> ZipStorageAdapter<TStorage> {
+    /// Decrypt and decompress the encrypted `entry`, the value of `key`, given `local`, its local file header followed by its compressed data.
+    ///
+    /// Entries with the AES extra field are decrypted with AES, and other encrypted entries with `ZipCrypto`.
+    /// The password is verified before the data is decrypted, as is the authentication code of AES encrypted entries.
+    ///
+    /// # Errors
+    /// Returns a [`StorageError`] if the adapter has no password, the password is wrong, the entry fails authentication, or its data cannot be decompressed.
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn decrypt_entry(
+        &self,
+        key: &StoreKey,
+        entry: &StoredEntry,
+        local: &[u8],
+    ) -> Result<Bytes, StorageError> {
+        let password = self
+            .password
+            .as_deref()
+            .ok_or_else(|| read_error(key, &ZipReadError::PasswordRequired))?;
+
+        // Split the local file header into its extra fields and data
+        let header = local.get(..30).ok_or_else(|| invalid(key))?;
+        let name_len = usize::from(u16::from_le_bytes([header[26], header[27]]));
+        let extra_len = usize::from(u16::from_le_bytes([header[28], header[29]]));
+        let extra = local
+            .get(30 + name_len..30 + name_len + extra_len)
+            .ok_or_else(|| invalid(key))?;
+        let data = &local[30 + name_len + extra_len..];
+
+        let (compressed, method, verify_crc32) = if u16::from(entry.method) == METHOD_AES {
+            let field = AesExtraField::find(extra).ok_or_else(|| invalid(key))?;
+            let compressed = decrypt_aes(key, &field, password, data)?;
+            // The CRC-32 of AE-2 entries is zero, the authentication code verifies them instead
+            (
+                compressed,
+                field.method,
+                field.vendor_version == AES_VENDOR_VERSION_AE1,
+            )
+        } else {
+            // The password is checked against the high byte of the modification time if the CRC-32 follows the data
+            let flags = u16::from_le_bytes([header[6], header[7]]);
+            let check = if flags & FLAG_DATA_DESCRIPTOR == 0 {
+                (entry.crc32 >> 24) as u8
+            } else {
+                header[11]
+            };
+            let compressed = decrypt_zip_crypto(key, password, check, data)?;
+            (compressed, entry.method, true)
+        };
+
+        let value = match method {
+            Method::Store => compressed,
+            Method::Deflate => miniz_oxide::inflate::decompress_to_vec_with_limit(
+                &compressed,
+                entry.uncompressed_size as usize,
+            )
+            .map_err(|err| StorageError::Other(format!("Decompression error: {err}")))?,
+            #[cfg(feature = "zstd")]
+            Method::Zstd => {
+                zstd::bulk::decompress(&compressed, entry.uncompressed_size as usize)
+                    .map_err(|err| StorageError::Other(format!("Decompression error: {err}")))?
+            }
+            method => {
+                return Err(StorageError::Unsupported(format!(
+                    "zip entry {key}: {}",
+                    ZipReadError::UnsupportedMethod(method)
+                )));
+            }
+        };
+        if value.len() as u64 != entry.uncompressed_size {
+            return Err(StorageError::Other(format!(
+                "zip decompressed entry size mismatch: expected {}, got {}",
+                entry.uncompressed_size,
+                value.len()
+            )));
+        }
+        if verify_crc32 {
+            let actual = crc32fast::hash(&value);
+            if actual != entry.crc32 {
+                return Err(Self::checksum_error(key, entry.crc32, actual));
+            }
+        }
+        Ok(Bytes::from(value))
+    }
+}
+
+/// An error reading the entry of `key`.
+fn read_error(key: &StoreKey, err: &ZipReadError) -> StorageError {
+    StorageError::Other(format!("zip entry {key}: {err}"))
+}
+
+/// An error for the invalid encrypted data of the entry of `key`.
+fn invalid(key: &StoreKey) -> StorageError {
+    StorageError::Other(format!("zip entry {key}: invalid encrypted data"))
+}
+
+/// Decrypt `data`, the data of an AES encrypted entry with the AES extra `field`, with `password`.
+///
+/// The password verification value and authentication code are verified before the data is decrypted.
+fn decrypt_aes(
+    key: &StoreKey,
+    field: &AesExtraField,
+    password: &[u8],
+    data: &[u8],
+) -> Result<Vec<u8>, StorageError> {
+    // Split the data into the salt, password verification value, encrypted data, and authentication code
+    let salt_size = field.key_size / 2;
+    if data.len() < salt_size + AES_PASSWORD_VERIFICATION_SIZE + AES_AUTHENTICATION_CODE_SIZE {
+        return Err(invalid(key));
+    }
+    let (salt, data) = data.split_at(salt_size);
+    let (verification, data) = data.split_at(AES_PASSWORD_VERIFICATION_SIZE);
+    let (encrypted, authentication_code) = data.split_at(data.len() - AES_AUTHENTICATION_CODE_SIZE);
+
+    // Derive the AES key, HMAC key, and password verification value
+    let mut derived = vec![0; 2 * field.key_size + AES_PASSWORD_VERIFICATION_SIZE];
+    pbkdf2::pbkdf2_hmac::<Sha1>(password, salt, AES_KEY_ITERATIONS, &mut derived);
+    let (aes_key, derived) = derived.split_at(field.key_size);
+    let (hmac_key, expected_verification) = derived.split_at(field.key_size);
+    if verification != expected_verification {
+        return Err(read_error(key, &ZipReadError::WrongPassword));
+    }
+    let mut mac = <Hmac<Sha1> as Mac>::new_from_slice(hmac_key)
+        .map_err(|err| StorageError::Other(err.to_string()))?;
+    mac.update(encrypted);
+    mac.verify_truncated_left(authentication_code)
+        .map_err(|_| read_error(key, &ZipReadError::AuthenticationFailed))?;
+
+    let mut compressed = encrypted.to_vec();
+    match field.key_size {
+        16 => apply_keystream::<Aes128>(aes_key, &mut compressed)?,
+        24 => apply_keystream::<Aes192>(aes_key, &mut compressed)?,
+        _ => apply_keystream::<Aes256>(aes_key, &mut compressed)?,
+    }
+    Ok(compressed)
+}
+
+/// Decrypt `data`, the data of a `ZipCrypto` encrypted entry, with `password`.
+///
+/// The last byte of the decrypted encryption header must match `check`.
+/// A wrong password passes this check with a probability of 1/256, in which case the CRC-32 of the entry is expected to not match.
+fn decrypt_zip_crypto(
+    key: &StoreKey,
+    password: &[u8],
+    check: u8,
+    data: &[u8],
+) -> Result<Vec<u8>, StorageError> {
+    if data.len() < ZIP_CRYPTO_HEADER_SIZE {
+        return Err(invalid(key));
+    }
+    let mut keys = ZipCryptoKeys::new(password);
+    let mut header = [0; ZIP_CRYPTO_HEADER_SIZE];
+    header.copy_from_slice(&data[..ZIP_CRYPTO_HEADER_SIZE]);
+    keys.decrypt(&mut header);
+    if header[ZIP_CRYPTO_HEADER_SIZE - 1] != check {
+        return Err(read_error(key, &ZipReadError::WrongPassword));
+    }
+    let mut compressed = data[ZIP_CRYPTO_HEADER_SIZE..].to_vec();
+    keys.decrypt(&mut compressed);
+    Ok(compressed)
+}
@@ -0,0 +1,604 @@
+//! Zip record encoding and decoding.
+//!
+//! `rc-zip` handles parsing of the central directory for reading, but the write path
+//! needs to locate, copy, and emit records itself.
+//! See the [APPNOTE](https://pkware.cachefly.net/webdocs/casestudies/APPNOTE.TXT) for the record layouts.
+
+const LOCAL_FILE_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+const CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0201_4b50;
+const CENTRAL_DIRECTORY_RECORD_SIZE: usize = 46;
+/// The offset of the local file header offset field in a central directory record.
+const CENTRAL_DIRECTORY_HEADER_OFFSET_OFFSET: usize = 42;
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0605_4b50;
+const END_OF_CENTRAL_DIRECTORY_SIZE: usize = 22;
+const ZIP64_END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0606_4b50;
+const ZIP64_END_OF_CENTRAL_DIRECTORY_SIZE: u64 = 56;
+const ZIP64_END_OF_CENTRAL_DIRECTORY_LOCATOR_SIGNATURE: u32 = 0x0706_4b50;
+const ZIP64_END_OF_CENTRAL_DIRECTORY_LOCATOR_SIZE: usize = 20;
+const ZIP64_EXTRA_FIELD_TAG: u16 = 0x0001;
+/// The size of the ZIP64 extra field of a local file header, with the uncompressed and compressed sizes.
+const ZIP64_LOCAL_EXTRA_FIELD_SIZE: u16 = 4 + 16;
+/// The size of the ZIP64 extra field of a central directory record, with the sizes and the local file header offset.
+const ZIP64_CENTRAL_DIRECTORY_EXTRA_FIELD_SIZE: u16 = 4 + 24;
+const DATA_DESCRIPTOR_SIGNATURE: u32 = 0x0807_4b50;
+/// The tag of the extra field that pads the local file header of an aligned entry, as written by Android's `zipalign`.
+const ALIGNMENT_EXTRA_FIELD_TAG: u16 = 0xD935;
+/// The size of the alignment extra field without padding: the tag, the length, and the alignment.
+const ALIGNMENT_EXTRA_FIELD_SIZE: usize = 6;
+/// The size of the fixed part of a local file header.
+const LOCAL_FILE_HEADER_SIZE: usize = 30;
+
+/// The offset of the CRC-32 field in a local file header.
+pub(crate) const LOCAL_FILE_HEADER_CRC32_OFFSET: u64 = 14;
+/// The offset of the CRC-32 field in a central directory record.
+const CENTRAL_DIRECTORY_CRC32_OFFSET: usize = 16;
+
+/// Version made by: UNIX, specification version 2.0.
+const VERSION_MADE_BY: u16 = 0x0314;
+/// Version needed to extract: 2.0.
+const VERSION_NEEDED: u16 = 20;
+/// Version needed to extract entries with ZIP64 extensions: 4.5.
+const VERSION_NEEDED_ZIP64: u16 = 45;
+/// Version needed to extract Zstandard compressed entries: 6.3.
+#[cfg(feature = "zstd")]
+const VERSION_NEEDED_ZSTD: u16 = 63;
+/// General purpose flag bit 3: the compressed data is followed by a data descriptor.
+pub(crate) const FLAG_DATA_DESCRIPTOR: u16 = 1 << 3;
+/// General purpose flag bit 11: the file name is UTF-8.
+const FLAG_UTF8: u16 = 1 << 11;
+/// MS-DOS date for 1980-01-01, the earliest representable date.
+///
+/// Written entries use a fixed timestamp so that archives are reproducible.
+const DOS_DATE: u16 = (1 << 5) | 1;
+const DOS_TIME: u16 = 0;
+/// External attributes of a regular file with mode `0o644`.
+const FILE_EXTERNAL_ATTRIBUTES: u32 = 0o100_644 << 16;
+/// External attributes of a directory with mode `0o755`, including the MS-DOS directory attribute.
+const DIRECTORY_EXTERNAL_ATTRIBUTES: u32 = (0o040_755 << 16) | 0x10;
+
+/// Zip compression method identifiers.
+pub(crate) const METHOD_STORE: u16 = 0;
+pub(crate) const METHOD_DEFLATE: u16 = 8;
+#[cfg(feature = "zstd")]
+pub(crate) const METHOD_ZSTD: u16 = 93;
+
+fn read_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([buf[offset], buf[offset + 1]])
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(
+        buf[offset..offset + 4]
+            .try_into()
+            .expect("slice of length 4"),
+    )
+}
+
+fn read_u64(buf: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(
+        buf[offset..offset + 8]
+            .try_into()
+            .expect("slice of length 8"),
+    )
+}
+
+/// The end of central directory record, merged with the ZIP64 end of central directory record if present.
+#[derive(Debug, Clone)]
+pub(crate) struct EndOfCentralDirectory {
+    /// The number of entries in the central directory.
+    pub(crate) num_entries: u64,
+    /// The size of the central directory in bytes.
+    pub(crate) directory_size: u64,
+    /// The offset of the start of the central directory.
+    pub(crate) directory_offset: u64,
+    /// The archive comment.
+    pub(crate) comment: Vec<u8>,
+    /// The offset of the ZIP64 end of central directory record, if the archive has one.
+    pub(crate) zip64_record_offset: Option<u64>,
+}
+
+impl EndOfCentralDirectory {
+    /// Find the end of central directory record in the trailing bytes of an archive.
+    ///
+    /// `tail_offset` is the offset of `tail` in the archive.
+    /// Returns [`None`] if no end of central directory record is found.
+    pub(crate) fn find(tail: &[u8], tail_offset: u64) -> Option<Self> {
+        let last = tail.len().checked_sub(END_OF_CENTRAL_DIRECTORY_SIZE)?;
+        // Search backwards, the record is followed only by its variable length comment
+        let position = (0..=last).rev().find(|&i| {
+            read_u32(tail, i) == END_OF_CENTRAL_DIRECTORY_SIGNATURE
+                && i + END_OF_CENTRAL_DIRECTORY_SIZE + usize::from(read_u16(tail, i + 20))
+                    == tail.len()
+        })?;
+        let record = &tail[position..];
+
+        let zip64_record_offset = position
+            .checked_sub(ZIP64_END_OF_CENTRAL_DIRECTORY_LOCATOR_SIZE)
+            .filter(|&locator| {
+                read_u32(tail, locator) == ZIP64_END_OF_CENTRAL_DIRECTORY_LOCATOR_SIGNATURE
+            })
+            .map(|locator| read_u64(tail, locator + 8))
+            .filter(|&offset| offset < tail_offset + position as u64);
+
+        Some(Self {
+            num_entries: u64::from(read_u16(record, 10)),
+            directory_size: u64::from(read_u32(record, 12)),
+            directory_offset: u64::from(read_u32(record, 16)),
+            comment: record[END_OF_CENTRAL_DIRECTORY_SIZE..].to_vec(),
+            zip64_record_offset,
+        })
+    }
+
+    /// Update the record with the values in a ZIP64 end of central directory record.
+    ///
+    /// Returns false if `record` is not a ZIP64 end of central directory record.
+    pub(crate) fn apply_zip64(&mut self, record: &[u8]) -> bool {
+        if (record.len() as u64) < ZIP64_END_OF_CENTRAL_DIRECTORY_SIZE
+            || read_u32(record, 0) != ZIP64_END_OF_CENTRAL_DIRECTORY_SIGNATURE
+        {
+            return false;
+        }
+        self.num_entries = read_u64(record, 32);
+        self.directory_size = read_u64(record, 40);
+        self.directory_offset = read_u64(record, 48);
+        true
+    }
+}
+
+/// A record in the central directory of an existing archive.
+pub(crate) struct CentralDirectoryRecord<'a> {
+    /// The raw bytes of the record, including the name, extra field, and comment.
+    pub(crate) raw: &'a [u8],
+    /// The offset of the local file header.
+    pub(crate) header_offset: u64,
+    /// The compressed size of the entry.
+    pub(crate) compressed_size: u64,
+    /// The general purpose bit flags of the entry.
+    pub(crate) flags: u16,
+    /// The offset of the local file header offset field in `raw`.
+    header_offset_field: usize,
+}
+
+impl CentralDirectoryRecord<'_> {
+    /// The offset of the CRC-32 field of the record, given `directory`, the central directory it was parsed from at `directory_offset`.
+    pub(crate) fn crc32_offset(&self, directory: &[u8], directory_offset: u64) -> u64 {
+        let record_offset = self.raw.as_ptr() as usize - directory.as_ptr() as usize;
+        directory_offset + (record_offset + CENTRAL_DIRECTORY_CRC32_OFFSET) as u64
+    }
+
+    /// The name of the entry.
+    pub(crate) fn name(&self) -> &[u8] {
+        let name_len = usize::from(read_u16(self.raw, 28));
+        &self.raw[CENTRAL_DIRECTORY_RECORD_SIZE..CENTRAL_DIRECTORY_RECORD_SIZE + name_len]
+    }
+
+    /// Append the record to `out`, with its local file header at `header_offset`.
+    ///
+    /// # Errors
+    /// Returns an error message if the record has no ZIP64 extended information and `header_offset` does not fit in 32 bits.
+    pub(crate) fn write_with_header_offset(
+        &self,
+        out: &mut Vec<u8>,
+        header_offset: u64,
+    ) -> Result<(), String> {
+        let start = out.len();
+        if self.header_offset_field == CENTRAL_DIRECTORY_HEADER_OFFSET_OFFSET {
+            let header_offset = u32::try_from(header_offset)
+                .ok()
+                .filter(|&offset| offset != u32::MAX)
+                .ok_or_else(|| {
+                    format!(
+                        "the local file header offset {header_offset} requires ZIP64 extensions"
+                    )
+                })?;
+            out.extend_from_slice(self.raw);
+            out[start + self.header_offset_field..][..4]
+                .copy_from_slice(&header_offset.to_le_bytes());
+        } else {
+            out.extend_from_slice(self.raw);
+            out[start + self.header_offset_field..][..8]
+                .copy_from_slice(&header_offset.to_le_bytes());
+        }
+        Ok(())
+    }
+}
+
+/// The offset of the CRC-32 field of a data descriptor from its start, given its first bytes.
+///
+/// The data descriptor signature is optional.
+pub(crate) fn data_descriptor_crc32_offset(descriptor: &[u8]) -> u64 {
+    if descriptor.len() >= 4 && read_u32(descriptor, 0) == DATA_DESCRIPTOR_SIGNATURE {
+        4
+    } else {
+        0
+    }
+}
+
+/// The size of the local data of the entry of `record`, its local file header, data, and data descriptor, given `local`, the bytes of the archive from its local file header.
+///
+/// # Errors
+/// Returns an error message if `local` does not start with a local file header or is too short.
+pub(crate) fn local_data_size(
+    local: &[u8],
+    record: &CentralDirectoryRecord<'_>,
+) -> Result<usize, String> {
+    if local.len() < LOCAL_FILE_HEADER_SIZE || read_u32(local, 0) != LOCAL_FILE_HEADER_SIGNATURE {
+        return Err("invalid local file header".to_string());
+    }
+    let name_len = usize::from(read_u16(local, 26));
+    let extra_len = usize::from(read_u16(local, 28));
+    let extra_start = LOCAL_FILE_HEADER_SIZE + name_len;
+    let data_start = extra_start + extra_len;
+    let mut size = usize::try_from(record.compressed_size)
+        .ok()
+        .and_then(|compressed_size| data_start.checked_add(compressed_size))
+        .ok_or_else(|| "entry is too large".to_string())?;
+    if record.flags & FLAG_DATA_DESCRIPTOR != 0 {
+        // The sizes in the data descriptor are 64-bit if the local file header has a ZIP64 extra field
+        let extra = local
+            .get(extra_start..data_start)
+            .ok_or_else(|| "truncated local file header".to_string())?;
+        let sizes_len = if find_extra_field(extra, ZIP64_EXTRA_FIELD_TAG).is_some() {
+            16
+        } else {
+            8
+        };
+        // The signature is optional, and some writers set the flag on directories without writing a data descriptor,
+        // so the data descriptor is identified by its CRC-32
+        let crc32 = read_u32(record.raw, CENTRAL_DIRECTORY_CRC32_OFFSET);
+        let read_at = |offset: usize| {
+            local
+                .get(offset..offset + 4)
+                .map(|_| read_u32(local, offset))
+        };
+        if read_at(size) == Some(DATA_DESCRIPTOR_SIGNATURE) && read_at(size + 4) == Some(crc32) {
+            size += 8 + sizes_len;
+        } else if read_at(size) == Some(crc32) {
+            size += 4 + sizes_len;
+        }
+    }
+    if size > local.len() {
+        return Err("truncated local data".to_string());
+    }
+    Ok(size)
+}
+
+/// Split a central directory into its records.
+///
+/// # Errors
+/// Returns an error message if the central directory is malformed.
+pub(crate) fn parse_central_directory(
+    mut directory: &[u8],
+) -> Result<Vec<CentralDirectoryRecord<'_>>, String> {
+    let mut records = Vec::new();
+    while !directory.is_empty() {
+        if directory.len() < CENTRAL_DIRECTORY_RECORD_SIZE
+            || read_u32(directory, 0) != CENTRAL_DIRECTORY_SIGNATURE
+        {
+            return Err("invalid central directory record".to_string());
+        }
+        let name_len = usize::from(read_u16(directory, 28));
+        let extra_len = usize::from(read_u16(directory, 30));
+        let comment_len = usize::from(read_u16(directory, 32));
+        let len = CENTRAL_DIRECTORY_RECORD_SIZE + name_len + extra_len + comment_len;
+        if directory.len() < len {
+            return Err("truncated central directory record".to_string());
+        }
+        let (raw, rest) = directory.split_at(len);
+
+        let extra_start = CENTRAL_DIRECTORY_RECORD_SIZE + name_len;
+        let extra = &raw[extra_start..extra_start + extra_len];
+        let zip64_field = || {
+            find_extra_field(extra, ZIP64_EXTRA_FIELD_TAG)
+                .ok_or_else(|| "missing ZIP64 extended information".to_string())
+        };
+        // Saturated fields are in the ZIP64 extra field, in the order uncompressed size, compressed size, and local file header offset
+        let uncompressed_saturated = read_u32(raw, 24) == u32::MAX;
+        let mut zip64_position = if uncompressed_saturated { 8 } else { 0 };
+
+        let mut compressed_size = u64::from(read_u32(raw, 20));
+        if compressed_size == u64::from(u32::MAX) {
+            compressed_size = zip64_field()?
+                .get(zip64_position..zip64_position + 8)
+                .map(|field| read_u64(field, 0))
+                .ok_or_else(|| "truncated ZIP64 extended information".to_string())?;
+            zip64_position += 8;
+        }
+
+        let mut header_offset_field = CENTRAL_DIRECTORY_HEADER_OFFSET_OFFSET;
+        let mut header_offset = u64::from(read_u32(raw, header_offset_field));
+        if header_offset == u64::from(u32::MAX) {
+            let field = zip64_field()?;
+            header_offset = field
+                .get(zip64_position..zip64_position + 8)
+                .map(|field| read_u64(field, 0))
+                .ok_or_else(|| "truncated ZIP64 extended information".to_string())?;
+            header_offset_field = field.as_ptr() as usize - raw.as_ptr() as usize + zip64_position;
+        }
+
+        records.push(CentralDirectoryRecord {
+            raw,
+            header_offset,
+            compressed_size,
+            flags: read_u16(raw, 8),
+            header_offset_field,
+        });
+        directory = rest;
+    }
+    Ok(records)
+}
+
+/// Find the data of the extra field with `tag`.
+fn find_extra_field(mut extra: &[u8], tag: u16) -> Option<&[u8]> {
+    while extra.len() >= 4 {
+        let field_tag = read_u16(extra, 0);
+        let field_len = usize::from(read_u16(extra, 2));
+        let field = extra.get(4..4 + field_len)?;
+        if field_tag == tag {
+            return Some(field);
+        }
+        extra = &extra[4 + field_len..];
+    }
+    None
+}
+
+/// The metadata of an entry written by the adapter.
+pub(crate) struct EntryRecord<'a> {
+    pub(crate) name: &'a str,
+    pub(crate) method: u16,
+    pub(crate) crc32: u32,
+    pub(crate) compressed_size: u64,
+    pub(crate) uncompressed_size: u64,
+    pub(crate) header_offset: u64,
+    /// Whether the entry is written with ZIP64 extended information extra fields.
+    pub(crate) zip64: bool,
+    /// The alignment of the data of the entry, or 0 or 1 if it is not aligned.
+    pub(crate) alignment: u64,
+    /// Whether the CRC-32 and sizes follow the data in a data descriptor, rather than being in the local file header.
+    pub(crate) data_descriptor: bool,
+}
+
+impl EntryRecord<'_> {
+    /// Returns true if the entry cannot be represented without ZIP64 extensions.
+    pub(crate) fn requires_zip64(&self) -> bool {
+        self.compressed_size >= u64::from(u32::MAX)
+            || self.uncompressed_size >= u64::from(u32::MAX)
+            || self.header_offset >= u64::from(u32::MAX)
+    }
+
+    /// The version needed to extract the entry.
+    fn version_needed(&self) -> u16 {
+        #[cfg(feature = "zstd")]
+        if self.method == METHOD_ZSTD {
+            return VERSION_NEEDED_ZSTD;
+        }
+        if self.zip64 {
+            VERSION_NEEDED_ZIP64
+        } else {
+            VERSION_NEEDED
+        }
+    }
+
+    /// The general purpose bit flags of the entry.
+    fn flags(&self) -> u16 {
+        if self.data_descriptor {
+            FLAG_UTF8 | FLAG_DATA_DESCRIPTOR
+        } else {
+            FLAG_UTF8
+        }
+    }
+
+    /// The value of a 32-bit field of the entry, which is saturated if the entry is written with ZIP64 extensions.
+    #[allow(clippy::cast_possible_truncation)]
+    fn field_u32(&self, value: u64) -> u32 {
+        if self.zip64 { u32::MAX } else { value as u32 }
+    }
+
+    /// The size of the ZIP64 extra field of the local file header.
+    fn local_zip64_extra_field_size(&self) -> usize {
+        if self.zip64 {
+            usize::from(ZIP64_LOCAL_EXTRA_FIELD_SIZE)
+        } else {
+            0
+        }
+    }
+
+    /// The number of padding bytes of the alignment extra field of the local file header, if the entry is aligned.
+    #[allow(clippy::cast_possible_truncation)]
+    fn alignment_padding(&self) -> Option<usize> {
+        if self.alignment <= 1 {
+            return None;
+        }
+        let unpadded_data_offset = self.header_offset
+            + (LOCAL_FILE_HEADER_SIZE
+                + self.name.len()
+                + self.local_zip64_extra_field_size()
+                + ALIGNMENT_EXTRA_FIELD_SIZE) as u64;
+        // The padding is less than the alignment, so it fits in a usize
+        Some(((self.alignment - unpadded_data_offset % self.alignment) % self.alignment) as usize)
+    }
+
+    /// The size of the extra field of the local file header.
+    pub(crate) fn local_extra_field_size(&self) -> usize {
+        self.local_zip64_extra_field_size()
+            + self
+                .alignment_padding()
+                .map_or(0, |padding| ALIGNMENT_EXTRA_FIELD_SIZE + padding)
+    }
+
+    /// Append a ZIP64 extended information extra field with `values` to `out`.
+    #[allow(clippy::cast_possible_truncation)]
+    fn write_zip64_extra_field(out: &mut Vec<u8>, values: &[u64]) {
+        out.extend_from_slice(&ZIP64_EXTRA_FIELD_TAG.to_le_bytes());
+        out.extend_from_slice(&((values.len() * 8) as u16).to_le_bytes());
+        for value in values {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    /// Append the local file header to `out`.
+    ///
+    /// The CRC-32 and sizes are zero if the entry has a data descriptor.
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn write_local_header(&self, out: &mut Vec<u8>) {
+        let (crc32, compressed_size, uncompressed_size) = if self.data_descriptor {
+            (0, 0, 0)
+        } else {
+            (self.crc32, self.compressed_size, self.uncompressed_size)
+        };
+        out.extend_from_slice(&LOCAL_FILE_HEADER_SIGNATURE.to_le_bytes());
+        out.extend_from_slice(&self.version_needed().to_le_bytes());
+        out.extend_from_slice(&self.flags().to_le_bytes());
+        out.extend_from_slice(&self.method.to_le_bytes());
+        out.extend_from_slice(&DOS_TIME.to_le_bytes());
+        out.extend_from_slice(&DOS_DATE.to_le_bytes());
+        out.extend_from_slice(&crc32.to_le_bytes());
+        out.extend_from_slice(&self.field_u32(compressed_size).to_le_bytes());
+        out.extend_from_slice(&self.field_u32(uncompressed_size).to_le_bytes());
+        out.extend_from_slice(&(self.name.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(self.local_extra_field_size() as u16).to_le_bytes());
+        out.extend_from_slice(self.name.as_bytes());
+        if self.zip64 {
+            Self::write_zip64_extra_field(out, &[uncompressed_size, compressed_size]);
+        }
+        if let Some(padding) = self.alignment_padding() {
+            out.extend_from_slice(&ALIGNMENT_EXTRA_FIELD_TAG.to_le_bytes());
+            out.extend_from_slice(&((2 + padding) as u16).to_le_bytes());
+            // The alignment is informative, it is saturated if it does not fit
+            let alignment = u16::try_from(self.alignment).unwrap_or(u16::MAX);
+            out.extend_from_slice(&alignment.to_le_bytes());
+            out.resize(out.len() + padding, 0);
+        }
+    }
+
+    /// Append the data descriptor to `out`, with a signature, and 64-bit sizes if the entry is written with ZIP64 extensions.
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn write_data_descriptor(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&DATA_DESCRIPTOR_SIGNATURE.to_le_bytes());
+        out.extend_from_slice(&self.crc32.to_le_bytes());
+        if self.zip64 {
+            out.extend_from_slice(&self.compressed_size.to_le_bytes());
+            out.extend_from_slice(&self.uncompressed_size.to_le_bytes());
+        } else {
+            out.extend_from_slice(&(self.compressed_size as u32).to_le_bytes());
+            out.extend_from_slice(&(self.uncompressed_size as u32).to_le_bytes());
+        }
+    }
+
+    /// Append the central directory record to `out`.
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn write_central_directory_record(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes());
+        out.extend_from_slice(&VERSION_MADE_BY.to_le_bytes());
+        out.extend_from_slice(&self.version_needed().to_le_bytes());
+        out.extend_from_slice(&self.flags().to_le_bytes());
+        out.extend_from_slice(&self.method.to_le_bytes());
+        out.extend_from_slice(&DOS_TIME.to_le_bytes());
+        out.extend_from_slice(&DOS_DATE.to_le_bytes());
+        out.extend_from_slice(&self.crc32.to_le_bytes());
+        out.extend_from_slice(&self.field_u32(self.compressed_size).to_le_bytes());
+        out.extend_from_slice(&self.field_u32(self.uncompressed_size).to_le_bytes());
+        out.extend_from_slice(&(self.name.len() as u16).to_le_bytes());
+        let extra = if self.zip64 {
+            ZIP64_CENTRAL_DIRECTORY_EXTRA_FIELD_SIZE
+        } else {
+            0
+        };
+        out.extend_from_slice(&extra.to_le_bytes()); // extra field length
+        out.extend_from_slice(&0u16.to_le_bytes()); // comment length
+        out.extend_from_slice(&0u16.to_le_bytes()); // disk number start
+        out.extend_from_slice(&0u16.to_le_bytes()); // internal attributes
+        let external_attributes = if self.name.ends_with('/') {
+            DIRECTORY_EXTERNAL_ATTRIBUTES
+        } else {
+            FILE_EXTERNAL_ATTRIBUTES
+        };
+        out.extend_from_slice(&external_attributes.to_le_bytes());
+        out.extend_from_slice(&self.field_u32(self.header_offset).to_le_bytes());
+        out.extend_from_slice(self.name.as_bytes());
+        if self.zip64 {
+            Self::write_zip64_extra_field(
+                out,
+                &[
+                    self.uncompressed_size,
+                    self.compressed_size,
+                    self.header_offset,
+                ],
+            );
+        }
+    }
+}
+
+/// Returns true if the end of central directory of an archive cannot be represented without ZIP64 extensions.
+pub(crate) fn end_of_central_directory_requires_zip64(
+    num_entries: u64,
+    directory_size: u64,
+    directory_offset: u64,
+) -> bool {
+    num_entries >= u64::from(u16::MAX)
+        || directory_size >= u64::from(u32::MAX)
+        || directory_offset >= u64::from(u32::MAX)
+}
+
+/// Append a ZIP64 end of central directory record and locator to `out`, which starts at `out_offset` in the archive.
+pub(crate) fn write_zip64_end_of_central_directory(
+    out: &mut Vec<u8>,
+    out_offset: u64,
+    num_entries: u64,
+    directory_size: u64,
+    directory_offset: u64,
+) {
+    let record_offset = out_offset + out.len() as u64;
+    out.extend_from_slice(&ZIP64_END_OF_CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes());
+    // The size of the remaining record
+    out.extend_from_slice(&(ZIP64_END_OF_CENTRAL_DIRECTORY_SIZE - 12).to_le_bytes());
+    out.extend_from_slice(&VERSION_MADE_BY.to_le_bytes());
+    out.extend_from_slice(&VERSION_NEEDED_ZIP64.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes()); // number of this disk
+    out.extend_from_slice(&0u32.to_le_bytes()); // disk with the central directory
+    out.extend_from_slice(&num_entries.to_le_bytes());
+    out.extend_from_slice(&num_entries.to_le_bytes());
+    out.extend_from_slice(&directory_size.to_le_bytes());
+    out.extend_from_slice(&directory_offset.to_le_bytes());
+
+    out.extend_from_slice(&ZIP64_END_OF_CENTRAL_DIRECTORY_LOCATOR_SIGNATURE.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes()); // disk with the ZIP64 end of central directory record
+    out.extend_from_slice(&record_offset.to_le_bytes());
+    out.extend_from_slice(&1u32.to_le_bytes()); // total number of disks
+}
+
+/// Append an end of central directory record to `out`.
+///
+/// Fields that do not fit are saturated, and must be followed by a ZIP64 end of central directory record written by [`write_zip64_end_of_central_directory`].
+/// All fields are saturated if `zip64` is true.
+pub(crate) fn write_end_of_central_directory(
+    out: &mut Vec<u8>,
+    num_entries: u64,
+    directory_size: u64,
+    directory_offset: u64,
+    comment: &[u8],
+    zip64: bool,
+) {
+    let field_u16 = |value: u64| {
+        u16::try_from(value)
+            .ok()
+            .filter(|_| !zip64)
+            .unwrap_or(u16::MAX)
+    };
+    let field_u32 = |value: u64| {
+        u32::try_from(value)
+            .ok()
+            .filter(|_| !zip64)
+            .unwrap_or(u32::MAX)
+    };
+    out.extend_from_slice(&END_OF_CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes()); // number of this disk
+    out.extend_from_slice(&0u16.to_le_bytes()); // disk with the central directory
+    out.extend_from_slice(&field_u16(num_entries).to_le_bytes());
+    out.extend_from_slice(&field_u16(num_entries).to_le_bytes());
+    out.extend_from_slice(&field_u32(directory_size).to_le_bytes());
+    out.extend_from_slice(&field_u32(directory_offset).to_le_bytes());
+    #[allow(clippy::cast_possible_truncation)]
+    out.extend_from_slice(&(comment.len() as u16).to_le_bytes());
+    out.extend_from_slice(comment);
+}
@@ -0,0 +1,1075 @@
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    ops::Range,
+    sync::{Arc, OnceLock},
+    time::SystemTime,
+};
+
+use rc_zip::{
+    EntryKind,
+    parse::{Archive, Entry, Method, Mode, Version},
+};
+#[cfg(feature = "write")]
+use zarrs_storage::Bytes;
+use zarrs_storage::{StoreKey, StoreKeys, StoreKeysPrefixes, StorePrefix, StorePrefixes};
+
+use crate::{
+    ArchiveCapabilities, FLAG_ENCRYPTED, SymlinkPolicy, UnsafeEntryNamePolicy, ZipEntryInfo,
+    ZipStorageAdapterCreateError, method_supported,
+};
+#[cfg(feature = "write")]
+use crate::{ZipWriteOptions, format::FLAG_DATA_DESCRIPTOR};
+
+/// An entry in the zip archive (either a file or directory).
+///
+/// Keys are shared with the `HashMap` of the index, so that the name of each entry is stored once.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ZipEntry {
+    Key(Arc<StoreKey>),
+    Prefix(StorePrefix),
+}
+
+impl ZipEntry {
+    pub(crate) fn as_str(&self) -> &str {
+        match self {
+            ZipEntry::Key(k) => k.as_str(),
+            ZipEntry::Prefix(p) => p.as_str(),
+        }
+    }
+}
+
+/// The fields of an entry in the zip archive that are used once the index is built.
+///
+/// This is a compact copy of an [`Entry`], without its name, which is the key of the entry in the index.
+#[derive(Debug, Clone)]
+pub(crate) struct StoredEntry {
+    /// The offset of the local file header.
+    pub(crate) header_offset: u64,
+    /// The size of the compressed data.
+    pub(crate) compressed_size: u64,
+    /// The size of the uncompressed data.
+    pub(crate) uncompressed_size: u64,
+    /// The CRC-32 of the uncompressed data.
+    pub(crate) crc32: u32,
+    /// The compression method.
+    pub(crate) method: Method,
+    /// The general purpose bit flags.
+    pub(crate) flags: u16,
+    /// The version needed to extract the entry.
+    pub(crate) reader_version: Version,
+    /// The last modification time.
+    pub(crate) modified: SystemTime,
+    /// The entry comment.
+    pub(crate) comment: Box<str>,
+}
+
+impl From<&Entry> for StoredEntry {
+    fn from(entry: &Entry) -> Self {
+        Self {
+            header_offset: entry.header_offset,
+            compressed_size: entry.compressed_size,
+            uncompressed_size: entry.uncompressed_size,
+            crc32: entry.crc32,
+            method: entry.method,
+            flags: entry.flags,
+            reader_version: entry.reader_version,
+            modified: entry.modified.into(),
+            comment: entry.comment.as_str().into(),
+        }
+    }
+}
+
+impl StoredEntry {
+    /// Whether the entry is encrypted, with either the traditional PKWARE encryption or the `WinZip` AES extension.
+    pub(crate) fn is_encrypted(&self) -> bool {
+        self.flags & FLAG_ENCRYPTED != 0
+    }
+
+    /// An [`Entry`] named `name` with the fields of this entry, for decompressing its value with `EntryFsm`.
+    ///
+    /// Fields that are not stored, such as the mode of the entry, are left empty.
+    pub(crate) fn to_entry(&self, name: &str) -> Entry {
+        Entry {
+            name: name.to_string(),
+            method: self.method,
+            comment: self.comment.to_string(),
+            modified: self.modified.into(),
+            created: None,
+            accessed: None,
+            header_offset: self.header_offset,
+            reader_version: self.reader_version,
+            flags: self.flags,
+            uid: None,
+            gid: None,
+            crc32: self.crc32,
+            compressed_size: self.compressed_size,
+            uncompressed_size: self.uncompressed_size,
+            mode: Mode(0),
+        }
+    }
+}
+
+/// The location of the value of a key.
+#[derive(Clone)]
+pub(crate) enum IndexEntry {
+    /// An entry in the zip archive.
+    Archived(StoredEntry),
+    /// A value that has been written but not yet finished into the zip archive.
+    #[cfg(feature = "write")]
+    Pending(Bytes, ZipWriteOptions),
+}
+
+impl IndexEntry {
+    /// The size of the value.
+    pub(crate) fn size(&self) -> u64 {
+        match self {
+            IndexEntry::Archived(entry) => entry.uncompressed_size,
+            #[cfg(feature = "write")]
+            IndexEntry::Pending(value, _) => value.len() as u64,
+        }
+    }
+
+    /// The size of the value in the zip archive.
+    fn stored_size(&self) -> u64 {
+        match self {
+            IndexEntry::Archived(entry) => entry.compressed_size,
+            #[cfg(feature = "write")]
+            IndexEntry::Pending(value, _) => value.len() as u64,
+        }
+    }
+}
+
+/// The pending changes to a zip archive.
+#[cfg(feature = "write")]
+pub(crate) struct PendingChanges {
+    /// The pending values and their write options in key order.
+    pub(crate) values: Vec<(StoreKey, Bytes, ZipWriteOptions)>,
+    /// The local header offsets of archived entries that have been erased or overwritten.
+    pub(crate) dropped: HashSet<u64>,
+    /// The directory entries to write in sorted order.
+    pub(crate) directories: Vec<StorePrefix>,
+    /// The archive comment, if it has been set.
+    pub(crate) comment: Option<Vec<u8>>,
+    /// The live stored entries that have been partially written in place, and need their CRC-32 updated.
+    pub(crate) stale_crc32s: Vec<LocalRecord>,
+}
+
+/// The location of the local data of an entry in a zip archive.
+#[cfg(feature = "write")]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct LocalRecord {
+    /// The offset of the local file header.
+    pub(crate) header_offset: u64,
+    /// The size of the compressed data.
+    pub(crate) compressed_size: u64,
+    /// Whether the compressed data is followed by a data descriptor.
+    pub(crate) data_descriptor: bool,
+}
+
+/// The options of the index of a zip archive.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct IndexOptions {
+    /// The handling of symbolic link entries.
+    pub(crate) symlink_policy: SymlinkPolicy,
+    /// Whether keys and prefixes are matched ignoring ASCII case.
+    pub(crate) case_insensitive: bool,
+    /// Whether `\` separators in entry names are replaced by `/`.
+    pub(crate) normalize_separators: bool,
+    /// The handling of entries with unsafe names.
+    pub(crate) unsafe_entry_name_policy: UnsafeEntryNamePolicy,
+    /// Whether directory entries are left out of the listing of the index.
+    pub(crate) ignore_directory_entries: bool,
+}
+
+/// The name and kind of `entry`, with `\` separators replaced by `/` if enabled by `options`.
+///
+/// An entry with a name ending in a separator is a directory.
+fn normalized_name(entry: &Entry, options: IndexOptions) -> (Cow<'_, str>, EntryKind) {
+    let kind = entry.kind();
+    if !options.normalize_separators || !entry.name.contains('\\') {
+        return (Cow::Borrowed(&entry.name), kind);
+    }
+    let name = entry.name.replace('\\', "/");
+    let kind = match kind {
+        EntryKind::File if name.ends_with('/') => EntryKind::Directory,
+        kind => kind,
+    };
+    (Cow::Owned(name), kind)
+}
+
+/// Whether the entry `name` is an absolute path or has a `..` component, so it could escape a directory it is extracted to.
+///
+/// Both `/` and `\` are separators, and a name starting with a drive letter such as `C:` is absolute, as on Windows.
+fn is_unsafe_name(name: &str) -> bool {
+    let drive = matches!(name.as_bytes(), [letter, b':', ..] if letter.is_ascii_alphabetic());
+    drive
+        || name.starts_with(['/', '\\'])
+        || name.split(['/', '\\']).any(|component| component == "..")
+}
+
+/// Whether `entry`, with the normalized `name`, is indexed given the handling of entries with unsafe names of `options`.
+///
+/// # Errors
+/// Returns [`ZipStorageAdapterCreateError::UnsafeEntryName`] if the name is unsafe with [`UnsafeEntryNamePolicy::Error`].
+fn is_indexed_name(
+    entry: &Entry,
+    name: &str,
+    options: IndexOptions,
+) -> Result<bool, ZipStorageAdapterCreateError> {
+    if !is_unsafe_name(name) {
+        return Ok(true);
+    }
+    match options.unsafe_entry_name_policy {
+        UnsafeEntryNamePolicy::Error => Err(ZipStorageAdapterCreateError::UnsafeEntryName(
+            entry.name.clone(),
+        )),
+        UnsafeEntryNamePolicy::Skip => Ok(false),
+    }
+}
+
+/// The minimum number of entries of a zip archive for which the names of its entries are indexed in parallel, and its sorted listing is sorted in parallel.
+#[cfg(feature = "rayon")]
+const PARALLEL_INDEX_THRESHOLD: usize = 65536;
+
+/// How an entry of a zip archive is indexed.
+enum IndexedName {
+    /// The entry is the value of a key, with its lowercase name if keys are matched ignoring ASCII case.
+    Key(Arc<StoreKey>, Option<String>),
+    /// The entry is a directory entry of a prefix.
+    Prefix(StorePrefix),
+    /// The entry is outside of the zip path, or is skipped.
+    Skipped,
+}
+
+/// The [`IndexedName`] of an entry, or the error building the index.
+type IndexedNameResult = Result<IndexedName, ZipStorageAdapterCreateError>;
+
+/// The name of an entry relative to `zip_path`, or [`None`] if it is outside of or is `zip_path`.
+fn strip_zip_path_prefix<'a>(name: &'a str, zip_path: &str) -> Option<&'a str> {
+    name.strip_prefix(zip_path).filter(|&n| !n.is_empty())
+}
+
+/// How `entry` is indexed within `zip_path` with `options`.
+///
+/// This is independent of the other entries of the zip archive, so the entries can be indexed in parallel.
+///
+/// # Errors
+/// Returns an error if the entry name is not a valid key or prefix, is unsafe with [`UnsafeEntryNamePolicy::Error`], or is a symbolic link with [`SymlinkPolicy::Error`].
+fn indexed_name(entry: &Entry, zip_path: &str, options: IndexOptions) -> IndexedNameResult {
+    let (name, kind) = normalized_name(entry, options);
+    let Some(stripped) = strip_zip_path_prefix(&name, zip_path) else {
+        return Ok(IndexedName::Skipped);
+    };
+    if !is_indexed_name(entry, &name, options)? {
+        return Ok(IndexedName::Skipped);
+    }
+    match (kind, options.symlink_policy) {
+        (EntryKind::File, _) | (EntryKind::Symlink, SymlinkPolicy::AsBytes) => {
+            let store_key = StoreKey::try_from(stripped)?;
+            let folded = options
+                .case_insensitive
+                .then(|| store_key.as_str().to_ascii_lowercase());
+            Ok(IndexedName::Key(Arc::new(store_key), folded))
+        }
+        (EntryKind::Directory, _) => Ok(IndexedName::Prefix(StorePrefix::try_from(stripped)?)),
+        (EntryKind::Symlink, SymlinkPolicy::Ignore) => Ok(IndexedName::Skipped),
+        (EntryKind::Symlink, SymlinkPolicy::Error) => {
+            Err(ZipStorageAdapterCreateError::Symlink(entry.name.clone()))
+        }
+    }
+}
+
+/// How each entry of `archive` is indexed, in the order of the entries, computed in parallel on the current `rayon` thread pool.
+#[cfg(feature = "rayon")]
+fn indexed_names_parallel(
+    archive: &Archive,
+    zip_path: &str,
+    options: IndexOptions,
+) -> Vec<IndexedNameResult> {
+    use rayon::iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
+
+    archive
+        .entries()
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .with_min_len(4096)
+        .map(|entry| indexed_name(entry, zip_path, options))
+        .collect()
+}
+
+/// The index of the entries of a zip archive.
+pub(crate) struct ZipIndex {
+    /// Total size of the zip file.
+    pub(crate) size: u64,
+    /// `HashMap` for O(1) entry lookup by key.
+    entries: HashMap<Arc<StoreKey>, IndexEntry>,
+    /// Sorted entries (keys and prefixes) for listing operations, built on first use.
+    ///
+    /// Reads of known keys only need `entries`, so the cost of sorting the entries is deferred until they are listed or written.
+    /// If keys are matched ignoring ASCII case, they are sorted by their lowercase names.
+    sorted_entries: OnceLock<Vec<ZipEntry>>,
+    /// The cumulative stored sizes of the keys of `sorted_entries`, from zero, built on first use.
+    ///
+    /// The stored size of the keys with a prefix is the difference of the sums at the ends of its range of `sorted_entries`.
+    stored_size_sums: OnceLock<Vec<u64>>,
+    /// The cumulative sizes of the values of the keys of `sorted_entries`, from zero, built on first use.
+    size_sums: OnceLock<Vec<u64>>,
+    /// The prefixes of the directory entries of the zip archive, from which `sorted_entries` is built with the keys of `entries`.
+    ///
+    /// This is empty once `sorted_entries` has been built and modified.
+    prefixes: Vec<StorePrefix>,
+    /// The number of symbolic link entries within the zip path, whether or not they are indexed.
+    symlinks: usize,
+    /// The keys of the entries by their lowercase names, if keys are matched ignoring ASCII case.
+    folded_keys: Option<HashMap<String, Arc<StoreKey>>>,
+    /// The raw bytes of the archive comment, including a pending comment.
+    pub(crate) comment: Vec<u8>,
+    /// Whether the archive comment has been set since the index was built.
+    #[cfg(feature = "write")]
+    comment_changed: bool,
+    /// The local data of all entries in the zip archive, including those outside of the zip path.
+    #[cfg(feature = "write")]
+    records: Vec<LocalRecord>,
+    /// Header offsets of the directory entries of prefixes within the zip path, or [`None`] for pending directory entries.
+    #[cfg(feature = "write")]
+    directories: HashMap<StorePrefix, Option<u64>>,
+    /// Header offsets of archived entries that have been erased, overwritten, or shadowed by a later entry with the same name.
+    #[cfg(feature = "write")]
+    dropped: HashSet<u64>,
+    /// Header offsets of stored entries that have been partially written in place since the CRC-32s were last updated.
+    #[cfg(feature = "write")]
+    stale_crc32s: HashSet<u64>,
+    /// Whether the index has changes that have not been written to the zip archive.
+    #[cfg(feature = "write")]
+    pub(crate) modified: bool,
+}
+
+/// The names of the parent directories of `key` from the outermost, such as `a/` and `a/b/` for `a/b/c`.
+#[cfg(feature = "write")]
+pub(crate) fn parent_directories(key: &StoreKey) -> impl Iterator<Item = &str> {
+    let key = key.as_str();
+    key.match_indices('/').map(|(i, _)| &key[..=i])
+}
+
+/// Compare the names of entries, ignoring ASCII case if `case_insensitive`.
+fn cmp_names(case_insensitive: bool, a: &str, b: &str) -> Ordering {
+    if case_insensitive {
+        a.bytes()
+            .map(|byte| byte.to_ascii_lowercase())
+            .cmp(b.bytes().map(|byte| byte.to_ascii_lowercase()))
+    } else {
+        a.cmp(b)
+    }
+}
+
+/// Compare the names of entries in the order of the sorted entries of the index, ignoring ASCII case first if `case_insensitive`.
+fn sort_order(case_insensitive: bool, a: &str, b: &str) -> Ordering {
+    cmp_names(case_insensitive, a, b).then_with(|| a.cmp(b))
+}
+
+impl ZipIndex {
+    /// Build the index of the entries of `archive` within `zip_path` with `options`.
+    ///
+    /// `comment` is the raw bytes of the archive comment.
+    pub(crate) fn new(
+        archive: &Archive,
+        comment: Vec<u8>,
+        size: u64,
+        zip_path: &str,
+        options: IndexOptions,
+    ) -> Result<Self, ZipStorageAdapterCreateError> {
+        let mut entries: HashMap<Arc<StoreKey>, IndexEntry> = HashMap::new();
+        let mut prefixes: Vec<StorePrefix> = Vec::new();
+        let mut folded_keys: Option<HashMap<String, Arc<StoreKey>>> =
+            options.case_insensitive.then(HashMap::new);
+        #[cfg(feature = "write")]
+        let mut directories: HashMap<StorePrefix, Option<u64>> = HashMap::new();
+        #[cfg(feature = "write")]
+        let mut dropped: HashSet<u64> = HashSet::new();
+        #[cfg(feature = "write")]
+        let records = archive
+            .entries()
+            .map(|entry| LocalRecord {
+                header_offset: entry.header_offset,
+                compressed_size: entry.compressed_size,
+                data_descriptor: entry.flags & FLAG_DATA_DESCRIPTOR != 0,
+            })
+            .collect();
+        #[cfg(feature = "rayon")]
+        let (num_entries, _) = archive.entries().size_hint();
+        #[cfg(feature = "rayon")]
+        let names = (num_entries >= PARALLEL_INDEX_THRESHOLD)
+            .then(|| indexed_names_parallel(archive, zip_path, options));
+        #[cfg(not(feature = "rayon"))]
+        let names: Option<Vec<IndexedNameResult>> = None;
+        let mut names = names.map(Vec::into_iter);
+        let mut symlinks = 0;
+        for entry in archive.entries() {
+            if entry.kind() == EntryKind::Symlink
+                && strip_zip_path_prefix(&entry.name, zip_path).is_some()
+            {
+                symlinks += 1;
+            }
+            let name = match names.as_mut() {
+                Some(names) => names.next().expect("a name per entry"),
+                None => indexed_name(entry, zip_path, options),
+            };
+            match name? {
+                IndexedName::Key(store_key, folded) => {
+                    // The last entry with a name wins, as when extracting the zip file
+                    #[cfg_attr(not(feature = "write"), allow(unused_variables))]
+                    let shadowed =
+                        entries.insert(store_key.clone(), IndexEntry::Archived(entry.into()));
+                    #[cfg(feature = "write")]
+                    if let Some(IndexEntry::Archived(shadowed)) = &shadowed {
+                        dropped.insert(shadowed.header_offset);
+                    }
+                    // Keys that differ only by case are ambiguous, the last entry wins
+                    if let (Some(folded_keys), Some(folded)) = (folded_keys.as_mut(), folded) {
+                        if let Some(shadowed) = folded_keys.insert(folded, store_key.clone()) {
+                            if shadowed != store_key {
+                                #[cfg(feature = "write")]
+                                if let Some(IndexEntry::Archived(shadowed)) = entries.get(&shadowed)
+                                {
+                                    dropped.insert(shadowed.header_offset);
+                                }
+                                entries.remove(&shadowed);
+                            }
+                        }
+                    }
+                }
+                IndexedName::Prefix(store_prefix) => {
+                    #[cfg(feature = "write")]
+                    if let Some(Some(shadowed)) =
+                        directories.insert(store_prefix.clone(), Some(entry.header_offset))
+                    {
+                        dropped.insert(shadowed);
+                    }
+                    if !options.ignore_directory_entries {
+                        prefixes.push(store_prefix);
+                    }
+                }
+                IndexedName::Skipped => {}
+            }
+        }
+
+        Ok(Self {
+            size,
+            entries,
+            sorted_entries: OnceLock::new(),
+            stored_size_sums: OnceLock::new(),
+            size_sums: OnceLock::new(),
+            prefixes,
+            symlinks,
+            folded_keys,
+            comment,
+            #[cfg(feature = "write")]
+            comment_changed: false,
+            #[cfg(feature = "write")]
+            records,
+            #[cfg(feature = "write")]
+            directories,
+            #[cfg(feature = "write")]
+            dropped,
+            #[cfg(feature = "write")]
+            stale_crc32s: HashSet::new(),
+            #[cfg(feature = "write")]
+            modified: false,
+        })
+    }
+
+    /// The number of keys in the index.
+    pub(crate) fn num_keys(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Get an entry by key using O(1) `HashMap` lookup.
+    pub(crate) fn get(&self, key: &StoreKey) -> Option<&IndexEntry> {
+        self.entries.get(self.resolve(key)?)
+    }
+
+    /// The key of the entry matching `key`, ignoring ASCII case if keys are matched ignoring case.
+    fn resolve<'a>(&'a self, key: &'a StoreKey) -> Option<&'a StoreKey> {
+        match &self.folded_keys {
+            Some(folded_keys) if !self.entries.contains_key(key) => folded_keys
+                .get(&key.as_str().to_ascii_lowercase())
+                .map(AsRef::as_ref),
+            _ => Some(key),
+        }
+    }
+
+    /// Returns true if keys are matched ignoring ASCII case.
+    pub(crate) fn case_insensitive(&self) -> bool {
+        self.folded_keys.is_some()
+    }
+
+    /// Strip `prefix` from `name`, ignoring ASCII case if keys are matched ignoring case.
+    fn strip_prefix<'a>(&self, name: &'a str, prefix: &str) -> Option<&'a str> {
+        if self.case_insensitive() {
+            name.get(..prefix.len())
+                .filter(|head| head.eq_ignore_ascii_case(prefix))
+                .map(|_| &name[prefix.len()..])
+        } else {
+            name.strip_prefix(prefix)
+        }
+    }
+
+    /// The sorted entries, which are built from the keys of the index and the directory entries on first use.
+    fn sorted_entries(&self) -> &[ZipEntry] {
+        self.sorted_entries.get_or_init(|| {
+            let mut sorted_entries: Vec<ZipEntry> = self
+                .entries
+                .keys()
+                .cloned()
+                .map(ZipEntry::Key)
+                .chain(self.prefixes.iter().cloned().map(ZipEntry::Prefix))
+                .collect();
+            let sort_order = |a: &ZipEntry, b: &ZipEntry| {
+                sort_order(self.case_insensitive(), a.as_str(), b.as_str())
+            };
+            // The sort order is total, so entries that compare equal are duplicate prefixes and need not be sorted stably
+            #[cfg(feature = "rayon")]
+            if sorted_entries.len() >= PARALLEL_INDEX_THRESHOLD {
+                rayon::slice::ParallelSliceMut::par_sort_unstable_by(
+                    sorted_entries.as_mut_slice(),
+                    sort_order,
+                );
+            } else {
+                sorted_entries.sort_unstable_by(sort_order);
+            }
+            #[cfg(not(feature = "rayon"))]
+            sorted_entries.sort_unstable_by(sort_order);
+            sorted_entries.dedup_by(|a, b| a.as_str() == b.as_str());
+            sorted_entries
+        })
+    }
+
+    /// Find the range of entries matching a prefix using binary search.
+    fn entries_with_prefix(&self, prefix: &StorePrefix) -> &[ZipEntry] {
+        &self.sorted_entries()[self.prefix_range(prefix)]
+    }
+
+    /// Find the range of `sorted_entries` matching a prefix using binary search.
+    fn prefix_range(&self, prefix: &StorePrefix) -> Range<usize> {
+        let prefix_str = prefix.as_str();
+
+        // Find start index: first entry >= prefix
+        let sorted_entries = self.sorted_entries();
+        let start = sorted_entries.partition_point(|e| {
+            cmp_names(self.case_insensitive(), e.as_str(), prefix_str).is_lt()
+        });
+
+        // Find end index: first entry that doesn't start with prefix
+        let end = sorted_entries[start..]
+            .partition_point(|e| self.strip_prefix(e.as_str(), prefix_str).is_some())
+            + start;
+
+        start..end
+    }
+
+    /// Get the immediate child prefix of a key relative to a parent prefix.
+    fn immediate_child_prefix(&self, key: &StoreKey, prefix: &StorePrefix) -> Option<StorePrefix> {
+        let key_str = key.as_str();
+        let prefix_str = prefix.as_str();
+
+        // Get the part after the prefix
+        let suffix = self.strip_prefix(key_str, prefix_str)?;
+
+        // Find the first '/' in the suffix to get the immediate child directory
+        if let Some(slash_pos) = suffix.find('/') {
+            let child = &suffix[..=slash_pos];
+            let full_prefix = format!("{prefix_str}{child}");
+            StorePrefix::try_from(full_prefix.as_str()).ok()
+        } else {
+            None
+        }
+    }
+
+    /// List all keys.
+    pub(crate) fn list(&self) -> StoreKeys {
+        // Filter to only keys, already sorted
+        self.sorted_entries()
+            .iter()
+            .filter_map(|e| match e {
+                ZipEntry::Key(k) => Some(StoreKey::clone(k)),
+                ZipEntry::Prefix(_) => None,
+            })
+            .collect()
+    }
+
+    /// List all keys with `prefix`.
+    pub(crate) fn list_prefix(&self, prefix: &StorePrefix) -> StoreKeys {
+        // Use binary search to find matching range, filter to keys only
+        self.entries_with_prefix(prefix)
+            .iter()
+            .filter_map(|e| match e {
+                ZipEntry::Key(k) => Some(StoreKey::clone(k)),
+                ZipEntry::Prefix(_) => None,
+            })
+            .collect()
+    }
+
+    /// List all keys with `prefix` that match `glob`.
+    #[cfg(feature = "glob")]
+    pub(crate) fn list_glob(&self, prefix: &StorePrefix, glob: &globset::GlobMatcher) -> StoreKeys {
+        self.entries_with_prefix(prefix)
+            .iter()
+            .filter_map(|e| match e {
+                ZipEntry::Key(k) if glob.is_match(k.as_str()) => Some(StoreKey::clone(k)),
+                ZipEntry::Key(_) | ZipEntry::Prefix(_) => None,
+            })
+            .collect()
+    }
+
+    /// List all keys with `prefix`, with the uncompressed size of their values.
+    pub(crate) fn list_prefix_with_sizes(&self, prefix: &StorePrefix) -> Vec<(StoreKey, u64)> {
+        self.entries_with_prefix(prefix)
+            .iter()
+            .filter_map(|e| match e {
+                ZipEntry::Key(k) => self
+                    .entries
+                    .get(k)
+                    .map(|entry| (StoreKey::clone(k), entry.size())),
+                ZipEntry::Prefix(_) => None,
+            })
+            .collect()
+    }
+
+    /// List the keys and immediate child prefixes of `prefix`.
+    pub(crate) fn list_dir(&self, prefix: &StorePrefix) -> StoreKeysPrefixes {
+        let mut keys: StoreKeys = vec![];
+        let prefixes = self.list_dir_impl(prefix, Some(&mut keys));
+
+        // Keys and prefixes are already sorted since sorted_entries is sorted
+        StoreKeysPrefixes::new(keys, prefixes)
+    }
+
+    /// List the immediate child prefixes of `prefix`.
+    pub(crate) fn list_dir_prefixes(&self, prefix: &StorePrefix) -> StorePrefixes {
+        self.list_dir_impl(prefix, None)
+    }
+
+    /// List the immediate child prefixes of `prefix`, and push its keys to `keys` if given.
+    fn list_dir_impl(
+        &self,
+        prefix: &StorePrefix,
+        mut keys: Option<&mut StoreKeys>,
+    ) -> StorePrefixes {
+        let mut prefixes: StorePrefixes = vec![];
+
+        // Use binary search to find matching range
+        for entry in self.entries_with_prefix(prefix) {
+            match entry {
+                ZipEntry::Key(key) => {
+                    let parent = key.parent();
+                    if cmp_names(self.case_insensitive(), parent.as_str(), prefix.as_str()).is_eq()
+                    {
+                        if let Some(keys) = keys.as_mut() {
+                            keys.push(StoreKey::clone(key));
+                        }
+                    } else if let Some(child_prefix) = self.immediate_child_prefix(key, prefix) {
+                        if prefixes.last() != Some(&child_prefix) {
+                            prefixes.push(child_prefix);
+                        }
+                    }
+                }
+                ZipEntry::Prefix(p) => {
+                    // Check if this prefix is an immediate child of the search prefix
+                    let p_str = p.as_str();
+                    let prefix_str = prefix.as_str();
+                    if let Some(suffix) = self.strip_prefix(p_str, prefix_str) {
+                        // Skip if suffix is empty (the prefix itself)
+                        if suffix.is_empty() {
+                            continue;
+                        }
+                        // Check if it's an immediate child (no additional '/' before the trailing one)
+                        let trimmed = suffix.trim_end_matches('/');
+                        if !trimmed.contains('/') && prefixes.last() != Some(p) {
+                            prefixes.push(p.clone());
+                        }
+                    }
+                }
+            }
+        }
+        prefixes
+    }
+
+    /// The keys of archived entries with a compression method that is not supported, and their methods, in key order.
+    pub(crate) fn unsupported_methods(&self) -> Vec<(StoreKey, Method)> {
+        let mut unsupported: Vec<(StoreKey, Method)> =
+            self.entries
+                .iter()
+                .filter_map(|(key, entry)| match entry {
+                    IndexEntry::Archived(entry) => (!method_supported(entry.method))
+                        .then(|| (StoreKey::clone(key), entry.method)),
+                    #[cfg(feature = "write")]
+                    IndexEntry::Pending(..) => None,
+                })
+                .collect();
+        unsupported.sort_unstable_by(|a, b| a.0.as_str().cmp(b.0.as_str()));
+        unsupported
+    }
+
+    /// The capabilities of the zip archive that are needed to read the archived entries of keys.
+    pub(crate) fn capabilities(&self) -> ArchiveCapabilities {
+        let mut capabilities = ArchiveCapabilities {
+            zip64: self.entries.len() >= usize::from(u16::MAX),
+            symlinks: self.symlinks,
+            ..ArchiveCapabilities::default()
+        };
+        for entry in self.entries.values() {
+            #[cfg_attr(not(feature = "write"), allow(irrefutable_let_patterns))]
+            let IndexEntry::Archived(entry) = entry else {
+                continue;
+            };
+            capabilities.zip64 |= [
+                entry.compressed_size,
+                entry.uncompressed_size,
+                entry.header_offset,
+            ]
+            .iter()
+            .any(|&value| value >= u64::from(u32::MAX));
+            capabilities.encrypted += usize::from(entry.is_encrypted());
+            capabilities.unsupported_methods += usize::from(!method_supported(entry.method));
+        }
+        capabilities
+    }
+
+    /// The local file header offsets of the archived entries of keys.
+    #[cfg_attr(not(feature = "write"), allow(clippy::unnecessary_filter_map))]
+    pub(crate) fn archived_header_offsets(&self) -> HashSet<u64> {
+        self.entries
+            .values()
+            .filter_map(|entry| match entry {
+                IndexEntry::Archived(entry) => Some(entry.header_offset),
+                #[cfg(feature = "write")]
+                IndexEntry::Pending(..) => None,
+            })
+            .collect()
+    }
+
+    /// The archived entries of keys, with their keys.
+    #[cfg_attr(not(feature = "write"), allow(clippy::unnecessary_filter_map))]
+    pub(crate) fn archived_entries(&self) -> Vec<(Arc<StoreKey>, StoredEntry)> {
+        self.entries
+            .iter()
+            .filter_map(|(key, entry)| match entry {
+                IndexEntry::Archived(entry) => Some((key.clone(), entry.clone())),
+                #[cfg(feature = "write")]
+                IndexEntry::Pending(..) => None,
+            })
+            .collect()
+    }
+
+    /// The names and kinds of all entries, in sorted order.
+    pub(crate) fn entries(&self) -> Vec<(String, ZipEntryInfo)> {
+        self.sorted_entries()
+            .iter()
+            .filter_map(|e| match e {
+                ZipEntry::Key(key) => {
+                    let info = match self.entries.get(key)? {
+                        IndexEntry::Archived(entry) => ZipEntryInfo::File {
+                            uncompressed_size: entry.uncompressed_size,
+                            compressed_size: entry.compressed_size,
+                            method: entry.method,
+                        },
+                        #[cfg(feature = "write")]
+                        IndexEntry::Pending(value, options) => ZipEntryInfo::File {
+                            uncompressed_size: value.len() as u64,
+                            compressed_size: value.len() as u64,
+                            method: options.compression.method(),
+                        },
+                    };
+                    Some((key.as_str().to_string(), info))
+                }
+                ZipEntry::Prefix(prefix) => {
+                    Some((prefix.as_str().to_string(), ZipEntryInfo::Directory))
+                }
+            })
+            .collect()
+    }
+
+    /// The total stored size of all keys with `prefix`.
+    pub(crate) fn size_prefix(&self, prefix: &StorePrefix) -> u64 {
+        self.sum_prefix(prefix, IndexEntry::stored_size, &self.stored_size_sums)
+    }
+
+    /// The total uncompressed size of all keys with `prefix`.
+    pub(crate) fn uncompressed_size_prefix(&self, prefix: &StorePrefix) -> u64 {
+        self.sum_prefix(prefix, IndexEntry::size, &self.size_sums)
+    }
+
+    /// The sum of `size` over the entries of all keys with `prefix`, from `sums`, the cumulative sums of `size` which are built on first use.
+    fn sum_prefix(
+        &self,
+        prefix: &StorePrefix,
+        size: fn(&IndexEntry) -> u64,
+        sums: &OnceLock<Vec<u64>>,
+    ) -> u64 {
+        let sums = sums.get_or_init(|| {
+            let sorted_entries = self.sorted_entries();
+            let mut sums = Vec::with_capacity(sorted_entries.len() + 1);
+            let mut sum = 0;
+            sums.push(sum);
+            for entry in sorted_entries {
+                if let ZipEntry::Key(key) = entry {
+                    sum += self.entries.get(key).map_or(0, size);
+                }
+                sums.push(sum);
+            }
+            sums
+        });
+        // Use binary search to find the matching range, the sum is the difference of the sums at its ends
+        let range = self.prefix_range(prefix);
+        sums[range.end] - sums[range.start]
+    }
+}
+
+#[cfg(feature = "write")]
+impl ZipIndex {
+    /// The sorted entries for modification, which are built first if they have not been used.
+    ///
+    /// The sums of sizes aligned with the sorted entries are invalidated.
+    fn sorted_entries_mut(&mut self) -> &mut Vec<ZipEntry> {
+        self.sorted_entries();
+        self.invalidate_size_sums();
+        // The directory entries are only needed to build the sorted entries
+        self.prefixes = Vec::new();
+        self.sorted_entries
+            .get_mut()
+            .expect("the sorted entries are built")
+    }
+
+    /// Invalidate the cumulative sizes of the sorted entries, so that they are rebuilt on next use.
+    fn invalidate_size_sums(&mut self) {
+        self.stored_size_sums.take();
+        self.size_sums.take();
+    }
+
+    /// Set the archive comment, which is pending until the zip archive is written.
+    pub(crate) fn set_comment(&mut self, comment: Vec<u8>) {
+        self.comment = comment;
+        self.comment_changed = true;
+        self.modified = true;
+    }
+
+    /// Insert a pending directory entry for `prefix`, unless it already has a directory entry.
+    pub(crate) fn insert_directory(&mut self, prefix: &StorePrefix) {
+        if prefix.as_str().is_empty() || self.directories.contains_key(prefix) {
+            return;
+        }
+        self.directories.insert(prefix.clone(), None);
+        let case_insensitive = self.case_insensitive();
+        let sorted_entries = self.sorted_entries_mut();
+        let position = sorted_entries
+            .partition_point(|e| sort_order(case_insensitive, e.as_str(), prefix.as_str()).is_lt());
+        sorted_entries.insert(position, ZipEntry::Prefix(prefix.clone()));
+        self.modified = true;
+    }
+
+    /// Insert a pending value for `key` to be written with `options`, replacing any existing value.
+    pub(crate) fn insert_pending(
+        &mut self,
+        key: &StoreKey,
+        value: Bytes,
+        options: ZipWriteOptions,
+    ) {
+        self.invalidate_size_sums();
+        let key = self.resolve(key).unwrap_or(key).clone();
+        if let Some(entry) = self.entries.get_mut(&key) {
+            let previous = std::mem::replace(entry, IndexEntry::Pending(value, options));
+            self.drop_entry(&previous);
+        } else {
+            let key = Arc::new(key);
+            let case_insensitive = self.case_insensitive();
+            let sorted_entries = self.sorted_entries_mut();
+            let position = sorted_entries.partition_point(|e| {
+                sort_order(case_insensitive, e.as_str(), key.as_str()).is_lt()
+            });
+            sorted_entries.insert(position, ZipEntry::Key(key.clone()));
+            if let Some(folded_keys) = self.folded_keys.as_mut() {
+                folded_keys.insert(key.as_str().to_ascii_lowercase(), key.clone());
+            }
+            self.entries
+                .insert(key, IndexEntry::Pending(value, options));
+        }
+        self.modified = true;
+    }
+
+    /// Remove `key` from the index.
+    pub(crate) fn remove(&mut self, key: &StoreKey) {
+        let Some(key) = self.resolve(key).cloned() else {
+            return;
+        };
+        let case_insensitive = self.case_insensitive();
+        let sorted_entries = self.sorted_entries_mut();
+        if let Ok(position) = sorted_entries
+            .binary_search_by(|e| sort_order(case_insensitive, e.as_str(), key.as_str()))
+        {
+            sorted_entries.remove(position);
+        }
+        if let Some(previous) = self.entries.remove(&key) {
+            self.drop_entry(&previous);
+            self.modified = true;
+        }
+    }
+
+    /// Remove all keys and directory entries with `prefix` from the index, returning the removed keys.
+    pub(crate) fn remove_prefix(&mut self, prefix: &StorePrefix) -> StoreKeys {
+        let range = self.prefix_range(prefix);
+        if range.is_empty() {
+            return vec![];
+        }
+        let mut keys = vec![];
+        for entry in self.sorted_entries_mut().drain(range).collect::<Vec<_>>() {
+            match entry {
+                ZipEntry::Key(key) => {
+                    if let Some(previous) = self.entries.remove(&key) {
+                        self.drop_entry(&previous);
+                    }
+                    keys.push(Arc::unwrap_or_clone(key));
+                }
+                ZipEntry::Prefix(prefix) => {
+                    if let Some(Some(header_offset)) = self.directories.remove(&prefix) {
+                        self.dropped.insert(header_offset);
+                    }
+                }
+            }
+        }
+        self.modified = true;
+        keys
+    }
+
+    /// Record that an entry has been replaced or removed.
+    fn drop_entry(&mut self, entry: &IndexEntry) {
+        if let IndexEntry::Archived(entry) = entry {
+            self.dropped.insert(entry.header_offset);
+        }
+    }
+
+    /// The local data of the entries in the zip archive that have not been erased or overwritten.
+    pub(crate) fn live_records(&self) -> Vec<LocalRecord> {
+        self.records
+            .iter()
+            .filter(|record| !self.dropped.contains(&record.header_offset))
+            .copied()
+            .collect()
+    }
+
+    /// Returns true if the stored entry with the local file header at `header_offset` has been partially written in place since its CRC-32 was last updated.
+    pub(crate) fn crc32_is_stale(&self, header_offset: u64) -> bool {
+        self.stale_crc32s.contains(&header_offset)
+    }
+
+    /// Record that the stored entry with the local file header at `header_offset` has been partially written in place.
+    pub(crate) fn mark_crc32_stale(&mut self, header_offset: u64) {
+        self.stale_crc32s.insert(header_offset);
+    }
+
+    /// Returns true if the index has changes that have not been written to the zip archive, or stale CRC-32s.
+    pub(crate) fn has_changes(&self) -> bool {
+        self.modified || !self.stale_crc32s.is_empty()
+    }
+
+    /// A snapshot of the pending values, the offsets of dropped entries, and the entries with stale CRC-32s.
+    ///
+    /// If `explicit_directories`, the directories of pending values without a directory entry are included in the directory entries to write.
+    pub(crate) fn pending_changes(&self, explicit_directories: bool) -> PendingChanges {
+        let mut directories: Vec<StorePrefix> = self
+            .directories
+            .iter()
+            .filter(|(_, header_offset)| header_offset.is_none())
+            .map(|(prefix, _)| prefix.clone())
+            .collect();
+        if explicit_directories {
+            directories.extend(
+                self.pending()
+                    .flat_map(|(key, _, _)| parent_directories(key))
+                    .filter_map(|directory| StorePrefix::new(directory).ok())
+                    .filter(|prefix| !self.directories.contains_key(prefix)),
+            );
+        }
+        directories.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        directories.dedup();
+        PendingChanges {
+            values: self
+                .pending()
+                .map(|(key, value, options)| (key.clone(), value.clone(), options.clone()))
+                .collect(),
+            dropped: self.dropped.clone(),
+            directories,
+            comment: self.comment_changed.then(|| self.comment.clone()),
+            stale_crc32s: self
+                .live_records()
+                .into_iter()
+                .filter(|record| self.stale_crc32s.contains(&record.header_offset))
+                .collect(),
+        }
+    }
+
+    /// Reapply the changes in `current` that were made after `written` was taken.
+    ///
+    /// `self` is the index of the archive rewritten with `written`.
+    /// Entries retained by a rewrite keep their offsets, so `current` and `self` agree on all entries that were not changed.
+    #[cfg(feature = "async")]
+    pub(crate) fn reapply_changes(&mut self, current: &ZipIndex, written: &PendingChanges) {
+        // Keys erased since the snapshot
+        let erased: Vec<StoreKey> = self
+            .entries
+            .keys()
+            .filter(|key| current.get(key).is_none())
+            .map(|key| StoreKey::clone(key))
+            .collect();
+        for key in &erased {
+            self.remove(key);
+        }
+
+        // Keys set since the snapshot
+        let written_values: HashMap<&StoreKey, &Bytes> = written
+            .values
+            .iter()
+            .map(|(key, value, _)| (key, value))
+            .collect();
+        for (key, value, options) in current.pending() {
+            let unchanged = written_values.get(key).is_some_and(|written| {
+                written.as_ptr() == value.as_ptr() && written.len() == value.len()
+            });
+            if !unchanged {
+                self.insert_pending(key, value.clone(), options.clone());
+            }
+        }
+
+        // Entries partially written in place since the snapshot
+        let updated: HashSet<u64> = written
+            .stale_crc32s
+            .iter()
+            .map(|record| record.header_offset)
+            .collect();
+        self.stale_crc32s = current.stale_crc32s.difference(&updated).copied().collect();
+
+        // Directory entries created since the snapshot
+        for (prefix, header_offset) in &current.directories {
+            if header_offset.is_none() && !written.directories.contains(prefix) {
+                self.insert_directory(prefix);
+            }
+        }
+
+        // The comment, if set since the snapshot
+        if current.comment_changed && written.comment.as_ref() != Some(&current.comment) {
+            self.set_comment(current.comment.clone());
+        }
+    }
+
+    /// The pending values and their write options in key order.
+    fn pending(&self) -> impl Iterator<Item = (&StoreKey, &Bytes, &ZipWriteOptions)> {
+        self.sorted_entries().iter().filter_map(|e| match e {
+            ZipEntry::Key(key) => match self.entries.get(key) {
+                Some(IndexEntry::Pending(value, options)) => Some((&**key, value, options)),
+                _ => None,
+            },
+            ZipEntry::Prefix(_) => None,
+        })
+    }
+}
@@ -25,42 +25,313 @@
 //! - the MIT license [LICENSE-MIT](https://docs.rs/crate/zarrs_zip/latest/source/LICENCE-MIT) or <http://opensource.org/licenses/MIT>, at your option.
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
+mod blocks;
+mod builder;
+mod cache;
+mod coalesce;
+#[cfg(any(feature = "lzma", feature = "zstd"))]
+mod decoder;
+#[cfg(feature = "encryption")]
+mod encryption;
+mod index;
+mod memory;
+mod preload;
+mod progress;
+mod seek;
 mod sync;
+mod validate;
 
 #[cfg(feature = "async")]
 mod r#async;
 
-use zarrs_storage::{StorageError, StoreKey, StoreKeyError, StorePrefix, StorePrefixError};
+#[cfg(feature = "rayon")]
+mod parallel;
 
-use rc_zip::parse::Entry;
+#[cfg(feature = "write")]
+mod format;
+#[cfg(feature = "mmap")]
+mod mmap;
+#[cfg(feature = "write")]
+mod overlay;
+#[cfg(feature = "write")]
+mod stream;
+#[cfg(feature = "write")]
+mod write;
+
+use zarrs_storage::{
+    Bytes, StorageError, StoreKey, StoreKeyError, StorePrefix, StorePrefixError, StorePrefixes,
+    byte_range::{ByteRange, InvalidByteRangeError},
+};
+
+use parking_lot::RwLock;
 use thiserror::Error;
 
-use std::collections::HashMap;
 use std::{
+    collections::HashMap,
+    ops::Range,
     path::{Path, PathBuf},
     sync::Arc,
+    time::SystemTime,
 };
 
-/// An entry in the zip archive (either a file or directory).
-#[derive(Debug, Clone, PartialEq, Eq)]
-enum ZipEntry {
-    Key(StoreKey),
-    Prefix(StorePrefix),
+pub use blocks::ZipEntryBlocks;
+pub use builder::ZipStorageAdapterBuilder;
+pub use cache::ZipCacheStats;
+pub use memory::ZipBytes;
+#[cfg(feature = "write")]
+pub use overlay::ZipOverlayAdapter;
+#[cfg(feature = "rayon")]
+pub use parallel::PARALLEL_DECOMPRESSION_THRESHOLD;
+pub use progress::{ZipReadProgress, ZipReadProgressCallback};
+pub use rc_zip::parse::Method;
+#[cfg(feature = "write")]
+pub use stream::ZipStreamWriter;
+pub use validate::ZipValidationIssue;
+#[cfg(feature = "write")]
+pub use write::{RepackSummary, StoreToZipSummary, merge_zips, store_to_zip};
+
+use cache::DecompressedCache;
+use coalesce::CoalescedRanges;
+use index::{IndexEntry, IndexOptions, StoredEntry, ZipIndex};
+use seek::SeekIndexes;
+
+/// The size of the fixed-size part of a local file header, before the file name and extra field.
+const LOCAL_FILE_HEADER_FIXED_SIZE: u64 = 30;
+
+/// The maximum gap in bytes between local file headers that are read in the same request when prefetching headers.
+///
+/// See [`ZipStorageAdapterBuilder::prefetch_headers`].
+const HEADER_PREFETCH_MAX_GAP: u64 = 64 * 1024;
+
+/// The maximum size in bytes of a request when prefetching local file headers.
+///
+/// See [`ZipStorageAdapterBuilder::prefetch_headers`].
+const HEADER_PREFETCH_MAX_SIZE: u64 = 16 * 1024 * 1024;
+
+/// The default maximum gap in bytes between byte ranges of a stored entry that are read in the same request.
+///
+/// See [`ZipStorageAdapter::with_coalesce_gap`].
+const DEFAULT_COALESCE_GAP: u64 = 4 * 1024;
+
+/// The default maximum compressed size in bytes of an entry that is read in a single request when it is decompressed in full.
+///
+/// See [`ZipStorageAdapter::with_single_read_threshold`].
+const DEFAULT_SINGLE_READ_THRESHOLD: u64 = 64 * 1024 * 1024;
+
+/// The default maximum number of concurrent requests of the zip file by an asynchronous read.
+///
+/// See [`ZipStorageAdapter::with_concurrency`].
+#[cfg(feature = "async")]
+const DEFAULT_CONCURRENCY: usize = 8;
+
+/// The size in bytes of the extra fields of a local file header that a single read of an entry allows for, if its data offset is not known.
+const SINGLE_READ_EXTRA_FIELD_ALLOWANCE: u64 = 256;
+
+/// The maximum size in bytes of a data descriptor, with a signature and ZIP64 sizes.
+const DATA_DESCRIPTOR_MAX_SIZE: u64 = 24;
+
+/// The size of the fixed-size part of the end of central directory record, before the archive comment.
+const END_OF_CENTRAL_DIRECTORY_FIXED_SIZE: usize = 22;
+
+/// The signature of the end of central directory record.
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0605_4b50;
+
+/// The signature of a local file header, which starts a zip file with at least one entry.
+const LOCAL_FILE_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+
+/// The maximum number of trailing bytes that can contain the end of central directory record.
+pub(crate) const END_OF_CENTRAL_DIRECTORY_SEARCH_SIZE: usize =
+    END_OF_CENTRAL_DIRECTORY_FIXED_SIZE + u16::MAX as usize;
+
+/// Whether the trailing bytes `tail` of a value contain the signature of an end of central directory record.
+///
+/// A value without the signature in its last [`END_OF_CENTRAL_DIRECTORY_SEARCH_SIZE`] bytes is not a zip file.
+fn has_end_of_central_directory(tail: &[u8]) -> bool {
+    tail.windows(4)
+        .any(|window| window == END_OF_CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes())
 }
 
-impl ZipEntry {
-    fn as_str(&self) -> &str {
-        match self {
-            ZipEntry::Key(k) => k.as_str(),
-            ZipEntry::Prefix(p) => p.as_str(),
+/// The error of a value at `key` of `size` bytes without an end of central directory record, given `head`, its first bytes if they could be read.
+///
+/// A value starting with a local file header is a zip file that is truncated, otherwise it is not a zip file.
+/// `requested_end` is the end of the last byte range that was requested as the value was parsed.
+fn missing_end_of_central_directory_error(
+    key: &StoreKey,
+    size: u64,
+    requested_end: u64,
+    head: Option<&[u8]>,
+) -> ZipStorageAdapterCreateError {
+    if head.is_some_and(|head| head == LOCAL_FILE_HEADER_SIGNATURE.to_le_bytes()) {
+        ZipStorageAdapterCreateError::TruncatedArchive {
+            expected: requested_end,
+            available: size,
         }
+    } else {
+        ZipStorageAdapterCreateError::NotAZipFile(key.clone())
+    }
+}
+
+/// The trailing bytes of a zip file that are read in a single request before it is parsed.
+///
+/// See [`ZipStorageAdapterBuilder::eocd_prefetch_bytes`].
+struct ArchivePrefetch {
+    /// The offset of `bytes` in the zip file.
+    offset: u64,
+    /// The trailing bytes of the zip file.
+    bytes: Bytes,
+}
+
+impl ArchivePrefetch {
+    /// At most `len` prefetched bytes from `offset` of the zip file, or [`None`] if the byte at `offset` was not prefetched.
+    fn get(&self, offset: u64, len: u64) -> Option<&[u8]> {
+        let start = usize::try_from(offset.checked_sub(self.offset)?).ok()?;
+        let available = self.bytes.len().checked_sub(start).filter(|&n| n > 0)?;
+        let len = usize::try_from(len).map_or(available, |len| len.min(available));
+        Some(&self.bytes[start..start + len])
+    }
+}
+
+/// The trailing bytes of a zip file that are read while it is parsed, from which the archive comment is taken.
+///
+/// `rc-zip` decodes the archive comment as text, so the raw bytes of the comment are taken from the end of central directory record instead.
+struct ArchiveTail {
+    /// The size of the zip file.
+    size: u64,
+    /// The offset of `bytes` in the zip file.
+    offset: u64,
+    /// The contiguous bytes read most recently, or the trailing bytes of the zip file once they have been read.
+    bytes: Vec<u8>,
+}
+
+impl ArchiveTail {
+    /// Start capturing the trailing bytes of a zip file of `size` bytes.
+    fn new(size: u64) -> Self {
+        Self {
+            size,
+            offset: 0,
+            bytes: Vec::new(),
+        }
+    }
+
+    /// The offset of the end of the captured bytes.
+    fn end(&self) -> u64 {
+        self.offset + self.bytes.len() as u64
+    }
+
+    /// Record that `data` was read from `offset` of the zip file.
+    fn push(&mut self, offset: u64, data: &[u8]) {
+        if !self.bytes.is_empty() && self.end() == self.size {
+            return;
+        }
+        if offset != self.end() {
+            self.bytes.clear();
+            self.offset = offset;
+        }
+        self.bytes.extend_from_slice(data);
+    }
+
+    /// The byte range of the archive comment in the zip file, or [`None`] if the end of central directory record was not captured.
+    fn comment_range(&self) -> Option<Range<u64>> {
+        let bytes = &self.bytes;
+        let read_u16 = |i: usize| usize::from(u16::from_le_bytes([bytes[i], bytes[i + 1]]));
+        let last = bytes
+            .len()
+            .checked_sub(END_OF_CENTRAL_DIRECTORY_FIXED_SIZE)?;
+        // Search backwards, the record is followed only by its variable length comment
+        (0..=last)
+            .rev()
+            .find(|&i| {
+                bytes[i..i + 4] == END_OF_CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes()
+                    && i + END_OF_CENTRAL_DIRECTORY_FIXED_SIZE + read_u16(i + 20) == bytes.len()
+            })
+            .map(|i| self.offset + (i + END_OF_CENTRAL_DIRECTORY_FIXED_SIZE) as u64..self.end())
+    }
+
+    /// The raw bytes of the archive comment, which is empty if the end of central directory record was not captured.
+    #[allow(clippy::cast_possible_truncation)]
+    fn comment(&self) -> Vec<u8> {
+        self.comment_range()
+            .map(|range| {
+                self.bytes[(range.start - self.offset) as usize..(range.end - self.offset) as usize]
+                    .to_vec()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Replace the bytes of `data`, read from `offset` of a zip file, that are within the archive `comment` with spaces.
+///
+/// `rc-zip` fails to parse a zip file with an archive comment that is not valid in the encoding of its entry names, such as a binary comment of a zip file with UTF-8 names.
+/// The comment is taken from the raw bytes of the zip file rather than `rc-zip`, so it is blanked when such a zip file is parsed again.
+fn blank_comment(data: &mut [u8], offset: u64, comment: Option<&Range<u64>>) {
+    let Some(comment) = comment else {
+        return;
+    };
+    let end = offset + data.len() as u64;
+    let start = comment.start.clamp(offset, end);
+    let stop = comment.end.clamp(offset, end);
+    if let (Ok(start), Ok(stop)) = (
+        usize::try_from(start - offset),
+        usize::try_from(stop - offset),
+    ) {
+        data[start..stop].fill(b' ');
     }
 }
 
 /// A zip storage adapter.
+///
+/// If a zip file has more than one entry with the same name, the last entry in its central directory wins, as when extracting the zip file.
+/// Earlier entries with the name are ignored, and are removed from the central directory by the next [`finish`](ZipStorageAdapter::finish) that rewrites it.
+///
+/// ### Concurrency
+/// The adapter is `Send + Sync` if the underlying storage is, and can be shared between threads, e.g. in an [`Arc`].
+/// Reads of the underlying storage are never made while holding a lock, so concurrent reads of different keys (or the same key) proceed independently:
+/// the index entry of a key is cloned before its data is read, and the data offset and decompressed entry caches are only locked to look up or insert a value.
+/// Concurrent reads of an uncached compressed entry each decompress it, rather than waiting for one another.
+/// Only [`finish`](ZipStorageAdapter::finish) holds a lock on the index while it reads and writes the underlying storage, blocking concurrent writes.
+///
+/// ### Nested zip files
+/// The adapter implements [`ReadableStorageTraits`](zarrs_storage::ReadableStorageTraits), so a zip file that is an entry of another zip file can be opened with an adapter over the adapter of the outer zip file.
+/// Reads of the inner zip file are byte ranges of its entry in the outer zip file:
+///  - if the entry is stored without compression, byte ranges are read directly from the outer zip file, so nesting is as efficient as opening the inner zip file directly, but
+///  - if the entry is compressed, each read decompresses the entry from its start, unless the decompressed entry is cached by the outer adapter (see [`with_cache`](ZipStorageAdapter::with_cache)).
+///
+/// Zip files that are nested in another zip file should be stored without compression.
+///
+/// ### Writing
+/// With the `write` feature, [`WritableStorageTraits`](zarrs_storage::WritableStorageTraits) is implemented if the underlying storage is readable and writable.
+/// With the `async` feature as well, `AsyncWritableStorageTraits` is implemented if the underlying storage is asynchronously readable and writable, and pending writes are written by `finish_async`.
+/// Writes are buffered in memory and are immediately visible to reads through the adapter, but the zip file itself is not modified until [`finish`](ZipStorageAdapter::finish) is called.
+/// Pending writes are discarded if the adapter is dropped without calling [`finish`](ZipStorageAdapter::finish).
+/// To edit a zip file on read-only storage, or write the edits to a new zip file, use a [`ZipOverlayAdapter`] instead.
+///
+/// The central directory of a zip file is at its end, so every [`finish`](ZipStorageAdapter::finish) rewrites the whole zip file:
+///  - the existing zip file is read in full and written back with the new entries appended and a new central directory,
+///  - the local data of erased or overwritten entries is not reclaimed, it remains in the zip file as unreferenced bytes (see [`dead_bytes`](ZipStorageAdapter::dead_bytes)), and
+///  - peak memory usage is the size of the existing zip file plus the size of all pending values.
+///
+/// `finish_async` also assembles the zip file in memory and writes it with a single `set`, as `AsyncWritableStorageTraits` has no multipart or streaming upload.
+/// To write a zip file in a single forward pass without holding it in memory, such as to a multipart upload to object storage, use a [`ZipStreamWriter`] instead.
+///
+/// Batch as many writes as possible between calls to [`finish`](ZipStorageAdapter::finish).
+/// By default, the rewritten zip file is staged under a temporary key so that a failed write does not corrupt the existing zip file, see [`ZipWriteOptions::staged`].
+/// Written entries are stored without compression by default, see [`with_write_options`](ZipStorageAdapter::with_write_options).
+///
+/// Setting a key that already exists is handled according to the [`ZipOverwritePolicy`] of the adapter, see [`with_overwrite_policy`](ZipStorageAdapter::with_overwrite_policy).
+///
+/// ### Appending
+/// An adapter opened with [`open_append`](ZipStorageAdapter::open_append) appends pending changes to the zip file in place if the underlying storage supports partial writes,
+/// rather than rewriting the entire zip file.
+/// New entries are written over the existing central directory, followed by a new central directory that includes the existing and new entries.
+/// Only the existing central directory is read, so the cost of [`finish`](ZipStorageAdapter::finish) is proportional to the size of the pending values and the number of entries, not the size of the zip file.
+/// If entries have been erased such that the result would be shorter than the existing central directory, it is written after the end of the zip file instead, and the existing central directory remains as unreferenced bytes.
+///
+/// ### Partial writes
+/// Partial writes to an entry that is stored without compression are written to the zip file in place if the underlying storage supports partial writes.
+/// They must not change the length of the entry, and partial writes to compressed entries are not supported.
+/// The CRC-32 of the entry is not updated until [`finish`](ZipStorageAdapter::finish) is called, so the zip file fails CRC-32 checks of other zip implementations until then.
+/// Partial writes to pending values are applied to the pending value.
 pub struct ZipStorageAdapter<TStorage: ?Sized> {
-    /// Total size of the zip file.
-    size: u64,
     /// Reference to underlying storage.
     storage: Arc<TStorage>,
     /// Store key for the zip file.
@@ -68,69 +339,1440 @@ pub struct ZipStorageAdapter<TStorage: ?Sized> {
     /// An empty key addresses the entire underlying storage resource,
     /// e.g. a `FilesystemStore` rooted directly at a zip file.
     key: StoreKey,
-    /// `HashMap` for O(1) entry lookup by key.
-    entries: HashMap<StoreKey, Entry>,
-    /// Sorted entries (keys and prefixes) for listing operations.
-    sorted_entries: Vec<ZipEntry>,
+    /// Path within the zip file that the adapter is rooted at.
+    zip_path: String,
+    /// The index of the entries in the zip file, shared by clones of the adapter.
+    index: Arc<RwLock<ZipIndex>>,
+    /// The cache of decompressed entries, shared by clones of the adapter.
+    cache: Option<Arc<DecompressedCache>>,
+    /// The seek indexes of deflated entries, shared by clones of the adapter.
+    seek_indexes: Option<Arc<SeekIndexes>>,
+    /// The function called with the progress of reads of compressed entries.
+    read_progress: Option<ZipReadProgressCallback>,
+    /// The password of encrypted entries.
+    #[cfg(feature = "encryption")]
+    password: Option<Vec<u8>>,
+    /// The offsets of the data of stored entries, keyed by the offset of their local file header.
+    ///
+    /// Populated lazily, so that the local file header of an entry is read at most once.
+    /// Local file header offsets are not reused when the zip file is written, and the offsets of entries that are no longer in the zip file are forgotten.
+    data_offsets: Arc<RwLock<HashMap<u64, u64>>>,
+    /// The values of small entries read when the adapter was built, by the offset of their local file headers.
+    ///
+    /// See [`ZipStorageAdapterBuilder::preload_small_entries`].
+    preloaded: Arc<RwLock<HashMap<u64, Bytes>>>,
+    /// The raw bytes of the archive comment, returned by [`comment`](ZipStorageAdapter::comment).
+    comment: Vec<u8>,
+    /// Whether full reads of stored entries are verified against their CRC-32.
+    verify_crc32: bool,
+    /// The maximum gap in bytes between byte ranges of a stored entry that are read in the same request.
+    coalesce_gap: u64,
+    /// The minimum size in bytes of the requests of compressed data as an entry is decompressed.
+    min_read_size: u64,
+    /// The maximum size in bytes of the requests of compressed data as an entry is decompressed.
+    read_buffer_size: u64,
+    /// The maximum compressed size in bytes of an entry that is read in a single request when it is decompressed in full.
+    single_read_threshold: u64,
+    /// The maximum number of concurrent requests of the zip file by an asynchronous read.
+    #[cfg(feature = "async")]
+    concurrency: usize,
+    /// The number of trailing bytes of the zip file that are read in a single request before it is parsed.
+    #[cfg_attr(not(feature = "write"), allow(dead_code))]
+    eocd_prefetch_bytes: u64,
+    /// The options of the index, when it is rebuilt.
+    #[cfg_attr(not(feature = "write"), allow(dead_code))]
+    index_options: IndexOptions,
+    /// The behaviour of writes to existing keys.
+    #[cfg(feature = "write")]
+    overwrite_policy: ZipOverwritePolicy,
+    /// The default options of written entries.
+    #[cfg(feature = "write")]
+    write_options: ZipWriteOptions,
+    /// Whether pending changes are appended to the zip file in place.
+    #[cfg(feature = "write")]
+    append: bool,
+    /// Serialises asynchronous calls to [`finish_async`](ZipStorageAdapter::finish_async).
+    #[cfg(all(feature = "async", feature = "write"))]
+    finish_lock: Arc<futures::lock::Mutex<()>>,
+    /// Runs the compression of [`finish_async`](ZipStorageAdapter::finish_async) off of the async executor.
+    #[cfg(all(feature = "async", feature = "write"))]
+    blocking_spawner: Option<ZipBlockingSpawner>,
+}
+
+/// A clone is a handle to the same adapter, which shares the index, caches, and memoized data offsets of the zip file, so that they are not duplicated or lost.
+///
+/// Pending changes made through one handle are visible to the others, and are written by [`finish`](ZipStorageAdapter::finish) through any of them.
+/// Options set on a clone, such as by [`with_cache`](ZipStorageAdapter::with_cache), only apply to that clone.
+impl<TStorage: ?Sized> Clone for ZipStorageAdapter<TStorage> {
+    fn clone(&self) -> Self {
+        Self {
+            storage: self.storage.clone(),
+            key: self.key.clone(),
+            zip_path: self.zip_path.clone(),
+            index: self.index.clone(),
+            cache: self.cache.clone(),
+            seek_indexes: self.seek_indexes.clone(),
+            read_progress: self.read_progress.clone(),
+            #[cfg(feature = "encryption")]
+            password: self.password.clone(),
+            data_offsets: self.data_offsets.clone(),
+            preloaded: self.preloaded.clone(),
+            comment: self.comment.clone(),
+            verify_crc32: self.verify_crc32,
+            coalesce_gap: self.coalesce_gap,
+            min_read_size: self.min_read_size,
+            read_buffer_size: self.read_buffer_size,
+            single_read_threshold: self.single_read_threshold,
+            #[cfg(feature = "async")]
+            concurrency: self.concurrency,
+            eocd_prefetch_bytes: self.eocd_prefetch_bytes,
+            index_options: self.index_options,
+            #[cfg(feature = "write")]
+            overwrite_policy: self.overwrite_policy,
+            #[cfg(feature = "write")]
+            write_options: self.write_options.clone(),
+            #[cfg(feature = "write")]
+            append: self.append,
+            #[cfg(all(feature = "async", feature = "write"))]
+            finish_lock: self.finish_lock.clone(),
+            #[cfg(all(feature = "async", feature = "write"))]
+            blocking_spawner: self.blocking_spawner.clone(),
+        }
+    }
 }
 
 impl<TStorage: ?Sized> core::fmt::Debug for ZipStorageAdapter<TStorage> {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let index = self.index.read();
         f.debug_struct("ZipStorageAdapter")
-            .field("size", &self.size)
+            .field("size", &index.size)
             .field("key", &self.key)
-            .field("num_entries", &self.entries.len())
+            .field("num_entries", &index.num_keys())
             .finish_non_exhaustive()
     }
 }
 
 impl<TStorage: ?Sized> ZipStorageAdapter<TStorage> {
-    fn strip_zip_path_prefix<'a>(name: &'a str, zip_path: &Path) -> Option<&'a str> {
-        let prefix = zip_path.to_str().unwrap_or("");
-        name.strip_prefix(prefix).filter(|&n| !n.is_empty())
+    /// Create a zip storage adapter from the index of a parsed zip file.
+    fn from_index(
+        storage: Arc<TStorage>,
+        key: StoreKey,
+        zip_path: String,
+        index: ZipIndex,
+        index_options: IndexOptions,
+        eocd_prefetch_bytes: u64,
+    ) -> Self {
+        let comment = index.comment.clone();
+        Self {
+            storage,
+            key,
+            zip_path,
+            index: Arc::new(RwLock::new(index)),
+            cache: None,
+            seek_indexes: None,
+            read_progress: None,
+            #[cfg(feature = "encryption")]
+            password: None,
+            data_offsets: Arc::new(RwLock::new(HashMap::new())),
+            preloaded: Arc::new(RwLock::new(HashMap::new())),
+            comment,
+            verify_crc32: false,
+            coalesce_gap: DEFAULT_COALESCE_GAP,
+            min_read_size: 0,
+            read_buffer_size: u64::MAX,
+            single_read_threshold: DEFAULT_SINGLE_READ_THRESHOLD,
+            #[cfg(feature = "async")]
+            concurrency: DEFAULT_CONCURRENCY,
+            eocd_prefetch_bytes,
+            index_options,
+            #[cfg(feature = "write")]
+            overwrite_policy: ZipOverwritePolicy::default(),
+            #[cfg(feature = "write")]
+            write_options: ZipWriteOptions::default(),
+            #[cfg(feature = "write")]
+            append: false,
+            #[cfg(all(feature = "async", feature = "write"))]
+            finish_lock: Arc::new(futures::lock::Mutex::new(())),
+            #[cfg(all(feature = "async", feature = "write"))]
+            blocking_spawner: None,
+        }
+    }
+
+    /// Create a zip storage adapter from the index of `archive` within `zip_path`.
+    fn from_parsed(
+        storage: Arc<TStorage>,
+        key: StoreKey,
+        zip_path: String,
+        archive: &ZipParsedArchive,
+        index_options: IndexOptions,
+        eocd_prefetch_bytes: u64,
+    ) -> Result<Self, ZipStorageAdapterCreateError> {
+        // Build entries map and sorted entries list
+        let index = ZipIndex::new(
+            &archive.archive,
+            archive.comment.clone(),
+            archive.size,
+            &zip_path,
+            index_options,
+        )?;
+        Ok(Self::from_index(
+            storage,
+            key,
+            zip_path,
+            index,
+            index_options,
+            eocd_prefetch_bytes,
+        ))
+    }
+
+    /// Create a new zip storage adapter to `path` within the zip file at `key` of `storage`, from its central directory parsed by [`parse`](ZipStorageAdapter::parse) or [`parse_async`](ZipStorageAdapter::parse_async).
+    ///
+    /// `path` is treated as a directory, see [`ZipStorageAdapterBuilder::path`].
+    /// The zip file is not read, so adapters rooted at different paths within a zip file can be created without parsing its central directory again.
+    /// `archive` must have been parsed from the current value at `key` of `storage`, it is not checked against the zip file.
+    ///
+    /// # Errors
+    /// Returns a [`ZipStorageAdapterCreateError`] if an entry within `path` is not a valid store key or prefix.
+    pub fn from_archive<T: Into<PathBuf>>(
+        storage: Arc<TStorage>,
+        key: StoreKey,
+        archive: &ZipParsedArchive,
+        path: T,
+    ) -> Result<Self, ZipStorageAdapterCreateError> {
+        let zip_path = Self::zip_path_str(&path.into());
+        Self::from_parsed(storage, key, zip_path, archive, IndexOptions::default(), 0)
+    }
+
+    /// Cache decompressed entries, holding at most `cache_bytes` bytes of decompressed data.
+    ///
+    /// Reads of a compressed entry that is not in the cache decompress the entire entry, rather than stopping at the end of the requested byte ranges.
+    /// Entries larger than `cache_bytes` are never cached.
+    /// Stored (uncompressed) entries are read directly from the underlying storage and are not cached, unless they are [prefetched](ZipStorageAdapter::prefetch).
+    #[must_use]
+    pub fn with_cache(mut self, cache_bytes: usize) -> Self {
+        self.cache = Some(Arc::new(DecompressedCache::new(cache_bytes)));
+        self
+    }
+
+    /// Index deflated entries for random access, with checkpoints at least `interval_bytes` decompressed bytes apart.
+    ///
+    /// Reads of a deflated entry record checkpoints of the decompressor at the start of deflate blocks, as they are decompressed.
+    /// Later reads resume decompressing from the last checkpoint before the requested byte ranges, rather than from the start of the entry,
+    /// so once an entry has been read in full, a read of its end or middle only reads and decompresses the compressed data from a nearby checkpoint.
+    /// This suits large deflated entries that are read partially and repeatedly, such as suffix reads of the index of a compressed shard.
+    ///
+    /// Each checkpoint holds 32 KiB of decompressed data, and the least recently used indexes are evicted to hold at most 2048 checkpoints (64 MiB).
+    /// A smaller `interval_bytes` reduces the data decompressed by a read, at the cost of more checkpoints per entry.
+    /// Reads that resume from a checkpoint do not verify the CRC-32 of the entry.
+    /// Entries that are decompressed in full to be cached by [`with_cache`](ZipStorageAdapter::with_cache) are not indexed.
+    #[must_use]
+    pub fn with_seek_index(mut self, interval_bytes: u64) -> Self {
+        let flush_points = self
+            .seek_indexes
+            .as_deref()
+            .is_some_and(SeekIndexes::flush_points);
+        self.seek_indexes = Some(Arc::new(SeekIndexes::new(interval_bytes, flush_points)));
+        self
+    }
+
+    /// Index deflated entries for random access at their full flush points.
+    ///
+    /// A full flush (`Z_FULL_FLUSH`) ends the compressed data at a byte boundary with an empty stored block (`00 00 FF FF`) and resets the window of the compressor,
+    /// so decompression can restart from it without any of the preceding data.
+    /// Reads of a deflated entry record each full flush point in the seek index of the entry as it is decompressed.
+    /// A flush point is checked by decompressing the 32 KiB that follow it from an empty window, so sync flushes (`Z_SYNC_FLUSH`), which do not reset the window, are not recorded.
+    ///
+    /// Unlike the checkpoints of [`with_seek_index`](ZipStorageAdapter::with_seek_index), flush points do not hold any decompressed data,
+    /// so entries written with frequent full flushes, such as by `pigz --independent`, can be indexed finely with little memory.
+    /// Without [`with_seek_index`](ZipStorageAdapter::with_seek_index), checkpoints are only recorded at flush points.
+    #[must_use]
+    pub fn with_seek_flush_points(mut self) -> Self {
+        let interval_bytes = self
+            .seek_indexes
+            .as_deref()
+            .map_or(u64::MAX, SeekIndexes::interval);
+        self.seek_indexes = Some(Arc::new(SeekIndexes::new(interval_bytes, true)));
+        self
+    }
+
+    /// Decrypt entries encrypted with the traditional PKWARE encryption (`ZipCrypto`) or the `WinZip` AES extension (AE-1 and AE-2) with `password`.
+    ///
+    /// The encryption of each entry is detected from its flags and extra fields.
+    /// The keys of an AES encrypted entry are derived from the password and the salt of the entry, and its authentication code is verified before it is decrypted.
+    /// `ZipCrypto` is weak encryption, and its password check accepts a wrong password with a probability of 1/256, which is then detected by the CRC-32 of the entry.
+    /// Encrypted entries are decrypted and decompressed in full by every read, unless they are cached by [`with_cache`](ZipStorageAdapter::with_cache).
+    /// Entries that are not encrypted are read as usual.
+    #[cfg(feature = "encryption")]
+    #[must_use]
+    pub fn with_password(mut self, password: impl Into<Vec<u8>>) -> Self {
+        self.password = Some(password.into());
+        self
+    }
+
+    /// Verify the CRC-32 of stored entries when they are read in full.
+    ///
+    /// The value of a stored entry is checked against the CRC-32 in the central directory when a byte range covers the entire value, such as with `get`.
+    /// Partial reads of stored entries are not verified.
+    ///
+    /// Compressed entries are always verified when they are decompressed in full, regardless of this setting.
+    /// Partial reads of compressed entries stop decompressing at the end of the requested byte ranges and are not verified, unless the entry is decompressed in full to be cached.
+    #[must_use]
+    pub fn with_crc32_verification(mut self) -> Self {
+        self.verify_crc32 = true;
+        self
+    }
+
+    /// Set the maximum gap in bytes between byte ranges of a stored entry that are read in the same request.
+    ///
+    /// Byte ranges of a stored entry that overlap or are at most `coalesce_gap` bytes apart are coalesced into a single request to the underlying storage, and the requested byte ranges are sliced out of it.
+    /// This reduces the number of requests of partial reads with many nearby byte ranges, such as the inner chunks of a shard, at the cost of reading the bytes between them.
+    /// A gap of `0` only coalesces byte ranges that overlap or are adjacent.
+    ///
+    /// Defaults to 4 KiB.
+    #[must_use]
+    pub fn with_coalesce_gap(mut self, coalesce_gap: u64) -> Self {
+        self.coalesce_gap = coalesce_gap;
+        self
+    }
+
+    /// Set the minimum size in bytes of the requests of the compressed data of an entry as it is decompressed.
+    ///
+    /// Compressed data is otherwise requested in increments the size of the decompressor's input buffer, which is a request every few KiB on high-latency storage.
+    /// With a minimum read size, compressed data is read into a buffer of at least `min_read_size` bytes and fed to the decompressor from it, and storage is only read again when it is drained.
+    /// Requests never extend beyond the compressed data of the entry and its data descriptor.
+    /// Asynchronous reads start at the larger of `min_read_size` and 64 KiB, and double in size.
+    ///
+    /// Defaults to `0`, reading in increments of the input buffer.
+    #[must_use]
+    pub fn with_min_read_size(mut self, min_read_size: u64) -> Self {
+        self.min_read_size = min_read_size;
+        self
+    }
+
+    /// Set the maximum size in bytes of the requests of the compressed data of an entry as it is decompressed.
+    ///
+    /// This bounds the memory of the compressed data held by a read on memory-constrained systems, such as 8 KiB.
+    /// The compressed data of a request is fed to the decompressor in increments of its input buffer, so a request may be larger or smaller than the input buffer.
+    /// The maximum takes precedence over [`with_min_read_size`](ZipStorageAdapter::with_min_read_size), and also bounds the reads of [`with_seek_index`](ZipStorageAdapter::with_seek_index) and [`get_blocks`](ZipStorageAdapter::get_blocks).
+    /// Entries read in a single request, see [`with_single_read_threshold`](ZipStorageAdapter::with_single_read_threshold), are not split.
+    /// A `read_buffer_size` of `0` is treated as `1`.
+    ///
+    /// Defaults to [`u64::MAX`], requests are only bounded by the input buffer or the minimum read size.
+    #[must_use]
+    pub fn with_read_buffer_size(mut self, read_buffer_size: u64) -> Self {
+        self.read_buffer_size = read_buffer_size.max(1);
+        self
+    }
+
+    /// Set the maximum compressed size in bytes of an entry that is read in a single request when it is decompressed in full.
+    ///
+    /// The local file header and compressed data of such an entry are read together, rather than the local file header first and the compressed data in increments.
+    /// This suits high-latency storage, such as object stores, where each request has a fixed cost.
+    /// Larger entries, and partial reads that stop decompressing before the end of an entry, read the compressed data incrementally, see [`with_min_read_size`](ZipStorageAdapter::with_min_read_size).
+    ///
+    /// Defaults to 64 MiB.
+    #[must_use]
+    pub fn with_single_read_threshold(mut self, single_read_threshold: u64) -> Self {
+        self.single_read_threshold = single_read_threshold;
+        self
+    }
+
+    /// Set the maximum number of concurrent requests of the zip file by an asynchronous read.
+    ///
+    /// The requests of the byte ranges of a stored entry, after coalescing, and of the local file headers prefetched by [`ZipStorageAdapterBuilder::prefetch_headers`] are independent,
+    /// so they are issued concurrently rather than one after another, which takes about a single round trip on high-latency storage, such as object stores.
+    /// Results are returned in the order of the byte ranges.
+    /// A smaller `concurrency` suits rate-limited storage, and `1` issues the requests one at a time.
+    ///
+    /// Defaults to `8`.
+    #[cfg(feature = "async")]
+    #[must_use]
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Call `read_progress` with the progress of reads of compressed entries as they are decompressed.
+    ///
+    /// The callback is called from the decompression loop after each step of decompression, with the bytes of the zip file read and the bytes decompressed so far by the read.
+    /// It is called on the thread performing the read, so it should return quickly.
+    /// Reads that resume from a checkpoint of a seek index count the bytes read and decompressed from the checkpoint.
+    /// Reads of stored entries, entries served from the cache, and encrypted entries do not report progress.
+    #[must_use]
+    pub fn with_read_progress(mut self, read_progress: ZipReadProgressCallback) -> Self {
+        self.read_progress = Some(read_progress);
+        self
+    }
+
+    /// Return the statistics of the decompressed entry cache.
+    ///
+    /// Returns [`None`] if the adapter was not created with a cache.
+    #[must_use]
+    pub fn cache_stats(&self) -> Option<ZipCacheStats> {
+        self.cache.as_deref().map(DecompressedCache::stats)
+    }
+
+    /// Set the behaviour of writes to keys that already exist.
+    ///
+    /// Defaults to [`ZipOverwritePolicy::Replace`].
+    #[cfg(feature = "write")]
+    #[must_use]
+    pub fn with_overwrite_policy(mut self, overwrite_policy: ZipOverwritePolicy) -> Self {
+        self.overwrite_policy = overwrite_policy;
+        self
+    }
+
+    /// Set the default options of written entries.
+    ///
+    /// These can be overridden for individual values with [`set_with_options`](ZipStorageAdapter::set_with_options).
+    /// Defaults to entries stored without compression.
+    #[cfg(feature = "write")]
+    #[must_use]
+    pub fn with_write_options(mut self, write_options: ZipWriteOptions) -> Self {
+        self.write_options = write_options;
+        self
+    }
+
+    /// Compress the entries written by [`finish_async`](ZipStorageAdapter::finish_async) with `blocking_spawner`, rather than on the async executor.
+    ///
+    /// Pending values are compressed as the zip file is rewritten or appended to, which blocks the task calling [`finish_async`](ZipStorageAdapter::finish_async) until they are all compressed.
+    /// With a blocking spawner, such as one calling `tokio::task::spawn_blocking`, the zip file is rewritten or appended to on a thread of a blocking pool instead, and the task awaits it.
+    /// By default, the compression runs on the task calling [`finish_async`](ZipStorageAdapter::finish_async).
+    #[cfg(all(feature = "async", feature = "write"))]
+    #[must_use]
+    pub fn with_blocking_spawner(mut self, blocking_spawner: ZipBlockingSpawner) -> Self {
+        self.blocking_spawner = Some(blocking_spawner);
+        self
+    }
+
+    /// The length of the decompressed prefix of `entry` that contains all of `byte_ranges`.
+    ///
+    /// A suffix byte range requires the entire entry.
+    fn decompressed_size_required(entry: &StoredEntry, byte_ranges: &[ByteRange]) -> u64 {
+        byte_ranges
+            .iter()
+            .map(|range| match range {
+                ByteRange::FromStart(start, Some(len)) => start.saturating_add(*len),
+                ByteRange::FromStart(_, None) | ByteRange::Suffix(_) => entry.uncompressed_size,
+            })
+            .max()
+            .unwrap_or(0)
+            .min(entry.uncompressed_size)
+    }
+
+    /// The size of a request of `len` bytes of compressed data as an entry is decompressed, bounded by the minimum read size and the read buffer size.
+    pub(crate) fn incremental_read_size(&self, len: u64) -> u64 {
+        len.max(self.min_read_size).min(self.read_buffer_size)
+    }
+
+    /// The end of the local data of the compressed `entry`, the value of `key`, if it is read in a single request to decompress `byte_ranges`.
+    ///
+    /// Returns [`None`] unless `byte_ranges` require the entire entry and its compressed size is at most the single read threshold.
+    /// See [`local_data_end`](Self::local_data_end).
+    fn single_read_end(
+        &self,
+        key: &StoreKey,
+        entry: &StoredEntry,
+        byte_ranges: &[ByteRange],
+    ) -> Option<u64> {
+        if entry.compressed_size > self.single_read_threshold
+            || Self::decompressed_size_required(entry, byte_ranges) < entry.uncompressed_size
+        {
+            return None;
+        }
+        Some(self.local_data_end(key, entry))
+    }
+
+    /// The end of the local data of `entry`, the value of `key`, including a data descriptor.
+    ///
+    /// The end is exact if the data offset of the entry is known.
+    /// Otherwise, it allows for a local file header with the name of the entry in the central directory and extra fields of up to [`SINGLE_READ_EXTRA_FIELD_ALLOWANCE`] bytes,
+    /// so that the local file header does not need to be read first.
+    fn local_data_end(&self, key: &StoreKey, entry: &StoredEntry) -> u64 {
+        let data_offset = self.cached_data_offset(entry.header_offset).unwrap_or(
+            entry.header_offset
+                + LOCAL_FILE_HEADER_FIXED_SIZE
+                + (self.zip_path.len() + key.as_str().len()) as u64
+                + SINGLE_READ_EXTRA_FIELD_ALLOWANCE,
+        );
+        let end = data_offset + entry.compressed_size + DATA_DESCRIPTOR_MAX_SIZE;
+        end.min(self.index.read().size)
+    }
+
+    /// Coalesce `byte_ranges` of the stored `entry`, with its data at `data_offset` of the zip file, into requests of the zip file.
+    ///
+    /// The byte ranges must be within the bounds of the entry.
+    fn coalesce_stored_ranges(
+        &self,
+        entry: &StoredEntry,
+        data_offset: u64,
+        byte_ranges: &[ByteRange],
+    ) -> CoalescedRanges {
+        let size = entry.uncompressed_size;
+        let ranges: Vec<Range<u64>> = byte_ranges
+            .iter()
+            .map(|range| data_offset + range.start(size)..data_offset + range.end(size))
+            .collect();
+        CoalescedRanges::new(&ranges, self.coalesce_gap)
+    }
+
+    /// The archived stored entries of `key_ranges` that are read together by [`get_partial_many_keys`](Self::get_partial_many_keys), [`None`] for those read individually.
+    ///
+    /// Entries that are encrypted, preloaded, prefetched, or requested with a byte range out of bounds are read individually.
+    fn batched_stored_entries(
+        &self,
+        key_ranges: &[(StoreKey, Vec<ByteRange>)],
+    ) -> Vec<Option<StoredEntry>> {
+        let entries: Vec<Option<StoredEntry>> = {
+            let index = self.index.read();
+            key_ranges
+                .iter()
+                .map(|(key, _)| match index.get(key) {
+                    Some(IndexEntry::Archived(entry))
+                        if entry.method == Method::Store && !entry.is_encrypted() =>
+                    {
+                        Some(entry.clone())
+                    }
+                    _ => None,
+                })
+                .collect()
+        };
+        entries
+            .into_iter()
+            .zip(key_ranges)
+            .map(|(entry, (key, byte_ranges))| {
+                entry.filter(|entry| {
+                    self.held_value(key, entry).is_none()
+                        && byte_ranges.iter().all(|range| {
+                            validate_byte_range(*range, entry.uncompressed_size).is_ok()
+                        })
+                })
+            })
+            .collect()
+    }
+
+    /// Coalesce the byte ranges of the batched stored `entries` of `key_ranges` into requests of the zip file, across keys.
+    ///
+    /// The local file headers of the entries must have been read.
+    ///
+    /// # Errors
+    /// Returns a [`StorageError`] if the data offset of an entry is not known.
+    fn coalesce_batched_ranges(
+        &self,
+        key_ranges: &[(StoreKey, Vec<ByteRange>)],
+        entries: &[Option<StoredEntry>],
+    ) -> Result<CoalescedRanges, StorageError> {
+        let mut ranges: Vec<Range<u64>> = Vec::new();
+        for ((_, byte_ranges), entry) in key_ranges.iter().zip(entries) {
+            if let Some(entry) = entry {
+                let data_offset =
+                    self.cached_data_offset(entry.header_offset)
+                        .ok_or_else(|| {
+                            StorageError::Other("Cannot read local file header".to_string())
+                        })?;
+                let size = entry.uncompressed_size;
+                ranges.extend(
+                    byte_ranges.iter().map(|range| {
+                        data_offset + range.start(size)..data_offset + range.end(size)
+                    }),
+                );
+            }
+        }
+        Ok(CoalescedRanges::new(&ranges, self.coalesce_gap))
+    }
+
+    /// The local file headers at `header_offsets` that have not been read, in runs that are read in a single request, as the range of bytes spanning each run and its header offsets.
+    ///
+    /// Headers are in the same run if they are at most [`HEADER_PREFETCH_MAX_GAP`] bytes apart and the run spans at most [`HEADER_PREFETCH_MAX_SIZE`] bytes.
+    fn header_prefetch_runs(
+        &self,
+        header_offsets: impl IntoIterator<Item = u64>,
+    ) -> Vec<(Range<u64>, Vec<u64>)> {
+        let mut header_offsets: Vec<u64> = {
+            let data_offsets = self.data_offsets.read();
+            header_offsets
+                .into_iter()
+                .filter(|header_offset| !data_offsets.contains_key(header_offset))
+                .collect()
+        };
+        header_offsets.sort_unstable();
+        header_offsets.dedup();
+
+        let mut runs: Vec<(Range<u64>, Vec<u64>)> = Vec::new();
+        for header_offset in header_offsets {
+            let header_end = header_offset + LOCAL_FILE_HEADER_FIXED_SIZE;
+            match runs.last_mut() {
+                Some((range, offsets))
+                    if header_offset - range.end <= HEADER_PREFETCH_MAX_GAP
+                        && header_end - range.start <= HEADER_PREFETCH_MAX_SIZE =>
+                {
+                    range.end = header_end;
+                    offsets.push(header_offset);
+                }
+                _ => runs.push((header_offset..header_end, vec![header_offset])),
+            }
+        }
+        runs
+    }
+
+    /// Memoize the data offsets of the entries with local file headers at `header_offsets`, given `bytes`, the bytes of the zip file from `start` that contain them.
+    ///
+    /// # Errors
+    /// Returns a [`ZipStorageAdapterCreateError`] if a header is not in `bytes` or is not a local file header.
+    fn data_offsets_from_headers(
+        &self,
+        start: u64,
+        bytes: &[u8],
+        header_offsets: &[u64],
+    ) -> Result<(), ZipStorageAdapterCreateError> {
+        for &header_offset in header_offsets {
+            let header = usize::try_from(header_offset - start)
+                .ok()
+                .and_then(|offset| bytes.get(offset..))
+                .unwrap_or_default();
+            self.data_offset_from_header(header_offset, header)?;
+        }
+        Ok(())
+    }
+
+    /// The data offset of the entry with the local file header at `header_offset`, if it has been read.
+    fn cached_data_offset(&self, header_offset: u64) -> Option<u64> {
+        self.data_offsets.read().get(&header_offset).copied()
+    }
+
+    /// The data offset of the entry with the local file header at `header`, parsed from the fixed-size part of the header.
+    ///
+    /// The data offset is memoized for subsequent reads.
+    ///
+    /// The sizes in the local file header are not used, they are zero if the entry has a data descriptor (general purpose flag bit 3).
+    /// The sizes of an entry are always taken from the central directory.
+    ///
+    /// # Errors
+    /// Returns a [`ZipStorageAdapterCreateError`] if `header` is too short or is not a local file header.
+    fn data_offset_from_header(
+        &self,
+        header_offset: u64,
+        header: &[u8],
+    ) -> Result<u64, ZipStorageAdapterCreateError> {
+        if header.len() < 30 {
+            return Err(ZipStorageAdapterCreateError::ZipError(
+                "Local file header too short".to_string(),
+            ));
+        }
+        if !header.starts_with(b"PK\x03\x04") {
+            return Err(ZipStorageAdapterCreateError::ZipError(format!(
+                "Invalid local file header signature at offset {header_offset}"
+            )));
+        }
+
+        // Local file header structure:
+        // Offset 26: filename length (2 bytes, little-endian)
+        // Offset 28: extra field length (2 bytes, little-endian)
+        let filename_len = u64::from(u16::from_le_bytes([header[26], header[27]]));
+        let extra_len = u64::from(u16::from_le_bytes([header[28], header[29]]));
+        let data_offset = header_offset + 30 + filename_len + extra_len;
+        // The sizes in the local file header are not needed, so a ZIP64 extra field is skipped with the others.
+        // Saturated (0xFFFFFFFF) sizes and offsets are resolved from the ZIP64 extra field of the central directory by `rc_zip`.
+
+        self.data_offsets.write().insert(header_offset, data_offset);
+        Ok(data_offset)
+    }
+
+    /// Convert an error decompressing `entry`, the value of `key`, to a [`StorageError`].
+    ///
+    /// Entries with an unsupported compression method return a [`StorageError::Unsupported`] naming the method,
+    /// including those that `rc_zip` rejects as an invalid local file header, such as LZMA compressed entries without the `lzma` feature.
+    /// Other errors, such as a corrupt compressed stream, return a [`StorageError::Other`] naming `key`.
+    fn decompression_error(
+        key: &StoreKey,
+        entry: &StoredEntry,
+        err: rc_zip::error::Error,
+    ) -> StorageError {
+        match err {
+            err if matches!(err, rc_zip::error::Error::Unsupported(_))
+                || !method_supported(entry.method) =>
+            {
+                StorageError::Unsupported(format!(
+                    "zip entry {key}: {}",
+                    ZipReadError::UnsupportedMethod(entry.method)
+                ))
+            }
+            rc_zip::error::Error::Format(rc_zip::error::FormatError::WrongChecksum {
+                expected,
+                actual,
+            }) => Self::checksum_error(key, expected, actual),
+            err => StorageError::Other(format!("zip entry {key}: decompression error: {err}")),
+        }
+    }
+
+    /// Return the metadata of the zip entry of `key`, without reading its data.
+    ///
+    /// Returns [`None`] if `key` is not an entry in the zip file, including values that are pending until the zip file is finished.
+    #[must_use]
+    pub fn entry_metadata(&self, key: &StoreKey) -> Option<ZipEntryMetadata> {
+        match self.index.read().get(key)? {
+            IndexEntry::Archived(entry) => Some(ZipEntryMetadata::from_entry(entry)),
+            #[cfg(feature = "write")]
+            IndexEntry::Pending(..) => None,
+        }
+    }
+
+    /// Return the compression method of the value of `key`, without reading its data.
+    ///
+    /// Values of stored entries are read directly from the zip file, while those of entries with other methods are decompressed.
+    /// Values that are pending until the zip file is finished return the method that they are written with.
+    /// Returns [`None`] if `key` has no value.
+    #[must_use]
+    pub fn method(&self, key: &StoreKey) -> Option<ZipCompressionMethod> {
+        match self.index.read().get(key)? {
+            IndexEntry::Archived(entry) => Some(entry.method.into()),
+            #[cfg(feature = "write")]
+            IndexEntry::Pending(_, options) => Some(options.compression.method().into()),
+        }
+    }
+
+    /// Returns true if `key` has a value, without reading its data.
+    ///
+    /// This includes values that are pending until the zip file is finished, but not directory entries, which are prefixes rather than keys.
+    #[must_use]
+    pub fn contains_key(&self, key: &StoreKey) -> bool {
+        self.index.read().get(key).is_some()
+    }
+
+    /// Return the name and kind of every entry in the zip file within the path of the adapter, in sorted order.
+    ///
+    /// Names are relative to the path of the adapter, and directory names have a trailing `/`.
+    /// Only directories with a directory entry in the zip file are included, not the implicit parent directories of keys.
+    /// Values that are pending until the zip file is finished are included as files, with the compressed size equal to the uncompressed size.
+    pub fn entries(&self) -> impl Iterator<Item = (String, ZipEntryInfo)> + use<TStorage> {
+        self.index.read().entries().into_iter()
+    }
+
+    /// The total uncompressed size of the values of all keys.
+    ///
+    /// Unlike [`size`](zarrs_storage::ListableStorageTraits::size), which is the size of the zip file, this is the size of the values once decompressed.
+    /// Directory entries are not counted, and pending values are counted with their size.
+    #[must_use]
+    pub fn uncompressed_size(&self) -> u64 {
+        self.uncompressed_size_prefix(&StorePrefix::root())
+    }
+
+    /// The total uncompressed size of the values of all keys with `prefix`.
+    ///
+    /// See [`uncompressed_size`](ZipStorageAdapter::uncompressed_size).
+    #[must_use]
+    pub fn uncompressed_size_prefix(&self, prefix: &StorePrefix) -> u64 {
+        self.index.read().uncompressed_size_prefix(prefix)
+    }
+
+    /// List all keys with `prefix` in sorted order, with the uncompressed size of their values.
+    ///
+    /// This is equivalent to [`list_prefix`](zarrs_storage::ListableStorageTraits::list_prefix) followed by [`size_key`](zarrs_storage::ReadableStorageTraits::size_key) for each key, in a single pass over the index.
+    /// Pending values are listed with their size.
+    #[must_use]
+    pub fn list_prefix_with_sizes(&self, prefix: &StorePrefix) -> Vec<(StoreKey, u64)> {
+        self.index.read().list_prefix_with_sizes(prefix)
+    }
+
+    /// List all keys matching the glob `pattern` in sorted order, such as `*/zarr.json` or `**/zarr.json`.
+    ///
+    /// Wildcards do not match `/`, except for `**` as a whole path component, which matches any number of components.
+    /// Only the keys with the literal prefix of `pattern` up to its last `/` before a wildcard are matched, found with a binary search of the sorted keys.
+    /// Keys are matched ignoring ASCII case if the adapter is [case-insensitive](ZipStorageAdapterBuilder::case_insensitive).
+    ///
+    /// # Errors
+    /// Returns a [`StorageError`] if `pattern` is not a valid glob.
+    #[cfg(feature = "glob")]
+    pub fn list_glob(&self, pattern: &str) -> Result<zarrs_storage::StoreKeys, StorageError> {
+        let index = self.index.read();
+        let glob = globset::GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .case_insensitive(index.case_insensitive())
+            .build()
+            .map_err(|err| StorageError::Other(format!("invalid glob pattern: {err}")))?
+            .compile_matcher();
+        let literal = pattern
+            .find(['*', '?', '[', '{', '\\'])
+            .map_or(pattern, |end| &pattern[..end]);
+        let prefix = literal
+            .rfind('/')
+            .and_then(|end| StorePrefix::new(&literal[..=end]).ok())
+            .unwrap_or_else(StorePrefix::root);
+        Ok(index.list_glob(&prefix, &glob))
     }
 
-    /// Get an entry by key using O(1) `HashMap` lookup.
-    fn get_entry(&self, key: &StoreKey) -> Option<&Entry> {
-        self.entries.get(key)
+    /// List the immediate child prefixes of `prefix` in sorted order, without its keys.
+    ///
+    /// This is equivalent to the prefixes of [`list_dir`](zarrs_storage::ListableStorageTraits::list_dir), without collecting the keys of `prefix`.
+    #[must_use]
+    pub fn list_dir_prefixes(&self, prefix: &StorePrefix) -> StorePrefixes {
+        self.index.read().list_dir_prefixes(prefix)
     }
 
-    /// Find the range of entries matching a prefix using binary search.
-    fn entries_with_prefix(&self, prefix: &StorePrefix) -> &[ZipEntry] {
-        let prefix_str = prefix.as_str();
+    /// The archive comment of the zip file, or [`None`] if it has no comment.
+    ///
+    /// The comment is returned as raw bytes, as zip files do not specify its encoding.
+    /// With the `write` feature, a comment set by [`set_comment`](ZipStorageAdapter::set_comment) is returned, even if it is pending until the zip file is finished.
+    #[must_use]
+    pub fn comment(&self) -> Option<&[u8]> {
+        (!self.comment.is_empty()).then_some(self.comment.as_slice())
+    }
 
-        // Find start index: first entry >= prefix
-        let start = self
-            .sorted_entries
-            .partition_point(|e| e.as_str() < prefix_str);
+    /// The error returned if the CRC-32 of the value of `key` is `actual` rather than `expected`.
+    fn checksum_error(key: &StoreKey, expected: u32, actual: u32) -> StorageError {
+        StorageError::Other(format!(
+            "zip entry {key}: {}",
+            ZipReadError::ChecksumMismatch { expected, actual }
+        ))
+    }
 
-        // Find end index: first entry that doesn't start with prefix
-        let end = self.sorted_entries[start..]
-            .partition_point(|e| e.as_str().starts_with(prefix_str))
-            + start;
+    /// The stored entry of `entry`, the value of `key`, if its entire value is read directly from the zip file in a single request, without the bookkeeping of byte ranges.
+    ///
+    /// Encrypted, preloaded, and prefetched entries, and pending values, are read as other byte ranges.
+    fn stored_value_entry<'a>(
+        &self,
+        key: &StoreKey,
+        entry: &'a IndexEntry,
+    ) -> Option<&'a StoredEntry> {
+        match entry {
+            IndexEntry::Archived(entry) => (entry.method == Method::Store
+                && !entry.is_encrypted()
+                && self.held_value(key, entry).is_none())
+            .then_some(entry),
+            #[cfg(feature = "write")]
+            IndexEntry::Pending(..) => None,
+        }
+    }
 
-        &self.sorted_entries[start..end]
+    /// Returns true if the CRC-32 of the stored `entry` is verified by a read of `byte_ranges`.
+    fn verifies_crc32(&self, entry: &StoredEntry, byte_ranges: &[ByteRange]) -> bool {
+        // The CRC-32 of an entry partially written in place is not updated until the zip file is finished
+        #[cfg(feature = "write")]
+        if self.index.read().crc32_is_stale(entry.header_offset) {
+            return false;
+        }
+        self.verify_crc32 && Self::full_range_position(entry, byte_ranges).is_some()
     }
 
-    /// Get the immediate child prefix of a key relative to a parent prefix.
-    fn immediate_child_prefix(key: &StoreKey, prefix: &StorePrefix) -> Option<StorePrefix> {
-        let key_str = key.as_str();
-        let prefix_str = prefix.as_str();
+    /// The position of the first of `byte_ranges` that covers the entire value of `entry`.
+    fn full_range_position(entry: &StoredEntry, byte_ranges: &[ByteRange]) -> Option<usize> {
+        byte_ranges.iter().position(|range| {
+            range.start(entry.uncompressed_size) == 0
+                && range.end(entry.uncompressed_size) == entry.uncompressed_size
+        })
+    }
 
-        // Get the part after the prefix
-        let suffix = key_str.strip_prefix(prefix_str)?;
+    /// Verify the CRC-32 of the stored `entry`, the value of `key`, given `values`, the values of `byte_ranges`.
+    ///
+    /// # Errors
+    /// Returns a [`StorageError`] if the CRC-32 of a value that covers the entire entry does not match.
+    fn verify_stored_crc32(
+        key: &StoreKey,
+        entry: &StoredEntry,
+        byte_ranges: &[ByteRange],
+        values: &[Bytes],
+    ) -> Result<(), StorageError> {
+        let value = Self::full_range_position(entry, byte_ranges).and_then(|i| values.get(i));
+        if let Some(value) = value {
+            let actual = crc32fast::hash(value);
+            if actual != entry.crc32 {
+                return Err(Self::checksum_error(key, entry.crc32, actual));
+            }
+        }
+        Ok(())
+    }
+
+    /// Return the keys of entries with a compression method that is not supported, and their methods.
+    ///
+    /// The values of these keys cannot be read, but other keys are unaffected.
+    #[must_use]
+    pub fn unsupported_methods(&self) -> Vec<(StoreKey, Method)> {
+        self.index.read().unsupported_methods()
+    }
+
+    /// Return the capabilities that are needed to read the zip file, such as ZIP64 and decryption, without reading any data.
+    ///
+    /// This can be checked when the adapter is created to reject a zip file that cannot be fully read, rather than failing on the first read of an affected key.
+    /// Only the entries within the path of the adapter are included, and values that are pending until the zip file is finished are not.
+    #[must_use]
+    pub fn capabilities(&self) -> ArchiveCapabilities {
+        self.index.read().capabilities()
+    }
+
+    /// Check that the compression methods of all entries are supported.
+    ///
+    /// # Errors
+    /// Returns [`ZipStorageAdapterCreateError::UnsupportedMethods`] listing the keys of entries with an unsupported compression method.
+    pub fn validate_methods(self) -> Result<Self, ZipStorageAdapterCreateError> {
+        let unsupported = self.unsupported_methods();
+        if unsupported.is_empty() {
+            Ok(self)
+        } else {
+            Err(ZipStorageAdapterCreateError::UnsupportedMethods(
+                unsupported,
+            ))
+        }
+    }
 
-        // Find the first '/' in the suffix to get the immediate child directory
-        if let Some(slash_pos) = suffix.find('/') {
-            let child = &suffix[..=slash_pos];
-            let full_prefix = format!("{prefix_str}{child}");
-            StorePrefix::try_from(full_prefix.as_str()).ok()
+    /// Convert a path within the zip file to the prefix of entry names.
+    ///
+    /// The path is a directory, so a non-empty prefix always ends with `/`.
+    /// Otherwise, a path of `a` would also match the entries of a sibling `ab/` directory.
+    fn zip_path_str(zip_path: &Path) -> String {
+        let zip_path = zip_path.to_str().unwrap_or("").trim_end_matches('/');
+        if zip_path.is_empty() {
+            String::new()
         } else {
-            None
+            format!("{zip_path}/")
+        }
+    }
+}
+
+/// The parsed central directory of a zip file, shared by adapters rooted at different paths within it.
+///
+/// See [`ZipStorageAdapter::parse`] and [`ZipStorageAdapter::from_archive`].
+pub struct ZipParsedArchive {
+    /// The parsed archive.
+    archive: rc_zip::parse::Archive,
+    /// The raw bytes of the archive comment.
+    comment: Vec<u8>,
+    /// The size of the zip file.
+    size: u64,
+}
+
+impl core::fmt::Debug for ZipParsedArchive {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ZipParsedArchive")
+            .field("size", &self.size)
+            .field("num_entries", &self.archive.entries().count())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The handling of symbolic link entries in a zip file by a [`ZipStorageAdapter`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+    /// Ignore symbolic links, they are not listed and cannot be read.
+    #[default]
+    Ignore,
+    /// Treat symbolic links as keys with the link target as their value.
+    ///
+    /// A zip file stores the target of a symbolic link as the data of its entry.
+    AsBytes,
+    /// Return [`ZipStorageAdapterCreateError::Symlink`] if the zip file has a symbolic link.
+    Error,
+}
+
+/// The handling of entries in a zip file with unsafe names by a [`ZipStorageAdapter`].
+///
+/// An entry name is unsafe if it is an absolute path, or has a `..` component, such as `../../etc/passwd`.
+/// Keys are not paths of the filesystem, but code using the keys of a zip file to extract its values could write outside of the directory it extracts to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnsafeEntryNamePolicy {
+    /// Return [`ZipStorageAdapterCreateError::UnsafeEntryName`] if the zip file has an entry with an unsafe name.
+    #[default]
+    Error,
+    /// Skip entries with unsafe names, they are not listed and cannot be read.
+    Skip,
+}
+
+/// The behaviour of writes to keys that already exist in a [`ZipStorageAdapter`].
+#[cfg(feature = "write")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ZipOverwritePolicy {
+    /// Replace the existing value.
+    ///
+    /// The local data of a replaced entry remains in the zip file as unreferenced bytes.
+    /// Writing to a key that has not yet been finished replaces the pending value without any such cost.
+    #[default]
+    Replace,
+    /// Return an error if the key exists.
+    Error,
+}
+
+/// The compression method of an entry in a zip file, returned by [`ZipStorageAdapter::method`].
+///
+/// Methods are identified by their code in the zip specification, independently of the zip implementation used to read them.
+/// Not all methods are supported, see [`ZipStorageAdapter::unsupported_methods`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ZipCompressionMethod {
+    /// No compression (method 0).
+    Stored,
+    /// Deflate (method 8).
+    Deflate,
+    /// Deflate64, also known as enhanced deflate (method 9).
+    Deflate64,
+    /// Bzip2 (method 12).
+    Bzip2,
+    /// Zstandard (method 93).
+    Zstd,
+    /// LZMA (method 14).
+    Lzma,
+    /// Another method, by its code.
+    ///
+    /// This includes entries encrypted with the `WinZip` AES extension (method 99), which record their compression method in an extra field.
+    Other(u16),
+}
+
+impl From<Method> for ZipCompressionMethod {
+    fn from(method: Method) -> Self {
+        match u16::from(method) {
+            0 => Self::Stored,
+            8 => Self::Deflate,
+            9 => Self::Deflate64,
+            12 => Self::Bzip2,
+            93 => Self::Zstd,
+            14 => Self::Lzma,
+            code => Self::Other(code),
+        }
+    }
+}
+
+impl ZipCompressionMethod {
+    /// The name of the method, or [`None`] for [`ZipCompressionMethod::Other`].
+    const fn name(self) -> Option<&'static str> {
+        match self {
+            Self::Stored => Some("stored"),
+            Self::Deflate => Some("deflate"),
+            Self::Deflate64 => Some("deflate64"),
+            Self::Bzip2 => Some("bzip2"),
+            Self::Zstd => Some("zstd"),
+            Self::Lzma => Some("lzma"),
+            Self::Other(_) => None,
         }
     }
 }
 
+/// The compression of entries written to a zip file.
+#[cfg(feature = "write")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ZipCompression {
+    /// Store entries without compression.
+    ///
+    /// Partial reads of stored entries only read the requested byte ranges, and stored entries can be partially written in place.
+    #[default]
+    Stored,
+    /// Compress entries with deflate at a level from 0 (fastest) to 10 (smallest).
+    ///
+    /// Levels above 10 are treated as 10.
+    Deflate(u8),
+    /// Compress entries with Zstandard at a level from 1 (fastest) to 22 (smallest).
+    #[cfg(feature = "zstd")]
+    Zstd(i32),
+}
+
+#[cfg(feature = "write")]
+impl ZipCompression {
+    /// The compression method of entries written with this compression.
+    pub(crate) fn method(self) -> Method {
+        match self {
+            ZipCompression::Stored => Method::Store,
+            ZipCompression::Deflate(_) => Method::Deflate,
+            #[cfg(feature = "zstd")]
+            ZipCompression::Zstd(_) => Method::Zstd,
+        }
+    }
+}
+
+/// A function choosing the compression of an entry from its key and the size of its value.
+#[cfg(feature = "write")]
+pub type ZipCompressionPolicy = Arc<dyn Fn(&StoreKey, usize) -> ZipCompression + Send + Sync>;
+
+/// A function running a blocking task to completion off of the async executor, such as on a thread of `tokio::task::spawn_blocking`.
+///
+/// See [`ZipStorageAdapter::with_blocking_spawner`].
+#[cfg(all(feature = "async", feature = "write"))]
+pub type ZipBlockingSpawner = Arc<dyn Fn(Box<dyn FnOnce() + Send>) + Send + Sync>;
+
+/// The compression policy for zarr hierarchies: chunks are stored, and metadata is deflated.
+///
+/// Keys with a `c` path component, such as `array/c/0/0` with the default chunk key encoding, are stored, since chunks are usually already compressed by a codec.
+/// Zarr V3 and V2 metadata keys (`zarr.json`, `.zarray`, `.zgroup`, `.zattrs`, and `.zmetadata`) are deflated at level 6.
+/// All other keys are stored.
+#[cfg(feature = "write")]
+#[must_use]
+pub fn zarr_compression_policy(key: &StoreKey, _size: usize) -> ZipCompression {
+    const METADATA_NAMES: [&str; 5] = ["zarr.json", ".zarray", ".zgroup", ".zattrs", ".zmetadata"];
+    let mut components = key.as_str().split('/');
+    let name = components.next_back().unwrap_or_default();
+    if components.any(|component| component == "c") {
+        ZipCompression::Stored
+    } else if METADATA_NAMES.contains(&name) {
+        ZipCompression::Deflate(6)
+    } else {
+        ZipCompression::Stored
+    }
+}
+
+/// Options for writing a zip file and its entries.
+#[cfg(feature = "write")]
+#[derive(Clone)]
+pub struct ZipWriteOptions {
+    compression: ZipCompression,
+    compression_policy: Option<ZipCompressionPolicy>,
+    force_zip64: bool,
+    alignment: u64,
+    staged: bool,
+    explicit_directories: bool,
+}
+
+#[cfg(feature = "write")]
+impl Default for ZipWriteOptions {
+    fn default() -> Self {
+        Self {
+            compression: ZipCompression::default(),
+            compression_policy: None,
+            force_zip64: false,
+            alignment: 0,
+            staged: true,
+            explicit_directories: false,
+        }
+    }
+}
+
+#[cfg(feature = "write")]
+impl core::fmt::Debug for ZipWriteOptions {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ZipWriteOptions")
+            .field("compression", &self.compression)
+            .field("compression_policy", &self.compression_policy.is_some())
+            .field("force_zip64", &self.force_zip64)
+            .field("alignment", &self.alignment)
+            .field("staged", &self.staged)
+            .field("explicit_directories", &self.explicit_directories)
+            .finish()
+    }
+}
+
+#[cfg(feature = "write")]
+impl ZipWriteOptions {
+    /// Set the compression of written entries.
+    ///
+    /// Defaults to [`ZipCompression::Stored`].
+    /// This is ignored if a [`compression_policy`](ZipWriteOptions::compression_policy) is set.
+    #[must_use]
+    pub fn compression(mut self, compression: ZipCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Choose the compression of each written entry with `compression_policy`, which is called with the key and the size of its value.
+    ///
+    /// See [`zarr_compression_policy`] for a policy suitable for zarr hierarchies.
+    #[must_use]
+    pub fn compression_policy(
+        mut self,
+        compression_policy: impl Fn(&StoreKey, usize) -> ZipCompression + Send + Sync + 'static,
+    ) -> Self {
+        self.compression_policy = Some(Arc::new(compression_policy));
+        self
+    }
+
+    /// Write entries with ZIP64 extensions, even if they are not required.
+    ///
+    /// ZIP64 extended information extra fields and a ZIP64 end of central directory record are always written when required, such as for entries or offsets of 4 GiB or more, or 65 535 or more entries.
+    /// Forcing them makes the layout of headers independent of the size of the zip file.
+    /// Defaults to `false`.
+    #[must_use]
+    pub fn force_zip64(mut self, force_zip64: bool) -> Self {
+        self.force_zip64 = force_zip64;
+        self
+    }
+
+    /// Align the data of stored entries to `alignment` bytes, such as 64 or 4096.
+    ///
+    /// The local file header of each stored entry is padded with an extra field so that its data starts at a multiple of `alignment` bytes from the start of the zip file, like Android's `zipalign`.
+    /// This suits memory mapping the zip file or direct reads of entries.
+    /// Compressed entries are not aligned.
+    /// The padding of an entry is less than `alignment` bytes, and writing an entry fails if it does not fit in the extra field (about 64 KiB).
+    /// Defaults to 0, no alignment.
+    #[must_use]
+    pub fn alignment(mut self, alignment: u64) -> Self {
+        self.alignment = alignment;
+        self
+    }
+
+    /// Set whether a rewritten zip file is staged under a temporary key before it replaces the zip file.
+    ///
+    /// If staged, the rewritten zip file is written in full to a temporary key alongside the zip file (`<key>.tmp-<nonce>`), and then written to the zip file and the temporary key erased.
+    /// A failure while writing the temporary key leaves the existing zip file untouched, and the temporary key is erased.
+    /// A failure while writing the zip file from the temporary key keeps the temporary key, which holds the complete rewritten zip file and is named in the error.
+    /// Staging writes the zip file twice, which may be too expensive for some stores.
+    ///
+    /// This applies to [`store_to_zip`] and to [`finish`](ZipStorageAdapter::finish) with the write options of the adapter.
+    /// A zip file at [`StoreKey::root()`] cannot be staged, nor can changes appended in place by an adapter opened with [`open_append`](ZipStorageAdapter::open_append).
+    /// Defaults to `true`.
+    #[must_use]
+    pub fn staged(mut self, staged: bool) -> Self {
+        self.staged = staged;
+        self
+    }
+
+    /// Set whether a directory entry is written for every directory of written keys that does not have one.
+    ///
+    /// Zip files do not need directory entries, but some readers expect them, such as to list a group with no keys.
+    /// If set, directory entries such as `a/` and `a/b/` are written for a key `a/b/c`, in sorted order before the keys.
+    /// Directory entries created with [`create_directory`](ZipStorageAdapter::create_directory) are always written.
+    ///
+    /// This applies to [`store_to_zip`] and to [`finish`](ZipStorageAdapter::finish) with the write options of the adapter.
+    /// Defaults to `false`.
+    #[must_use]
+    pub fn explicit_directories(mut self, explicit_directories: bool) -> Self {
+        self.explicit_directories = explicit_directories;
+        self
+    }
+
+    /// The options of the entry of `key` with a value of `size` bytes, with the compression policy applied.
+    pub(crate) fn resolve(&self, key: &StoreKey, size: usize) -> Self {
+        let compression = self
+            .compression_policy
+            .as_ref()
+            .map_or(self.compression, |policy| policy(key, size));
+        Self {
+            compression,
+            compression_policy: None,
+            force_zip64: self.force_zip64,
+            alignment: self.alignment,
+            staged: self.staged,
+            explicit_directories: self.explicit_directories,
+        }
+    }
+}
+
+/// The compression method of entries encrypted with the `WinZip` AES extension.
+const METHOD_AES: u16 = 99;
+/// General purpose flag bit 0: the entry is encrypted.
+const FLAG_ENCRYPTED: u16 = 1;
+
+/// Returns true if entries compressed with `method` can be read.
+///
+/// Methods other than store and deflate require the corresponding crate feature.
+/// AES encrypted entries are supported with the `encryption` feature, although the method they were compressed with may not be.
+fn method_supported(method: Method) -> bool {
+    matches!(method, Method::Store | Method::Deflate)
+        || (cfg!(feature = "deflate64") && method == Method::Deflate64)
+        || (cfg!(feature = "bzip2") && method == Method::Bzip2)
+        || (cfg!(feature = "lzma") && method == Method::Lzma)
+        || (cfg!(feature = "zstd") && method == Method::Zstd)
+        || (cfg!(feature = "encryption") && u16::from(method) == METHOD_AES)
+}
+
+/// The crate feature that supports reading entries compressed with `method`, if it is not enabled.
+fn method_feature(method: Method) -> Option<&'static str> {
+    if method_supported(method) {
+        return None;
+    }
+    match ZipCompressionMethod::from(method) {
+        ZipCompressionMethod::Deflate64 => Some("deflate64"),
+        ZipCompressionMethod::Bzip2 => Some("bzip2"),
+        ZipCompressionMethod::Zstd => Some("zstd"),
+        ZipCompressionMethod::Lzma => Some("lzma"),
+        ZipCompressionMethod::Other(METHOD_AES) => Some("encryption"),
+        _ => None,
+    }
+}
+
+/// The message of [`ZipReadError::UnsupportedMethod`], naming `method` by name and code, and the crate feature that supports it.
+fn unsupported_method_message(method: Method) -> String {
+    let code = u16::from(method);
+    let name = match ZipCompressionMethod::from(method).name() {
+        Some(name) => format!("{name} ({code})"),
+        None => code.to_string(),
+    };
+    let hint = method_feature(method)
+        .map(|feature| format!("; enable the `{feature}` feature"))
+        .unwrap_or_default();
+    format!("unsupported compression method {name}{hint}")
+}
+
+/// Returns true if `byte_ranges` is a single byte range spanning the entire value of `size` bytes.
+fn is_entire_value(byte_ranges: &[ByteRange], size: u64) -> bool {
+    match byte_ranges {
+        [ByteRange::FromStart(0, None)] => true,
+        [ByteRange::FromStart(0, Some(len)) | ByteRange::Suffix(len)] => *len == size,
+        _ => false,
+    }
+}
+
+/// Validate that `byte_range` is within a value of `size` bytes.
+///
+/// A suffix byte range longer than the value is out of bounds, rather than clamped to the start of the value.
+///
+/// # Errors
+/// Returns an [`InvalidByteRangeError`] if the byte range is out of bounds.
+fn validate_byte_range(byte_range: ByteRange, size: u64) -> Result<(), InvalidByteRangeError> {
+    let valid = match byte_range {
+        ByteRange::FromStart(start, Some(len)) => {
+            start.checked_add(len).is_some_and(|end| end <= size)
+        }
+        ByteRange::FromStart(start, None) => start <= size,
+        ByteRange::Suffix(len) => len <= size,
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(InvalidByteRangeError::new(byte_range, size))
+    }
+}
+
+/// The byte ranges of a request of a value, split into those within the bounds of the value and the errors of those that are not.
+///
+/// The valid byte ranges are read together, and their values are interleaved with the errors of the invalid byte ranges in the order of the request by [`merge`](Self::merge).
+struct ValidatedByteRanges {
+    /// The byte ranges within the bounds of the value.
+    valid: Vec<ByteRange>,
+    /// The error of each byte range of the request, [`None`] if it is valid.
+    errors: Vec<Option<InvalidByteRangeError>>,
+}
+
+impl ValidatedByteRanges {
+    /// Validate `byte_ranges` of a value of `size` bytes.
+    fn new(byte_ranges: Vec<ByteRange>, size: u64) -> Self {
+        let errors: Vec<_> = byte_ranges
+            .iter()
+            .map(|range| validate_byte_range(*range, size).err())
+            .collect();
+        let valid = if errors.iter().all(Option::is_none) {
+            byte_ranges
+        } else {
+            byte_ranges
+                .into_iter()
+                .zip(&errors)
+                .filter_map(|(range, error)| error.is_none().then_some(range))
+                .collect()
+        };
+        Self { valid, errors }
+    }
+
+    /// Returns true if all of the byte ranges are valid.
+    fn all_valid(&self) -> bool {
+        self.valid.len() == self.errors.len()
+    }
+
+    /// Interleave `values` of the valid byte ranges with the errors of the invalid byte ranges.
+    fn merge<'a>(
+        self,
+        mut values: impl Iterator<Item = Result<Bytes, StorageError>> + Send + 'a,
+    ) -> impl Iterator<Item = Result<Bytes, StorageError>> + Send + 'a {
+        self.errors.into_iter().map(move |error| match error {
+            Some(error) => Err(error.into()),
+            None => values.next().unwrap_or_else(|| {
+                Err(StorageError::Other("missing byte range value".to_string()))
+            }),
+        })
+    }
+
+    /// Interleave a stream of `values` of the valid byte ranges with the errors of the invalid byte ranges.
+    #[cfg(feature = "async")]
+    fn merge_async(
+        self,
+        values: zarrs_storage::AsyncBytesIterator<'_>,
+    ) -> zarrs_storage::AsyncBytesIterator<'_> {
+        use futures::StreamExt;
+
+        futures::stream::unfold(
+            (self.errors.into_iter(), values),
+            |(mut errors, mut values)| async move {
+                let value = match errors.next()? {
+                    Some(error) => Err(error.into()),
+                    None => values.next().await.unwrap_or_else(|| {
+                        Err(StorageError::Other("missing byte range value".to_string()))
+                    }),
+                };
+                Some((value, (errors, values)))
+            },
+        )
+        .boxed()
+    }
+}
+
+/// The metadata of an entry in a zip file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ZipEntryMetadata {
+    /// The CRC-32 of the uncompressed data.
+    pub crc32: u32,
+    /// The size in bytes of the compressed data.
+    pub compressed_size: u64,
+    /// The size in bytes of the uncompressed data.
+    pub uncompressed_size: u64,
+    /// The last modification time.
+    pub modified: SystemTime,
+    /// The entry comment, which is empty if the entry has no comment.
+    pub comment: String,
+}
+
+impl ZipEntryMetadata {
+    /// The metadata of `entry`.
+    fn from_entry(entry: &StoredEntry) -> Self {
+        Self {
+            crc32: entry.crc32,
+            compressed_size: entry.compressed_size,
+            uncompressed_size: entry.uncompressed_size,
+            modified: entry.modified,
+            comment: entry.comment.to_string(),
+        }
+    }
+}
+
+/// The capabilities needed to read a zip file, returned by [`ZipStorageAdapter::capabilities`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ArchiveCapabilities {
+    /// Whether there are too many entries for the end of central directory record, or an entry has a size or local file header offset that needs the ZIP64 extensions.
+    pub zip64: bool,
+    /// The number of encrypted entries, which need a password and the `encryption` feature to be read.
+    pub encrypted: usize,
+    /// The number of entries with a compression method that is not supported.
+    ///
+    /// See [`ZipStorageAdapter::unsupported_methods`].
+    pub unsupported_methods: usize,
+    /// The number of symbolic link entries, whether or not they are keys.
+    ///
+    /// See [`SymlinkPolicy`].
+    pub symlinks: usize,
+}
+
+/// The kind of an entry in a zip file, returned by [`ZipStorageAdapter::entries`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZipEntryInfo {
+    /// A file, the value of a key.
+    File {
+        /// The size in bytes of the uncompressed data.
+        uncompressed_size: u64,
+        /// The size in bytes of the compressed data.
+        compressed_size: u64,
+        /// The compression method.
+        method: Method,
+    },
+    /// A directory.
+    Directory,
+}
+
+/// A zip read error.
+#[derive(Debug, Error)]
+pub enum ZipReadError {
+    /// An entry uses a compression method that is not supported.
+    #[error("{}", unsupported_method_message(*.0))]
+    UnsupportedMethod(Method),
+    /// The CRC-32 of the value of an entry does not match the central directory.
+    #[error("CRC-32 mismatch, expected {expected:#010x} but the value has {actual:#010x}")]
+    ChecksumMismatch {
+        /// The CRC-32 in the central directory.
+        expected: u32,
+        /// The CRC-32 of the value.
+        actual: u32,
+    },
+    /// An entry is encrypted, and the adapter has no password.
+    #[error("the entry is encrypted and no password was supplied")]
+    PasswordRequired,
+    /// An entry is encrypted with a different password.
+    #[error("wrong password")]
+    WrongPassword,
+    /// The authentication code of an encrypted entry does not match its data.
+    #[error("the encrypted data failed authentication")]
+    AuthenticationFailed,
+}
+
 /// A zip store creation error.
 #[derive(Debug, Error)]
 pub enum ZipStorageAdapterCreateError {
@@ -140,9 +1782,46 @@ pub enum ZipStorageAdapterCreateError {
     /// An existing directory.
     #[error("{0} is an existing directory, not a zip file")]
     ExistingDir(PathBuf),
+    /// An existing key.
+    #[error("{0} already exists")]
+    ExistingKey(StoreKey),
     /// A zip error.
     #[error("{0}")]
     ZipError(String),
+    /// A value that is not a zip file, as it has no end of central directory record.
+    #[error("{0} is not a zip file")]
+    NotAZipFile(StoreKey),
+    /// A symbolic link entry, with [`SymlinkPolicy::Error`].
+    #[error("{0} is a symbolic link")]
+    Symlink(String),
+    /// An entry name that is an absolute path or has a `..` component, with [`UnsafeEntryNamePolicy::Error`].
+    #[error("{0} is an unsafe entry name, it is an absolute path or has a `..` component")]
+    UnsafeEntryName(String),
+    /// Entries with a compression method that is not supported.
+    #[error("entries use compression methods that are not supported: {0:?}")]
+    UnsupportedMethods(Vec<(StoreKey, Method)>),
+    /// The total size of the values of the entries to preload exceeds the maximum, see [`ZipStorageAdapterBuilder::preload_all`].
+    #[error(
+        "the entries have a total size of {size} bytes, exceeding the maximum of {max_size} bytes to preload"
+    )]
+    PreloadTooLarge {
+        /// The total size of the values of the entries.
+        size: u64,
+        /// The maximum total size.
+        max_size: u64,
+    },
+    /// A zip file that ends before the data it refers to, such as an incomplete download.
+    #[error(
+        "the zip file is truncated, it is at least {expected} bytes but only {available} bytes are available"
+    )]
+    TruncatedArchive {
+        /// The end of the byte range that was requested past the end of the value as the zip file was parsed.
+        ///
+        /// This is a lower bound of the size of the zip file, not its exact size.
+        expected: u64,
+        /// The size of the value.
+        available: u64,
+    },
     /// A storage error.
     #[error(transparent)]
     StorageError(#[from] StorageError),
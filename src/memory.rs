@@ -0,0 +1,84 @@
+//! Zip files in memory.
+
+use std::sync::Arc;
+
+use zarrs_storage::{
+    Bytes, MaybeBytesIterator, ReadableStorageTraits, StorageError, StoreKey,
+    byte_range::ByteRangeIterator,
+};
+
+use crate::{ZipStorageAdapter, ZipStorageAdapterCreateError, validate_byte_range};
+
+/// A zip file in memory, the underlying storage of an adapter created with [`ZipStorageAdapter::from_bytes`] or `ZipStorageAdapter::new_mmap`.
+///
+/// The zip file is the value of [`StoreKey::root()`].
+/// Byte ranges are returned as slices of the zip file, without copying.
+pub struct ZipBytes {
+    /// The zip file.
+    bytes: Bytes,
+}
+
+impl ZipBytes {
+    /// Create the storage of the zip file `bytes`.
+    pub(crate) fn new(bytes: Bytes) -> Self {
+        Self { bytes }
+    }
+}
+
+impl core::fmt::Debug for ZipBytes {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ZipBytes")
+            .field("size", &self.bytes.len())
+            .finish()
+    }
+}
+
+impl ReadableStorageTraits for ZipBytes {
+    fn get_partial_many<'a>(
+        &'a self,
+        key: &StoreKey,
+        byte_ranges: ByteRangeIterator<'a>,
+    ) -> Result<MaybeBytesIterator<'a>, StorageError> {
+        if !key.as_str().is_empty() {
+            return Ok(None);
+        }
+        let size = self.bytes.len() as u64;
+        let slices = byte_ranges
+            .map(|range| {
+                validate_byte_range(range, size)?;
+                Ok(self.bytes.slice(range.to_range_usize(size)))
+            })
+            .collect::<Vec<_>>();
+        Ok(Some(Box::new(slices.into_iter())))
+    }
+
+    fn size_key(&self, key: &StoreKey) -> Result<Option<u64>, StorageError> {
+        Ok(key.as_str().is_empty().then_some(self.bytes.len() as u64))
+    }
+
+    fn supports_get_partial(&self) -> bool {
+        true
+    }
+}
+
+impl ZipStorageAdapter<ZipBytes> {
+    /// Create a new zip storage adapter for the zip file `data`, which is in memory.
+    ///
+    /// This suits small zip files that have been fetched or downloaded in full, without wrapping them in a store under a key.
+    /// Stored entries are returned as slices of `data` without copying, and compressed entries are decompressed from it.
+    ///
+    /// ```
+    /// use zarrs_storage::{ReadableStorageTraits, StoreKey};
+    /// use zarrs_zip::ZipStorageAdapter;
+    ///
+    /// let zip_store = ZipStorageAdapter::from_bytes(std::fs::read("tests/zarr.zip")?)?;
+    /// assert!(zip_store.get(&StoreKey::new("foo/zarr.json")?)?.is_some());
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    ///
+    /// # Errors
+    /// Returns a [`ZipStorageAdapterCreateError`] if `data` is not a valid zip file.
+    pub fn from_bytes(data: impl Into<Bytes>) -> Result<Self, ZipStorageAdapterCreateError> {
+        Self::new(Arc::new(ZipBytes::new(data.into())), StoreKey::root())
+    }
+}
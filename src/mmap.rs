@@ -0,0 +1,31 @@
+//! Memory-mapped zip files.
+
+use std::{fs::File, path::Path, sync::Arc};
+
+use memmap2::Mmap;
+use zarrs_storage::{Bytes, StoreKey};
+
+use crate::{ZipBytes, ZipStorageAdapter, ZipStorageAdapterCreateError};
+
+impl ZipStorageAdapter<ZipBytes> {
+    /// Create a new zip storage adapter for the zip file at `path` on the local filesystem, which is memory-mapped.
+    ///
+    /// The zip file is mapped once, rather than read through a store with a system call per request.
+    /// Stored entries are returned as slices of the memory map without copying, and compressed entries are decompressed from it.
+    /// This suits large zip files that are read repeatedly.
+    ///
+    /// # Safety
+    /// The zip file must not be modified or truncated while the adapter or any value read from it is alive.
+    /// Values are slices of the memory map, so a modification by this or another process changes values that have already been read,
+    /// and reading a part of the memory map that is beyond the end of a truncated file raises `SIGBUS` on Unix, terminating the process.
+    ///
+    /// # Errors
+    /// Returns a [`ZipStorageAdapterCreateError`] if the file cannot be opened or mapped, or is not a valid zip file.
+    pub unsafe fn new_mmap(path: impl AsRef<Path>) -> Result<Self, ZipStorageAdapterCreateError> {
+        let file = File::open(path)?;
+        // SAFETY: The caller guarantees that the file is not modified while it is mapped.
+        let mmap = unsafe { Mmap::map(&file)? };
+        let storage = Arc::new(ZipBytes::new(Bytes::from_owner(mmap)));
+        Self::new(storage, StoreKey::root())
+    }
+}
@@ -0,0 +1,197 @@
+//! A writable overlay of pending changes over a read-only zip file.
+
+use std::sync::Arc;
+
+use zarrs_storage::{
+    Bytes, ListableStorageTraits, MaybeBytesIterator, OffsetBytesIterator, ReadableStorageTraits,
+    StorageError, StoreKey, StoreKeys, StoreKeysPrefixes, StorePrefix, WritableStorageTraits,
+    byte_range::ByteRangeIterator,
+};
+
+use crate::{
+    ZipStorageAdapter, ZipStorageAdapterCreateError,
+    write::{apply_partial_writes, rewrite_archive, set_staged},
+};
+
+/// A writable overlay over a zip file that is never modified.
+///
+/// Writes and erases are held in memory, layered over the entries of the zip file:
+///  - reads of a written key return its pending value,
+///  - erased keys mask the entries of the zip file,
+///  - listings merge the entries of the zip file with the written keys.
+///
+/// [`commit`](ZipOverlayAdapter::commit) packs the entries of the zip file and the overlay into a new zip file.
+/// Unlike writing to a [`ZipStorageAdapter`], the underlying storage only needs to be readable, and partial writes are always applied to a pending copy of the value rather than to the zip file in place.
+///
+/// ```
+/// # use std::sync::Arc;
+/// use zarrs_storage::{StoreKey, WritableStorageTraits, store::MemoryStore};
+/// use zarrs_filesystem::FilesystemStore;
+/// use zarrs_zip::ZipOverlayAdapter;
+///
+/// # let fs_store = Arc::new(FilesystemStore::new("tests/zarr.zip")?);
+/// let overlay = ZipOverlayAdapter::new(fs_store, StoreKey::root())?;
+/// overlay.erase(&StoreKey::new("foo/zarr.json")?)?;
+/// let dst_store = MemoryStore::new();
+/// overlay.commit(&dst_store, &StoreKey::new("edited.zip")?)?;
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+pub struct ZipOverlayAdapter<TStorage: ?Sized> {
+    /// The adapter of the zip file, holding the overlay as pending changes.
+    zip: ZipStorageAdapter<TStorage>,
+}
+
+impl<TStorage: ?Sized> core::fmt::Debug for ZipOverlayAdapter<TStorage> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ZipOverlayAdapter")
+            .field("zip", &self.zip)
+            .finish()
+    }
+}
+
+impl<TStorage: ?Sized> From<ZipStorageAdapter<TStorage>> for ZipOverlayAdapter<TStorage> {
+    /// Create an overlay over the zip file of `zip`, including its pending changes.
+    fn from(zip: ZipStorageAdapter<TStorage>) -> Self {
+        Self { zip }
+    }
+}
+
+impl<TStorage: ?Sized + ReadableStorageTraits> ZipOverlayAdapter<TStorage> {
+    /// Create a new overlay over the zip file at `key` of `storage`.
+    ///
+    /// Pass [`StoreKey::root()`] to treat the entire `storage` as the zip file
+    /// (e.g., a `FilesystemStore` rooted directly at a zip file).
+    ///
+    /// # Errors
+    /// Returns a [`ZipStorageAdapterCreateError`] if the store value at `key` is not a valid zip file.
+    pub fn new(
+        storage: Arc<TStorage>,
+        key: StoreKey,
+    ) -> Result<Self, ZipStorageAdapterCreateError> {
+        Ok(ZipStorageAdapter::new(storage, key)?.into())
+    }
+
+    /// Pack the entries of the zip file and the overlay into a new zip file at `dst_key` of `dst_storage`.
+    ///
+    /// Entries of the zip file that are not masked by the overlay are copied verbatim, and written keys are compressed according to the write options of the adapter.
+    /// The zip file is read in full, and the new zip file is staged under a temporary key unless disabled by [`ZipWriteOptions::staged`](crate::ZipWriteOptions::staged).
+    /// The overlay is retained, so a later commit includes the same changes.
+    ///
+    /// # Errors
+    /// Returns a [`StorageError`] if the zip file cannot be read, an entry cannot be written, or the new zip file cannot be written to `dst_storage`.
+    pub fn commit<TDst: ?Sized + WritableStorageTraits>(
+        &self,
+        dst_storage: &TDst,
+        dst_key: &StoreKey,
+    ) -> Result<(), StorageError> {
+        let changes = self
+            .zip
+            .index
+            .read()
+            .pending_changes(self.zip.write_options.explicit_directories);
+        let archive = self
+            .zip
+            .storage
+            .get(&self.zip.key)?
+            .ok_or_else(|| self.zip.zip_not_found())?;
+        let archive = rewrite_archive(&archive, &self.zip.zip_path, &changes)?;
+        set_staged(
+            dst_storage,
+            dst_key,
+            archive.into(),
+            self.zip.write_options.staged,
+        )
+    }
+}
+
+impl<TStorage: ?Sized> ZipOverlayAdapter<TStorage> {
+    /// The adapter of the zip file, with the overlay as its pending changes.
+    #[must_use]
+    pub fn inner(&self) -> &ZipStorageAdapter<TStorage> {
+        &self.zip
+    }
+
+    /// Unwrap the adapter of the zip file, with the overlay as its pending changes.
+    ///
+    /// If the underlying storage is writable, [`finish`](ZipStorageAdapter::finish) writes the overlay to the zip file.
+    #[must_use]
+    pub fn into_inner(self) -> ZipStorageAdapter<TStorage> {
+        self.zip
+    }
+}
+
+impl<TStorage: ?Sized + ReadableStorageTraits> ReadableStorageTraits
+    for ZipOverlayAdapter<TStorage>
+{
+    fn get_partial_many<'a>(
+        &'a self,
+        key: &StoreKey,
+        byte_ranges: ByteRangeIterator<'a>,
+    ) -> Result<MaybeBytesIterator<'a>, StorageError> {
+        self.zip.get_partial_many(key, byte_ranges)
+    }
+
+    fn size_key(&self, key: &StoreKey) -> Result<Option<u64>, StorageError> {
+        self.zip.size_key(key)
+    }
+
+    fn supports_get_partial(&self) -> bool {
+        true
+    }
+}
+
+impl<TStorage: ?Sized + ReadableStorageTraits> ListableStorageTraits
+    for ZipOverlayAdapter<TStorage>
+{
+    fn list(&self) -> Result<StoreKeys, StorageError> {
+        self.zip.list()
+    }
+
+    fn list_prefix(&self, prefix: &StorePrefix) -> Result<StoreKeys, StorageError> {
+        self.zip.list_prefix(prefix)
+    }
+
+    fn list_dir(&self, prefix: &StorePrefix) -> Result<StoreKeysPrefixes, StorageError> {
+        self.zip.list_dir(prefix)
+    }
+
+    fn size(&self) -> Result<u64, StorageError> {
+        ListableStorageTraits::size(&self.zip)
+    }
+
+    fn size_prefix(&self, prefix: &StorePrefix) -> Result<u64, StorageError> {
+        self.zip.size_prefix(prefix)
+    }
+}
+
+impl<TStorage: ?Sized + ReadableStorageTraits> WritableStorageTraits
+    for ZipOverlayAdapter<TStorage>
+{
+    fn set(&self, key: &StoreKey, value: Bytes) -> Result<(), StorageError> {
+        self.zip.set_impl(key, value, &self.zip.write_options)
+    }
+
+    fn set_partial_many<'a>(
+        &'a self,
+        key: &StoreKey,
+        offset_values: OffsetBytesIterator<'a>,
+    ) -> Result<(), StorageError> {
+        // Read-modify-write, the zip file is never written in place
+        let value = apply_partial_writes(self.zip.get(key)?, offset_values)?;
+        self.zip.set_impl(key, value, &self.zip.write_options)
+    }
+
+    fn erase(&self, key: &StoreKey) -> Result<(), StorageError> {
+        self.zip.erase_impl(key);
+        Ok(())
+    }
+
+    fn erase_prefix(&self, prefix: &StorePrefix) -> Result<(), StorageError> {
+        self.zip.erase_prefix_impl(prefix);
+        Ok(())
+    }
+
+    fn supports_set_partial(&self) -> bool {
+        false
+    }
+}
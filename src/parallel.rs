@@ -0,0 +1,121 @@
+//! Parallel decompression of entries.
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use rc_zip::parse::Method;
+use zarrs_storage::{
+    Bytes, MaybeBytes, ReadableStorageTraits, StorageError, StoreKey, byte_range::ByteRange,
+};
+
+use crate::{
+    DATA_DESCRIPTOR_MAX_SIZE, ZipStorageAdapter,
+    index::{IndexEntry, StoredEntry},
+};
+
+/// The minimum total decompressed size in bytes of the entries decompressed in parallel by [`ZipStorageAdapter::get_many`].
+///
+/// Below this, the overhead of dispatching to the thread pool outweighs the decompression time.
+/// See `benches/get_many.rs`.
+pub const PARALLEL_DECOMPRESSION_THRESHOLD: u64 = 256 * 1024;
+
+/// The value of a key requested by [`ZipStorageAdapter::get_many`].
+enum Value {
+    /// The value is available without decompression.
+    Ready(MaybeBytes),
+    /// The value is an entry that must be decompressed.
+    Compressed(StoredEntry),
+    /// The value is read by [`get`](ReadableStorageTraits::get).
+    Get,
+}
+
+impl<TStorage: ?Sized + ReadableStorageTraits> ZipStorageAdapter<TStorage> {
+    /// Get the values of `keys`, decompressing compressed entries in parallel.
+    ///
+    /// Returns [`None`] if the compressed entries are too small to benefit from parallel decompression, or the thread pool has a single thread.
+    pub(crate) fn get_many_parallel(
+        &self,
+        keys: &[StoreKey],
+    ) -> Result<Option<Vec<MaybeBytes>>, StorageError> {
+        let values: Vec<Value> = {
+            let index = self.index.read();
+            keys.iter()
+                .map(|key| match index.get(key) {
+                    None => Value::Ready(None),
+                    Some(IndexEntry::Archived(entry))
+                        if entry.method != Method::Store && !entry.is_encrypted() =>
+                    {
+                        let cached = self
+                            .cache
+                            .as_ref()
+                            .and_then(|cache| cache.get(key, entry.header_offset));
+                        match cached {
+                            Some(value) => Value::Ready(Some(value)),
+                            None => Value::Compressed(entry.clone()),
+                        }
+                    }
+                    Some(_) => Value::Get,
+                })
+                .collect()
+        };
+
+        let (num_compressed, total_size) = values
+            .iter()
+            .filter_map(|value| match value {
+                Value::Compressed(entry) => Some(entry.uncompressed_size),
+                _ => None,
+            })
+            .fold((0, 0), |(count, total), size| (count + 1, total + size));
+        if num_compressed < 2
+            || total_size < PARALLEL_DECOMPRESSION_THRESHOLD
+            || rayon::current_num_threads() < 2
+        {
+            return Ok(None);
+        }
+
+        // Read the compressed data sequentially, the underlying storage may not benefit from concurrent reads
+        let size = self.index.read().size;
+        let mut compressed = Vec::with_capacity(num_compressed);
+        for (key, value) in keys.iter().zip(&values) {
+            if let Value::Compressed(entry) = value {
+                let data_offset = self
+                    .calculate_data_offset(entry.header_offset)
+                    .map_err(|err| StorageError::Other(err.to_string()))?;
+                let end =
+                    (data_offset + entry.compressed_size + DATA_DESCRIPTOR_MAX_SIZE).min(size);
+                let data = self
+                    .storage
+                    .get_partial(
+                        &self.key,
+                        ByteRange::FromStart(entry.header_offset, Some(end - entry.header_offset)),
+                    )?
+                    .ok_or_else(|| {
+                        StorageError::Other("Cannot read compressed data".to_string())
+                    })?;
+                compressed.push((key, entry, data));
+            }
+        }
+
+        // Decompress in parallel
+        let progress = self.read_progress.as_ref();
+        let decompressed: Vec<Bytes> = compressed
+            .into_par_iter()
+            .map(|(key, entry, data)| Self::decompress_in_memory(key, entry, &data, progress))
+            .collect::<Result<_, _>>()?;
+
+        let mut decompressed = decompressed.into_iter();
+        let mut results = Vec::with_capacity(keys.len());
+        for (key, value) in keys.iter().zip(values) {
+            results.push(match value {
+                Value::Ready(value) => value,
+                Value::Compressed(entry) => {
+                    let value = decompressed.next().expect("one value per compressed entry");
+                    if let Some(cache) = &self.cache {
+                        cache.insert(key.clone(), entry.header_offset, value.clone());
+                    }
+                    Some(value)
+                }
+                Value::Get => self.get(key)?,
+            });
+        }
+        Ok(Some(results))
+    }
+}
@@ -0,0 +1,244 @@
+//! Preloading of entries when a zip storage adapter is built, and prefetching of entries ahead of access.
+
+use std::{ops::Range, sync::Arc};
+
+use rc_zip::parse::Method;
+use zarrs_storage::{Bytes, StorageError, StoreKey};
+
+use crate::{
+    HEADER_PREFETCH_MAX_GAP, HEADER_PREFETCH_MAX_SIZE, ZipStorageAdapter,
+    ZipStorageAdapterCreateError,
+    index::{IndexEntry, StoredEntry},
+    method_supported,
+};
+
+/// A run of entries that are preloaded in a single request, as the range of bytes spanning their local data and the entries.
+pub(crate) type PreloadRun = (Range<u64>, Vec<(Arc<StoreKey>, StoredEntry)>);
+
+/// A run of local file headers that are read in a single request, as the range of bytes spanning the headers and their offsets.
+pub(crate) type HeaderRun = (Range<u64>, Vec<u64>);
+
+impl<TStorage: ?Sized> ZipStorageAdapter<TStorage> {
+    /// The archived entries with values of at most `max_size` bytes that have not been preloaded, in runs that are read in a single request.
+    ///
+    /// Encrypted entries and entries with an unsupported compression method are not preloaded.
+    pub(crate) fn preload_runs(&self, max_size: u64) -> Vec<PreloadRun> {
+        let mut entries = self.index.read().archived_entries();
+        {
+            let preloaded = self.preloaded.read();
+            entries.retain(|(_, entry)| {
+                entry.uncompressed_size <= max_size
+                    && !entry.is_encrypted()
+                    && method_supported(entry.method)
+                    && !preloaded.contains_key(&entry.header_offset)
+            });
+        }
+        self.local_data_runs(entries)
+    }
+
+    /// The archived entries that have not been preloaded, in runs that are read in a single request.
+    ///
+    /// Encrypted entries and entries with an unsupported compression method are not preloaded.
+    ///
+    /// # Errors
+    /// Returns [`ZipStorageAdapterCreateError::PreloadTooLarge`] if the total size of the values of the entries exceeds `max_total_size` bytes.
+    pub(crate) fn preload_all_runs(
+        &self,
+        max_total_size: u64,
+    ) -> Result<Vec<PreloadRun>, ZipStorageAdapterCreateError> {
+        let mut entries = self.index.read().archived_entries();
+        {
+            let preloaded = self.preloaded.read();
+            entries.retain(|(_, entry)| {
+                !entry.is_encrypted()
+                    && method_supported(entry.method)
+                    && !preloaded.contains_key(&entry.header_offset)
+            });
+        }
+        let size = entries.iter().fold(0u64, |size, (_, entry)| {
+            size.saturating_add(entry.uncompressed_size)
+        });
+        if size > max_total_size {
+            return Err(ZipStorageAdapterCreateError::PreloadTooLarge {
+                size,
+                max_size: max_total_size,
+            });
+        }
+        Ok(self.local_data_runs(entries))
+    }
+
+    /// The entries of `keys` that have not been prefetched, as runs of entries with values that are held in the cache and runs of the local file headers of the others.
+    ///
+    /// The values of entries that are encrypted, have an unsupported compression method, or do not fit in the cache are not held, only their data offsets are read.
+    /// Keys that do not exist or are pending, and entries that were preloaded, are skipped.
+    pub(crate) fn prefetch_runs(&self, keys: &[StoreKey]) -> (Vec<PreloadRun>, Vec<HeaderRun>) {
+        let mut entries = Vec::new();
+        let mut header_offsets = Vec::new();
+        {
+            let index = self.index.read();
+            let preloaded = self.preloaded.read();
+            for key in keys {
+                #[cfg_attr(not(feature = "write"), allow(irrefutable_let_patterns))]
+                let Some(IndexEntry::Archived(entry)) = index.get(key) else {
+                    continue;
+                };
+                if preloaded.contains_key(&entry.header_offset) {
+                    continue;
+                }
+                let cache = self.cache.as_ref().filter(|cache| {
+                    cache.fits(entry.uncompressed_size)
+                        && !entry.is_encrypted()
+                        && method_supported(entry.method)
+                });
+                match cache {
+                    Some(cache) if cache.contains(key, entry.header_offset) => {}
+                    Some(_) => entries.push((Arc::new(key.clone()), entry.clone())),
+                    None => header_offsets.push(entry.header_offset),
+                }
+            }
+        }
+        (
+            self.local_data_runs(entries),
+            self.header_prefetch_runs(header_offsets),
+        )
+    }
+
+    /// `entries` in runs that are read in a single request.
+    ///
+    /// Entries are in the same run if their local data are at most [`HEADER_PREFETCH_MAX_GAP`] bytes apart and the run spans at most [`HEADER_PREFETCH_MAX_SIZE`] bytes.
+    fn local_data_runs(&self, mut entries: Vec<(Arc<StoreKey>, StoredEntry)>) -> Vec<PreloadRun> {
+        entries.sort_unstable_by_key(|(_, entry)| entry.header_offset);
+
+        let mut runs: Vec<PreloadRun> = Vec::new();
+        for (key, entry) in entries {
+            let start = entry.header_offset;
+            let end = self.local_data_end(&key, &entry);
+            match runs.last_mut() {
+                Some((range, entries))
+                    if start.saturating_sub(range.end) <= HEADER_PREFETCH_MAX_GAP
+                        && end - range.start <= HEADER_PREFETCH_MAX_SIZE =>
+                {
+                    range.end = range.end.max(end);
+                    entries.push((key, entry));
+                }
+                _ => runs.push((start..end, vec![(key, entry)])),
+            }
+        }
+        runs
+    }
+
+    /// Decode and hold the values of the `entries` of a run, given `bytes`, the bytes of the zip file from `start` that contain their local data.
+    ///
+    /// If `within_cache`, values count against the capacity of the cache, and values that do not fit in its remaining capacity are not held.
+    /// An entry with local data beyond `bytes` is skipped, it is read from storage when requested.
+    ///
+    /// # Errors
+    /// Returns a [`ZipStorageAdapterCreateError`] if a local file header is invalid or an entry cannot be decompressed.
+    pub(crate) fn preload_run(
+        &mut self,
+        start: u64,
+        bytes: &Bytes,
+        entries: &[(Arc<StoreKey>, StoredEntry)],
+        within_cache: bool,
+    ) -> Result<(), ZipStorageAdapterCreateError> {
+        for (key, entry) in entries {
+            let Some(local) = self.local_data(start, bytes, entry)? else {
+                continue;
+            };
+            if let Some(cache) = self.cache.as_mut().filter(|_| within_cache) {
+                // Entries are preloaded when the adapter is built, before the cache is shared by a clone
+                if !Arc::get_mut(cache).is_some_and(|cache| cache.reserve(entry.uncompressed_size))
+                {
+                    continue;
+                }
+            }
+            let value = self
+                .decode_local_data(key, entry, &local)
+                .map_err(|err| ZipStorageAdapterCreateError::ZipError(err.to_string()))?;
+            self.preloaded.write().insert(entry.header_offset, value);
+        }
+        Ok(())
+    }
+
+    /// Decode the values of the `entries` of a run into the cache, given `bytes`, the bytes of the zip file from `start` that contain their local data.
+    ///
+    /// An entry with local data beyond `bytes` is skipped, it is read from storage when requested.
+    ///
+    /// # Errors
+    /// Returns a [`StorageError`] if a local file header is invalid or an entry cannot be decompressed.
+    pub(crate) fn prefetch_run(
+        &self,
+        start: u64,
+        bytes: &Bytes,
+        entries: &[(Arc<StoreKey>, StoredEntry)],
+    ) -> Result<(), StorageError> {
+        let Some(cache) = &self.cache else {
+            return Ok(());
+        };
+        for (key, entry) in entries {
+            let local = self
+                .local_data(start, bytes, entry)
+                .map_err(|err| StorageError::Other(err.to_string()))?;
+            let Some(local) = local else {
+                continue;
+            };
+            let value = self.decode_local_data(key, entry, &local)?;
+            cache.insert((**key).clone(), entry.header_offset, value);
+        }
+        Ok(())
+    }
+
+    /// The local data of `entry`, from its local file header to the end of its compressed data, given `bytes`, the bytes of the zip file from `start`.
+    ///
+    /// Returns [`None`] if the compressed data extends beyond `bytes`.
+    ///
+    /// # Errors
+    /// Returns a [`ZipStorageAdapterCreateError`] if the local file header is invalid.
+    fn local_data(
+        &self,
+        start: u64,
+        bytes: &Bytes,
+        entry: &StoredEntry,
+    ) -> Result<Option<Bytes>, ZipStorageAdapterCreateError> {
+        let local_start = usize::try_from(entry.header_offset - start).unwrap_or(usize::MAX);
+        let local = bytes.slice(local_start.min(bytes.len())..);
+        let data_offset = self.data_offset_from_header(entry.header_offset, &local)?;
+        let data_end = usize::try_from(data_offset - entry.header_offset + entry.compressed_size)
+            .ok()
+            .filter(|&data_end| data_end <= local.len());
+        Ok(data_end.map(|data_end| local.slice(..data_end)))
+    }
+
+    /// Decode the value of `entry`, the value of `key`, from `local`, its local data.
+    ///
+    /// The value of a stored entry is copied, so that it does not hold the rest of the bytes it was read with.
+    ///
+    /// # Errors
+    /// Returns a [`StorageError`] if the entry cannot be decompressed.
+    fn decode_local_data(
+        &self,
+        key: &StoreKey,
+        entry: &StoredEntry,
+        local: &Bytes,
+    ) -> Result<Bytes, StorageError> {
+        if entry.method == Method::Store {
+            let data_start = local.len() - usize::try_from(entry.compressed_size).unwrap_or(0);
+            Ok(Bytes::copy_from_slice(&local[data_start..]))
+        } else {
+            Self::decompress_in_memory(key, entry, local, self.read_progress.as_ref())
+        }
+    }
+
+    /// The value of `entry`, the value of `key`, held by the adapter, if it was preloaded or is a stored entry prefetched into the cache.
+    ///
+    /// The values of compressed entries prefetched into the cache are read from the cache as usual.
+    pub(crate) fn held_value(&self, key: &StoreKey, entry: &StoredEntry) -> Option<Bytes> {
+        if let Some(value) = self.preloaded.read().get(&entry.header_offset) {
+            return Some(value.clone());
+        }
+        self.cache
+            .as_ref()
+            .filter(|_| entry.method == Method::Store)
+            .and_then(|cache| cache.get_stored(key, entry.header_offset))
+    }
+}
@@ -0,0 +1,44 @@
+use std::sync::Arc;
+
+use zarrs_storage::StoreKey;
+
+use crate::index::StoredEntry;
+
+/// The progress of a read of a compressed entry.
+///
+/// See [`ZipStorageAdapter::with_read_progress`](crate::ZipStorageAdapter::with_read_progress).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ZipReadProgress {
+    /// The number of bytes of the zip file read so far, including the local file header of the entry.
+    pub bytes_read: u64,
+    /// The number of bytes decompressed so far.
+    pub bytes_decompressed: u64,
+    /// The size of the compressed data of the entry.
+    pub compressed_size: u64,
+    /// The size of the uncompressed data of the entry.
+    pub uncompressed_size: u64,
+}
+
+/// A function called with the key and [`ZipReadProgress`] of a compressed entry as it is read and decompressed.
+pub type ZipReadProgressCallback = Arc<dyn Fn(&StoreKey, ZipReadProgress) + Send + Sync>;
+
+/// Call `callback`, if any, with the progress of a read of `entry`, the value of `key`.
+pub(crate) fn report_read_progress(
+    callback: Option<&ZipReadProgressCallback>,
+    key: &StoreKey,
+    entry: &StoredEntry,
+    bytes_read: u64,
+    bytes_decompressed: u64,
+) {
+    if let Some(callback) = callback {
+        callback(
+            key,
+            ZipReadProgress {
+                bytes_read,
+                bytes_decompressed,
+                compressed_size: entry.compressed_size,
+                uncompressed_size: entry.uncompressed_size,
+            },
+        );
+    }
+}
@@ -0,0 +1,477 @@
+//! Seek indexes of deflated entries, for decompressing byte ranges from a nearby checkpoint rather than the start of the entry.
+
+use std::{ops::Range, sync::Arc};
+
+use lru::LruCache;
+use miniz_oxide::inflate::{
+    TINFLStatus,
+    core::{
+        BlockBoundaryState, DecompressorOxide, TINFL_LZ_DICT_SIZE, decompress,
+        inflate_flags::{
+            TINFL_FLAG_HAS_MORE_INPUT, TINFL_FLAG_STOP_ON_BLOCK_BOUNDARY,
+            TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF,
+        },
+    },
+};
+use parking_lot::Mutex;
+use rc_zip::parse::Method;
+use zarrs_storage::{Bytes, StorageError, StoreKey, byte_range::ByteRange};
+
+use crate::{ZipStorageAdapter, index::StoredEntry};
+
+/// The maximum size of the checkpoints held by the seek indexes of an adapter.
+///
+/// Checkpoints recorded at an interval hold a 32 KiB window of decompressed data, so this is 2048 such checkpoints.
+const SEEK_INDEX_MAX_SIZE: usize = 2048 * TINFL_LZ_DICT_SIZE;
+
+/// The bytes ending an empty stored block, which ends the data of a sync or full flush at a byte boundary.
+const FLUSH_MARKER: [u8; 4] = [0x00, 0x00, 0xFF, 0xFF];
+
+/// The size of the first read of the compressed data of an entry decompressed with a seek index.
+pub(crate) const SEEK_INITIAL_READ_SIZE: u64 = 64 * 1024;
+
+/// The maximum size of subsequent reads of the compressed data of an entry decompressed with a seek index.
+pub(crate) const SEEK_MAX_READ_SIZE: u64 = 8 * 1024 * 1024;
+
+/// A point at the start of a block of the deflate stream of an entry, from which decompression can resume.
+struct Checkpoint {
+    /// The offset of the checkpoint in the decompressed value.
+    out_offset: u64,
+    /// The offset of the checkpoint in the compressed data, after the byte holding the bits in `state`.
+    in_offset: u64,
+    /// The state of the decompressor, with the bits of the preceding byte that belong to the next block.
+    state: BlockBoundaryState,
+    /// The window of decompressed data that can be referenced by the following blocks, or [`None`] at a full flush point, where no following block references earlier data.
+    window: Option<Box<[u8]>>,
+}
+
+impl Checkpoint {
+    /// The number of bytes held by the checkpoint.
+    fn size(&self) -> usize {
+        size_of::<Self>() + self.window.as_ref().map_or(0, |window| window.len())
+    }
+}
+
+/// A least recently used cache of the seek indexes of deflated entries, with a bounded size of checkpoints.
+///
+/// Indexes are stored with the local header offset of their entry, so that an index is not used for a key that has since been rewritten.
+pub(crate) struct SeekIndexes {
+    /// The minimum number of decompressed bytes between checkpoints.
+    interval: u64,
+    /// Whether checkpoints are also recorded at the full flush points of the deflate stream.
+    flush_points: bool,
+    inner: Mutex<SeekIndexesInner>,
+}
+
+struct SeekIndexesInner {
+    indexes: LruCache<StoreKey, (u64, Vec<Arc<Checkpoint>>)>,
+    size: usize,
+}
+
+impl SeekIndexes {
+    /// Create new seek indexes with checkpoints at least `interval` decompressed bytes apart, and at full flush points if `flush_points`.
+    pub(crate) fn new(interval: u64, flush_points: bool) -> Self {
+        Self {
+            interval,
+            flush_points,
+            inner: Mutex::new(SeekIndexesInner {
+                indexes: LruCache::unbounded(),
+                size: 0,
+            }),
+        }
+    }
+
+    /// The minimum number of decompressed bytes between checkpoints.
+    pub(crate) fn interval(&self) -> u64 {
+        self.interval
+    }
+
+    /// Whether checkpoints are also recorded at the full flush points of the deflate stream.
+    pub(crate) fn flush_points(&self) -> bool {
+        self.flush_points
+    }
+
+    /// Start decompressing the entry of `key` with its local header at `header_offset` from the last checkpoint at or before `start`.
+    pub(crate) fn inflater(&self, key: &StoreKey, header_offset: u64, start: u64) -> SeekInflater {
+        let mut inner = self.inner.lock();
+        let checkpoints = inner
+            .indexes
+            .get(key)
+            .filter(|(offset, _)| *offset == header_offset)
+            .map(|(_, checkpoints)| checkpoints.as_slice())
+            .unwrap_or_default();
+        let last = checkpoints
+            .last()
+            .map_or(0, |checkpoint| checkpoint.out_offset);
+        let position = checkpoints.partition_point(|checkpoint| checkpoint.out_offset <= start);
+        let checkpoint = position.checked_sub(1).map(|i| &*checkpoints[i]);
+        SeekInflater::new(checkpoint, self.interval, last, self.flush_points)
+    }
+
+    /// Add the checkpoints recorded by `inflater` to the seek index of `key` with its local header at `header_offset`, evicting the least recently used indexes as needed.
+    pub(crate) fn insert(&self, key: &StoreKey, header_offset: u64, mut inflater: SeekInflater) {
+        if inflater.checkpoints.is_empty() {
+            return;
+        }
+        // Flush points are recorded once the data following them is checked, after later checkpoints
+        inflater
+            .checkpoints
+            .sort_by_key(|checkpoint| checkpoint.out_offset);
+        let mut inner = self.inner.lock();
+        let inner = &mut *inner;
+        if inner
+            .indexes
+            .peek(key)
+            .is_none_or(|(offset, _)| *offset != header_offset)
+        {
+            if let Some((_, previous)) = inner.indexes.put(key.clone(), (header_offset, Vec::new()))
+            {
+                inner.size -= previous
+                    .iter()
+                    .map(|checkpoint| checkpoint.size())
+                    .sum::<usize>();
+            }
+        }
+        let Some((_, checkpoints)) = inner.indexes.get_mut(key) else {
+            return;
+        };
+        let mut size: usize = checkpoints.iter().map(|checkpoint| checkpoint.size()).sum();
+        let last = checkpoints
+            .last()
+            .map_or(0, |checkpoint| checkpoint.out_offset);
+        for checkpoint in inflater.checkpoints {
+            if checkpoint.out_offset <= last {
+                continue;
+            }
+            if size + checkpoint.size() > SEEK_INDEX_MAX_SIZE {
+                break;
+            }
+            size += checkpoint.size();
+            inner.size += checkpoint.size();
+            checkpoints.push(checkpoint);
+        }
+        while inner.size > SEEK_INDEX_MAX_SIZE {
+            let Some((_, (_, evicted))) = inner.indexes.pop_lru() else {
+                break;
+            };
+            inner.size -= evicted
+                .iter()
+                .map(|checkpoint| checkpoint.size())
+                .sum::<usize>();
+        }
+    }
+
+    /// Remove the seek index of `key`.
+    #[cfg(feature = "write")]
+    pub(crate) fn remove(&self, key: &StoreKey) {
+        let mut inner = self.inner.lock();
+        if let Some((_, previous)) = inner.indexes.pop(key) {
+            inner.size -= previous
+                .iter()
+                .map(|checkpoint| checkpoint.size())
+                .sum::<usize>();
+        }
+    }
+}
+
+/// A decompressor of a deflated entry that records checkpoints for its seek index.
+pub(crate) struct SeekInflater {
+    decompressor: Box<DecompressorOxide>,
+    /// The circular window of decompressed data, with the byte at offset `i` of the value at `i % TINFL_LZ_DICT_SIZE`.
+    window: Box<[u8]>,
+    /// The offset in the compressed data of the next byte to decompress.
+    in_offset: u64,
+    /// The offset in the decompressed value of the next byte to decompress.
+    out_offset: u64,
+    /// The minimum number of decompressed bytes between checkpoints.
+    interval: u64,
+    /// The offset in the decompressed value after which the next checkpoint is recorded.
+    next_checkpoint: u64,
+    /// The offset in the decompressed value after which full flush points are recorded, or [`None`] if they are not recorded.
+    flush_points_after: Option<u64>,
+    /// The last four bytes of compressed data consumed by the decompressor.
+    tail: [u8; 4],
+    /// The probes of the flush points that are yet to be checked.
+    probes: Vec<FlushPointProbe>,
+    /// The checkpoints recorded by the decompressor.
+    checkpoints: Vec<Arc<Checkpoint>>,
+    /// Whether the end of the deflate stream has been reached.
+    done: bool,
+}
+
+impl SeekInflater {
+    /// Start decompressing from `checkpoint`, or the start of the entry if [`None`].
+    ///
+    /// Checkpoints are recorded after `last`, the offset of the last checkpoint of the seek index.
+    fn new(checkpoint: Option<&Checkpoint>, interval: u64, last: u64, flush_points: bool) -> Self {
+        let (decompressor, window, in_offset, out_offset) = match checkpoint {
+            Some(checkpoint) => (
+                DecompressorOxide::from_block_boundary_state(&checkpoint.state),
+                checkpoint
+                    .window
+                    .clone()
+                    .unwrap_or_else(|| vec![0; TINFL_LZ_DICT_SIZE].into_boxed_slice()),
+                checkpoint.in_offset,
+                checkpoint.out_offset,
+            ),
+            None => (
+                DecompressorOxide::new(),
+                vec![0; TINFL_LZ_DICT_SIZE].into_boxed_slice(),
+                0,
+                0,
+            ),
+        };
+        Self {
+            decompressor: Box::new(decompressor),
+            window,
+            in_offset,
+            out_offset,
+            interval,
+            next_checkpoint: last.saturating_add(interval),
+            flush_points_after: flush_points.then_some(last),
+            tail: [0; 4],
+            probes: Vec::new(),
+            checkpoints: Vec::new(),
+            done: false,
+        }
+    }
+
+    /// The offset in the compressed data of the next byte to decompress.
+    pub(crate) fn in_offset(&self) -> u64 {
+        self.in_offset
+    }
+
+    /// The offset in the decompressed value of the next byte to decompress.
+    pub(crate) fn out_offset(&self) -> u64 {
+        self.out_offset
+    }
+
+    /// Returns true if the end of the deflate stream or `end` of the decompressed value has been reached.
+    pub(crate) fn is_finished(&self, end: u64) -> bool {
+        self.done || self.out_offset >= end
+    }
+
+    /// Decompress `input`, the compressed data from [`in_offset`](SeekInflater::in_offset), appending the decompressed bytes within `range` to `output`.
+    ///
+    /// All of `input` is consumed, unless the decompressor is [finished](SeekInflater::is_finished) at the end of `range`.
+    /// `has_more_input` is false if `input` ends the compressed data.
+    ///
+    /// # Errors
+    /// Returns a [`StorageError`] if the compressed data is invalid or ends before the deflate stream.
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn inflate(
+        &mut self,
+        mut input: &[u8],
+        has_more_input: bool,
+        range: &Range<u64>,
+        output: &mut Vec<u8>,
+    ) -> Result<(), StorageError> {
+        let mut flags = TINFL_FLAG_STOP_ON_BLOCK_BOUNDARY;
+        if has_more_input {
+            flags |= TINFL_FLAG_HAS_MORE_INPUT;
+        }
+        while !self.is_finished(range.end) {
+            let window_offset = (self.out_offset % TINFL_LZ_DICT_SIZE as u64) as usize;
+            let (status, consumed, written) = decompress(
+                &mut self.decompressor,
+                input,
+                &mut self.window,
+                window_offset,
+                flags,
+            );
+            let (consumed_input, remaining_input) = input.split_at(consumed);
+            input = remaining_input;
+            self.in_offset += consumed as u64;
+            if self.flush_points_after.is_some() {
+                self.probe(consumed_input);
+            }
+
+            // Append the decompressed bytes that are within the range
+            let start = self.out_offset;
+            let end = start + written as u64;
+            let from = window_offset + (range.start.clamp(start, end) - start) as usize;
+            let to = window_offset + (range.end.clamp(start, end) - start) as usize;
+            output.extend_from_slice(&self.window[from..to]);
+            self.out_offset = end;
+
+            match status {
+                TINFLStatus::Done => self.done = true,
+                TINFLStatus::BlockBoundary => {
+                    if self.out_offset >= self.next_checkpoint {
+                        self.checkpoint();
+                    }
+                    self.start_probe();
+                }
+                TINFLStatus::HasMoreOutput => {}
+                TINFLStatus::NeedsMoreInput if has_more_input => return Ok(()),
+                status => {
+                    return Err(StorageError::Other(format!(
+                        "Decompression error: {status:?}"
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Record a checkpoint at the current block boundary.
+    fn checkpoint(&mut self) {
+        if let Some(state) = self.decompressor.block_boundary_state() {
+            self.checkpoints.push(Arc::new(Checkpoint {
+                out_offset: self.out_offset,
+                in_offset: self.in_offset,
+                state,
+                window: Some(self.window.clone()),
+            }));
+            self.next_checkpoint = self.out_offset.saturating_add(self.interval);
+        }
+    }
+
+    /// Start a probe of the current block boundary if it is a flush point after the last checkpoint of the seek index.
+    fn start_probe(&mut self) {
+        if self
+            .flush_points_after
+            .is_none_or(|after| self.out_offset <= after)
+            || self.tail != FLUSH_MARKER
+        {
+            return;
+        }
+        if self
+            .decompressor
+            .block_boundary_state()
+            .is_some_and(|state| state.num_bits == 0)
+        {
+            self.probes.push(FlushPointProbe {
+                decompressor: Box::new(DecompressorOxide::new()),
+                output: vec![0; TINFL_LZ_DICT_SIZE].into_boxed_slice(),
+                written: 0,
+                out_offset: self.out_offset,
+                in_offset: self.in_offset,
+            });
+        }
+    }
+
+    /// Decompress `consumed_input`, the compressed data just consumed by the decompressor, with the pending probes.
+    ///
+    /// A checkpoint without a window is recorded at each flush point that decompression can restart from.
+    fn probe(&mut self, consumed_input: &[u8]) {
+        let tail_len = consumed_input.len().min(FLUSH_MARKER.len());
+        self.tail.rotate_left(tail_len);
+        self.tail[FLUSH_MARKER.len() - tail_len..]
+            .copy_from_slice(&consumed_input[consumed_input.len() - tail_len..]);
+
+        let checkpoints = &mut self.checkpoints;
+        self.probes
+            .retain_mut(|probe| match probe.probe(consumed_input) {
+                None => true,
+                Some(restartable) => {
+                    if restartable {
+                        checkpoints.push(Arc::new(Checkpoint {
+                            out_offset: probe.out_offset,
+                            in_offset: probe.in_offset,
+                            state: BlockBoundaryState::default(),
+                            window: None,
+                        }));
+                    }
+                    false
+                }
+            });
+    }
+}
+
+/// A check of whether decompression can restart at a flush point of a deflate stream, without the preceding data.
+///
+/// Both a sync flush and a full flush end the compressed data at a byte boundary with an empty stored block,
+/// but only after a full flush do the following blocks not reference the data before it.
+/// The probe decompresses the window of data following the flush point into a buffer that does not wrap, so a reference before the flush point fails.
+struct FlushPointProbe {
+    decompressor: Box<DecompressorOxide>,
+    /// The data decompressed from the flush point.
+    output: Box<[u8]>,
+    /// The number of bytes of `output` decompressed so far.
+    written: usize,
+    /// The offset of the flush point in the decompressed value.
+    out_offset: u64,
+    /// The offset of the flush point in the compressed data.
+    in_offset: u64,
+}
+
+impl FlushPointProbe {
+    /// Decompress `input`, the compressed data following the data probed so far.
+    ///
+    /// Returns whether decompression can restart at the flush point, or [`None`] if more input is needed to tell.
+    fn probe(&mut self, input: &[u8]) -> Option<bool> {
+        let (status, _, written) = decompress(
+            &mut self.decompressor,
+            input,
+            &mut self.output,
+            self.written,
+            TINFL_FLAG_HAS_MORE_INPUT | TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF,
+        );
+        self.written += written;
+        match status {
+            TINFLStatus::Done | TINFLStatus::HasMoreOutput => Some(true),
+            TINFLStatus::NeedsMoreInput => None,
+            _ => Some(false),
+        }
+    }
+}
+
+impl<TStorage: ?Sized> ZipStorageAdapter<TStorage> {
+    /// The seek indexes used to decompress `entry`, if it is deflated and is not decompressed in full to be cached.
+    pub(crate) fn seek_indexes(&self, entry: &StoredEntry) -> Option<&SeekIndexes> {
+        let cached = self
+            .cache
+            .as_ref()
+            .is_some_and(|cache| cache.fits(entry.uncompressed_size));
+        self.seek_indexes
+            .as_deref()
+            .filter(|_| entry.method == Method::Deflate && !cached)
+    }
+
+    /// The span of the decompressed value of `entry` from the start of the first of `byte_ranges` to the end of the last.
+    pub(crate) fn seek_span(entry: &StoredEntry, byte_ranges: &[ByteRange]) -> Range<u64> {
+        let size = entry.uncompressed_size;
+        let start = byte_ranges.iter().map(|range| range.start(size)).min();
+        let end = byte_ranges.iter().map(|range| range.end(size)).max();
+        start.unwrap_or(0)..end.unwrap_or(0)
+    }
+
+    /// Slice the values of `byte_ranges` out of `decompressed`, the `span` of the decompressed value of `entry`, the value of `key`.
+    ///
+    /// The CRC-32 of the entry is verified if `span` is the entire value.
+    ///
+    /// # Errors
+    /// Returns a [`StorageError`] if `decompressed` is not the length of `span` or the CRC-32 does not match.
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn seek_indexed_values(
+        key: &StoreKey,
+        entry: &StoredEntry,
+        byte_ranges: &[ByteRange],
+        span: &Range<u64>,
+        decompressed: Vec<u8>,
+    ) -> Result<Vec<Bytes>, StorageError> {
+        if decompressed.len() as u64 != span.end - span.start {
+            return Err(StorageError::Other(format!(
+                "zip decompressed entry size mismatch: expected {}, got {}",
+                span.end - span.start,
+                decompressed.len()
+            )));
+        }
+        if span.start == 0 && span.end == entry.uncompressed_size {
+            let actual = crc32fast::hash(&decompressed);
+            if actual != entry.crc32 {
+                return Err(Self::checksum_error(key, entry.crc32, actual));
+            }
+        }
+        let decompressed = Bytes::from(decompressed);
+        Ok(byte_ranges
+            .iter()
+            .map(|range| {
+                let start = range.start(entry.uncompressed_size) - span.start;
+                let end = range.end(entry.uncompressed_size) - span.start;
+                decompressed.slice(start as usize..end as usize)
+            })
+            .collect())
+    }
+}
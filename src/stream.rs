@@ -0,0 +1,271 @@
+//! Writing of zip archives in a single forward pass.
+
+use std::io::{self, Read, Write};
+
+use miniz_oxide::deflate::core::{
+    CompressorOxide, TDEFLFlush, TDEFLStatus, compress, create_comp_flags_from_zip_params,
+};
+
+use crate::{
+    ZipCompression, ZipWriteOptions,
+    format::{EntryRecord, METHOD_DEFLATE, METHOD_STORE},
+    write::write_central_directory,
+};
+
+#[cfg(feature = "zstd")]
+use crate::format::METHOD_ZSTD;
+
+/// The size of the buffer that values are read into, and of each block of deflate compressed output.
+const STREAM_BUFFER_SIZE: usize = 64 * 1024;
+
+/// A streaming compressor of the data of an entry.
+enum Encoder {
+    Stored,
+    Deflate(Box<CompressorOxide>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::stream::write::Encoder<'static, Vec<u8>>),
+}
+
+impl Encoder {
+    /// Create an encoder for `compression`, returning it with its compression method.
+    #[cfg_attr(not(feature = "zstd"), allow(clippy::unnecessary_wraps))]
+    fn new(compression: ZipCompression) -> io::Result<(u16, Self)> {
+        Ok(match compression {
+            ZipCompression::Stored => (METHOD_STORE, Self::Stored),
+            ZipCompression::Deflate(level) => {
+                let flags = create_comp_flags_from_zip_params(level.into(), 0, 0);
+                (
+                    METHOD_DEFLATE,
+                    Self::Deflate(Box::new(CompressorOxide::new(flags))),
+                )
+            }
+            #[cfg(feature = "zstd")]
+            ZipCompression::Zstd(level) => (
+                METHOD_ZSTD,
+                Self::Zstd(zstd::stream::write::Encoder::new(Vec::new(), level)?),
+            ),
+        })
+    }
+
+    /// Compress `input`, appending the compressed data that is ready to `out`.
+    fn encode(&mut self, input: &[u8], out: &mut Vec<u8>) -> io::Result<()> {
+        match self {
+            Self::Stored => {
+                out.extend_from_slice(input);
+                Ok(())
+            }
+            Self::Deflate(compressor) => deflate(compressor, input, out, TDEFLFlush::None),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(encoder) => {
+                encoder.write_all(input)?;
+                out.append(encoder.get_mut());
+                Ok(())
+            }
+        }
+    }
+
+    /// Finish compressing, appending the remaining compressed data to `out`.
+    fn finish(self, out: &mut Vec<u8>) -> io::Result<()> {
+        match self {
+            Self::Stored => Ok(()),
+            Self::Deflate(mut compressor) => deflate(&mut compressor, &[], out, TDEFLFlush::Finish),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(encoder) => {
+                out.append(&mut encoder.finish()?);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Deflate `input` with `compressor`, appending the compressed output to `out`.
+///
+/// If `flush` is [`TDEFLFlush::Finish`], the end of the deflate stream is written.
+fn deflate(
+    compressor: &mut CompressorOxide,
+    mut input: &[u8],
+    out: &mut Vec<u8>,
+    flush: TDEFLFlush,
+) -> io::Result<()> {
+    loop {
+        let start = out.len();
+        out.resize(start + STREAM_BUFFER_SIZE, 0);
+        let (status, bytes_in, bytes_out) = compress(compressor, input, &mut out[start..], flush);
+        out.truncate(start + bytes_out);
+        input = &input[bytes_in..];
+        match status {
+            TDEFLStatus::Done => return Ok(()),
+            TDEFLStatus::Okay if input.is_empty() && flush != TDEFLFlush::Finish => return Ok(()),
+            TDEFLStatus::Okay => {}
+            TDEFLStatus::BadParam | TDEFLStatus::PutBufFailed => {
+                return Err(io::Error::other("deflate compression failed"));
+            }
+        }
+    }
+}
+
+/// A writer of a zip file in a single forward pass, for sinks that cannot seek, such as a multipart upload to object storage.
+///
+/// The CRC-32 and sizes of an entry are not known until its value has been compressed, so each entry is written with general purpose flag bit 3 set, zeros in its local file header, and a data descriptor after its data.
+/// Readers take the sizes from the central directory, which is written by [`finish`](ZipStreamWriter::finish), so the zip file can be read by a [`ZipStorageAdapter`](crate::ZipStorageAdapter) and other zip implementations.
+///
+/// Entries are written with the compression and alignment of the write options.
+/// The compression policy of the write options is not applied, as the size of a value is not known in advance.
+/// An entry with sizes of 4 GiB or more requires [`ZipWriteOptions::force_zip64`], as the ZIP64 extra field must be written before its data.
+///
+/// ```
+/// # use std::sync::Arc;
+/// use zarrs_storage::{ReadableStorageTraits, StoreKey, WritableStorageTraits, store::MemoryStore};
+/// use zarrs_zip::{ZipCompression, ZipStorageAdapter, ZipStreamWriter, ZipWriteOptions};
+///
+/// let options = ZipWriteOptions::default().compression(ZipCompression::Deflate(6));
+/// let mut writer = ZipStreamWriter::new(Vec::new(), options);
+/// writer.write_entry("zarr.json", &b"{}"[..])?;
+/// let zip = writer.finish()?;
+///
+/// let store = Arc::new(MemoryStore::new());
+/// let key = StoreKey::new("streamed.zip")?;
+/// store.set(&key, zip.into())?;
+/// let zip_store = ZipStorageAdapter::new(store, key)?;
+/// assert_eq!(zip_store.get(&StoreKey::new("zarr.json")?)?.unwrap(), &b"{}"[..]);
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+pub struct ZipStreamWriter<W> {
+    /// The sink that the zip file is written to.
+    sink: W,
+    /// The number of bytes written to the sink.
+    offset: u64,
+    /// The central directory records.
+    directory: Vec<u8>,
+    /// The number of central directory records.
+    num_entries: u64,
+    /// The options of written entries.
+    options: ZipWriteOptions,
+}
+
+impl<W> core::fmt::Debug for ZipStreamWriter<W> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ZipStreamWriter")
+            .field("offset", &self.offset)
+            .field("num_entries", &self.num_entries)
+            .field("options", &self.options)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<W: Write> ZipStreamWriter<W> {
+    /// Create a writer of a zip file to `sink`, with entries written with `options`.
+    #[must_use]
+    pub fn new(sink: W, options: ZipWriteOptions) -> Self {
+        Self {
+            sink,
+            offset: 0,
+            directory: Vec::new(),
+            num_entries: 0,
+            options,
+        }
+    }
+
+    /// Write an entry named `name` with the value read from `reader` until it is exhausted.
+    ///
+    /// The value is compressed and written to the sink as it is read, so it is never held in memory in full.
+    ///
+    /// # Errors
+    /// Returns an [`io::Error`] if `name` is too long, `reader` or the sink fail, the value cannot be compressed, or the entry requires ZIP64 extensions that were not forced.
+    /// The zip file is invalid if an error occurs after the entry has started to be written to the sink.
+    pub fn write_entry<R: Read>(&mut self, name: &str, mut reader: R) -> io::Result<()> {
+        if name.len() > usize::from(u16::MAX) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("the name of {name} is too long"),
+            ));
+        }
+        let (method, mut encoder) = Encoder::new(self.options.compression)?;
+        // Only the data of stored entries is aligned, compressed data cannot be read in place
+        let alignment = if method == METHOD_STORE {
+            self.options.alignment
+        } else {
+            0
+        };
+        let mut record = EntryRecord {
+            name,
+            method,
+            crc32: 0,
+            compressed_size: 0,
+            uncompressed_size: 0,
+            header_offset: self.offset,
+            zip64: self.options.force_zip64,
+            alignment,
+            data_descriptor: true,
+        };
+        record.zip64 |= record.requires_zip64();
+        if record.local_extra_field_size() > usize::from(u16::MAX) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{name} cannot be aligned to {alignment} bytes"),
+            ));
+        }
+
+        let mut out = Vec::new();
+        record.write_local_header(&mut out);
+        self.write_out(&mut out)?;
+
+        let mut hasher = crc32fast::Hasher::new();
+        let mut buffer = vec![0; STREAM_BUFFER_SIZE];
+        loop {
+            let len = match reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(len) => len,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+            hasher.update(&buffer[..len]);
+            record.uncompressed_size += len as u64;
+            encoder.encode(&buffer[..len], &mut out)?;
+            record.compressed_size += out.len() as u64;
+            self.write_out(&mut out)?;
+        }
+        encoder.finish(&mut out)?;
+        record.compressed_size += out.len() as u64;
+        record.crc32 = hasher.finalize();
+        if !record.zip64 && record.requires_zip64() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{name} is 4 GiB or larger, which requires forcing ZIP64 extensions"),
+            ));
+        }
+
+        record.write_data_descriptor(&mut out);
+        self.write_out(&mut out)?;
+        record.write_central_directory_record(&mut self.directory);
+        self.num_entries += 1;
+        Ok(())
+    }
+
+    /// Write the central directory and return the sink.
+    ///
+    /// # Errors
+    /// Returns an [`io::Error`] if the sink fails.
+    pub fn finish(mut self) -> io::Result<W> {
+        let mut out = Vec::with_capacity(self.directory.len());
+        write_central_directory(
+            &mut out,
+            self.offset,
+            &self.directory,
+            self.num_entries,
+            &[],
+            self.options.force_zip64,
+        );
+        self.sink.write_all(&out)?;
+        self.sink.flush()?;
+        Ok(self.sink)
+    }
+
+    /// Write `out` to the sink and clear it.
+    fn write_out(&mut self, out: &mut Vec<u8>) -> io::Result<()> {
+        self.sink.write_all(out)?;
+        self.offset += out.len() as u64;
+        out.clear();
+        Ok(())
+    }
+}
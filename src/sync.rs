@@ -1,18 +1,42 @@
-use std::{collections::HashMap, path::PathBuf, sync::Arc};
+use std::{ops::Range, path::PathBuf, sync::Arc};
 
-use crate::ZipEntry;
+#[cfg(any(feature = "lzma", feature = "zstd"))]
+use crate::decoder::EntryDecoder;
+use crate::{
+    index::{IndexEntry, IndexOptions, StoredEntry},
+    preload::PreloadRun,
+    progress::report_read_progress,
+    seek::{SEEK_INITIAL_READ_SIZE, SEEK_MAX_READ_SIZE, SeekIndexes},
+};
+#[cfg(feature = "write")]
+use crate::{
+    index::{LocalRecord, PendingChanges, ZipIndex},
+    write::{
+        ArchiveWriter, append_archive, apply_partial_writes, central_directory_offset,
+        crc32_data_len, crc32_updates, dead_bytes, pending_changes_error, repack_archive,
+        rewrite_archive, set_staged,
+    },
+};
 
-use super::{ZipStorageAdapter, ZipStorageAdapterCreateError};
+#[cfg(feature = "write")]
+use super::RepackSummary;
+use super::{
+    ArchivePrefetch, ArchiveTail, DATA_DESCRIPTOR_MAX_SIZE, END_OF_CENTRAL_DIRECTORY_SEARCH_SIZE,
+    ValidatedByteRanges, ZipParsedArchive, ZipReadProgressCallback, ZipStorageAdapter,
+    ZipStorageAdapterBuilder, ZipStorageAdapterCreateError, blank_comment,
+    has_end_of_central_directory, is_entire_value, missing_end_of_central_directory_error,
+};
 use rc_zip::{
-    Entry, EntryKind,
     fsm::{ArchiveFsm, EntryFsm, FsmResult},
     parse::Method,
 };
 use zarrs_storage::{
-    Bytes, ListableStorageTraits, MaybeBytesIterator, ReadableStorageTraits, StorageError,
-    StoreKey, StorePrefix, StorePrefixes,
-    byte_range::{ByteRange, ByteRangeIterator, InvalidByteRangeError},
+    Bytes, BytesIterator, ListableStorageTraits, MaybeBytes, MaybeBytesIterator,
+    ReadableStorageTraits, StorageError, StoreKey, StorePrefix,
+    byte_range::{ByteRange, ByteRangeIterator},
 };
+#[cfg(feature = "write")]
+use zarrs_storage::{OffsetBytesIterator, WritableStorageTraits};
 use zarrs_storage::{StoreKeys, StoreKeysPrefixes};
 
 impl<TStorage: ?Sized + ReadableStorageTraits> ZipStorageAdapter<TStorage> {
@@ -30,8 +54,58 @@ impl<TStorage: ?Sized + ReadableStorageTraits> ZipStorageAdapter<TStorage> {
         Self::new_with_path(storage, key, "")
     }
 
+    /// Create a new zip storage adapter with a cache of decompressed entries.
+    ///
+    /// See [`with_cache`](ZipStorageAdapter::with_cache).
+    ///
+    /// # Errors
+    /// Returns a [`ZipStorageAdapterCreateError`] if the store value at `key` is not a valid zip file.
+    pub fn new_with_cache(
+        storage: Arc<TStorage>,
+        key: StoreKey,
+        cache_bytes: usize,
+    ) -> Result<Self, ZipStorageAdapterCreateError> {
+        ZipStorageAdapterBuilder::new(storage, key)
+            .cache_bytes(cache_bytes)
+            .build()
+    }
+
+    /// Create a new zip storage adapter that verifies the CRC-32 of stored entries read in full.
+    ///
+    /// See [`with_crc32_verification`](ZipStorageAdapter::with_crc32_verification).
+    ///
+    /// # Errors
+    /// Returns a [`ZipStorageAdapterCreateError`] if the store value at `key` is not a valid zip file.
+    pub fn new_verified(
+        storage: Arc<TStorage>,
+        key: StoreKey,
+    ) -> Result<Self, ZipStorageAdapterCreateError> {
+        ZipStorageAdapterBuilder::new(storage, key)
+            .verify_crc(true)
+            .build()
+    }
+
+    /// Create a new zip storage adapter that decrypts encrypted entries with `password`.
+    ///
+    /// See [`with_password`](ZipStorageAdapter::with_password).
+    ///
+    /// # Errors
+    /// Returns a [`ZipStorageAdapterCreateError`] if the store value at `key` is not a valid zip file.
+    #[cfg(feature = "encryption")]
+    pub fn new_encrypted(
+        storage: Arc<TStorage>,
+        key: StoreKey,
+        password: impl Into<Vec<u8>>,
+    ) -> Result<Self, ZipStorageAdapterCreateError> {
+        ZipStorageAdapterBuilder::new(storage, key)
+            .password(password)
+            .build()
+    }
+
     /// Create a new zip storage adapter to `path` within the zip file.
     ///
+    /// `path` is treated as a directory, see [`ZipStorageAdapterBuilder::path`].
+    ///
     /// Pass [`StoreKey::root()`] to treat the entire `storage` as the zip file
     /// (e.g., a `FilesystemStore` rooted directly at a zip file).
     ///
@@ -42,78 +116,146 @@ impl<TStorage: ?Sized + ReadableStorageTraits> ZipStorageAdapter<TStorage> {
         key: StoreKey,
         path: T,
     ) -> Result<Self, ZipStorageAdapterCreateError> {
-        let zip_path = path.into();
+        ZipStorageAdapterBuilder::new(storage, key)
+            .path(path)
+            .build()
+    }
 
+    /// Parse the central directory of the zip file at `key` of `storage`, so that adapters rooted at different paths within it can be created without parsing it again.
+    ///
+    /// See [`from_archive`](ZipStorageAdapter::from_archive).
+    ///
+    /// # Errors
+    /// Returns a [`ZipStorageAdapterCreateError`] if the store value at `key` is not a valid zip file.
+    pub fn parse(
+        storage: &Arc<TStorage>,
+        key: &StoreKey,
+    ) -> Result<Arc<ZipParsedArchive>, ZipStorageAdapterCreateError> {
+        Self::parse_with_prefetch(storage, key, 0).map(Arc::new)
+    }
+
+    /// Parse the central directory of the zip file at `key` of `storage`, reading `eocd_prefetch_bytes` trailing bytes of the zip file in a single request first.
+    fn parse_with_prefetch(
+        storage: &Arc<TStorage>,
+        key: &StoreKey,
+        eocd_prefetch_bytes: u64,
+    ) -> Result<ZipParsedArchive, ZipStorageAdapterCreateError> {
         // Get zip file size
         let size = storage
-            .size_key(&key)?
+            .size_key(key)?
             .ok_or_else(|| StorageError::UnknownKeySize(key.clone()))?;
 
         // Parse the archive using ArchiveFsm
-        let archive = Self::parse_archive(&storage, &key, size)?;
-
-        // Build entries map and sorted entries list
-        let mut entries: HashMap<StoreKey, Entry> = HashMap::new();
-        let mut sorted_entries: Vec<ZipEntry> = Vec::new();
-        for entry in archive.entries() {
-            if let Some(stripped) = Self::strip_zip_path_prefix(&entry.name, &zip_path) {
-                match entry.kind() {
-                    EntryKind::File => {
-                        let store_key = StoreKey::try_from(stripped)?;
-                        entries.insert(store_key.clone(), entry.clone()); // FIXME: It'd be nice to avoid the clone, needs rc-zip change
-                        sorted_entries.push(ZipEntry::Key(store_key));
-                    }
-                    EntryKind::Directory => {
-                        let store_prefix = StorePrefix::try_from(stripped)?;
-                        sorted_entries.push(ZipEntry::Prefix(store_prefix));
-                    }
-                    EntryKind::Symlink => {
-                        // Ignore symlinks
-                    }
-                }
-            }
-        }
-        sorted_entries.sort_by(|a, b| a.as_str().cmp(b.as_str()));
-
-        Ok(Self {
+        let (archive, comment) = Self::parse_archive(storage, key, size, eocd_prefetch_bytes)?;
+        Ok(ZipParsedArchive {
+            archive,
+            comment,
             size,
+        })
+    }
+
+    /// Open the zip file at `key` of `storage`, rooted at `zip_path` within the zip file.
+    pub(crate) fn open(
+        storage: Arc<TStorage>,
+        key: StoreKey,
+        zip_path: String,
+        index_options: IndexOptions,
+        eocd_prefetch_bytes: u64,
+    ) -> Result<Self, ZipStorageAdapterCreateError> {
+        let archive = Self::parse_with_prefetch(&storage, &key, eocd_prefetch_bytes)?;
+        Self::from_parsed(
             storage,
             key,
-            entries,
-            sorted_entries,
-        })
+            zip_path,
+            &archive,
+            index_options,
+            eocd_prefetch_bytes,
+        )
     }
 
     /// Parse the zip archive using `ArchiveFsm`.
+    ///
+    /// Also returns the raw bytes of the archive comment.
     fn parse_archive(
         storage: &Arc<TStorage>,
         key: &StoreKey,
         size: u64,
-    ) -> Result<rc_zip::parse::Archive, ZipStorageAdapterCreateError> {
+        eocd_prefetch_bytes: u64,
+    ) -> Result<(rc_zip::parse::Archive, Vec<u8>), ZipStorageAdapterCreateError> {
         let mut fsm = ArchiveFsm::new(size);
+        let mut tail = ArchiveTail::new(size);
+        // The byte range of the archive comment, if it is blanked as the zip file is parsed again
+        let mut comment = None;
+        // The end of the last byte range requested by the FSM
+        let mut requested_end = 0;
+
+        // Read the trailing bytes of the zip file upfront, so that requests of the FSM within them are served without reading storage
+        let prefetch = if eocd_prefetch_bytes > 0 && size > 0 {
+            let len = eocd_prefetch_bytes.min(size);
+            let bytes = storage
+                .get_partial(key, ByteRange::Suffix(len))?
+                .ok_or_else(|| {
+                    ZipStorageAdapterCreateError::ZipError("Cannot read zip data".to_string())
+                })?;
+            Some(ArchivePrefetch {
+                offset: size - len,
+                bytes,
+            })
+        } else {
+            None
+        };
 
         loop {
             // Check if FSM needs more data
             if let Some(offset) = fsm.wants_read() {
                 let space = fsm.space();
+                requested_end = offset + space.len() as u64;
                 // Don't request more than what's left in the file
                 let remaining = size.saturating_sub(offset);
                 let to_read = (space.len() as u64).min(remaining);
 
                 if to_read > 0 {
-                    // Read from storage at the requested offset
-                    let byte_range = ByteRange::FromStart(offset, Some(to_read));
-                    let data = storage.get_partial(key, byte_range)?.ok_or_else(|| {
-                        ZipStorageAdapterCreateError::ZipError("Cannot read zip data".to_string())
-                    })?;
+                    let read;
+                    let data = if let Some(data) = prefetch
+                        .as_ref()
+                        .and_then(|prefetch| prefetch.get(offset, to_read))
+                    {
+                        data
+                    } else {
+                        // Read from storage at the requested offset
+                        let byte_range = ByteRange::FromStart(offset, Some(to_read));
+                        read = storage.get_partial(key, byte_range)?.ok_or_else(|| {
+                            ZipStorageAdapterCreateError::ZipError(
+                                "Cannot read zip data".to_string(),
+                            )
+                        })?;
+                        &read[..]
+                    };
 
                     // Copy data into FSM buffer
                     let copy_len = data.len().min(space.len());
                     space[..copy_len].copy_from_slice(&data[..copy_len]);
+                    tail.push(offset, &data[..copy_len]);
+                    blank_comment(&mut space[..copy_len], offset, comment.as_ref());
                     fsm.fill(copy_len);
                 } else {
-                    // No more data to read, signal EOF by filling 0 bytes
-                    fsm.fill(0);
+                    // The FSM wants data past the end of the file, which it would otherwise wait for indefinitely
+                    // An empty value, or one with neither an end of central directory record nor a local file header, is not a zip file rather than truncated
+                    return Err(
+                        match Self::parse_archive_error(
+                            storage,
+                            key,
+                            size,
+                            requested_end,
+                            "unexpected end of file",
+                        ) {
+                            err @ ZipStorageAdapterCreateError::NotAZipFile(_) => err,
+                            _ => ZipStorageAdapterCreateError::TruncatedArchive {
+                                expected: requested_end,
+                                available: size,
+                            },
+                        },
+                    );
                 }
             }
 
@@ -123,54 +265,283 @@ impl<TStorage: ?Sized + ReadableStorageTraits> ZipStorageAdapter<TStorage> {
                     fsm = next_fsm;
                 }
                 Ok(FsmResult::Done(archive)) => {
-                    return Ok(archive);
+                    return Ok((archive, tail.comment()));
+                }
+                Err(rc_zip::error::Error::Encoding(_))
+                    if comment.is_none() && tail.comment_range().is_some() =>
+                {
+                    // The archive comment may not be text in the encoding of the entry names, parse again with it blanked
+                    comment = tail.comment_range();
+                    fsm = ArchiveFsm::new(size);
+                    tail = ArchiveTail::new(size);
                 }
                 Err(e) => {
-                    return Err(ZipStorageAdapterCreateError::ZipError(e.to_string()));
+                    return Err(Self::parse_archive_error(
+                        storage,
+                        key,
+                        size,
+                        requested_end,
+                        e,
+                    ));
                 }
             }
         }
     }
 
+    /// The error of a zip file at `key` of `storage` of `size` bytes that failed to parse with `err`, after requesting bytes up to `requested_end`.
+    ///
+    /// Returns [`ZipStorageAdapterCreateError::NotAZipFile`] if the value has no end of central directory record,
+    /// or [`ZipStorageAdapterCreateError::TruncatedArchive`] if it also starts with a local file header.
+    fn parse_archive_error(
+        storage: &Arc<TStorage>,
+        key: &StoreKey,
+        size: u64,
+        requested_end: u64,
+        err: impl std::fmt::Display,
+    ) -> ZipStorageAdapterCreateError {
+        let window_size = size.min(END_OF_CENTRAL_DIRECTORY_SEARCH_SIZE as u64);
+        let tail = if window_size == 0 {
+            Some(Bytes::new())
+        } else {
+            storage
+                .get_partial(key, ByteRange::Suffix(window_size))
+                .ok()
+                .flatten()
+        };
+        match tail {
+            Some(tail) if !has_end_of_central_directory(&tail) => {
+                let head = if size >= 4 {
+                    storage
+                        .get_partial(key, ByteRange::FromStart(0, Some(4)))
+                        .ok()
+                        .flatten()
+                } else {
+                    None
+                };
+                missing_end_of_central_directory_error(key, size, requested_end, head.as_deref())
+            }
+            _ => ZipStorageAdapterCreateError::ZipError(err.to_string()),
+        }
+    }
+
+    /// Get the values of `keys`.
+    ///
+    /// With the `rayon` feature, if the compressed entries among `keys` that are not cached have a total decompressed size of at least [`PARALLEL_DECOMPRESSION_THRESHOLD`](crate::PARALLEL_DECOMPRESSION_THRESHOLD) bytes,
+    /// their compressed data is read from the underlying storage sequentially and then decompressed in parallel.
+    /// Otherwise, the values are read as by [`get_partial_many_keys`](Self::get_partial_many_keys), so that the values of adjacent stored entries are read together.
+    ///
+    /// Entries are decompressed on the current `rayon` thread pool, so the number of threads can be bounded by calling this within `rayon::ThreadPool::install`.
+    /// The compressed data of all of the entries is held in memory until they are decompressed, so peak memory usage can be bounded by getting the values of large entries in batches.
+    ///
+    /// # Errors
+    /// Returns a [`StorageError`] if the value of any key cannot be read.
+    pub fn get_many(&self, keys: &[StoreKey]) -> Result<Vec<MaybeBytes>, StorageError> {
+        #[cfg(feature = "rayon")]
+        if let Some(values) = self.get_many_parallel(keys)? {
+            return Ok(values);
+        }
+        let key_ranges: Vec<_> = keys
+            .iter()
+            .map(|key| (key.clone(), vec![ByteRange::FromStart(0, None)]))
+            .collect();
+        Ok(self
+            .get_partial_many_keys(&key_ranges)?
+            .into_iter()
+            .map(|values| values.and_then(|values| values.into_iter().next()))
+            .collect())
+    }
+
+    /// Get `byte_ranges` of the values of many keys, given as `(key, byte_ranges)` pairs.
+    ///
+    /// The byte ranges of stored (uncompressed) entries are translated to byte ranges of the zip file and coalesced across keys,
+    /// so that entries which are adjacent in the zip file, such as chunks written sequentially, are read in a few requests to the underlying storage rather than one or two per key.
+    /// The local file headers of these entries that have not been read are also read together, before their data.
+    /// The byte ranges of other entries are read for each key, as by [`get_partial_many`](ReadableStorageTraits::get_partial_many).
+    ///
+    /// Returns the values of the byte ranges of each key in the order requested, [`None`] if the key does not exist.
+    ///
+    /// # Errors
+    /// Returns a [`StorageError`] if any of the byte ranges cannot be read or are out of bounds.
+    pub fn get_partial_many_keys(
+        &self,
+        key_ranges: &[(StoreKey, Vec<ByteRange>)],
+    ) -> Result<Vec<Option<Vec<Bytes>>>, StorageError> {
+        let entries = self.batched_stored_entries(key_ranges);
+
+        // Read the local file headers of the batched entries that have not been read
+        let header_offsets = entries.iter().flatten().map(|entry| entry.header_offset);
+        self.read_header_runs(&self.header_prefetch_runs(header_offsets))
+            .map_err(|err| StorageError::Other(err.to_string()))?;
+
+        // Read the byte ranges of the batched entries in as few requests as possible
+        let coalesced = self.coalesce_batched_ranges(key_ranges, &entries)?;
+        let requests = coalesced.requests();
+        let values = if requests.is_empty() {
+            Vec::new()
+        } else {
+            self.storage
+                .get_partial_many(&self.key, Box::new(requests.into_iter()))?
+                .ok_or_else(|| StorageError::Other("Entry data not found".to_string()))?
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        let mut values = coalesced.split(&values)?.into_iter();
+
+        key_ranges
+            .iter()
+            .zip(entries)
+            .map(|((key, byte_ranges), entry)| {
+                let Some(entry) = entry else {
+                    return self
+                        .get_impl(key, Box::new(byte_ranges.iter().copied()))?
+                        .map(Iterator::collect)
+                        .transpose();
+                };
+                let values: Vec<Bytes> = values.by_ref().take(byte_ranges.len()).collect();
+                if self.verifies_crc32(&entry, byte_ranges) {
+                    Self::verify_stored_crc32(key, &entry, byte_ranges, &values)?;
+                }
+                Ok(Some(values))
+            })
+            .collect()
+    }
+
+    /// Read the values of `keys` ahead of access, such as the chunks that a viewer will read next.
+    ///
+    /// The local data of the entries are read in a few requests, coalescing nearby entries, and their values are decompressed and held in the cache of [`with_cache`](ZipStorageAdapter::with_cache),
+    /// evicting the least recently used values as usual.
+    /// While they remain in the cache, later reads of the keys do not read the underlying storage, including those of stored entries, which are otherwise not cached.
+    /// Without a cache, and for entries that are encrypted or do not fit in the cache, only the local file headers of the entries are read, so that later reads do not need to read them.
+    /// Keys that do not exist are ignored.
+    ///
+    /// # Errors
+    /// Returns a [`StorageError`] if the entries cannot be read or decompressed.
+    pub fn prefetch(&self, keys: &[StoreKey]) -> Result<(), StorageError> {
+        let (runs, header_runs) = self.prefetch_runs(keys);
+        self.read_header_runs(&header_runs)
+            .map_err(|err| StorageError::Other(err.to_string()))?;
+        if runs.is_empty() {
+            return Ok(());
+        }
+        let byte_ranges = runs
+            .iter()
+            .map(|(range, _)| ByteRange::FromStart(range.start, Some(range.end - range.start)));
+        let values = self
+            .storage
+            .get_partial_many(&self.key, Box::new(byte_ranges))?
+            .ok_or_else(|| StorageError::Other("Cannot read prefetched entries".to_string()))?;
+        for ((range, entries), bytes) in runs.iter().zip(values) {
+            self.prefetch_run(range.start, &bytes?, entries)?;
+        }
+        Ok(())
+    }
+
     fn get_impl(
         &self,
         key: &StoreKey,
         byte_ranges: ByteRangeIterator<'_>,
     ) -> Result<MaybeBytesIterator<'_>, StorageError> {
-        let Some(entry) = self.get_entry(key) else {
+        // Clone the entry so that the index is not locked while reading
+        let Some(entry) = self.index.read().get(key).cloned() else {
             return Ok(None);
         };
 
         let byte_ranges: Vec<ByteRange> = byte_ranges.collect();
+        if is_entire_value(&byte_ranges, entry.size()) {
+            // Fast path: a single byte range spanning the entire value
+            let value = self.get_value(key, entry)?;
+            return Ok(value.map(|value| Box::new(std::iter::once(Ok(value))) as BytesIterator<'_>));
+        }
 
-        // Validate that all byte ranges are within bounds
-        for range in &byte_ranges {
-            let end = match range {
-                ByteRange::FromStart(start, Some(len)) => start.saturating_add(*len),
-                ByteRange::FromStart(start, None) => *start, // Reading to end is always valid if start is valid
-                ByteRange::Suffix(_) => 0,                   // Suffix is clamped, always valid
-            };
-            if end > entry.uncompressed_size {
-                return Err(InvalidByteRangeError::new(*range, entry.uncompressed_size).into());
-            }
+        // Validate the byte ranges before reading or decompressing the entry, an out of bounds byte range yields an error in place of its value
+        let byte_ranges = ValidatedByteRanges::new(byte_ranges, entry.size());
+        if byte_ranges.all_valid() {
+            return self.get_entry(key, entry, &byte_ranges.valid);
         }
+        let values = if byte_ranges.valid.is_empty() {
+            Some(Box::new(std::iter::empty()) as BytesIterator<'_>)
+        } else {
+            self.get_entry(key, entry, &byte_ranges.valid)?
+        };
+        Ok(values.map(|values| Box::new(byte_ranges.merge(values)) as BytesIterator<'_>))
+    }
 
-        match entry.method {
-            Method::Store => {
-                // Fast path: read directly from storage
-                self.get_stored_entry(entry, &byte_ranges)
-            }
-            _ => {
-                // Decompress the entry using EntryFsm
-                self.get_compressed_entry(entry, &byte_ranges)
+    /// Read the entire value of `entry`, the value of `key`.
+    ///
+    /// The value of a stored entry is read in a single request of the zip file, without validating, coalescing, and splitting byte ranges.
+    fn get_value(&self, key: &StoreKey, entry: IndexEntry) -> Result<MaybeBytes, StorageError> {
+        let byte_ranges = [ByteRange::FromStart(0, None)];
+        let Some(stored) = self.stored_value_entry(key, &entry) else {
+            return self
+                .get_entry(key, entry, &byte_ranges)?
+                .and_then(|mut values| values.next())
+                .transpose();
+        };
+        let data_offset = self
+            .calculate_data_offset(stored.header_offset)
+            .map_err(|e| StorageError::Other(e.to_string()))?;
+        let value = self
+            .storage
+            .get_partial(
+                &self.key,
+                ByteRange::FromStart(data_offset, Some(stored.uncompressed_size)),
+            )?
+            .ok_or_else(|| StorageError::Other("Entry data not found".to_string()))?;
+        if self.verifies_crc32(stored, &byte_ranges) {
+            Self::verify_stored_crc32(key, stored, &byte_ranges, std::slice::from_ref(&value))?;
+        }
+        Ok(Some(value))
+    }
+
+    /// Read `byte_ranges` of `entry`, the value of `key`, which are within its bounds.
+    fn get_entry(
+        &self,
+        key: &StoreKey,
+        entry: IndexEntry,
+        byte_ranges: &[ByteRange],
+    ) -> Result<MaybeBytesIterator<'_>, StorageError> {
+        #[cfg_attr(not(feature = "write"), allow(irrefutable_let_patterns))]
+        if let IndexEntry::Archived(entry) = &entry {
+            if let Some(value) = self.held_value(key, entry) {
+                // Serve the byte ranges from the value read when the adapter was built or prefetched
+                let values: Vec<_> = byte_ranges
+                    .iter()
+                    .map(|range| Ok(value.slice(range.to_range_usize(value.len() as u64))))
+                    .collect();
+                return Ok(Some(Box::new(values.into_iter())));
             }
         }
+        match entry {
+            IndexEntry::Archived(entry) => match entry.method {
+                #[cfg(feature = "encryption")]
+                _ if entry.is_encrypted() => {
+                    // Decrypt and decompress the entire entry
+                    self.get_encrypted_entry(key, &entry, byte_ranges)
+                }
+                Method::Store => {
+                    // Fast path: read directly from storage
+                    self.get_stored_entry(key, &entry, byte_ranges)
+                }
+                _ => {
+                    // Decompress the entry using EntryFsm
+                    self.get_compressed_entry(key, &entry, byte_ranges)
+                }
+            },
+            #[cfg(feature = "write")]
+            IndexEntry::Pending(value, _) => Ok(Some(Box::new(
+                byte_ranges
+                    .iter()
+                    .map(move |range| Ok(value.slice(range.to_range_usize(value.len() as u64))))
+                    .collect::<Vec<_>>()
+                    .into_iter(),
+            ))),
+        }
     }
 
     /// Fast path for stored (uncompressed) entries.
     fn get_stored_entry(
         &self,
-        entry: &Entry,
+        key: &StoreKey,
+        entry: &StoredEntry,
         byte_ranges: &[ByteRange],
     ) -> Result<MaybeBytesIterator<'_>, StorageError> {
         // Calculate data offset by reading local file header
@@ -178,112 +549,409 @@ impl<TStorage: ?Sized + ReadableStorageTraits> ZipStorageAdapter<TStorage> {
             .calculate_data_offset(entry.header_offset)
             .map_err(|e| StorageError::Other(e.to_string()))?;
 
-        // Translate relative byte ranges to absolute zip file offsets
-        let translated: Vec<ByteRange> = byte_ranges
+        // Coalesce nearby byte ranges into fewer requests of the zip file
+        let coalesced = self.coalesce_stored_ranges(entry, data_offset, byte_ranges);
+        let values = self
+            .storage
+            .get_partial_many(&self.key, Box::new(coalesced.requests().into_iter()))?
+            .ok_or_else(|| StorageError::Other("Entry data not found".to_string()))?
+            .collect::<Result<Vec<_>, _>>()?;
+        let values = coalesced.split(&values)?;
+        if self.verifies_crc32(entry, byte_ranges) {
+            Self::verify_stored_crc32(key, entry, byte_ranges, &values)?;
+        }
+        Ok(Some(Box::new(values.into_iter().map(Ok))))
+    }
+
+    /// Path for encrypted entries, which are read, decrypted, and decompressed in full.
+    ///
+    /// The decrypted entry is cached if it fits in the cache.
+    #[cfg(feature = "encryption")]
+    fn get_encrypted_entry(
+        &self,
+        key: &StoreKey,
+        entry: &StoredEntry,
+        byte_ranges: &[ByteRange],
+    ) -> Result<MaybeBytesIterator<'_>, StorageError> {
+        let cached = self
+            .cache
+            .as_ref()
+            .filter(|cache| cache.fits(entry.uncompressed_size))
+            .and_then(|cache| cache.get(key, entry.header_offset));
+        let value = if let Some(value) = cached {
+            value
+        } else {
+            // Read the local file header with the data, it has the AES extra field and the flags
+            let data_offset = self
+                .calculate_data_offset(entry.header_offset)
+                .map_err(|e| StorageError::Other(e.to_string()))?;
+            let len = data_offset - entry.header_offset + entry.compressed_size;
+            let local = self
+                .storage
+                .get_partial(
+                    &self.key,
+                    ByteRange::FromStart(entry.header_offset, Some(len)),
+                )?
+                .ok_or_else(|| StorageError::Other("Entry data not found".to_string()))?;
+            let value = self.decrypt_entry(key, entry, &local)?;
+            if let Some(cache) = &self.cache {
+                cache.insert(key.clone(), entry.header_offset, value.clone());
+            }
+            value
+        };
+
+        let values: Vec<_> = byte_ranges
             .iter()
-            .map(|range| match range {
-                ByteRange::FromStart(start, len) => {
-                    let actual_len = len.unwrap_or(entry.uncompressed_size.saturating_sub(*start));
-                    ByteRange::FromStart(data_offset + start, Some(actual_len))
-                }
-                ByteRange::Suffix(len) => {
-                    let start = data_offset + entry.uncompressed_size.saturating_sub(*len);
-                    ByteRange::FromStart(start, Some((*len).min(entry.uncompressed_size)))
-                }
-            })
+            .map(|range| Ok(value.slice(range.to_range_usize(entry.uncompressed_size))))
             .collect();
-
-        // Retrieve the bytes
-        self.storage
-            .get_partial_many(&self.key, Box::new(translated.into_iter()))?
-            .ok_or_else(|| StorageError::Other("Entry data not found".to_string()))
-            .map(Some)
+        Ok(Some(Box::new(values.into_iter())))
     }
 
     /// Slower path for compressed entries using `EntryFsm`.
     ///
-    /// Decodes the entire entry and then slices out the requested byte ranges.
-    #[allow(clippy::cast_possible_truncation)]
+    /// Decodes the entry up to the end of the requested byte ranges and then slices them out.
+    /// If the entry can be cached, it is decoded in full and cached.
     fn get_compressed_entry(
         &self,
-        entry: &Entry,
+        key: &StoreKey,
+        entry: &StoredEntry,
         byte_ranges: &[ByteRange],
     ) -> Result<MaybeBytesIterator<'_>, StorageError> {
-        let decompressed = self.decompress_entry(entry)?;
-
-        let mut results = Vec::with_capacity(byte_ranges.len());
-        for range in byte_ranges {
-            let range = range.to_range_usize(entry.uncompressed_size);
-            results.push(Ok(Bytes::copy_from_slice(&decompressed[range])));
+        if let Some(seek_indexes) = self.seek_indexes(entry) {
+            let values = self.get_seek_indexed_entry(key, entry, byte_ranges, seek_indexes)?;
+            return Ok(Some(Box::new(values.into_iter().map(Ok))));
         }
 
+        let decompressed = match &self.cache {
+            Some(cache) if cache.fits(entry.uncompressed_size) => {
+                if let Some(decompressed) = cache.get(key, entry.header_offset) {
+                    decompressed
+                } else {
+                    // Decompress the entire entry so that it can be cached
+                    let decompressed = Bytes::from(self.decompress_entry(
+                        key,
+                        entry,
+                        &[ByteRange::FromStart(0, None)],
+                    )?);
+                    cache.insert(key.clone(), entry.header_offset, decompressed.clone());
+                    decompressed
+                }
+            }
+            _ => Bytes::from(self.decompress_entry(key, entry, byte_ranges)?),
+        };
+
+        // The byte ranges share the allocation of the decompressed entry, rather than being copied out of it
+        let results: Vec<_> = byte_ranges
+            .iter()
+            .map(|range| Ok(decompressed.slice(range.to_range_usize(entry.uncompressed_size))))
+            .collect();
+
         Ok(Some(Box::new(results.into_iter())))
     }
 
-    /// Decompress an entry using `EntryFsm`.
+    /// Decompress the byte ranges of a deflated entry, resuming from the last checkpoint of its seek index before them.
+    ///
+    /// Checkpoints are recorded in the seek index as the entry is decompressed.
+    fn get_seek_indexed_entry(
+        &self,
+        key: &StoreKey,
+        entry: &StoredEntry,
+        byte_ranges: &[ByteRange],
+        seek_indexes: &SeekIndexes,
+    ) -> Result<Vec<Bytes>, StorageError> {
+        let span = Self::seek_span(entry, byte_ranges);
+        let data_offset = self
+            .calculate_data_offset(entry.header_offset)
+            .map_err(|e| StorageError::Other(e.to_string()))?;
+
+        let mut inflater = seek_indexes.inflater(key, entry.header_offset, span.start);
+        let out_start = inflater.out_offset();
+        let mut decompressed = Vec::new();
+        let mut read_size = SEEK_INITIAL_READ_SIZE;
+        let mut bytes_read = 0;
+        while !inflater.is_finished(span.end) {
+            let offset = inflater.in_offset();
+            let len = entry
+                .compressed_size
+                .saturating_sub(offset)
+                .min(read_size)
+                .min(self.read_buffer_size);
+            let input = if len == 0 {
+                Bytes::new()
+            } else {
+                self.storage
+                    .get_partial(
+                        &self.key,
+                        ByteRange::FromStart(data_offset + offset, Some(len)),
+                    )?
+                    .ok_or_else(|| StorageError::Other("Cannot read compressed data".to_string()))?
+            };
+            inflater.inflate(
+                &input,
+                offset + len < entry.compressed_size,
+                &span,
+                &mut decompressed,
+            )?;
+            bytes_read += len;
+            report_read_progress(
+                self.read_progress.as_ref(),
+                key,
+                entry,
+                bytes_read,
+                inflater.out_offset() - out_start,
+            );
+            read_size = (read_size * 2).min(SEEK_MAX_READ_SIZE);
+        }
+
+        let values = Self::seek_indexed_values(key, entry, byte_ranges, &span, decompressed)?;
+        seek_indexes.insert(key, entry.header_offset, inflater);
+        Ok(values)
+    }
+
+    /// Decompress an entry using `EntryFsm`, stopping once the end of `byte_ranges` has been decompressed.
+    fn decompress_entry(
+        &self,
+        key: &StoreKey,
+        entry: &StoredEntry,
+        byte_ranges: &[ByteRange],
+    ) -> Result<Vec<u8>, StorageError> {
+        let size = self.index.read().size;
+        // A small entry read in full is read in a single request with its local file header
+        let single_read_end = self.single_read_end(key, entry, byte_ranges);
+        let end_offset = if self.min_read_size > 0 && single_read_end.is_none() {
+            // The end of the local data of the entry, including a data descriptor
+            let data_offset = self
+                .calculate_data_offset(entry.header_offset)
+                .map_err(|err| StorageError::Other(err.to_string()))?;
+            (data_offset + entry.compressed_size + DATA_DESCRIPTOR_MAX_SIZE).min(size)
+        } else {
+            size
+        };
+        let min_end = single_read_end.unwrap_or(0);
+        let progress = self.read_progress.as_ref();
+        Self::decompress_entry_with(
+            key,
+            entry,
+            byte_ranges,
+            end_offset,
+            progress,
+            |offset, len| {
+                let len = self
+                    .incremental_read_size(len)
+                    .max(min_end.saturating_sub(offset))
+                    .min(end_offset - offset);
+                self.storage
+                    .get_partial(&self.key, ByteRange::FromStart(offset, Some(len)))?
+                    .ok_or_else(|| StorageError::Other("Cannot read compressed data".to_string()))
+            },
+        )
+    }
+
+    /// Read the local file headers of all entries, coalescing nearby headers into larger requests, and memoize their data offsets.
+    ///
+    /// See [`ZipStorageAdapterBuilder::prefetch_headers`].
+    ///
+    /// # Errors
+    /// Returns a [`ZipStorageAdapterCreateError`] if the headers cannot be read or are invalid.
+    pub(crate) fn prefetch_headers(&self) -> Result<(), ZipStorageAdapterCreateError> {
+        let runs = self.header_prefetch_runs(self.index.read().archived_header_offsets());
+        self.read_header_runs(&runs)
+    }
+
+    /// Read the local file headers in `runs`, one request per run, and memoize their data offsets.
+    ///
+    /// # Errors
+    /// Returns a [`ZipStorageAdapterCreateError`] if the headers cannot be read or are invalid.
+    pub(crate) fn read_header_runs(
+        &self,
+        runs: &[(Range<u64>, Vec<u64>)],
+    ) -> Result<(), ZipStorageAdapterCreateError> {
+        if runs.is_empty() {
+            return Ok(());
+        }
+        let byte_ranges = runs
+            .iter()
+            .map(|(range, _)| ByteRange::FromStart(range.start, Some(range.end - range.start)));
+        let values = self
+            .storage
+            .get_partial_many(&self.key, Box::new(byte_ranges))?
+            .ok_or_else(|| {
+                ZipStorageAdapterCreateError::ZipError("Cannot read local file headers".to_string())
+            })?;
+        for ((range, header_offsets), bytes) in runs.iter().zip(values) {
+            self.data_offsets_from_headers(range.start, &bytes?, header_offsets)?;
+        }
+        Ok(())
+    }
+
+    /// Read the values of all entries of at most `max_size` bytes, coalescing nearby entries into larger requests, and hold them for later reads.
+    ///
+    /// See [`ZipStorageAdapterBuilder::preload_small_entries`].
+    ///
+    /// # Errors
+    /// Returns a [`ZipStorageAdapterCreateError`] if the entries cannot be read or decompressed.
+    pub(crate) fn preload_small_entries(
+        &mut self,
+        max_size: u64,
+    ) -> Result<(), ZipStorageAdapterCreateError> {
+        let runs = self.preload_runs(max_size);
+        self.preload(&runs, true)
+    }
+
+    /// Read the values of all entries, coalescing nearby entries into larger requests, and hold them for later reads.
+    ///
+    /// See [`ZipStorageAdapterBuilder::preload_all`].
+    ///
+    /// # Errors
+    /// Returns a [`ZipStorageAdapterCreateError`] if the total size of the values exceeds `max_total_size` bytes, or the entries cannot be read or decompressed.
+    pub(crate) fn preload_all(
+        &mut self,
+        max_total_size: u64,
+    ) -> Result<(), ZipStorageAdapterCreateError> {
+        let runs = self.preload_all_runs(max_total_size)?;
+        self.preload(&runs, false)
+    }
+
+    /// Read the entries of `runs`, a request per run, and hold their values for later reads.
+    ///
+    /// # Errors
+    /// Returns a [`ZipStorageAdapterCreateError`] if the entries cannot be read or decompressed.
+    fn preload(
+        &mut self,
+        runs: &[PreloadRun],
+        within_cache: bool,
+    ) -> Result<(), ZipStorageAdapterCreateError> {
+        if runs.is_empty() {
+            return Ok(());
+        }
+        let byte_ranges = runs
+            .iter()
+            .map(|(range, _)| ByteRange::FromStart(range.start, Some(range.end - range.start)));
+        let values = self
+            .storage
+            .get_partial_many(&self.key, Box::new(byte_ranges))?
+            .ok_or_else(|| {
+                ZipStorageAdapterCreateError::ZipError("Cannot read preloaded entries".to_string())
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        for ((range, entries), bytes) in runs.iter().zip(&values) {
+            self.preload_run(range.start, bytes, entries, within_cache)?;
+        }
+        Ok(())
+    }
+
+    /// Calculate the data offset by reading the local file header.
+    ///
+    /// The local file header is 30 bytes fixed + variable name/extra fields.
+    /// The header of each entry is read at most once, subsequent calls return the memoized data offset.
+    pub(crate) fn calculate_data_offset(
+        &self,
+        header_offset: u64,
+    ) -> Result<u64, ZipStorageAdapterCreateError> {
+        if let Some(data_offset) = self.cached_data_offset(header_offset) {
+            return Ok(data_offset);
+        }
+
+        // Read 30-byte local file header
+        let byte_range = ByteRange::FromStart(header_offset, Some(30));
+        let header = self
+            .storage
+            .get_partial(&self.key, byte_range)?
+            .ok_or_else(|| {
+                ZipStorageAdapterCreateError::ZipError("Cannot read local file header".to_string())
+            })?;
+
+        self.data_offset_from_header(header_offset, &header)
+    }
+}
+
+impl<TStorage: ?Sized> ZipStorageAdapter<TStorage> {
+    /// Decompress an entry using `EntryFsm`, stopping once the end of `byte_ranges` has been decompressed.
+    ///
+    /// Zstandard and LZMA compressed entries are decoded with an `EntryDecoder` instead.
+    /// `read(offset, len)` returns bytes of the zip file at `offset`, which may be fewer or more than `len`, but at least one byte unless the zip file ends.
+    /// The zip file is never requested beyond `end_offset`.
+    /// `progress` is called after each step of decompression.
     #[allow(clippy::cast_possible_truncation)]
-    fn decompress_entry(&self, entry: &Entry) -> Result<Vec<u8>, StorageError> {
+    pub(crate) fn decompress_entry_with(
+        key: &StoreKey,
+        entry: &StoredEntry,
+        byte_ranges: &[ByteRange],
+        end_offset: u64,
+        progress: Option<&ZipReadProgressCallback>,
+        mut read: impl FnMut(u64, u64) -> Result<Bytes, StorageError>,
+    ) -> Result<Vec<u8>, StorageError> {
+        // Pre-allocate output buffer, the tail of the entry beyond the byte ranges is not needed
+        let expected_size = Self::decompressed_size_required(entry, byte_ranges) as usize;
+
+        #[cfg(any(feature = "lzma", feature = "zstd"))]
+        if let Some(decoder) = EntryDecoder::new(key, entry, expected_size)? {
+            return Self::decode_entry_with(key, entry, decoder, end_offset, progress, read);
+        }
+
         // Create EntryFsm with the entry
-        let mut fsm = EntryFsm::new(Some(entry.clone()), None);
+        let mut fsm = EntryFsm::new(Some(entry.to_entry(key.as_str())), None);
 
         // Read position starts at header_offset (EntryFsm will parse local header first)
         let mut read_offset = entry.header_offset;
 
-        // Pre-allocate output buffer
-        let expected_size = entry.uncompressed_size as usize;
         let mut decompressed: Vec<u8> = Vec::with_capacity(expected_size);
         let mut write_offset = 0usize;
+        // Data that has been read but not yet fed to the FSM, `read` may return more than requested
+        let mut buffered = Bytes::new();
 
         loop {
+            if write_offset == expected_size && expected_size < entry.uncompressed_size as usize {
+                // Stop decompressing early
+                break;
+            }
+
             // Feed data to FSM if it wants to read
             if fsm.wants_read() {
                 let space = fsm.space();
-                // Don't request more than what's left in the file
-                let remaining = self.size.saturating_sub(read_offset);
-                let to_read = (space.len() as u64).min(remaining);
-
-                if to_read > 0 {
-                    let byte_range = ByteRange::FromStart(read_offset, Some(to_read));
-
-                    let data = self
-                        .storage
-                        .get_partial(&self.key, byte_range)?
-                        .ok_or_else(|| {
-                            StorageError::Other("Cannot read compressed data".to_string())
-                        })?;
-
-                    let copy_len = data.len().min(space.len());
-                    space[..copy_len].copy_from_slice(&data[..copy_len]);
-                    let filled = fsm.fill(copy_len);
-                    read_offset += filled as u64;
-                } else {
-                    // No more data to read, signal EOF
-                    fsm.fill(0);
+                if buffered.is_empty() {
+                    // Don't request more than what's left in the file
+                    let remaining = end_offset.saturating_sub(read_offset);
+                    let to_read = (space.len() as u64).min(remaining);
+                    if to_read > 0 {
+                        buffered = read(read_offset, to_read)?;
+                        read_offset += buffered.len() as u64;
+                    }
                 }
+
+                // Signals EOF if nothing is buffered
+                let copy_len = buffered.len().min(space.len());
+                space[..copy_len].copy_from_slice(&buffered[..copy_len]);
+                let filled = fsm.fill(copy_len);
+                buffered = buffered.slice(filled..);
             }
 
             // Write directly into the spare capacity
             // SAFETY: We pass uninitialized memory to fsm.process, which will write
             // `outcome.bytes_written` bytes, and won't read.
-            let spare = decompressed.spare_capacity_mut();
+            let spare = &mut decompressed.spare_capacity_mut()[write_offset..];
             let out_slice = unsafe {
-                std::slice::from_raw_parts_mut(
-                    spare.as_mut_ptr().cast::<u8>(),
-                    expected_size.saturating_sub(write_offset),
-                )
+                std::slice::from_raw_parts_mut(spare.as_mut_ptr().cast::<u8>(), spare.len())
             };
 
             match fsm.process(out_slice) {
                 Ok(FsmResult::Continue((next_fsm, outcome))) => {
                     write_offset += outcome.bytes_written;
                     fsm = next_fsm;
+                    report_read_progress(
+                        progress,
+                        key,
+                        entry,
+                        read_offset - entry.header_offset,
+                        write_offset as u64,
+                    );
                 }
                 Ok(FsmResult::Done(_buffer)) => {
                     // Decompression complete
                     break;
                 }
                 Err(e) => {
-                    return Err(StorageError::Other(format!("Decompression error: {e}")));
+                    return Err(Self::decompression_error(key, entry, e));
                 }
             }
         }
@@ -304,41 +972,43 @@ impl<TStorage: ?Sized + ReadableStorageTraits> ZipStorageAdapter<TStorage> {
         Ok(decompressed)
     }
 
-    /// Calculate the data offset by reading the local file header.
-    ///
-    /// The local file header is 30 bytes fixed + variable name/extra fields.
-    fn calculate_data_offset(
-        &self,
-        header_offset: u64,
-    ) -> Result<u64, ZipStorageAdapterCreateError> {
-        // Read 30-byte local file header
-        let byte_range = ByteRange::FromStart(header_offset, Some(30));
-        let header = self
-            .storage
-            .get_partial(&self.key, byte_range)?
-            .ok_or_else(|| {
-                ZipStorageAdapterCreateError::ZipError("Cannot read local file header".to_string())
-            })?;
-
-        if header.len() < 30 {
-            return Err(ZipStorageAdapterCreateError::ZipError(
-                "Local file header too short".to_string(),
-            ));
-        }
-
-        // Local file header structure:
-        // Offset 26: filename length (2 bytes, little-endian)
-        // Offset 28: extra field length (2 bytes, little-endian)
-        let filename_len = u64::from(u16::from_le_bytes([header[26], header[27]]));
-        let extra_len = u64::from(u16::from_le_bytes([header[28], header[29]]));
-
-        Ok(header_offset + 30 + filename_len + extra_len)
+    /// Decompress `entry`, the value of `key`, from `data`, the bytes of the zip file starting at its local file header.
+    pub(crate) fn decompress_in_memory(
+        key: &StoreKey,
+        entry: &StoredEntry,
+        data: &Bytes,
+        progress: Option<&ZipReadProgressCallback>,
+    ) -> Result<Bytes, StorageError> {
+        let end_offset = entry.header_offset + data.len() as u64;
+        Self::decompress_entry_with(
+            key,
+            entry,
+            &[ByteRange::FromStart(0, None)],
+            end_offset,
+            progress,
+            |offset, len| {
+                let start = usize::try_from(offset - entry.header_offset)
+                    .unwrap_or(usize::MAX)
+                    .min(data.len());
+                let len = usize::try_from(len).unwrap_or(usize::MAX);
+                Ok(data.slice(start..start.saturating_add(len).min(data.len())))
+            },
+        )
+        .map(Bytes::from)
     }
 }
 
 impl<TStorage: ?Sized + ReadableStorageTraits> ReadableStorageTraits
     for ZipStorageAdapter<TStorage>
 {
+    fn get(&self, key: &StoreKey) -> Result<MaybeBytes, StorageError> {
+        // Clone the entry so that the index is not locked while reading
+        let Some(entry) = self.index.read().get(key).cloned() else {
+            return Ok(None);
+        };
+        self.get_value(key, entry)
+    }
+
     fn get_partial_many<'a>(
         &'a self,
         key: &StoreKey,
@@ -348,7 +1018,7 @@ impl<TStorage: ?Sized + ReadableStorageTraits> ReadableStorageTraits
     }
 
     fn size_key(&self, key: &StoreKey) -> Result<Option<u64>, StorageError> {
-        Ok(self.get_entry(key).map(|e| e.uncompressed_size))
+        Ok(self.index.read().get(key).map(IndexEntry::size))
     }
 
     fn supports_get_partial(&self) -> bool {
@@ -360,83 +1030,274 @@ impl<TStorage: ?Sized + ReadableStorageTraits> ListableStorageTraits
     for ZipStorageAdapter<TStorage>
 {
     fn list(&self) -> Result<StoreKeys, StorageError> {
-        // Filter to only keys, already sorted
-        Ok(self
-            .sorted_entries
-            .iter()
-            .filter_map(|e| match e {
-                ZipEntry::Key(k) => Some(k.clone()),
-                ZipEntry::Prefix(_) => None,
-            })
-            .collect())
+        Ok(self.index.read().list())
     }
 
     fn list_prefix(&self, prefix: &StorePrefix) -> Result<StoreKeys, StorageError> {
-        // Use binary search to find matching range, filter to keys only
-        Ok(self
-            .entries_with_prefix(prefix)
-            .iter()
-            .filter_map(|e| match e {
-                ZipEntry::Key(k) => Some(k.clone()),
-                ZipEntry::Prefix(_) => None,
-            })
-            .collect())
+        Ok(self.index.read().list_prefix(prefix))
     }
 
     fn list_dir(&self, prefix: &StorePrefix) -> Result<StoreKeysPrefixes, StorageError> {
-        let mut keys: StoreKeys = vec![];
-        let mut prefixes: StorePrefixes = vec![];
-
-        // Use binary search to find matching range
-        for entry in self.entries_with_prefix(prefix) {
-            match entry {
-                ZipEntry::Key(key) => {
-                    let parent = key.parent();
-                    if &parent == prefix {
-                        keys.push(key.clone());
-                    } else if let Some(child_prefix) = Self::immediate_child_prefix(key, prefix) {
-                        if prefixes.last() != Some(&child_prefix) {
-                            prefixes.push(child_prefix);
-                        }
-                    }
-                }
-                ZipEntry::Prefix(p) => {
-                    // Check if this prefix is an immediate child of the search prefix
-                    let p_str = p.as_str();
-                    let prefix_str = prefix.as_str();
-                    if let Some(suffix) = p_str.strip_prefix(prefix_str) {
-                        // Skip if suffix is empty (the prefix itself)
-                        if suffix.is_empty() {
-                            continue;
-                        }
-                        // Check if it's an immediate child (no additional '/' before the trailing one)
-                        let trimmed = suffix.trim_end_matches('/');
-                        if !trimmed.contains('/') && prefixes.last() != Some(p) {
-                            prefixes.push(p.clone());
-                        }
-                    }
-                }
-            }
-        }
-
-        // Keys and prefixes are already sorted since sorted_entries is sorted
-        Ok(StoreKeysPrefixes::new(keys, prefixes))
+        Ok(self.index.read().list_dir(prefix))
     }
 
     fn size(&self) -> Result<u64, StorageError> {
-        Ok(self.size)
+        Ok(self.index.read().size)
     }
 
     fn size_prefix(&self, prefix: &StorePrefix) -> Result<u64, StorageError> {
-        // Use binary search to find matching range, then lookup in HashMap for keys only
-        Ok(self
-            .entries_with_prefix(prefix)
-            .iter()
-            .filter_map(|e| match e {
-                ZipEntry::Key(k) => self.entries.get(k),
-                ZipEntry::Prefix(_) => None,
-            })
-            .map(|e| e.compressed_size)
-            .sum())
+        Ok(self.index.read().size_prefix(prefix))
+    }
+}
+
+#[cfg(feature = "write")]
+impl<TStorage: ?Sized + ReadableStorageTraits + WritableStorageTraits> ZipStorageAdapter<TStorage> {
+    /// Create a new zip storage adapter for an empty zip file.
+    ///
+    /// An empty zip file is written to `key` in `storage`.
+    /// Pass [`StoreKey::root()`] to treat the entire `storage` as the zip file.
+    ///
+    /// # Errors
+    /// Returns a [`ZipStorageAdapterCreateError::ExistingKey`] if `key` already exists in `storage` and `overwrite` is false.
+    /// Returns a [`ZipStorageAdapterCreateError`] if the empty zip file cannot be written.
+    pub fn create(
+        storage: Arc<TStorage>,
+        key: StoreKey,
+        overwrite: bool,
+    ) -> Result<Self, ZipStorageAdapterCreateError> {
+        if !overwrite && storage.size_key(&key)?.is_some() {
+            return Err(ZipStorageAdapterCreateError::ExistingKey(key));
+        }
+        let archive = ArchiveWriter::new().finish();
+        storage.set(&key, archive.into())?;
+        Self::new(storage, key)
+    }
+
+    /// Create a new zip storage adapter that appends to an existing zip file.
+    ///
+    /// See [Appending](ZipStorageAdapter#appending).
+    ///
+    /// # Errors
+    /// Returns a [`ZipStorageAdapterCreateError`] if the store value at `key` is not a valid zip file.
+    pub fn open_append(
+        storage: Arc<TStorage>,
+        key: StoreKey,
+    ) -> Result<Self, ZipStorageAdapterCreateError> {
+        let mut adapter = Self::new(storage, key)?;
+        adapter.append = true;
+        Ok(adapter)
+    }
+
+    /// Write pending changes to the zip file.
+    ///
+    /// This rewrites the entire zip file, see [Writing](ZipStorageAdapter#writing), unless the adapter was opened with [`open_append`](ZipStorageAdapter::open_append).
+    /// The CRC-32s of stored entries that have been partially written in place are also updated, see [Partial writes](ZipStorageAdapter#partial-writes).
+    /// This is a no-op if there are no pending changes.
+    ///
+    /// # Errors
+    /// Returns a [`StorageError`] if the zip file cannot be read, rewritten, or written to the underlying storage.
+    pub fn finish(&self) -> Result<(), StorageError> {
+        // Writes are blocked until the index is rebuilt
+        let mut index = self.index.write();
+        if !index.has_changes() {
+            return Ok(());
+        }
+
+        let changes = index.pending_changes(self.write_options.explicit_directories);
+        self.update_crc32s(index.size, &changes.stale_crc32s)?;
+        let size = if !index.modified {
+            index.size
+        } else if self.append && self.storage.supports_set_partial() {
+            self.append_changes(index.size, &changes)?
+        } else {
+            self.rewrite_changes(&changes)?
+        };
+
+        // Rebuild the index from the new central directory
+        let (archive, comment) =
+            Self::parse_archive(&self.storage, &self.key, size, self.eocd_prefetch_bytes)
+                .map_err(|err| StorageError::Other(err.to_string()))?;
+        *index = ZipIndex::new(&archive, comment, size, &self.zip_path, self.index_options)
+            .map_err(|err| StorageError::Other(err.to_string()))?;
+        self.retain_data_offsets(&index);
+        Ok(())
+    }
+
+    /// Find the offset of the central directory of the zip file of `size` bytes.
+    ///
+    /// Also returns the trailing bytes of the zip file that were read to find it, and their offset.
+    fn find_central_directory(&self, size: u64) -> Result<(u64, Bytes, u64), StorageError> {
+        let window_size = size.min(END_OF_CENTRAL_DIRECTORY_SEARCH_SIZE as u64);
+        let window_offset = size - window_size;
+        let window = self
+            .storage
+            .get_partial(&self.key, ByteRange::Suffix(window_size))?
+            .ok_or_else(|| self.zip_not_found())?;
+        let directory_offset = central_directory_offset(&window, window_offset)?;
+        Ok((directory_offset, window, window_offset))
+    }
+
+    /// The number of bytes in the zip file that are not referenced by its central directory.
+    ///
+    /// These are the local data of erased and overwritten entries, which is not reclaimed when the zip file is written, see [Writing](ZipStorageAdapter#writing).
+    /// Entries that have been erased or overwritten but not yet finished are counted, as are entries shadowed by a later entry with the same name.
+    /// The local file header of each entry is read the first time this is called, and data descriptors are assumed to have a signature and 32-bit sizes.
+    ///
+    /// # Errors
+    /// Returns a [`StorageError`] if the zip file cannot be read.
+    pub fn dead_bytes(&self) -> Result<u64, StorageError> {
+        let (size, live) = {
+            let index = self.index.read();
+            (index.size, index.live_records())
+        };
+        let (directory_offset, _, _) = self.find_central_directory(size)?;
+        dead_bytes(size, directory_offset, &live, |header_offset| {
+            self.calculate_data_offset(header_offset)
+                .map_err(|err| StorageError::Other(err.to_string()))
+        })
+    }
+
+    /// Repack the zip file to `dst_key` of `dst_storage`, without the unreferenced bytes of erased and overwritten entries.
+    ///
+    /// The local data of each entry is copied verbatim in the order of the central directory, without recompression, so compression methods and CRC-32s are preserved.
+    /// The zip file is read in full, and the repacked zip file is staged under a temporary key before it is written to `dst_key`, see [`ZipWriteOptions::staged`](crate::ZipWriteOptions::staged).
+    /// The zip file of the adapter is not modified, unless it is the destination.
+    /// In that case, the adapter must be reopened after repacking.
+    ///
+    /// See [`dead_bytes`](ZipStorageAdapter::dead_bytes) for the number of bytes that would be reclaimed.
+    ///
+    /// # Errors
+    /// Returns a [`StorageError`] if there are pending changes, or the zip file cannot be read or the repacked zip file cannot be written.
+    pub fn repack<TDst: ?Sized + WritableStorageTraits>(
+        &self,
+        dst_storage: &TDst,
+        dst_key: &StoreKey,
+    ) -> Result<RepackSummary, StorageError> {
+        if self.index.read().has_changes() {
+            return Err(pending_changes_error());
+        }
+        let archive = self
+            .storage
+            .get(&self.key)?
+            .ok_or_else(|| self.zip_not_found())?;
+        let (repacked, summary) = repack_archive(&archive)?;
+        set_staged(dst_storage, dst_key, repacked.into(), true)?;
+        Ok(summary)
+    }
+
+    /// Rewrite the entire zip file with `changes`, returning its new size.
+    fn rewrite_changes(&self, changes: &PendingChanges) -> Result<u64, StorageError> {
+        let archive = self
+            .storage
+            .get(&self.key)?
+            .ok_or_else(|| self.zip_not_found())?;
+        let archive = rewrite_archive(&archive, &self.zip_path, changes)?;
+        let size = archive.len() as u64;
+        set_staged(
+            &*self.storage,
+            &self.key,
+            archive.into(),
+            self.write_options.staged,
+        )?;
+        Ok(size)
+    }
+
+    /// Read the trailing bytes of the zip file of `size` bytes from its central directory, returning their offset.
+    fn central_directory_tail(&self, size: u64) -> Result<(u64, Bytes), StorageError> {
+        let (directory_offset, window, window_offset) = self.find_central_directory(size)?;
+        let tail = if let Some(start) = directory_offset.checked_sub(window_offset) {
+            window.slice(
+                usize::try_from(start)
+                    .unwrap_or(usize::MAX)
+                    .min(window.len())..,
+            )
+        } else {
+            self.storage
+                .get_partial(&self.key, ByteRange::FromStart(directory_offset, None))?
+                .ok_or_else(|| self.zip_not_found())?
+        };
+        Ok((directory_offset, tail))
+    }
+
+    /// Update the CRC-32s of the `stale` stored entries of the zip file of `size` bytes, which have been partially written in place.
+    fn update_crc32s(&self, size: u64, stale: &[LocalRecord]) -> Result<(), StorageError> {
+        if stale.is_empty() {
+            return Ok(());
+        }
+        let (directory_offset, tail) = self.central_directory_tail(size)?;
+        let mut entries = Vec::with_capacity(stale.len());
+        for record in stale {
+            let data_offset = self
+                .calculate_data_offset(record.header_offset)
+                .map_err(|err| StorageError::Other(err.to_string()))?;
+            let byte_range = ByteRange::FromStart(data_offset, Some(crc32_data_len(record)));
+            let data = self
+                .storage
+                .get_partial(&self.key, byte_range)?
+                .ok_or_else(|| self.zip_not_found())?;
+            entries.push((*record, data_offset, data));
+        }
+        for (offset, crc32) in crc32_updates(&tail, directory_offset, &entries)? {
+            self.storage.set_partial(&self.key, offset, crc32)?;
+        }
+        Ok(())
+    }
+
+    /// Append `changes` to the zip file of `size` bytes in place, returning its new size.
+    fn append_changes(&self, size: u64, changes: &PendingChanges) -> Result<u64, StorageError> {
+        let (directory_offset, tail) = self.central_directory_tail(size)?;
+        let (offset, bytes) =
+            append_archive(&tail, directory_offset, size, &self.zip_path, changes)?;
+        let appended = bytes.len() as u64;
+        self.storage.set_partial(&self.key, offset, bytes.into())?;
+        Ok(offset + appended)
+    }
+}
+
+#[cfg(feature = "write")]
+impl<TStorage: ?Sized + ReadableStorageTraits + WritableStorageTraits> WritableStorageTraits
+    for ZipStorageAdapter<TStorage>
+{
+    fn set(&self, key: &StoreKey, value: Bytes) -> Result<(), StorageError> {
+        self.set_impl(key, value, &self.write_options)
+    }
+
+    fn set_partial_many<'a>(
+        &'a self,
+        key: &StoreKey,
+        offset_values: OffsetBytesIterator<'a>,
+    ) -> Result<(), StorageError> {
+        let offset_values: Vec<(u64, Bytes)> = offset_values.collect();
+        let Some(header_offset) =
+            self.in_place_header_offset(key, &offset_values, self.storage.supports_set_partial())?
+        else {
+            // Read-modify-write, the new value is pending until the zip file is finished
+            let value = apply_partial_writes(self.get(key)?, offset_values)?;
+            return self.set_impl(key, value, &self.write_options);
+        };
+
+        let data_offset = self
+            .calculate_data_offset(header_offset)
+            .map_err(|err| StorageError::Other(err.to_string()))?;
+        for (offset, value) in offset_values {
+            self.storage
+                .set_partial(&self.key, data_offset + offset, value)?;
+        }
+        self.written_in_place(key, header_offset);
+        Ok(())
+    }
+
+    fn erase(&self, key: &StoreKey) -> Result<(), StorageError> {
+        self.erase_impl(key);
+        Ok(())
+    }
+
+    fn erase_prefix(&self, prefix: &StorePrefix) -> Result<(), StorageError> {
+        self.erase_prefix_impl(prefix);
+        Ok(())
+    }
+
+    fn supports_set_partial(&self) -> bool {
+        self.storage.supports_set_partial()
     }
 }
@@ -0,0 +1,133 @@
+//! Validation of the central directory of a zip file against the layout of its entries.
+
+use rc_zip::parse::Method;
+use thiserror::Error;
+use zarrs_storage::StoreKey;
+
+use crate::{LOCAL_FILE_HEADER_FIXED_SIZE, ZipStorageAdapter};
+
+/// An inconsistency of an entry in the central directory of a zip file, found by [`ZipStorageAdapter::validate`].
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ZipValidationIssue {
+    /// The local file header of an entry is beyond the end of the zip file.
+    #[error(
+        "zip entry {key}: the local file header at offset {header_offset} is beyond the end of the zip file of {size} bytes"
+    )]
+    HeaderOutOfBounds {
+        /// The key of the entry.
+        key: StoreKey,
+        /// The offset of the local file header in the central directory.
+        header_offset: u64,
+        /// The size of the zip file.
+        size: u64,
+    },
+    /// The compressed data of an entry extends beyond the end of the zip file.
+    #[error(
+        "zip entry {key}: the compressed data ends at offset {end}, beyond the end of the zip file of {size} bytes"
+    )]
+    DataOutOfBounds {
+        /// The key of the entry.
+        key: StoreKey,
+        /// The least offset of the end of the compressed data.
+        end: u64,
+        /// The size of the zip file.
+        size: u64,
+    },
+    /// The local data of an entry overlaps the local data of another entry.
+    #[error("zip entry {key}: the local data overlaps the local data of {other}")]
+    Overlap {
+        /// The key of the entry.
+        key: StoreKey,
+        /// The key of the entry with the local data it overlaps, which starts before it.
+        other: StoreKey,
+    },
+    /// The compressed size of a stored entry that is not encrypted differs from its uncompressed size.
+    #[error(
+        "zip entry {key}: the entry is stored, but its compressed size {compressed_size} differs from its uncompressed size {uncompressed_size}"
+    )]
+    StoredSizeMismatch {
+        /// The key of the entry.
+        key: StoreKey,
+        /// The compressed size in the central directory.
+        compressed_size: u64,
+        /// The uncompressed size in the central directory.
+        uncompressed_size: u64,
+    },
+}
+
+impl<TStorage: ?Sized> ZipStorageAdapter<TStorage> {
+    /// Cross-check the entries of the central directory of the zip file against its size and each other, without reading the zip file.
+    ///
+    /// This is a pre-flight check of a suspect zip file, which distinguishes a key that does not exist from an entry that is inconsistent.
+    /// An entry is inconsistent if its local file header or compressed data is beyond the end of the zip file,
+    /// its local data overlaps that of another entry, or it is stored with a compressed size that differs from its uncompressed size.
+    /// The local data of an entry is taken to span from its local file header to the end of its compressed data,
+    /// with a local file header of the fixed size unless it has been read, so an issue is never reported for a consistent zip file.
+    /// Only the entries of keys within the path of the adapter are checked, and pending values are not checked.
+    ///
+    /// # Errors
+    /// Returns the [`ZipValidationIssue`]s of the inconsistent entries, in the order of their local file headers.
+    pub fn validate(&self) -> Result<(), Vec<ZipValidationIssue>> {
+        let (mut entries, size) = {
+            let index = self.index.read();
+            (index.archived_entries(), index.size)
+        };
+        entries.sort_unstable_by_key(|(_, entry)| entry.header_offset);
+
+        let mut issues = Vec::new();
+        // The key and end of the local data that ends last of the entries so far
+        let mut last: Option<(&StoreKey, u64)> = None;
+        for (key, entry) in &entries {
+            let data_offset = self.cached_data_offset(entry.header_offset).unwrap_or(
+                entry
+                    .header_offset
+                    .saturating_add(LOCAL_FILE_HEADER_FIXED_SIZE),
+            );
+            let end = data_offset.saturating_add(entry.compressed_size);
+            if entry
+                .header_offset
+                .saturating_add(LOCAL_FILE_HEADER_FIXED_SIZE)
+                > size
+            {
+                issues.push(ZipValidationIssue::HeaderOutOfBounds {
+                    key: (**key).clone(),
+                    header_offset: entry.header_offset,
+                    size,
+                });
+            } else if end > size {
+                issues.push(ZipValidationIssue::DataOutOfBounds {
+                    key: (**key).clone(),
+                    end,
+                    size,
+                });
+            }
+            if let Some((other, other_end)) = last {
+                if entry.header_offset < other_end {
+                    issues.push(ZipValidationIssue::Overlap {
+                        key: (**key).clone(),
+                        other: other.clone(),
+                    });
+                }
+            }
+            if entry.method == Method::Store
+                && !entry.is_encrypted()
+                && entry.compressed_size != entry.uncompressed_size
+            {
+                issues.push(ZipValidationIssue::StoredSizeMismatch {
+                    key: (**key).clone(),
+                    compressed_size: entry.compressed_size,
+                    uncompressed_size: entry.uncompressed_size,
+                });
+            }
+            if last.is_none_or(|(_, other_end)| end > other_end) {
+                last = Some((key, end));
+            }
+        }
+
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(issues)
+        }
+    }
+}
@@ -0,0 +1,905 @@
+//! Rewriting of zip archives with pending entries.
+
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    ops::Range,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rc_zip::parse::Method;
+
+use zarrs_storage::{
+    Bytes, ListableStorageTraits, MaybeBytes, ReadableStorageTraits, StorageError, StoreKey,
+    StorePrefix, WritableStorageTraits,
+};
+
+use crate::{
+    END_OF_CENTRAL_DIRECTORY_SEARCH_SIZE, ZipCompression, ZipOverwritePolicy, ZipStorageAdapter,
+    ZipWriteOptions,
+    index::{IndexEntry, LocalRecord, PendingChanges, ZipIndex, parent_directories},
+};
+
+#[cfg(feature = "zstd")]
+use crate::format::METHOD_ZSTD;
+use crate::format::{
+    CentralDirectoryRecord, EndOfCentralDirectory, EntryRecord, LOCAL_FILE_HEADER_CRC32_OFFSET,
+    METHOD_DEFLATE, METHOD_STORE, data_descriptor_crc32_offset,
+    end_of_central_directory_requires_zip64, local_data_size, parse_central_directory,
+    write_end_of_central_directory, write_zip64_end_of_central_directory,
+};
+
+pub(crate) fn zip_error(message: impl std::fmt::Display) -> StorageError {
+    StorageError::Other(format!("zip write error: {message}"))
+}
+
+/// The offset of `offset` relative to `base_offset`, if it is not before it.
+fn relative_offset(offset: u64, base_offset: u64) -> Option<usize> {
+    usize::try_from(offset.checked_sub(base_offset)?).ok()
+}
+
+/// Find the end of central directory record in `bytes`, the trailing bytes of an archive from `bytes_offset`.
+///
+/// # Errors
+/// Returns a [`StorageError`] if the end of central directory record is not found, or the archive has a ZIP64 end of central directory record that is not in `bytes`.
+fn find_end_of_central_directory(
+    bytes: &[u8],
+    bytes_offset: u64,
+) -> Result<EndOfCentralDirectory, StorageError> {
+    let search_offset = bytes
+        .len()
+        .saturating_sub(END_OF_CENTRAL_DIRECTORY_SEARCH_SIZE);
+    let mut eocd =
+        EndOfCentralDirectory::find(&bytes[search_offset..], bytes_offset + search_offset as u64)
+            .ok_or_else(|| zip_error("end of central directory record not found"))?;
+    if let Some(offset) = eocd.zip64_record_offset {
+        let record = relative_offset(offset, bytes_offset)
+            .and_then(|offset| bytes.get(offset..))
+            .unwrap_or_default();
+        if !eocd.apply_zip64(record) {
+            return Err(zip_error("invalid ZIP64 end of central directory record"));
+        }
+    }
+    Ok(eocd)
+}
+
+/// Find the offset of the central directory of an archive, given `bytes`, its trailing bytes from `bytes_offset`.
+///
+/// `bytes` must include the end of central directory record, and the ZIP64 end of central directory record if the archive has one.
+///
+/// # Errors
+/// Returns a [`StorageError`] if the end of central directory records are not found.
+pub(crate) fn central_directory_offset(
+    bytes: &[u8],
+    bytes_offset: u64,
+) -> Result<u64, StorageError> {
+    Ok(find_end_of_central_directory(bytes, bytes_offset)?.directory_offset)
+}
+
+/// Writes a zip archive that retains the entries of an existing archive.
+///
+/// The local data of retained entries is copied verbatim, new entries are appended after it, and then a new central directory is written.
+pub(crate) struct ArchiveWriter {
+    /// The offset in the archive of the start of `out`.
+    base_offset: u64,
+    /// The archive being written, from `base_offset`.
+    out: Vec<u8>,
+    /// The central directory records.
+    directory: Vec<u8>,
+    /// The number of central directory records.
+    num_entries: u64,
+    /// The archive comment.
+    comment: Vec<u8>,
+    /// Whether a ZIP64 end of central directory record is written regardless of the size of the archive.
+    force_zip64: bool,
+}
+
+impl ArchiveWriter {
+    /// Start writing an empty archive.
+    pub(crate) fn new() -> Self {
+        Self {
+            base_offset: 0,
+            out: Vec::new(),
+            directory: Vec::new(),
+            num_entries: 0,
+            comment: Vec::new(),
+            force_zip64: false,
+        }
+    }
+
+    /// Start rewriting `archive`, retaining the entries with local headers that are not at a `dropped` offset.
+    ///
+    /// `additional` is the number of bytes to reserve for new entries.
+    ///
+    /// # Errors
+    /// Returns a [`StorageError`] if `archive` is not a valid zip archive.
+    pub(crate) fn from_archive(
+        archive: &[u8],
+        dropped: &HashSet<u64>,
+        additional: usize,
+    ) -> Result<Self, StorageError> {
+        let (eocd, directory_range) = Self::find_directory(archive, 0)?;
+
+        let mut out = Vec::with_capacity(directory_range.end + additional);
+        out.extend_from_slice(&archive[..directory_range.start]);
+        Self::retain_records(0, out, &archive[directory_range], eocd.comment, dropped)
+    }
+
+    /// Start appending to an archive, given `tail`, its trailing bytes from `tail_offset`, which must include its central directory.
+    ///
+    /// The local data of existing entries is not copied, the returned bytes are written from `base_offset` in the archive.
+    ///
+    /// # Errors
+    /// Returns a [`StorageError`] if `tail` does not contain a valid central directory.
+    pub(crate) fn append(
+        tail: &[u8],
+        tail_offset: u64,
+        base_offset: u64,
+        dropped: &HashSet<u64>,
+    ) -> Result<Self, StorageError> {
+        let (eocd, directory_range) = Self::find_directory(tail, tail_offset)?;
+        Self::retain_records(
+            base_offset,
+            Vec::new(),
+            &tail[directory_range],
+            eocd.comment,
+            dropped,
+        )
+    }
+
+    /// Find the end of central directory record and the range of the central directory in `bytes`, the trailing bytes of an archive from `bytes_offset`.
+    ///
+    /// # Errors
+    /// Returns a [`StorageError`] if the end of central directory record is not found or the central directory is not in `bytes`.
+    fn find_directory(
+        bytes: &[u8],
+        bytes_offset: u64,
+    ) -> Result<(EndOfCentralDirectory, Range<usize>), StorageError> {
+        let eocd = find_end_of_central_directory(bytes, bytes_offset)?;
+        let directory_range = relative_offset(eocd.directory_offset, bytes_offset)
+            .zip(usize::try_from(eocd.directory_size).ok())
+            .and_then(|(offset, size)| Some(offset..offset.checked_add(size)?))
+            .filter(|range| range.end <= bytes.len())
+            .ok_or_else(|| zip_error("central directory is out of bounds"))?;
+        Ok((eocd, directory_range))
+    }
+
+    /// Start writing after `out`, retaining the records in `directory` of entries with local headers that are not at a `dropped` offset.
+    fn retain_records(
+        base_offset: u64,
+        out: Vec<u8>,
+        directory: &[u8],
+        comment: Vec<u8>,
+        dropped: &HashSet<u64>,
+    ) -> Result<Self, StorageError> {
+        let records = parse_central_directory(directory).map_err(zip_error)?;
+        let mut retained = Vec::with_capacity(directory.len());
+        let mut num_entries = 0;
+        for record in records
+            .iter()
+            .filter(|record| !dropped.contains(&record.header_offset))
+        {
+            retained.extend_from_slice(record.raw);
+            num_entries += 1;
+        }
+
+        Ok(Self {
+            base_offset,
+            out,
+            directory: retained,
+            num_entries,
+            comment,
+            force_zip64: false,
+        })
+    }
+
+    /// Replace the archive comment with `comment`, if it is set.
+    fn set_comment(&mut self, comment: Option<&Vec<u8>>) {
+        if let Some(comment) = comment {
+            self.comment.clone_from(comment);
+        }
+    }
+
+    /// Append an entry, compressed according to `options`.
+    ///
+    /// ZIP64 extensions are used if the entry requires them or `options` forces them.
+    ///
+    /// # Errors
+    /// Returns a [`StorageError`] if the entry cannot be compressed, its name is too long, or its alignment is too large.
+    pub(crate) fn add_entry(
+        &mut self,
+        name: &str,
+        value: &[u8],
+        options: &ZipWriteOptions,
+    ) -> Result<(), StorageError> {
+        let (method, data) = match options.compression {
+            ZipCompression::Stored => (METHOD_STORE, Cow::Borrowed(value)),
+            ZipCompression::Deflate(level) => (
+                METHOD_DEFLATE,
+                Cow::Owned(miniz_oxide::deflate::compress_to_vec(value, level)),
+            ),
+            #[cfg(feature = "zstd")]
+            ZipCompression::Zstd(level) => (
+                METHOD_ZSTD,
+                Cow::Owned(zstd::bulk::compress(value, level).map_err(zip_error)?),
+            ),
+        };
+        if name.len() > usize::from(u16::MAX) {
+            return Err(zip_error(format!("the name of {name} is too long")));
+        }
+        // Only the data of stored entries is aligned, compressed data cannot be read in place
+        let alignment = if method == METHOD_STORE {
+            options.alignment
+        } else {
+            0
+        };
+        let mut record = EntryRecord {
+            name,
+            method,
+            crc32: crc32fast::hash(value),
+            compressed_size: data.len() as u64,
+            uncompressed_size: value.len() as u64,
+            header_offset: self.base_offset + self.out.len() as u64,
+            zip64: options.force_zip64,
+            alignment,
+            data_descriptor: false,
+        };
+        record.zip64 |= record.requires_zip64();
+        if record.local_extra_field_size() > usize::from(u16::MAX) {
+            return Err(zip_error(format!(
+                "{name} cannot be aligned to {alignment} bytes"
+            )));
+        }
+        self.force_zip64 |= options.force_zip64;
+        record.write_local_header(&mut self.out);
+        self.out.extend_from_slice(&data);
+        record.write_central_directory_record(&mut self.directory);
+        self.num_entries += 1;
+        Ok(())
+    }
+
+    /// Append a directory entry named `name`, which must end with `/`.
+    ///
+    /// # Errors
+    /// Returns a [`StorageError`] if the name is too long.
+    pub(crate) fn add_directory(&mut self, name: &str) -> Result<(), StorageError> {
+        self.add_entry(name, &[], &ZipWriteOptions::default())
+    }
+
+    /// Append the directory entries `directories` within `zip_path`, followed by the pending values of `changes`.
+    ///
+    /// # Errors
+    /// Returns a [`StorageError`] if an entry cannot be written.
+    fn add_changes(
+        &mut self,
+        zip_path: &str,
+        changes: &PendingChanges,
+    ) -> Result<(), StorageError> {
+        self.set_comment(changes.comment.as_ref());
+        for prefix in &changes.directories {
+            self.add_directory(&format!("{zip_path}{prefix}"))?;
+        }
+        for (key, value, options) in &changes.values {
+            self.add_entry(&format!("{zip_path}{key}"), value, options)?;
+        }
+        Ok(())
+    }
+
+    /// Append the entry of `record` in `archive` verbatim, without recompression.
+    ///
+    /// # Errors
+    /// Returns a [`StorageError`] if the local data of the entry is invalid or out of bounds, or the entry requires ZIP64 extensions that it does not have.
+    fn copy_entry(
+        &mut self,
+        archive: &[u8],
+        record: &CentralDirectoryRecord<'_>,
+    ) -> Result<(), StorageError> {
+        let local = usize::try_from(record.header_offset)
+            .ok()
+            .and_then(|offset| archive.get(offset..))
+            .ok_or_else(|| zip_error("local file header is out of bounds"))?;
+        let size = local_data_size(local, record).map_err(zip_error)?;
+        record
+            .write_with_header_offset(
+                &mut self.directory,
+                self.base_offset + self.out.len() as u64,
+            )
+            .map_err(zip_error)?;
+        self.out.extend_from_slice(&local[..size]);
+        self.num_entries += 1;
+        Ok(())
+    }
+
+    /// Write the central directory and return the archive.
+    ///
+    /// A ZIP64 end of central directory record is written if the archive requires it or an entry was added with ZIP64 extensions forced.
+    pub(crate) fn finish(mut self) -> Vec<u8> {
+        write_central_directory(
+            &mut self.out,
+            self.base_offset,
+            &self.directory,
+            self.num_entries,
+            &self.comment,
+            self.force_zip64,
+        );
+        self.out
+    }
+}
+
+/// Append the central directory with `num_entries` records, `directory`, and the end of central directory records to `out`, which starts at `out_offset` in the archive.
+///
+/// A ZIP64 end of central directory record is written if the archive requires it or `force_zip64` is true.
+pub(crate) fn write_central_directory(
+    out: &mut Vec<u8>,
+    out_offset: u64,
+    directory: &[u8],
+    num_entries: u64,
+    comment: &[u8],
+    force_zip64: bool,
+) {
+    let directory_offset = out_offset + out.len() as u64;
+    let directory_size = directory.len() as u64;
+    let zip64 = force_zip64
+        || end_of_central_directory_requires_zip64(num_entries, directory_size, directory_offset);
+    out.extend_from_slice(directory);
+    if zip64 {
+        write_zip64_end_of_central_directory(
+            out,
+            out_offset,
+            num_entries,
+            directory_size,
+            directory_offset,
+        );
+    }
+    write_end_of_central_directory(
+        out,
+        num_entries,
+        directory_size,
+        directory_offset,
+        comment,
+        zip64,
+    );
+}
+
+/// A summary of a zip file written by [`store_to_zip`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StoreToZipSummary {
+    /// The number of entries written.
+    pub num_entries: u64,
+    /// The total size in bytes of the values of the entries.
+    pub bytes_in: u64,
+    /// The size in bytes of the zip file.
+    pub bytes_out: u64,
+}
+
+/// A summary of a zip file repacked by [`ZipStorageAdapter::repack`] or merged by [`merge_zips`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RepackSummary {
+    /// The number of entries copied.
+    pub num_entries: u64,
+    /// The size in bytes of the source zip files.
+    pub bytes_in: u64,
+    /// The size in bytes of the repacked or merged zip file.
+    pub bytes_out: u64,
+    /// The number of bytes reclaimed, `bytes_in - bytes_out`.
+    pub bytes_reclaimed: u64,
+}
+
+/// Write all keys of `src` to a new zip file at `dst_key` in `dst_storage`.
+///
+/// Entries are written in key order with `options`, and any existing value at `dst_key` is replaced.
+/// The compression policy of `options` is applied to each key.
+/// The zip file is assembled in memory before it is written to `dst_storage`, so it must fit in memory.
+///
+/// # Errors
+/// Returns a [`StorageError`] if `src` cannot be read, an entry cannot be compressed, or the zip file cannot be written to `dst_storage`.
+pub fn store_to_zip<TSrc, TDst>(
+    src: &TSrc,
+    dst_storage: &TDst,
+    dst_key: &StoreKey,
+    options: &ZipWriteOptions,
+) -> Result<StoreToZipSummary, StorageError>
+where
+    TSrc: ?Sized + ReadableStorageTraits + ListableStorageTraits,
+    TDst: ?Sized + WritableStorageTraits,
+{
+    let mut keys = src.list()?;
+    keys.sort();
+
+    let mut writer = ArchiveWriter::new();
+    let mut bytes_in = 0;
+    let mut directories = HashSet::new();
+    for key in &keys {
+        if options.explicit_directories {
+            // Keys are sorted, so directory entries are written in sorted order before their keys
+            for directory in parent_directories(key) {
+                if directories.insert(directory) {
+                    writer.add_directory(directory)?;
+                }
+            }
+        }
+        // A key may be erased after it is listed
+        if let Some(value) = src.get(key)? {
+            writer.add_entry(key.as_str(), &value, &options.resolve(key, value.len()))?;
+            bytes_in += value.len() as u64;
+        }
+    }
+    let num_entries = writer.num_entries;
+    let archive = writer.finish();
+    let bytes_out = archive.len() as u64;
+    set_staged(dst_storage, dst_key, archive.into(), options.staged)?;
+
+    Ok(StoreToZipSummary {
+        num_entries,
+        bytes_in,
+        bytes_out,
+    })
+}
+
+/// Repack `archive`, copying the local data of the entries in its central directory without unreferenced bytes.
+///
+/// Entries are copied verbatim in the order of the central directory, so their compression methods and CRC-32s are preserved.
+///
+/// # Errors
+/// Returns a [`StorageError`] if `archive` is not a valid zip archive.
+pub(crate) fn repack_archive(archive: &[u8]) -> Result<(Vec<u8>, RepackSummary), StorageError> {
+    let (eocd, directory_range) = ArchiveWriter::find_directory(archive, 0)?;
+    let records = parse_central_directory(&archive[directory_range]).map_err(zip_error)?;
+    let mut writer = ArchiveWriter {
+        comment: eocd.comment,
+        ..ArchiveWriter::new()
+    };
+    for record in &records {
+        writer.copy_entry(archive, record)?;
+    }
+    let num_entries = writer.num_entries;
+    let repacked = writer.finish();
+    let bytes_in = archive.len() as u64;
+    let bytes_out = repacked.len() as u64;
+    Ok((
+        repacked,
+        RepackSummary {
+            num_entries,
+            bytes_in,
+            bytes_out,
+            bytes_reclaimed: bytes_in.saturating_sub(bytes_out),
+        },
+    ))
+}
+
+/// Merge the keys of the zip files of `sources` into a new zip file at `dst_key` in `dst_storage`.
+///
+/// The local data and central directory record of the entry of each key are copied verbatim, without recompression, so compression methods and CRC-32s are preserved.
+/// Entries keep their names in their source zip file, including the path of the source adapter, and are copied in the order of the sources and their central directories.
+/// Directory entries are not copied.
+///
+/// The source zip files are read in full, and the merged zip file is staged under a temporary key before it is written to `dst_key`, see [`ZipWriteOptions::staged`].
+///
+/// # Errors
+/// Returns a [`StorageError`] if a source has pending changes, an entry name is in more than one source, or a source zip file cannot be read or the merged zip file cannot be written.
+pub fn merge_zips<TSrc, TDst>(
+    sources: &[&ZipStorageAdapter<TSrc>],
+    dst_storage: &TDst,
+    dst_key: &StoreKey,
+) -> Result<RepackSummary, StorageError>
+where
+    TSrc: ?Sized + ReadableStorageTraits,
+    TDst: ?Sized + WritableStorageTraits,
+{
+    let mut writer = ArchiveWriter::new();
+    let mut names = HashSet::new();
+    let mut bytes_in = 0;
+    for source in sources {
+        let header_offsets = {
+            let index = source.index.read();
+            if index.has_changes() {
+                return Err(pending_changes_error());
+            }
+            index.archived_header_offsets()
+        };
+        let archive = source
+            .storage
+            .get(&source.key)?
+            .ok_or_else(|| source.zip_not_found())?;
+        bytes_in += archive.len() as u64;
+        let (_, directory_range) = ArchiveWriter::find_directory(&archive, 0)?;
+        let records = parse_central_directory(&archive[directory_range]).map_err(zip_error)?;
+        for record in records
+            .iter()
+            .filter(|record| header_offsets.contains(&record.header_offset))
+        {
+            let name = record.name();
+            if !names.insert(name.to_vec()) {
+                return Err(zip_error(format!(
+                    "{} is in more than one zip file",
+                    String::from_utf8_lossy(name)
+                )));
+            }
+            writer.copy_entry(&archive, record)?;
+        }
+    }
+    let num_entries = writer.num_entries;
+    let merged = writer.finish();
+    let bytes_out = merged.len() as u64;
+    set_staged(dst_storage, dst_key, merged.into(), true)?;
+    Ok(RepackSummary {
+        num_entries,
+        bytes_in,
+        bytes_out,
+        bytes_reclaimed: bytes_in.saturating_sub(bytes_out),
+    })
+}
+
+/// The error returned by operations that require the zip file to have no pending changes.
+pub(crate) fn pending_changes_error() -> StorageError {
+    zip_error("the zip file has pending changes, call finish first")
+}
+
+/// A unique temporary key alongside `key` for staging a zip file, or [`None`] if `key` is the root.
+pub(crate) fn temporary_key(key: &StoreKey) -> Option<StoreKey> {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    if key.as_str().is_empty() {
+        return None;
+    }
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_nanos());
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    StoreKey::new(format!(
+        "{key}.tmp-{:x}-{nanos:x}-{count:x}",
+        std::process::id()
+    ))
+    .ok()
+}
+
+/// The error of a failure to write `key` from the zip file staged at `temporary_key`, which is kept.
+pub(crate) fn staged_copy_error(
+    key: &StoreKey,
+    temporary_key: &StoreKey,
+    err: &StorageError,
+) -> StorageError {
+    zip_error(format!(
+        "{key} cannot be written, the staged zip file is kept at {temporary_key}: {err}"
+    ))
+}
+
+/// Set `key` of `storage` to `value`, staged under a temporary key first if `staged`.
+///
+/// See [`ZipWriteOptions::staged`].
+///
+/// # Errors
+/// Returns a [`StorageError`] if the temporary key or `key` cannot be written, or the temporary key cannot be erased.
+/// If `key` cannot be written, the temporary key is kept and named in the error.
+pub(crate) fn set_staged<TStorage: ?Sized + WritableStorageTraits>(
+    storage: &TStorage,
+    key: &StoreKey,
+    value: Bytes,
+    staged: bool,
+) -> Result<(), StorageError> {
+    let Some(temporary_key) = temporary_key(key).filter(|_| staged) else {
+        return storage.set(key, value);
+    };
+    if let Err(err) = storage.set(&temporary_key, value.clone()) {
+        // The temporary key may be partially written, the error writing it takes precedence over an error erasing it
+        storage.erase(&temporary_key).ok();
+        return Err(err);
+    }
+    // The staged zip file is complete, so it is kept if it cannot be written to `key`
+    storage
+        .set(key, value)
+        .map_err(|err| staged_copy_error(key, &temporary_key, &err))?;
+    storage.erase(&temporary_key)
+}
+
+/// Rewrite `archive` with the `changes` applied.
+///
+/// The names of pending entries are prefixed by `zip_path`.
+///
+/// # Errors
+/// Returns a [`StorageError`] if `archive` is not a valid zip archive or an entry cannot be written.
+pub(crate) fn rewrite_archive(
+    archive: &[u8],
+    zip_path: &str,
+    changes: &PendingChanges,
+) -> Result<Vec<u8>, StorageError> {
+    let additional = changes.values.iter().map(|(_, value, _)| value.len()).sum();
+    let mut writer = ArchiveWriter::from_archive(archive, &changes.dropped, additional)?;
+    writer.add_changes(zip_path, changes)?;
+    Ok(writer.finish())
+}
+
+/// Append the `changes` to an archive of `size` bytes, given `tail`, its trailing bytes from its central directory at `tail_offset`.
+///
+/// New entries are written over the existing central directory, unless the result would not reach the end of the archive, in which case they are written after it.
+/// Returns the offset to write the returned bytes to.
+///
+/// # Errors
+/// Returns a [`StorageError`] if `tail` does not start with a valid central directory or an entry cannot be written.
+pub(crate) fn append_archive(
+    tail: &[u8],
+    tail_offset: u64,
+    size: u64,
+    zip_path: &str,
+    changes: &PendingChanges,
+) -> Result<(u64, Vec<u8>), StorageError> {
+    let append = |base_offset| {
+        let mut writer = ArchiveWriter::append(tail, tail_offset, base_offset, &changes.dropped)?;
+        writer.add_changes(zip_path, changes)?;
+        Ok::<_, StorageError>(writer.finish())
+    };
+
+    let bytes = append(tail_offset)?;
+    if tail_offset + bytes.len() as u64 >= size {
+        Ok((tail_offset, bytes))
+    } else {
+        // The underlying storage cannot be truncated, so leave the existing central directory as unreferenced bytes
+        Ok((size, append(size)?))
+    }
+}
+
+/// The size of a data descriptor with a signature and 32-bit sizes.
+const DATA_DESCRIPTOR_SIZE: u64 = 16;
+
+/// The number of bytes of an archive of `size` bytes with its central directory at `directory_offset` that are not referenced by `live` entries.
+///
+/// `data_offset` returns the offset of the data of an entry from the offset of its local file header.
+///
+/// # Errors
+/// Returns a [`StorageError`] if `data_offset` fails.
+pub(crate) fn dead_bytes(
+    size: u64,
+    directory_offset: u64,
+    live: &[LocalRecord],
+    mut data_offset: impl FnMut(u64) -> Result<u64, StorageError>,
+) -> Result<u64, StorageError> {
+    let mut referenced = size.saturating_sub(directory_offset);
+    for record in live {
+        let data_offset = data_offset(record.header_offset)?;
+        let descriptor_size = if record.data_descriptor {
+            DATA_DESCRIPTOR_SIZE
+        } else {
+            0
+        };
+        referenced += data_offset - record.header_offset + record.compressed_size + descriptor_size;
+    }
+    Ok(size.saturating_sub(referenced))
+}
+
+/// The number of bytes to read from the data offset of a stored entry to update its CRC-32 with [`crc32_updates`].
+///
+/// This is the data of the entry, followed by the first 4 bytes of its data descriptor if it has one.
+pub(crate) fn crc32_data_len(record: &LocalRecord) -> u64 {
+    if record.data_descriptor {
+        record.compressed_size + 4
+    } else {
+        record.compressed_size
+    }
+}
+
+/// The CRC-32 fields to update for stored `entries` that have been partially written in place, as offsets and values.
+///
+/// Each entry is its local data record, its data offset, and its data read with [`crc32_data_len`].
+/// `tail` is the trailing bytes of the archive from `tail_offset`, which must include its central directory.
+///
+/// # Errors
+/// Returns a [`StorageError`] if `tail` does not contain a valid central directory or an entry is not in it.
+pub(crate) fn crc32_updates(
+    tail: &[u8],
+    tail_offset: u64,
+    entries: &[(LocalRecord, u64, Bytes)],
+) -> Result<Vec<(u64, Bytes)>, StorageError> {
+    let (_, directory_range) = ArchiveWriter::find_directory(tail, tail_offset)?;
+    let directory_offset = tail_offset + directory_range.start as u64;
+    let directory = &tail[directory_range];
+    let records = parse_central_directory(directory).map_err(zip_error)?;
+    let records: HashMap<u64, _> = records
+        .iter()
+        .map(|record| (record.header_offset, record))
+        .collect();
+
+    let mut updates = Vec::with_capacity(entries.len() * 2);
+    for (record, data_offset, data) in entries {
+        let size = usize::try_from(record.compressed_size)
+            .ok()
+            .filter(|&size| size <= data.len())
+            .ok_or_else(|| zip_error("stored entry is truncated"))?;
+        let crc32 = Bytes::copy_from_slice(&crc32fast::hash(&data[..size]).to_le_bytes());
+        let local_offset = if record.data_descriptor {
+            data_offset + record.compressed_size + data_descriptor_crc32_offset(&data[size..])
+        } else {
+            record.header_offset + LOCAL_FILE_HEADER_CRC32_OFFSET
+        };
+        let directory_record = records
+            .get(&record.header_offset)
+            .ok_or_else(|| zip_error("entry is not in the central directory"))?;
+        updates.push((local_offset, crc32.clone()));
+        updates.push((
+            directory_record.crc32_offset(directory, directory_offset),
+            crc32,
+        ));
+    }
+    Ok(updates)
+}
+
+/// Apply partial writes to an existing value, extending it with zeros as needed.
+///
+/// # Errors
+/// Returns a [`StorageError`] if an offset cannot be addressed on this platform.
+pub(crate) fn apply_partial_writes(
+    value: MaybeBytes,
+    offset_values: impl IntoIterator<Item = (u64, Bytes)>,
+) -> Result<Bytes, StorageError> {
+    let mut value = value.map(Vec::from).unwrap_or_default();
+    for (offset, bytes) in offset_values {
+        let start = usize::try_from(offset).map_err(|_| {
+            StorageError::Other(format!("offset {offset} is too large for this platform"))
+        })?;
+        let end = start + bytes.len();
+        if value.len() < end {
+            value.resize(end, 0);
+        }
+        value[start..end].copy_from_slice(&bytes);
+    }
+    Ok(value.into())
+}
+
+impl<TStorage: ?Sized> ZipStorageAdapter<TStorage> {
+    /// Set the value of `key`, overriding the write options of the adapter.
+    ///
+    /// See [`with_write_options`](ZipStorageAdapter::with_write_options).
+    ///
+    /// # Errors
+    /// Returns a [`StorageError`] if `key` exists and the overwrite policy is [`ZipOverwritePolicy::Error`].
+    pub fn set_with_options(
+        &self,
+        key: &StoreKey,
+        value: Bytes,
+        options: &ZipWriteOptions,
+    ) -> Result<(), StorageError> {
+        self.set_impl(key, value, options)
+    }
+
+    /// Set a pending value for `key` to be written with `options`, subject to the overwrite policy.
+    ///
+    /// The compression policy of `options` is applied when the value is set.
+    pub(crate) fn set_impl(
+        &self,
+        key: &StoreKey,
+        value: Bytes,
+        options: &ZipWriteOptions,
+    ) -> Result<(), StorageError> {
+        let options = options.resolve(key, value.len());
+        let mut index = self.index.write();
+        if self.overwrite_policy == ZipOverwritePolicy::Error && index.get(key).is_some() {
+            return Err(StorageError::Other(format!(
+                "cannot overwrite {key} in the zip file {}",
+                self.key
+            )));
+        }
+        index.insert_pending(key, value, options);
+        self.invalidate_cache(key);
+        Ok(())
+    }
+
+    /// Set the archive comment of the zip file, which is written by [`finish`](ZipStorageAdapter::finish).
+    ///
+    /// The comment can be any bytes, including text that is not UTF-8.
+    /// It is written by [`finish`](ZipStorageAdapter::finish) through any clone of the adapter, but only returned by [`comment`](ZipStorageAdapter::comment) of this adapter and clones made after it is set.
+    ///
+    /// # Errors
+    /// Returns a [`StorageError`] if `comment` is longer than 65535 bytes, the maximum length of an archive comment.
+    pub fn set_comment(&mut self, comment: &[u8]) -> Result<(), StorageError> {
+        if comment.len() > usize::from(u16::MAX) {
+            return Err(StorageError::Other(format!(
+                "the archive comment is {} bytes, the maximum is {} bytes",
+                comment.len(),
+                u16::MAX
+            )));
+        }
+        self.index.write().set_comment(comment.to_vec());
+        self.comment = comment.to_vec();
+        Ok(())
+    }
+
+    /// Create a directory entry for `prefix`, which is written by [`finish`](ZipStorageAdapter::finish).
+    ///
+    /// The directory is listed by [`list_dir`](zarrs_storage::ListableStorageTraits::list_dir) even if it has no keys, such as an empty group.
+    /// This is a no-op if `prefix` is the root or already has a directory entry.
+    /// See also [`ZipWriteOptions::explicit_directories`].
+    pub fn create_directory(&self, prefix: &StorePrefix) {
+        self.index.write().insert_directory(prefix);
+    }
+
+    /// Erase `key`.
+    pub(crate) fn erase_impl(&self, key: &StoreKey) {
+        self.index.write().remove(key);
+        self.invalidate_cache(key);
+    }
+
+    /// Erase all keys with `prefix`.
+    pub(crate) fn erase_prefix_impl(&self, prefix: &StorePrefix) {
+        let keys = self.index.write().remove_prefix(prefix);
+        for key in &keys {
+            self.invalidate_cache(key);
+        }
+    }
+
+    /// The local file header offset of the stored entry of `key`, checking that `offset_values` can be written to it in place.
+    ///
+    /// Returns [`None`] if `key` is not an entry in the zip file, such as a pending value.
+    ///
+    /// # Errors
+    /// Returns a [`StorageError`] if the entry is compressed, a write would change its length, or the underlying storage does not support partial writes.
+    pub(crate) fn in_place_header_offset(
+        &self,
+        key: &StoreKey,
+        offset_values: &[(u64, Bytes)],
+        supports_set_partial: bool,
+    ) -> Result<Option<u64>, StorageError> {
+        let index = self.index.read();
+        let Some(IndexEntry::Archived(entry)) = index.get(key) else {
+            return Ok(None);
+        };
+        if entry.method != Method::Store {
+            return Err(StorageError::Unsupported(format!(
+                "cannot partially write {key}, its zip entry is compressed with the {:?} compression method",
+                entry.method
+            )));
+        }
+        if let Some((offset, value)) = offset_values.iter().find(|(offset, value)| {
+            offset
+                .checked_add(value.len() as u64)
+                .is_none_or(|end| end > entry.uncompressed_size)
+        }) {
+            return Err(StorageError::Other(format!(
+                "cannot partially write {} bytes at offset {offset} of {key}, which would change the length of its {} byte zip entry",
+                value.len(),
+                entry.uncompressed_size
+            )));
+        }
+        if !supports_set_partial {
+            return Err(StorageError::Unsupported(format!(
+                "cannot partially write {key}, the underlying storage does not support partial writes"
+            )));
+        }
+        Ok(Some(entry.header_offset))
+    }
+
+    /// Forget the memoized data offsets and preloaded values of entries that are not in `index`, the index of the zip file after it has been written.
+    pub(crate) fn retain_data_offsets(&self, index: &ZipIndex) {
+        let live: HashSet<u64> = index
+            .live_records()
+            .iter()
+            .map(|record| record.header_offset)
+            .collect();
+        self.data_offsets
+            .write()
+            .retain(|header_offset, _| live.contains(header_offset));
+        self.preloaded
+            .write()
+            .retain(|header_offset, _| live.contains(header_offset));
+    }
+
+    /// Record that `key`, the stored entry with its local file header at `header_offset`, has been partially written in place.
+    pub(crate) fn written_in_place(&self, key: &StoreKey, header_offset: u64) {
+        self.index.write().mark_crc32_stale(header_offset);
+        self.preloaded.write().remove(&header_offset);
+        self.invalidate_cache(key);
+    }
+
+    /// The error returned if the zip file is not found in the underlying storage.
+    pub(crate) fn zip_not_found(&self) -> StorageError {
+        StorageError::Other(format!("zip file {} not found", self.key))
+    }
+
+    /// Remove the decompressed value and seek index of `key` from the cache.
+    fn invalidate_cache(&self, key: &StoreKey) {
+        if let Some(cache) = &self.cache {
+            cache.remove(key);
+        }
+        if let Some(seek_indexes) = &self.seek_indexes {
+            seek_indexes.remove(key);
+        }
+    }
+}
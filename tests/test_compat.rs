@@ -1,10 +1,11 @@
 #![allow(missing_docs)]
 
-use std::{error::Error, path::PathBuf, sync::Arc};
+use std::{error::Error, fs::File, io::Write, path::PathBuf, sync::Arc};
 
 use zarrs::{array::Array, array_subset::ArraySubset, storage::StoreKey};
 use zarrs_filesystem::FilesystemStore;
 use zarrs_zip::ZipStorageAdapter;
+use zip::write::SimpleFileOptions;
 
 #[test]
 fn zarr_python_compat_zip_store() -> Result<(), Box<dyn Error>> {
@@ -21,3 +22,31 @@ fn zarr_python_compat_zip_store() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+#[test]
+fn zarr_python_compat_nested_zip_store() -> Result<(), Box<dyn Error>> {
+    // A zip file with the zip file of the Zarr hierarchy as an entry stored without compression
+    let tmp_dir = tempfile::TempDir::new()?;
+    {
+        let file = File::create(tmp_dir.path().join("bundle.zip"))?;
+        let mut zip = zip::ZipWriter::new(file);
+        let options =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        zip.start_file("data.zip", options)?;
+        zip.write_all(&std::fs::read("tests/zarr.zip")?)?;
+        zip.finish()?;
+    }
+
+    let store = Arc::new(FilesystemStore::new(tmp_dir.path())?);
+    let outer = Arc::new(ZipStorageAdapter::new(store, StoreKey::new("bundle.zip")?)?);
+    let inner = Arc::new(ZipStorageAdapter::new(outer, StoreKey::new("data.zip")?)?);
+
+    let array = Array::open(inner, "/foo")?;
+    assert_eq!(array.shape(), vec![100, 100]);
+    let elements = array.retrieve_array_subset_elements::<u8>(&ArraySubset::new_with_shape(
+        array.shape().to_vec(),
+    ))?;
+    assert_eq!(elements, vec![42u8; 100 * 100]);
+
+    Ok(())
+}
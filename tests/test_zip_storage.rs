@@ -17,6 +17,8 @@ use zarrs_storage::{
     store::MemoryStore,
 };
 use zarrs_zip::ZipStorageAdapter;
+#[cfg(feature = "write")]
+use zarrs_zip::ZipWriteOptions;
 
 #[cfg(feature = "async")]
 use zarrs_storage::{AsyncListableStorageTraits, AsyncReadableStorageTraits};
@@ -143,6 +145,11 @@ fn zip_root() -> Result<(), Box<dyn Error>> {
             "a/f/".try_into()?,
         ]
     );
+    assert_eq!(&store.list_dir_prefixes(&"a/".try_into()?), list.prefixes());
+    assert_eq!(
+        store.list_dir_prefixes(&"".try_into()?),
+        &["a/".try_into()?, "b/".try_into()?, "c/".try_into()?]
+    );
 
     assert_eq!(
         store.get(&"a/b/zarr.json".try_into()?)?.unwrap(),
@@ -156,6 +163,41 @@ fn zip_root() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_builder() -> Result<(), Box<dyn Error>> {
+    use zarrs_zip::ZipStorageAdapterBuilder;
+
+    let path = tempfile::TempDir::new()?;
+    let mut path = path.path().to_path_buf();
+    let store: Arc<FilesystemStore> = Arc::new(FilesystemStore::new(path.clone())?);
+    path.push("test.zip");
+    zip_write(&path)?;
+    let key = StoreKey::new("test.zip")?;
+
+    let zip_store = ZipStorageAdapterBuilder::new(store.clone(), key.clone())
+        .path("a/")
+        .cache_bytes(1 << 20)
+        .verify_crc(true)
+        .build()?;
+    let with_path = ZipStorageAdapter::new_with_path(store.clone(), key.clone(), "a/")?;
+    assert_eq!(zip_store.list()?, with_path.list()?);
+    assert_eq!(
+        zip_store.get(&"b/zarr.json".try_into()?)?.unwrap(),
+        vec![0, 1, 2, 3]
+    );
+    assert!(zip_store.cache_stats().is_some());
+
+    let zip_store = ZipStorageAdapterBuilder::new(store.clone(), key.clone()).build()?;
+    assert_eq!(
+        zip_store.list()?,
+        ZipStorageAdapter::new(store, key)?.list()?
+    );
+    assert!(zip_store.cache_stats().is_none());
+
+    Ok(())
+}
+
 #[test]
 #[cfg_attr(miri, ignore)]
 fn zip_path() -> Result<(), Box<dyn Error>> {
@@ -218,6 +260,196 @@ fn zip_path() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+#[cfg(feature = "write")]
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_explicit_directories() -> Result<(), Box<dyn Error>> {
+    use zarrs_zip::{ZipEntryInfo, store_to_zip};
+
+    let directories = |zip_store: &ZipStorageAdapter<MemoryStore>| -> Vec<String> {
+        zip_store
+            .entries()
+            .filter(|(_, info)| *info == ZipEntryInfo::Directory)
+            .map(|(name, _)| name)
+            .collect()
+    };
+
+    let src = MemoryStore::new();
+    src.set(&"a/b/zarr.json".try_into()?, vec![0].into())?;
+    src.set(&"a/c/0".try_into()?, vec![1].into())?;
+    src.set(&"zarr.json".try_into()?, vec![2].into())?;
+    let store = Arc::new(MemoryStore::new());
+    let key = StoreKey::new("test.zip")?;
+    let options = ZipWriteOptions::default().explicit_directories(true);
+    store_to_zip(&src, &*store, &key, &options)?;
+    let zip_store = ZipStorageAdapter::new(store.clone(), key.clone())?;
+    assert_eq!(directories(&zip_store), ["a/", "a/b/", "a/c/"]);
+    assert_eq!(zip_store.list()?.len(), 3);
+
+    // Created directories are written without keys, and written keys add their directories once
+    let zip_store = zip_store.with_write_options(options);
+    zip_store.create_directory(&"empty/".try_into()?);
+    zip_store.create_directory(&"empty/".try_into()?);
+    zip_store.create_directory(&"a/".try_into()?);
+    zip_store.set(&"a/d/e/0".try_into()?, vec![3].into())?;
+    zip_store.set(&"a/d/e/1".try_into()?, vec![4].into())?;
+    assert_eq!(
+        zip_store.list_dir(&"".try_into()?)?.prefixes(),
+        &["a/".try_into()?, "empty/".try_into()?]
+    );
+    zip_store.finish()?;
+
+    let zip_store = ZipStorageAdapter::new(store.clone(), key.clone())?;
+    assert_eq!(
+        directories(&zip_store),
+        ["a/", "a/b/", "a/c/", "a/d/", "a/d/e/", "empty/"]
+    );
+    let list = zip_store.list_dir(&"".try_into()?)?;
+    assert_eq!(list.prefixes(), &["a/".try_into()?, "empty/".try_into()?]);
+    let list = zip_store.list_dir(&"empty/".try_into()?)?;
+    assert!(list.keys().is_empty() && list.prefixes().is_empty());
+    assert_eq!(zip_store.get(&"a/d/e/1".try_into()?)?.unwrap(), vec![4]);
+
+    // Other zip implementations read the directory entries
+    let bytes = store.get(&key)?.unwrap();
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes.to_vec()))?;
+    assert!(archive.by_name("empty/")?.is_dir());
+    assert!(archive.by_name("a/d/e/")?.is_dir());
+    assert!(archive.by_name("a/d/e/0")?.is_file());
+
+    Ok(())
+}
+
+#[cfg(feature = "write")]
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_comment() -> Result<(), Box<dyn Error>> {
+    let store = Arc::new(MemoryStore::new());
+    let key = StoreKey::new("test.zip")?;
+    let mut zip_store = ZipStorageAdapter::create(store.clone(), key.clone(), false)?;
+    assert_eq!(zip_store.comment(), None);
+
+    // A UTF-8 comment is pending until the zip file is finished
+    let utf8 = "créé par zarrs_zip, doi:10.1234/abc".as_bytes();
+    zip_store.set(&"a".try_into()?, vec![0].into())?;
+    zip_store.set_comment(utf8)?;
+    assert_eq!(zip_store.comment(), Some(utf8));
+    zip_store.finish()?;
+    assert_eq!(zip_store.comment(), Some(utf8));
+    let zip_store = ZipStorageAdapter::new(store.clone(), key.clone())?;
+    assert_eq!(zip_store.comment(), Some(utf8));
+
+    // A binary comment, including bytes that look like an end of central directory record
+    let binary = [0xff, 0x00, 0x50, 0x4b, 0x05, 0x06, 0x00, 0x80];
+    let mut zip_store = ZipStorageAdapter::open_append(store.clone(), key.clone())?;
+    zip_store.set_comment(&binary)?;
+    zip_store.finish()?;
+    let zip_store = ZipStorageAdapter::new(store.clone(), key.clone())?;
+    assert_eq!(zip_store.comment(), Some(&binary[..]));
+    assert_eq!(zip_store.get(&"a".try_into()?)?.unwrap(), vec![0]);
+
+    // The comment is retained by rewrites that do not set it
+    zip_store.set(&"b".try_into()?, vec![1].into())?;
+    zip_store.finish()?;
+    let mut zip_store = ZipStorageAdapter::new(store.clone(), key.clone())?;
+    assert_eq!(zip_store.comment(), Some(&binary[..]));
+
+    assert!(zip_store.set_comment(&vec![0; 65536]).is_err());
+    zip_store.set_comment(&vec![b'x'; 65535])?;
+    zip_store.finish()?;
+    let zip_store = ZipStorageAdapter::new(store, key)?;
+    assert_eq!(zip_store.comment(), Some(vec![b'x'; 65535].as_slice()));
+
+    Ok(())
+}
+
+#[cfg(feature = "write")]
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_duplicate_names() -> Result<(), Box<dyn Error>> {
+    use zarrs_zip::ZipStreamWriter;
+
+    let mut writer = ZipStreamWriter::new(Vec::new(), ZipWriteOptions::default());
+    writer.write_entry("a/b.json", &[0u8][..])?;
+    writer.write_entry("a/c.json", &[1u8][..])?;
+    writer.write_entry("a/b.json", &[2u8, 3][..])?;
+    let bytes = writer.finish()?;
+
+    let store = Arc::new(MemoryStore::new());
+    let key = StoreKey::new("test.zip")?;
+    store.set(&key, bytes.into())?;
+    let zip_store = ZipStorageAdapter::new(store.clone(), key.clone())?;
+
+    // The last entry with a name wins
+    assert_eq!(
+        zip_store.list()?,
+        &["a/b.json".try_into()?, "a/c.json".try_into()?]
+    );
+    assert_eq!(
+        zip_store.list_dir(&"a/".try_into()?)?.keys(),
+        &["a/b.json".try_into()?, "a/c.json".try_into()?]
+    );
+    assert_eq!(zip_store.get(&"a/b.json".try_into()?)?.unwrap(), vec![2, 3]);
+    assert_eq!(zip_store.size_key(&"a/b.json".try_into()?)?, Some(2));
+    assert_eq!(zip_store.entries().count(), 2);
+
+    // The shadowed entry is unreferenced, and is removed by a rewrite
+    assert!(zip_store.dead_bytes()? > 0);
+    zip_store.set(&"d".try_into()?, vec![4].into())?;
+    zip_store.finish()?;
+    let zip_store = ZipStorageAdapter::new(store.clone(), key)?;
+    assert_eq!(zip_store.entries().count(), 3);
+    let summary = zip_store.repack(&*store, &StoreKey::new("repacked.zip")?)?;
+    assert_eq!(summary.num_entries, 3);
+    assert_eq!(zip_store.get(&"a/b.json".try_into()?)?.unwrap(), vec![2, 3]);
+
+    Ok(())
+}
+
+#[cfg(feature = "write")]
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_path_directory_boundary() -> Result<(), Box<dyn Error>> {
+    let store = Arc::new(MemoryStore::new());
+    let key = StoreKey::new("test.zip")?;
+    let zip_store = ZipStorageAdapter::create(store.clone(), key.clone(), false)?;
+    zip_store.set(&"a/x".try_into()?, vec![0].into())?;
+    zip_store.set(&"a/b/z".try_into()?, vec![1].into())?;
+    zip_store.set(&"ab/y".try_into()?, vec![2].into())?;
+    zip_store.finish()?;
+
+    for path in ["a", "a/"] {
+        let zip_store = ZipStorageAdapter::new_with_path(store.clone(), key.clone(), path)?;
+        assert_eq!(
+            zip_store.list()?,
+            &["b/z".try_into()?, "x".try_into()?],
+            "{path}"
+        );
+        assert_eq!(zip_store.get(&"x".try_into()?)?.unwrap(), vec![0]);
+        assert!(zip_store.get(&"b/y".try_into()?)?.is_none());
+    }
+
+    let zip_store = ZipStorageAdapter::new_with_path(store.clone(), key.clone(), "ab")?;
+    assert_eq!(zip_store.list()?, &["y".try_into()?]);
+
+    // Writes through a path without a trailing slash are placed within the directory
+    let zip_store = ZipStorageAdapter::new_with_path(store.clone(), key.clone(), "a")?;
+    zip_store.set(&"w".try_into()?, vec![3].into())?;
+    zip_store.finish()?;
+    let zip_store = ZipStorageAdapter::new(store, key)?;
+    assert_eq!(
+        zip_store.list()?,
+        &[
+            "a/b/z".try_into()?,
+            "a/w".try_into()?,
+            "a/x".try_into()?,
+            "ab/y".try_into()?,
+        ]
+    );
+
+    Ok(())
+}
+
 #[test]
 #[cfg_attr(miri, ignore)]
 fn zip_empty_directory() -> Result<(), Box<dyn Error>> {
@@ -266,6 +498,7 @@ fn zip_empty_directory() -> Result<(), Box<dyn Error>> {
     let list = store.list_dir(&"a/".try_into()?)?;
     assert_eq!(list.keys(), &["a/file.txt".try_into()?]);
     assert_eq!(list.prefixes(), &["a/empty/".try_into()?]);
+    assert_eq!(&store.list_dir_prefixes(&"a/".try_into()?), list.prefixes());
 
     // list_dir at a/empty/ should be empty (no files, no subdirectories)
     let list = store.list_dir(&"a/empty/".try_into()?)?;
@@ -275,6 +508,45 @@ fn zip_empty_directory() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_ignore_directory_entries() -> Result<(), Box<dyn Error>> {
+    use zarrs_zip::{ZipEntryInfo, ZipStorageAdapterBuilder};
+
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    zip.add_directory("a/", options)?;
+    zip.start_file("a/file.txt", options)?;
+    zip.write_all(b"hello")?;
+    zip.add_directory("a/empty/", options)?;
+    let bytes = zip.finish()?.into_inner();
+
+    let store = Arc::new(MemoryStore::new());
+    store.set(&StoreKey::new("test.zip")?, bytes.into())?;
+    let zip_store = ZipStorageAdapterBuilder::new(store, StoreKey::new("test.zip")?)
+        .ignore_directory_entries(true)
+        .build()?;
+
+    // The parent directories of keys are listed, but not empty directories
+    assert_eq!(zip_store.list()?, &["a/file.txt".try_into()?]);
+    let list = zip_store.list_dir(&"".try_into()?)?;
+    assert_eq!(list.prefixes(), &["a/".try_into()?]);
+    let list = zip_store.list_dir(&"a/".try_into()?)?;
+    assert_eq!(list.keys(), &["a/file.txt".try_into()?]);
+    assert_eq!(list.prefixes(), &[]);
+    assert!(
+        zip_store
+            .entries()
+            .all(|(_, info)| !matches!(info, ZipEntryInfo::Directory))
+    );
+    assert_eq!(
+        zip_store.get(&"a/file.txt".try_into()?)?.unwrap(),
+        b"hello".as_slice()
+    );
+
+    Ok(())
+}
+
 #[test]
 #[cfg_attr(miri, ignore)]
 fn store_test_read_list() -> Result<(), Box<dyn Error>> {
@@ -316,46 +588,4756 @@ fn store_test_read_list() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-#[cfg(feature = "async")]
-#[tokio::test]
+#[test]
 #[cfg_attr(miri, ignore)]
-async fn zip_async_remote() -> Result<(), Box<dyn Error>> {
-    use object_store::http::HttpBuilder;
-    use zarrs_object_store::AsyncObjectStore;
+fn zip_deflate_partial() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::byte_range::ByteRange;
 
-    // Create an HTTP object store pointing to the raw GitHub URL
-    let http_store = HttpBuilder::new()
-        .with_url("https://github.com/zarrs/zarrs_zip/raw/refs/heads/main/tests/")
-        .build()?;
-    let async_store = Arc::new(AsyncObjectStore::new(http_store));
+    let data: Vec<u8> = (0..1_000_000u32).map(|i| (i % 251) as u8).collect();
+    let tmp_dir = tempfile::TempDir::new()?;
+    {
+        let file = File::create(tmp_dir.path().join("test.zip"))?;
+        let mut zip = zip::ZipWriter::new(file);
+        let options =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        zip.start_file("data", options)?;
+        zip.write_all(&data)?;
+        zip.finish()?;
+    }
 
-    // Create the zip storage adapter asynchronously
-    let zip_store =
-        Arc::new(ZipStorageAdapter::new_async(async_store, StoreKey::new("zarr.zip")?).await?);
+    let store = FilesystemStore::new(tmp_dir.path())?;
+    let store = ZipStorageAdapter::new(store.into(), StoreKey::new("test.zip")?)?;
+    let key = "data".try_into()?;
+    assert_eq!(store.get(&key)?.unwrap(), data);
+    assert_eq!(
+        store
+            .get_partial(&key, ByteRange::FromStart(10, Some(20)))?
+            .unwrap(),
+        &data[10..30]
+    );
+    assert_eq!(
+        store
+            .get_partial(&key, ByteRange::FromStart(500_000, Some(100_000)))?
+            .unwrap(),
+        &data[500_000..600_000]
+    );
+    assert_eq!(
+        store.get_partial(&key, ByteRange::Suffix(100))?.unwrap(),
+        &data[data.len() - 100..]
+    );
 
-    // Test list
-    let keys = zip_store.list().await?;
-    assert!(!keys.is_empty(), "Expected non-empty list of keys");
+    Ok(())
+}
 
-    // Test list_prefix for the root
-    let root_keys = zip_store.list_prefix(&"".try_into()?).await?;
-    assert_eq!(keys, root_keys);
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_deflate_partial_early_stop() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::{
+        byte_range::ByteRange,
+        storage_adapter::performance_metrics::PerformanceMetricsStorageAdapter,
+    };
 
-    // Test list_dir at root
-    let root_dir = zip_store.list_dir(&"".try_into()?).await?;
+    // Incompressible data, so that the compressed data is as large as the entry
+    let mut state = 1u32;
+    let data: Vec<u8> = (0..4_000_000)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            state.to_le_bytes()[2]
+        })
+        .collect();
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    zip.start_file("data", options)?;
+    zip.write_all(&data)?;
+    let bytes = zip.finish()?.into_inner();
+    let size = bytes.len();
+
+    let memory_store = Arc::new(MemoryStore::new());
+    memory_store.set(&StoreKey::new("test.zip")?, bytes.into())?;
+    let store = Arc::new(PerformanceMetricsStorageAdapter::new(memory_store));
+    let zip_store = ZipStorageAdapter::new(store.clone(), StoreKey::new("test.zip")?)?;
+    let key = "data".try_into()?;
+
+    // Ranges near the start of the entry only decompress its start
+    for byte_ranges in [
+        vec![ByteRange::FromStart(0, Some(4096))],
+        vec![
+            ByteRange::FromStart(100_000, Some(10)),
+            ByteRange::FromStart(0, Some(10)),
+        ],
+    ] {
+        let bytes_read = store.bytes_read();
+        let values = zip_store
+            .get_partial_many(&key, Box::new(byte_ranges.clone().into_iter()))?
+            .unwrap();
+        assert!(store.bytes_read() - bytes_read < size / 4);
+        for (value, byte_range) in values.zip(&byte_ranges) {
+            assert_eq!(value?, &data[byte_range.to_range_usize(data.len() as u64)]);
+        }
+    }
+
+    // Ranges with a suffix or reaching the end decompress the entire entry
+    for byte_ranges in [
+        vec![ByteRange::FromStart(0, Some(10)), ByteRange::Suffix(10)],
+        vec![
+            ByteRange::FromStart(10, None),
+            ByteRange::FromStart(0, Some(10)),
+        ],
+        vec![ByteRange::FromStart(3_999_990, Some(10))],
+    ] {
+        let bytes_read = store.bytes_read();
+        let values = zip_store
+            .get_partial_many(&key, Box::new(byte_ranges.clone().into_iter()))?
+            .unwrap();
+        assert!(store.bytes_read() - bytes_read >= size / 2);
+        for (value, byte_range) in values.zip(&byte_ranges) {
+            assert_eq!(value?, &data[byte_range.to_range_usize(data.len() as u64)]);
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_get_blocks() -> Result<(), Box<dyn Error>> {
+    let data: Vec<u8> = (0..1_000_000u32).map(|i| (i % 251) as u8).collect();
+    let tmp_dir = tempfile::TempDir::new()?;
+    {
+        let file = File::create(tmp_dir.path().join("test.zip"))?;
+        let mut zip = zip::ZipWriter::new(file);
+        for method in [
+            zip::CompressionMethod::Deflated,
+            zip::CompressionMethod::Stored,
+        ] {
+            let options = SimpleFileOptions::default().compression_method(method);
+            zip.start_file(format!("{method:?}"), options)?;
+            zip.write_all(&data)?;
+        }
+        zip.start_file("empty", SimpleFileOptions::default())?;
+        zip.finish()?;
+    }
+
+    let store = FilesystemStore::new(tmp_dir.path())?;
+    let store =
+        ZipStorageAdapter::new(store.into(), StoreKey::new("test.zip")?)?.with_crc32_verification();
+    for key in ["Deflated", "Stored"] {
+        let blocks = store
+            .get_blocks(&key.try_into()?, 64 * 1024)?
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(blocks.len(), 16);
+        assert!(blocks[..15].iter().all(|block| block.len() == 64 * 1024));
+        assert_eq!(blocks.concat(), data);
+    }
+    assert_eq!(
+        store
+            .get_blocks(&"empty".try_into()?, 1024)?
+            .unwrap()
+            .count(),
+        0
+    );
+    assert!(store.get_blocks(&"missing".try_into()?, 1024)?.is_none());
+    assert!(store.get_blocks(&"Stored".try_into()?, 0).is_err());
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_deflate_shared_byte_ranges() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::byte_range::ByteRange;
+
+    let data: Vec<u8> = (0..1_000_000u32)
+        .map(|i| (i % 251).to_le_bytes()[0])
+        .collect();
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    zip.start_file("deflated", options)?;
+    zip.write_all(&data)?;
+    let memory_store = Arc::new(MemoryStore::new());
+    memory_store.set(
+        &StoreKey::new("test.zip")?,
+        zip.finish()?.into_inner().into(),
+    )?;
+    let key = StoreKey::new("deflated")?;
+
+    // Many byte ranges of a decompressed entry are slices of one allocation
+    let byte_ranges: Vec<ByteRange> = (0..100)
+        .map(|i| ByteRange::FromStart(i * 10_000, Some(10_000)))
+        .collect();
+    let zip_store = ZipStorageAdapter::new(memory_store.clone(), StoreKey::new("test.zip")?)?;
+    let values = zip_store
+        .get_partial_many(&key, Box::new(byte_ranges.clone().into_iter()))?
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()?;
+    for (i, value) in values.iter().enumerate() {
+        assert_eq!(value, &data[i * 10_000..(i + 1) * 10_000]);
+        assert_eq!(value.as_ptr(), values[0].as_ptr().wrapping_add(i * 10_000));
+    }
+
+    // Byte ranges of a cached entry are slices of the cached value
+    let zip_store = ZipStorageAdapter::new_with_cache(
+        memory_store.clone(),
+        StoreKey::new("test.zip")?,
+        2_000_000,
+    )?;
+    let value = zip_store.get(&key)?.unwrap();
+    let values = zip_store
+        .get_partial_many(&key, Box::new(byte_ranges.into_iter()))?
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()?;
+    for (i, value_range) in values.iter().enumerate() {
+        assert_eq!(
+            value_range.as_ptr(),
+            value.as_ptr().wrapping_add(i * 10_000)
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_deflate_invalid_byte_range() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::byte_range::ByteRange;
+
+    let tmp_dir = tempfile::TempDir::new()?;
+    {
+        let file = File::create(tmp_dir.path().join("test.zip"))?;
+        let mut zip = zip::ZipWriter::new(file);
+        let options =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        zip.start_file("data", options)?;
+        zip.write_all(&[7; 100])?;
+        zip.finish()?;
+    }
+
+    let store = FilesystemStore::new(tmp_dir.path())?;
+    let store = ZipStorageAdapter::new_with_cache(store.into(), StoreKey::new("test.zip")?, 1000)?;
+    let key = "data".try_into()?;
+
+    // Out of bounds byte ranges are rejected before the entry is decompressed
+    for range in [
+        ByteRange::FromStart(100, Some(1)),
+        ByteRange::FromStart(101, None),
+        ByteRange::FromStart(u64::MAX, Some(1)),
+        ByteRange::Suffix(101),
+    ] {
+        assert!(store.get_partial(&key, range).is_err());
+    }
+    assert_eq!(store.cache_stats().unwrap().misses, 0);
+
+    // Byte ranges up to the end of the entry are valid
+    assert_eq!(
+        store
+            .get_partial(&key, ByteRange::FromStart(100, None))?
+            .unwrap(),
+        &[][..]
+    );
+    assert_eq!(
+        store.get_partial(&key, ByteRange::Suffix(100))?.unwrap(),
+        &[7; 100][..]
+    );
+
+    // An out of bounds byte range yields an error without failing the other byte ranges
+    let values: Vec<_> = store
+        .get_partial_many(
+            &key,
+            Box::new(
+                [
+                    ByteRange::FromStart(0, Some(10)),
+                    ByteRange::FromStart(100, Some(1)),
+                    ByteRange::Suffix(5),
+                ]
+                .into_iter(),
+            ),
+        )?
+        .unwrap()
+        .collect();
+    assert_eq!(values.len(), 3);
+    assert_eq!(values[0].as_ref().unwrap(), &[7; 10][..]);
+    assert!(values[1].is_err());
+    assert_eq!(values[2].as_ref().unwrap(), &[7; 5][..]);
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_uncompressed_size() -> Result<(), Box<dyn Error>> {
+    let tmp_dir = tempfile::TempDir::new()?;
+    {
+        let file = File::create(tmp_dir.path().join("test.zip"))?;
+        let mut zip = zip::ZipWriter::new(file);
+        let deflated =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        let stored =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        zip.add_directory("a/", stored)?;
+        zip.add_directory("empty/", stored)?;
+        zip.start_file("a/x", deflated)?;
+        zip.write_all(&[1; 10_000])?;
+        zip.start_file("a/y", deflated)?;
+        zip.write_all(&[2; 5_000])?;
+        zip.start_file("ab/z", stored)?;
+        zip.write_all(&[3; 100])?;
+        zip.finish()?;
+    }
+
+    let store = FilesystemStore::new(tmp_dir.path())?;
+    let store = ZipStorageAdapter::new(store.into(), StoreKey::new("test.zip")?)?;
+    assert_eq!(store.uncompressed_size(), 15_100);
+    assert_eq!(store.uncompressed_size_prefix(&"a/".try_into()?), 15_000);
+    assert_eq!(store.uncompressed_size_prefix(&"ab/".try_into()?), 100);
+    assert_eq!(store.uncompressed_size_prefix(&"empty/".try_into()?), 0);
+    assert!(store.size_prefix(&"a/".try_into()?)? < 15_000);
+
+    assert_eq!(
+        store.list_prefix_with_sizes(&"a/".try_into()?),
+        &[("a/x".try_into()?, 10_000), ("a/y".try_into()?, 5_000)]
+    );
+    assert_eq!(
+        store.list_prefix_with_sizes(&"".try_into()?),
+        &[
+            ("a/x".try_into()?, 10_000),
+            ("a/y".try_into()?, 5_000),
+            ("ab/z".try_into()?, 100)
+        ]
+    );
     assert!(
-        !root_dir.keys().is_empty() || !root_dir.prefixes().is_empty(),
-        "Expected non-empty root directory listing"
+        store
+            .list_prefix_with_sizes(&"empty/".try_into()?)
+            .is_empty()
     );
 
-    // Test reading zarr.json if it exists
-    if let Some(data) = zip_store.get(&"zarr.json".try_into()?).await? {
-        assert!(!data.is_empty(), "Expected non-empty zarr.json");
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_size_prefix_nested() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::StorePrefix;
+
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let deflated =
+        SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    let stored = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    zip.add_directory("g/", stored)?;
+    zip.add_directory("g/empty/", stored)?;
+    let mut names = Vec::new();
+    for (i, group) in ["g/a/", "g/a/b/", "g/ab/", "g/b/c/d/", "h/"]
+        .iter()
+        .enumerate()
+    {
+        for j in 0..5 {
+            let name = format!("{group}c/{j}");
+            let options = if j % 2 == 0 { deflated } else { stored };
+            zip.start_file(name.as_str(), options)?;
+            zip.write_all(&vec![u8::try_from(j)?; 100 * (i + 1) + j])?;
+            names.push(name);
+        }
     }
+    let store = Arc::new(MemoryStore::new());
+    let zip_key = StoreKey::new("test.zip")?;
+    store.set(&zip_key, zip.finish()?.into_inner().into())?;
+    let zip_store = ZipStorageAdapter::new(store, zip_key)?;
 
-    // Test size
-    let size = zip_store.size().await?;
-    assert!(size > 0, "Expected non-zero zip file size");
+    // The sizes of the keys with every prefix match the sums of the sizes of the listed keys
+    let mut prefixes = vec![String::new(), "g/empty/".to_string(), "x/".to_string()];
+    for name in &names {
+        prefixes.extend(name.match_indices('/').map(|(i, _)| name[..=i].to_string()));
+    }
+    for prefix in prefixes {
+        let prefix = StorePrefix::new(prefix)?;
+        let keys = zip_store.list_prefix(&prefix)?;
+        let metadata: Vec<_> = keys
+            .iter()
+            .map(|key| zip_store.entry_metadata(key).unwrap())
+            .collect();
+        assert_eq!(
+            zip_store.size_prefix(&prefix)?,
+            metadata.iter().map(|m| m.compressed_size).sum::<u64>()
+        );
+        assert_eq!(
+            zip_store.uncompressed_size_prefix(&prefix),
+            metadata.iter().map(|m| m.uncompressed_size).sum::<u64>()
+        );
+    }
+    let prefix: StorePrefix = "g/a/".try_into()?;
+    assert_eq!(zip_store.uncompressed_size_prefix(&prefix), 510 + 1010);
+
+    // The sums are rebuilt after writes
+    #[cfg(feature = "write")]
+    {
+        zip_store.set(&"g/a/new".try_into()?, vec![0; 7].into())?;
+        assert_eq!(zip_store.uncompressed_size_prefix(&prefix), 510 + 1010 + 7);
+        zip_store.set(&"g/a/c/1".try_into()?, vec![0; 1].into())?;
+        assert_eq!(
+            zip_store.uncompressed_size_prefix(&prefix),
+            510 + 1010 + 7 - 100
+        );
+        zip_store.erase(&"g/a/c/0".try_into()?)?;
+        assert_eq!(
+            zip_store.uncompressed_size_prefix(&prefix),
+            510 + 1010 + 7 - 200
+        );
+        zip_store.erase_prefix(&"g/a/b/".try_into()?)?;
+        assert_eq!(zip_store.uncompressed_size_prefix(&prefix), 510 + 7 - 200);
+        assert_eq!(
+            zip_store.size_prefix(&"g/".try_into()?)?,
+            zip_store.size_prefix(&prefix)?
+                + zip_store.size_prefix(&"g/ab/".try_into()?)?
+                + zip_store.size_prefix(&"g/b/".try_into()?)?
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_zstd() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::byte_range::ByteRange;
+
+    // More than 1 MiB, which is more than a single block of a Zstandard frame
+    let data: Vec<u8> = (0..1_500_000u32).map(|i| (i % 251) as u8).collect();
+    let tmp_dir = tempfile::TempDir::new()?;
+    {
+        let file = File::create(tmp_dir.path().join("test.zip"))?;
+        let mut zip = zip::ZipWriter::new(file);
+        let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Zstd);
+        zip.start_file("data", options)?;
+        zip.write_all(&data)?;
+        zip.finish()?;
+    }
+
+    let fs_store: Arc<FilesystemStore> = FilesystemStore::new(tmp_dir.path())?.into();
+    let store = ZipStorageAdapter::new(fs_store.clone(), StoreKey::new("test.zip")?)?;
+    let key = "data".try_into()?;
+    if cfg!(feature = "zstd") {
+        assert_eq!(store.get(&key)?.unwrap(), data);
+        assert_eq!(
+            store
+                .get_partial(&key, ByteRange::FromStart(10, Some(20)))?
+                .unwrap(),
+            &data[10..30]
+        );
+        assert_eq!(
+            store
+                .get_partial(&key, ByteRange::FromStart(1_400_000, Some(20)))?
+                .unwrap(),
+            &data[1_400_000..1_400_020]
+        );
+        assert_eq!(
+            store.get_partial(&key, ByteRange::Suffix(20))?.unwrap(),
+            &data[data.len() - 20..]
+        );
+    } else {
+        let err = store.get(&key).unwrap_err();
+        assert!(matches!(err, zarrs_storage::StorageError::Unsupported(_)));
+        assert!(
+            err.to_string()
+                .contains("unsupported compression method zstd (93); enable the `zstd` feature")
+        );
+        return Ok(());
+    }
+
+    // Zstandard entries are checked against the CRC-32 of the central directory as they are decompressed
+    let path = tmp_dir.path().join("test.zip");
+    let mut bytes = std::fs::read(&path)?;
+    let central_header = bytes
+        .windows(4)
+        .position(|signature| signature == b"PK\x01\x02")
+        .unwrap();
+    for crc32 in [14, central_header + 16] {
+        bytes[crc32] ^= 0xff;
+    }
+    std::fs::write(&path, bytes)?;
+    let store = ZipStorageAdapter::new(fs_store, StoreKey::new("test.zip")?)?;
+    let err = store.get(&key).unwrap_err();
+    assert!(err.to_string().contains("CRC-32 mismatch"));
+
+    Ok(())
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_zstd_streaming() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::byte_range::ByteRange;
+
+    // Poorly compressible data of more than 1 MiB, so that the compressed data is decoded in multiple chunks
+    let mut state = 1u32;
+    let data: Vec<u8> = (0..1_500_000)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            state.to_le_bytes()[2]
+        })
+        .collect();
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Zstd);
+    let tmp_dir = tempfile::TempDir::new()?;
+    let mut zip = zip::ZipWriter::new(File::create(tmp_dir.path().join("test.zip"))?);
+    zip.start_file("data", options)?;
+    zip.write_all(&data)?;
+    zip.finish()?;
+    // And with a data descriptor
+    let mut zip = zip::ZipWriter::new_stream(File::create(tmp_dir.path().join("stream.zip"))?);
+    zip.start_file("data", options)?;
+    zip.write_all(&data)?;
+    zip.finish()?;
+
+    let fs_store: Arc<FilesystemStore> = FilesystemStore::new(tmp_dir.path())?.into();
+    let key: StoreKey = "data".try_into()?;
+    for name in ["test.zip", "stream.zip"] {
+        for read_buffer_size in [u64::MAX, 64 * 1024] {
+            let store = ZipStorageAdapter::new(fs_store.clone(), StoreKey::new(name)?)?
+                .with_read_buffer_size(read_buffer_size);
+            assert_eq!(store.get(&key)?.unwrap(), data);
+            for (byte_range, expected) in [
+                (ByteRange::FromStart(0, Some(10)), &data[..10]),
+                (
+                    ByteRange::FromStart(1_200_000, Some(10)),
+                    &data[1_200_000..1_200_010],
+                ),
+                (ByteRange::Suffix(10), &data[data.len() - 10..]),
+            ] {
+                assert_eq!(store.get_partial(&key, byte_range)?.unwrap(), expected);
+            }
+            let blocks = store
+                .get_blocks(&key, 1024 * 1024)?
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()?;
+            assert_eq!(blocks.concat(), data);
+        }
+    }
+
+    // A corrupt entry is an error naming the key rather than a panic
+    let path = tmp_dir.path().join("test.zip");
+    let mut bytes = std::fs::read(&path)?;
+    bytes[100_000..100_100].fill(0);
+    std::fs::write(&path, bytes)?;
+    let store = ZipStorageAdapter::new(fs_store, StoreKey::new("test.zip")?)?;
+    let err = store.get(&key).unwrap_err();
+    assert!(err.to_string().contains("zip entry data"));
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_unsupported_method() -> Result<(), Box<dyn Error>> {
+    use zarrs_zip::{Method, ZipStorageAdapterCreateError};
+
+    let tmp_dir = tempfile::TempDir::new()?;
+    {
+        let file = File::create(tmp_dir.path().join("test.zip"))?;
+        let mut zip = zip::ZipWriter::new(file);
+        let deflated =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        zip.start_file("a", deflated)?;
+        zip.write_all(b"deflated")?;
+        let stored =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        zip.start_file("b", stored)?;
+        zip.write_all(b"ppmd")?;
+        zip.finish()?;
+    }
+
+    // Relabel the last entry as PPMd (method 98) in its local file header and central directory header
+    let path = tmp_dir.path().join("test.zip");
+    let mut bytes = std::fs::read(&path)?;
+    for (signature, method) in [(b"PK\x03\x04", 8), (b"PK\x01\x02", 10)] {
+        let header = bytes
+            .windows(4)
+            .rposition(|window| window == signature)
+            .unwrap();
+        bytes[header + method..header + method + 2].copy_from_slice(&98u16.to_le_bytes());
+    }
+    std::fs::write(&path, bytes)?;
+
+    let store: Arc<FilesystemStore> = FilesystemStore::new(tmp_dir.path())?.into();
+    let zip_key = StoreKey::new("test.zip")?;
+    let zip_store = ZipStorageAdapter::new(store.clone(), zip_key.clone())?;
+    assert_eq!(
+        zip_store.get(&"a".try_into()?)?.unwrap(),
+        b"deflated".as_slice()
+    );
+    let err = zip_store.get(&"b".try_into()?).unwrap_err();
+    assert!(matches!(err, zarrs_storage::StorageError::Unsupported(_)));
+    assert!(
+        err.to_string()
+            .contains("unsupported compression method 98")
+    );
+    assert_eq!(
+        zip_store.unsupported_methods(),
+        vec![("b".try_into()?, Method::from(98))]
+    );
+
+    let Err(ZipStorageAdapterCreateError::UnsupportedMethods(unsupported)) =
+        ZipStorageAdapter::new(store, zip_key)?.validate_methods()
+    else {
+        panic!("expected unsupported methods");
+    };
+    assert_eq!(unsupported.len(), 1);
+    assert_eq!(unsupported[0].0.as_str(), "b");
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_bzip2() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::byte_range::ByteRange;
+    use zarrs_zip::ZipCompressionMethod;
+
+    // Written by Python's zipfile with `ZIP_BZIP2`
+    let storage = Arc::new(FilesystemStore::new(Path::new("tests/bzip2.zip"))?);
+    let store = ZipStorageAdapter::new(storage, StoreKey::root())?;
+    let data: Vec<u8> = (0..10_000u32)
+        .map(|i| u8::try_from(i % 251).unwrap())
+        .collect();
+    let (key, empty) = ("data".try_into()?, "empty".try_into()?);
+    assert_eq!(store.method(&key), Some(ZipCompressionMethod::Bzip2));
+    assert_eq!(store.method(&empty), Some(ZipCompressionMethod::Bzip2));
+    if !cfg!(feature = "bzip2") {
+        let err = store.get(&key).unwrap_err();
+        assert!(matches!(err, zarrs_storage::StorageError::Unsupported(_)));
+        assert!(
+            err.to_string()
+                .contains("unsupported compression method bzip2 (12); enable the `bzip2` feature")
+        );
+        return Ok(());
+    }
+
+    assert!(store.unsupported_methods().is_empty());
+    assert_eq!(store.get(&key)?.unwrap(), data);
+    let values = store
+        .get_partial_many(
+            &key,
+            Box::new(
+                [
+                    ByteRange::FromStart(10, Some(20)),
+                    ByteRange::FromStart(9_000, None),
+                    ByteRange::Suffix(5),
+                ]
+                .into_iter(),
+            ),
+        )?
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(values, [&data[10..30], &data[9_000..], &data[9_995..]]);
+    assert_eq!(store.get(&empty)?.unwrap(), &[][..]);
+    assert_eq!(
+        store
+            .get_partial(&empty, ByteRange::FromStart(0, Some(0)))?
+            .unwrap(),
+        &[][..]
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_lzma() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::byte_range::ByteRange;
+    use zarrs_zip::ZipCompressionMethod;
+
+    // Written by Python's zipfile with `ZIP_LZMA`, the data of the entry starts with the LZMA properties header
+    let bytes = std::fs::read("tests/lzma.zip")?;
+    let data: Vec<u8> = (0..100_000u32)
+        .map(|i| u8::try_from(i * 7 % 251).unwrap())
+        .collect();
+    let store = Arc::new(MemoryStore::new());
+    let zip_key = StoreKey::new("test.zip")?;
+    store.set(&zip_key, bytes.clone().into())?;
+    let zip_store = ZipStorageAdapter::new(store.clone(), zip_key.clone())?;
+    let key = "data".try_into()?;
+    assert_eq!(zip_store.method(&key), Some(ZipCompressionMethod::Lzma));
+    if !cfg!(feature = "lzma") {
+        let err = zip_store.get(&key).unwrap_err();
+        assert!(matches!(err, zarrs_storage::StorageError::Unsupported(_)));
+        assert!(
+            err.to_string()
+                .contains("unsupported compression method lzma (14); enable the `lzma` feature")
+        );
+        return Ok(());
+    }
+
+    assert!(zip_store.unsupported_methods().is_empty());
+    assert_eq!(zip_store.get(&key)?.unwrap(), data);
+    assert_eq!(
+        zip_store
+            .get_partial(&key, ByteRange::Suffix(100))?
+            .unwrap(),
+        &data[data.len() - 100..]
+    );
+    assert_eq!(
+        zip_store
+            .get_partial(&key, ByteRange::FromStart(10, Some(20)))?
+            .unwrap(),
+        &data[10..30]
+    );
+    let blocks = zip_store
+        .get_blocks(&key, 64 * 1024)?
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(blocks.concat(), data);
+    // The properties header is split across reads with a small read buffer
+    let zip_store = zip_store.with_read_buffer_size(3);
+    assert_eq!(zip_store.get(&key)?.unwrap(), data);
+
+    // Corrupt the properties of the LZMA stream, after the version and size of the properties header
+    let mut bytes = bytes;
+    let name_len = usize::from(u16::from_le_bytes([bytes[26], bytes[27]]));
+    let extra_len = usize::from(u16::from_le_bytes([bytes[28], bytes[29]]));
+    bytes[30 + name_len + extra_len + 4] = 0xff;
+    store.set(&zip_key, bytes.into())?;
+    let zip_store = ZipStorageAdapter::new(store, zip_key)?;
+    let err = zip_store.get(&key).unwrap_err();
+    assert!(matches!(err, zarrs_storage::StorageError::Other(_)));
+    assert!(err.to_string().contains("zip entry data"));
+    assert!(err.to_string().contains("invalid LZMA properties"));
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_deflate64() -> Result<(), Box<dyn Error>> {
+    use zarrs_zip::{Method, ZipCompressionMethod};
+
+    // Deflate streams of stored blocks are also valid Deflate64 streams
+    let data: Vec<u8> = (0..10_000u32)
+        .map(|i| u8::try_from(i % 251).unwrap())
+        .collect();
+    let compressed = miniz_oxide::deflate::compress_to_vec(&data, 0);
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    zip.start_file("data", options)?;
+    zip.write_all(&compressed)?;
+    let mut bytes = zip.finish()?.into_inner();
+
+    // Relabel the entry as Deflate64 (method 9) of `data` in its local file header and central directory header
+    let central_header = bytes
+        .windows(4)
+        .position(|signature| signature == b"PK\x01\x02")
+        .unwrap();
+    for (method, crc32, uncompressed_size) in [
+        (8, 14, 22),
+        (
+            central_header + 10,
+            central_header + 16,
+            central_header + 24,
+        ),
+    ] {
+        bytes[method..method + 2].copy_from_slice(&9u16.to_le_bytes());
+        bytes[crc32..crc32 + 4].copy_from_slice(&crc32fast::hash(&data).to_le_bytes());
+        bytes[uncompressed_size..uncompressed_size + 4]
+            .copy_from_slice(&u32::try_from(data.len())?.to_le_bytes());
+    }
+
+    let store = Arc::new(MemoryStore::new());
+    let zip_key = StoreKey::new("test.zip")?;
+    store.set(&zip_key, bytes.into())?;
+    let zip_store = ZipStorageAdapter::new(store, zip_key)?;
+    let key = "data".try_into()?;
+    assert_eq!(
+        zip_store.method(&key),
+        Some(ZipCompressionMethod::Deflate64)
+    );
+    if cfg!(feature = "deflate64") {
+        assert!(zip_store.unsupported_methods().is_empty());
+        assert_eq!(zip_store.get(&key)?.unwrap(), data);
+    } else {
+        assert_eq!(
+            zip_store.unsupported_methods(),
+            vec![(key.clone(), Method::Deflate64)]
+        );
+        // Reading the entry is an error naming the method rather than a panic
+        let err = zip_store.get(&key).unwrap_err();
+        assert!(matches!(err, zarrs_storage::StorageError::Unsupported(_)));
+        assert!(err.to_string().contains(
+            "unsupported compression method deflate64 (9); enable the `deflate64` feature"
+        ));
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_zip64() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::byte_range::ByteRange;
+
+    let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    let tmp_dir = tempfile::TempDir::new()?;
+    let path = tmp_dir.path().join("test.zip");
+    {
+        let mut zip = zip::ZipWriter::new(File::create(&path)?);
+        for method in [
+            zip::CompressionMethod::Stored,
+            zip::CompressionMethod::Deflated,
+        ] {
+            // Force the ZIP64 extra field, with saturated 32-bit sizes in the local file header
+            let options = SimpleFileOptions::default()
+                .compression_method(method)
+                .large_file(true);
+            zip.start_file(format!("{method}"), options)?;
+            zip.write_all(&data)?;
+        }
+        // More than 65,535 entries require the ZIP64 end of central directory record
+        let options =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        for i in 0..70_000 {
+            zip.start_file(format!("n/{i}"), options)?;
+        }
+        zip.finish()?;
+    }
+    let bytes = std::fs::read(&path)?;
+    // The ZIP64 end of central directory record is present
+    assert!(bytes.windows(4).any(|w| w == [0x50, 0x4b, 0x06, 0x06]));
+
+    let store = FilesystemStore::new(tmp_dir.path())?;
+    let store = ZipStorageAdapter::new(store.into(), StoreKey::new("test.zip")?)?;
+    assert_eq!(store.list_prefix(&"n/".try_into()?)?.len(), 70_000);
+    assert_eq!(store.get(&"n/69999".try_into()?)?.unwrap(), b"".as_slice());
+    for key in ["Stored", "Deflated"] {
+        let key = key.try_into()?;
+        assert_eq!(store.size_key(&key)?, Some(data.len() as u64));
+        assert_eq!(store.get(&key)?.unwrap(), data);
+        assert_eq!(
+            store.get_partial(&key, ByteRange::Suffix(10))?.unwrap(),
+            &data[data.len() - 10..]
+        );
+    }
+
+    Ok(())
+}
+
+/// A local file header of a stored entry, with `size` as both its compressed and uncompressed size.
+fn stored_local_header(name: &str, crc32: u32, size: u32, extra: &[u8]) -> Vec<u8> {
+    let mut header = Vec::new();
+    header.extend_from_slice(b"PK\x03\x04");
+    header.extend_from_slice(&[45, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    header.extend_from_slice(&crc32.to_le_bytes());
+    header.extend_from_slice(&size.to_le_bytes());
+    header.extend_from_slice(&size.to_le_bytes());
+    header.extend_from_slice(&u16::try_from(name.len()).unwrap().to_le_bytes());
+    header.extend_from_slice(&u16::try_from(extra.len()).unwrap().to_le_bytes());
+    header.extend_from_slice(name.as_bytes());
+    header.extend_from_slice(extra);
+    header
+}
+
+/// A central directory header of a stored entry, with `size` as both its compressed and uncompressed size.
+fn stored_central_header(name: &str, crc32: u32, size: u32, offset: u32, extra: &[u8]) -> Vec<u8> {
+    let mut header = Vec::new();
+    header.extend_from_slice(b"PK\x01\x02");
+    header.extend_from_slice(&[45, 0, 45, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    header.extend_from_slice(&crc32.to_le_bytes());
+    header.extend_from_slice(&size.to_le_bytes());
+    header.extend_from_slice(&size.to_le_bytes());
+    header.extend_from_slice(&u16::try_from(name.len()).unwrap().to_le_bytes());
+    header.extend_from_slice(&u16::try_from(extra.len()).unwrap().to_le_bytes());
+    header.extend_from_slice(&[0; 10]);
+    header.extend_from_slice(&offset.to_le_bytes());
+    header.extend_from_slice(name.as_bytes());
+    header.extend_from_slice(extra);
+    header
+}
+
+/// A ZIP64 extended information extra field with `fields`.
+fn zip64_extra_field(fields: &[u64]) -> Vec<u8> {
+    let mut extra = Vec::new();
+    extra.extend_from_slice(&1u16.to_le_bytes());
+    extra.extend_from_slice(&u16::try_from(fields.len() * 8).unwrap().to_le_bytes());
+    for field in fields {
+        extra.extend_from_slice(&field.to_le_bytes());
+    }
+    extra
+}
+
+/// The ZIP64 end of central directory record at `zip64_end_offset` and its locator, and the end of central directory record, of a zip file with `num_entries` entries and a central directory of `size` bytes at `offset`.
+fn zip64_end_records(num_entries: u16, size: u64, offset: u64, zip64_end_offset: u64) -> Vec<u8> {
+    let mut end = Vec::new();
+    end.extend_from_slice(b"PK\x06\x06");
+    end.extend_from_slice(&44u64.to_le_bytes());
+    end.extend_from_slice(&[45, 0, 45, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    end.extend_from_slice(&u64::from(num_entries).to_le_bytes());
+    end.extend_from_slice(&u64::from(num_entries).to_le_bytes());
+    end.extend_from_slice(&size.to_le_bytes());
+    end.extend_from_slice(&offset.to_le_bytes());
+    end.extend_from_slice(b"PK\x06\x07");
+    end.extend_from_slice(&0u32.to_le_bytes());
+    end.extend_from_slice(&zip64_end_offset.to_le_bytes());
+    end.extend_from_slice(&1u32.to_le_bytes());
+    end.extend_from_slice(b"PK\x05\x06");
+    end.extend_from_slice(&[0; 4]);
+    end.extend_from_slice(&num_entries.to_le_bytes());
+    end.extend_from_slice(&num_entries.to_le_bytes());
+    end.extend_from_slice(&u32::try_from(size).unwrap_or(u32::MAX).to_le_bytes());
+    end.extend_from_slice(&u32::try_from(offset).unwrap_or(u32::MAX).to_le_bytes());
+    end.extend_from_slice(&[0, 0]);
+    end
+}
+
+#[test]
+#[ignore = "writes a sparse zip file larger than 4 GiB"]
+fn zip_zip64_large() -> Result<(), Box<dyn Error>> {
+    use std::io::{Seek, SeekFrom};
+    use zarrs_storage::byte_range::ByteRange;
+
+    // A stored entry larger than 4 GiB, mostly a hole in a sparse file, followed by an entry with a local file header beyond 4 GiB
+    const BIG_SIZE: u64 = (4 << 30) + (1 << 20);
+    let markers: [(u64, &[u8]); 3] = [
+        (0, b"start"),
+        ((4 << 30) - 2, b"boundary"),
+        (BIG_SIZE - 3, b"end"),
+    ];
+    let mut crc = crc32fast::Hasher::new();
+    for offset in (0..BIG_SIZE).step_by(1 << 20) {
+        let mut chunk = vec![0u8; 1 << 20];
+        for (marker_offset, marker) in markers {
+            for (i, byte) in (marker_offset..).zip(marker) {
+                if let Some(b) = i
+                    .checked_sub(offset)
+                    .and_then(|j| chunk.get_mut(usize::try_from(j).ok()?))
+                {
+                    *b = *byte;
+                }
+            }
+        }
+        crc.update(&chunk);
+    }
+    let big_crc32 = crc.finalize();
+
+    let tmp_dir = tempfile::TempDir::new()?;
+    let path = tmp_dir.path().join("test.zip");
+    let mut file = File::create(&path)?;
+    // The local file header of the large entry has saturated sizes and the ZIP64 extra field
+    let big_extra = zip64_extra_field(&[BIG_SIZE; 2]);
+    let big_header = stored_local_header("big", big_crc32, u32::MAX, &big_extra);
+    file.write_all(&big_header)?;
+    let big_data_offset = big_header.len() as u64;
+    for (marker_offset, marker) in markers {
+        file.seek(SeekFrom::Start(big_data_offset + marker_offset))?;
+        file.write_all(marker)?;
+    }
+    let small_offset = big_data_offset + BIG_SIZE;
+    let small_crc32 = crc32fast::hash(b"after");
+    file.seek(SeekFrom::Start(small_offset))?;
+    file.write_all(&stored_local_header("small", small_crc32, 5, &[]))?;
+    file.write_all(b"after")?;
+
+    // The central directory beyond 4 GiB, with the ZIP64 end of central directory record and locator
+    let central_directory_offset = file.stream_position()?;
+    let mut central_directory = stored_central_header("big", big_crc32, u32::MAX, 0, &big_extra);
+    central_directory.extend(stored_central_header(
+        "small",
+        small_crc32,
+        5,
+        u32::MAX,
+        &zip64_extra_field(&[small_offset]),
+    ));
+    file.write_all(&central_directory)?;
+    let zip64_end_offset = file.stream_position()?;
+    file.write_all(&zip64_end_records(
+        2,
+        central_directory.len() as u64,
+        central_directory_offset,
+        zip64_end_offset,
+    ))?;
+    drop(file);
+    assert!(std::fs::metadata(&path)?.len() > 4 << 30);
+
+    let store = FilesystemStore::new(tmp_dir.path())?;
+    let zip_store = ZipStorageAdapter::new(store.into(), StoreKey::new("test.zip")?)?;
+    assert_eq!(zip_store.list()?, &["big".try_into()?, "small".try_into()?]);
+    assert_eq!(zip_store.validate(), Ok(()));
+    assert!(zip_store.capabilities().zip64);
+
+    // Sizes and offsets beyond 4 GiB are read from the ZIP64 extra fields
+    let (big, small) = ("big".try_into()?, "small".try_into()?);
+    assert_eq!(zip_store.size_key(&big)?, Some(BIG_SIZE));
+    let metadata = zip_store.entry_metadata(&big).unwrap();
+    assert_eq!(
+        (metadata.compressed_size, metadata.crc32),
+        (BIG_SIZE, big_crc32)
+    );
+    assert_eq!(zip_store.get(&small)?.unwrap(), b"after"[..]);
+    assert_eq!(zip_store.size_prefix(&"".try_into()?)?, BIG_SIZE + 5);
+
+    // Partial reads of the large entry either side of 4 GiB
+    for (marker_offset, marker) in markers {
+        let byte_range = ByteRange::FromStart(marker_offset, Some(marker.len() as u64));
+        assert_eq!(zip_store.get_partial(&big, byte_range)?.unwrap(), marker);
+    }
+    assert_eq!(
+        zip_store.get_partial(&big, ByteRange::Suffix(3))?.unwrap(),
+        b"end"[..]
+    );
+    assert_eq!(
+        zip_store
+            .get_partial(&big, ByteRange::FromStart(1 << 32, Some(4)))?
+            .unwrap(),
+        b"unda"[..]
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "write")]
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_write_zip64() -> Result<(), Box<dyn Error>> {
+    let zip_key = StoreKey::new("test.zip")?;
+    let is_zip64 = |bytes: &[u8]| bytes.windows(4).any(|w| w == [0x50, 0x4b, 0x06, 0x06]);
+
+    // More than 65,535 entries require the ZIP64 end of central directory record
+    let memory_store = Arc::new(MemoryStore::default());
+    for i in 0..70_000 {
+        memory_store.set(&StoreKey::new(format!("c/{i}"))?, vec![1, 2, 3].into())?;
+    }
+    let dst_store = Arc::new(MemoryStore::default());
+    let summary = zarrs_zip::store_to_zip(
+        &*memory_store,
+        &*dst_store,
+        &zip_key,
+        &ZipWriteOptions::default(),
+    )?;
+    assert_eq!(summary.num_entries, 70_000);
+    let archive = dst_store.get(&zip_key)?.unwrap();
+    assert!(is_zip64(&archive));
+    assert_eq!(
+        zip::ZipArchive::new(std::io::Cursor::new(archive.to_vec()))?.len(),
+        70_000
+    );
+    let zip_store = ZipStorageAdapter::open_append(dst_store.clone(), zip_key.clone())?;
+    assert_eq!(zip_store.list()?.len(), 70_000);
+    assert_eq!(
+        zip_store.get(&"c/69999".try_into()?)?.unwrap(),
+        vec![1, 2, 3]
+    );
+
+    // Appending to a ZIP64 archive
+    zip_store.set(&"zarr.json".try_into()?, b"{}".to_vec().into())?;
+    zip_store.finish()?;
+    let zip_store = ZipStorageAdapter::new(dst_store, zip_key.clone())?;
+    assert_eq!(zip_store.list()?.len(), 70_001);
+    assert_eq!(
+        zip_store.get(&"zarr.json".try_into()?)?.unwrap(),
+        b"{}".as_slice()
+    );
+
+    // ZIP64 extensions can be forced for small archives
+    let memory_store = Arc::new(MemoryStore::default());
+    memory_store.set(&"a".try_into()?, vec![0, 1, 2, 3].into())?;
+    memory_store.set(&"b".try_into()?, vec![4, 5].into())?;
+    let dst_store = Arc::new(MemoryStore::default());
+    let options = ZipWriteOptions::default().force_zip64(true);
+    zarrs_zip::store_to_zip(&*memory_store, &*dst_store, &zip_key, &options)?;
+    let archive = dst_store.get(&zip_key)?.unwrap();
+    assert!(is_zip64(&archive));
+    let mut zip = zip::ZipArchive::new(std::io::Cursor::new(archive.to_vec()))?;
+    let mut value = Vec::new();
+    zip.by_name("a")?.read_to_end(&mut value)?;
+    assert_eq!(value, vec![0, 1, 2, 3]);
+    let zip_store = ZipStorageAdapter::new(dst_store, zip_key)?;
+    assert_eq!(zip_store.get(&"b".try_into()?)?.unwrap(), vec![4, 5]);
+    assert_eq!(zip_store.size_key(&"a".try_into()?)?, Some(4));
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_data_descriptor() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::byte_range::ByteRange;
+
+    let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    let tmp_dir = tempfile::TempDir::new()?;
+    let path = tmp_dir.path().join("test.zip");
+    {
+        // A streaming writer cannot seek back to the local file header, so sizes and CRC-32s are written to data descriptors
+        let mut zip = zip::ZipWriter::new_stream(File::create(&path)?);
+        for method in [
+            zip::CompressionMethod::Stored,
+            zip::CompressionMethod::Deflated,
+        ] {
+            let options = SimpleFileOptions::default().compression_method(method);
+            zip.start_file(format!("{method}"), options)?;
+            zip.write_all(&data)?;
+        }
+        zip.finish()?;
+    }
+    let bytes = std::fs::read(&path)?;
+    // General purpose flag bit 3 is set, and the sizes in the local file header are zero
+    assert_eq!(u16::from_le_bytes([bytes[6], bytes[7]]) & (1 << 3), 1 << 3);
+    assert_eq!(&bytes[18..26], &[0; 8]);
+
+    let store = FilesystemStore::new(tmp_dir.path())?;
+    let store = ZipStorageAdapter::new_verified(store.into(), StoreKey::new("test.zip")?)?;
+    for key in ["Stored", "Deflated"] {
+        let key = key.try_into()?;
+        assert_eq!(store.size_key(&key)?, Some(data.len() as u64));
+        assert_eq!(store.get(&key)?.unwrap(), data);
+        assert_eq!(
+            store
+                .get_partial(&key, ByteRange::FromStart(50_000, Some(10)))?
+                .unwrap(),
+            &data[50_000..50_010]
+        );
+        assert_eq!(
+            store.get_partial(&key, ByteRange::Suffix(10))?.unwrap(),
+            &data[data.len() - 10..]
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_capabilities() -> Result<(), Box<dyn Error>> {
+    use zarrs_zip::{ArchiveCapabilities, SymlinkPolicy, ZipStorageAdapterBuilder};
+
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    zip.start_file("a/zarr.json", SimpleFileOptions::default())?;
+    zip.write_all(b"{}")?;
+    let bzip2 = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Bzip2);
+    zip.start_file("a/c/0", bzip2)?;
+    zip.write_all(b"bzip2")?;
+    let aes = SimpleFileOptions::default().with_aes_encryption(zip::AesMode::Aes256, "password");
+    zip.start_file("a/c/1", aes)?;
+    zip.write_all(b"aes")?;
+    zip.add_symlink(
+        "b/zarr.json",
+        "../a/zarr.json",
+        SimpleFileOptions::default(),
+    )?;
+    let bytes = zip.finish()?.into_inner();
+
+    let store = Arc::new(MemoryStore::new());
+    let zip_key = StoreKey::new("test.zip")?;
+    store.set(&zip_key, bytes.into())?;
+    let zip_store = ZipStorageAdapter::new(store.clone(), zip_key.clone())?;
+    // The bzip2 entry and the AES encrypted entry need the `bzip2` and `encryption` features
+    let unsupported_methods =
+        usize::from(!cfg!(feature = "bzip2")) + usize::from(!cfg!(feature = "encryption"));
+    assert_eq!(
+        zip_store.capabilities(),
+        ArchiveCapabilities {
+            zip64: false,
+            encrypted: 1,
+            unsupported_methods,
+            symlinks: 1,
+        }
+    );
+
+    // Symbolic links are counted whether or not they are keys, but only within the path of the adapter
+    let zip_store = ZipStorageAdapterBuilder::new(store.clone(), zip_key.clone())
+        .symlink_policy(SymlinkPolicy::AsBytes)
+        .build()?;
+    assert_eq!(zip_store.capabilities().symlinks, 1);
+    let zip_store = ZipStorageAdapterBuilder::new(store, zip_key)
+        .path("a")
+        .build()?;
+    assert_eq!(
+        zip_store.capabilities(),
+        ArchiveCapabilities {
+            zip64: false,
+            encrypted: 1,
+            unsupported_methods,
+            symlinks: 0,
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_symlink() -> Result<(), Box<dyn Error>> {
+    use zarrs_zip::{SymlinkPolicy, ZipStorageAdapterBuilder, ZipStorageAdapterCreateError};
+
+    let tmp_dir = tempfile::TempDir::new()?;
+    let path = tmp_dir.path().join("test.zip");
+    {
+        let mut zip = zip::ZipWriter::new(File::create(&path)?);
+        zip.start_file("a/zarr.json", SimpleFileOptions::default())?;
+        zip.write_all(b"{}")?;
+        zip.add_symlink(
+            "b/zarr.json",
+            "../a/zarr.json",
+            SimpleFileOptions::default(),
+        )?;
+        zip.finish()?;
+    }
+    let store: Arc<FilesystemStore> = Arc::new(FilesystemStore::new(tmp_dir.path())?);
+    let zip_key = StoreKey::new("test.zip")?;
+    let builder = || ZipStorageAdapterBuilder::new(store.clone(), zip_key.clone());
+    let key_a = StoreKey::new("a/zarr.json")?;
+    let key_b = StoreKey::new("b/zarr.json")?;
+
+    // Symbolic links are ignored by default
+    let zip_store = ZipStorageAdapter::new(store.clone(), zip_key.clone())?;
+    assert_eq!(zip_store.list()?, vec![key_a.clone()]);
+    assert!(zip_store.get(&key_b)?.is_none());
+    let zip_store = builder().symlink_policy(SymlinkPolicy::Ignore).build()?;
+    assert_eq!(zip_store.list()?, vec![key_a.clone()]);
+
+    // The value of a symbolic link is its target
+    let zip_store = builder().symlink_policy(SymlinkPolicy::AsBytes).build()?;
+    assert_eq!(zip_store.list()?, vec![key_a.clone(), key_b.clone()]);
+    assert_eq!(
+        zip_store.get(&key_b)?.unwrap(),
+        b"../a/zarr.json".as_slice()
+    );
+    assert_eq!(zip_store.size_key(&key_b)?, Some(14));
+
+    assert!(matches!(
+        builder().symlink_policy(SymlinkPolicy::Error).build(),
+        Err(ZipStorageAdapterCreateError::Symlink(name)) if name == "b/zarr.json"
+    ));
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_concurrent_reads() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::byte_range::ByteRange;
+    use zarrs_zip::ZipStorageAdapterBuilder;
+
+    const NUM_THREADS: u8 = 16;
+
+    fn assert_send_sync<T: ?Sized + Send + Sync>() {}
+    assert_send_sync::<ZipStorageAdapter<FilesystemStore>>();
+    assert_send_sync::<ZipStorageAdapter<dyn ReadableStorageTraits>>();
+
+    let value = |i: u8| -> Vec<u8> { (0..10_000u32).map(|j| (j % 251) as u8 ^ i).collect() };
+    let tmp_dir = tempfile::TempDir::new()?;
+    let path = tmp_dir.path().join("test.zip");
+    {
+        let mut zip = zip::ZipWriter::new(File::create(&path)?);
+        for i in 0..NUM_THREADS {
+            let method = if i % 2 == 0 {
+                zip::CompressionMethod::Stored
+            } else {
+                zip::CompressionMethod::Deflated
+            };
+            let options = SimpleFileOptions::default().compression_method(method);
+            zip.start_file(format!("c/{i}"), options)?;
+            zip.write_all(&value(i))?;
+        }
+        zip.finish()?;
+    }
+
+    let store: Arc<FilesystemStore> = Arc::new(FilesystemStore::new(tmp_dir.path())?);
+    let zip_key = StoreKey::new("test.zip")?;
+    for zip_store in [
+        ZipStorageAdapter::new(store.clone(), zip_key.clone())?,
+        ZipStorageAdapterBuilder::new(store.clone(), zip_key.clone())
+            .cache_bytes(1 << 20)
+            .verify_crc(true)
+            .build()?,
+    ] {
+        let zip_store = Arc::new(zip_store);
+        std::thread::scope(|scope| {
+            let threads: Vec<_> = (0..NUM_THREADS)
+                .map(|i| {
+                    let zip_store = zip_store.clone();
+                    scope.spawn(move || -> Result<(), String> {
+                        let key = StoreKey::new(format!("c/{i}")).map_err(|e| e.to_string())?;
+                        let expected = value(i);
+                        for _ in 0..20 {
+                            let full = zip_store.get(&key).map_err(|e| e.to_string())?;
+                            assert_eq!(full.unwrap(), expected);
+                            let partial = zip_store
+                                .get_partial(&key, ByteRange::FromStart(5_000, Some(100)))
+                                .map_err(|e| e.to_string())?;
+                            assert_eq!(partial.unwrap(), &expected[5_000..5_100]);
+                        }
+                        Ok(())
+                    })
+                })
+                .collect();
+            for thread in threads {
+                thread.join().expect("thread panicked")?;
+            }
+            Ok::<_, String>(())
+        })?;
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_entry_metadata() -> Result<(), Box<dyn Error>> {
+    let data: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+    let tmp_dir = tempfile::TempDir::new()?;
+    let path = tmp_dir.path().join("test.zip");
+    {
+        let mut zip = zip::ZipWriter::new(File::create(&path)?);
+        let options = SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated)
+            .last_modified_time(zip::DateTime::from_date_and_time(2020, 1, 2, 3, 4, 6)?);
+        zip.start_file("data", options)?;
+        zip.write_all(&data)?;
+        zip.finish()?;
+    }
+    let mut zip = zip::ZipArchive::new(File::open(&path)?)?;
+    let file = zip.by_name("data")?;
+
+    let store = FilesystemStore::new(tmp_dir.path())?;
+    let store = ZipStorageAdapter::new(store.into(), StoreKey::new("test.zip")?)?;
+    let metadata = store.entry_metadata(&"data".try_into()?).unwrap();
+    assert_eq!(metadata.crc32, file.crc32());
+    assert_eq!(metadata.compressed_size, file.compressed_size());
+    assert_eq!(metadata.uncompressed_size, data.len() as u64);
+    assert_eq!(
+        metadata.modified,
+        std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_577_934_246)
+    );
+    assert!(metadata.comment.is_empty());
+    assert!(store.entry_metadata(&"missing".try_into()?).is_none());
+
+    Ok(())
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_mmap() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::byte_range::ByteRange;
+
+    let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    let tmp_dir = tempfile::TempDir::new()?;
+    let path = tmp_dir.path().join("test.zip");
+    {
+        let mut zip = zip::ZipWriter::new(File::create(&path)?);
+        let stored =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        let deflated =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        zip.start_file("a/stored", stored)?;
+        zip.write_all(&data)?;
+        zip.start_file("a/deflated", deflated)?;
+        zip.write_all(&data)?;
+        zip.finish()?;
+    }
+
+    // SAFETY: The zip file is not modified while it is mapped
+    let mmap_store = unsafe { ZipStorageAdapter::new_mmap(&path)? };
+    let store = ZipStorageAdapter::new(Arc::new(FilesystemStore::new(&path)?), StoreKey::root())?;
+    assert_eq!(mmap_store.list()?, store.list()?);
+    for key in ["a/stored", "a/deflated"] {
+        let key = key.try_into()?;
+        assert_eq!(mmap_store.get(&key)?.unwrap(), data);
+        assert_eq!(
+            mmap_store
+                .get_partial(&key, ByteRange::FromStart(1000, Some(10)))?
+                .unwrap(),
+            &data[1000..1010]
+        );
+        assert!(
+            mmap_store
+                .get_partial(&key, ByteRange::FromStart(100_000, Some(1)))
+                .is_err()
+        );
+    }
+    assert!(mmap_store.get(&"missing".try_into()?)?.is_none());
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_contains_key() -> Result<(), Box<dyn Error>> {
+    let path = Path::new("tests/zarr.zip");
+    let store = ZipStorageAdapter::new(Arc::new(FilesystemStore::new(path)?), StoreKey::root())?;
+    assert!(store.contains_key(&"zarr.json".try_into()?));
+    assert!(store.contains_key(&"foo/c/0/1".try_into()?));
+    assert!(!store.contains_key(&"foo/c/0/100".try_into()?));
+    assert!(!store.contains_key(&"foo".try_into()?));
+    Ok(())
+}
+
+#[cfg(feature = "glob")]
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_list_glob() -> Result<(), Box<dyn Error>> {
+    use zarrs_zip::ZipStorageAdapterBuilder;
+
+    let path = Path::new("tests/zarr.zip");
+    let storage = Arc::new(FilesystemStore::new(path)?);
+    let store = ZipStorageAdapter::new(storage.clone(), StoreKey::root())?;
+    let list_glob = |store: &ZipStorageAdapter<FilesystemStore>, pattern: &str| {
+        store
+            .list_glob(pattern)
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(list_glob(&store, "*/zarr.json"), ["foo/zarr.json"]);
+    assert_eq!(
+        list_glob(&store, "**/zarr.json"),
+        ["foo/zarr.json", "zarr.json"]
+    );
+    assert_eq!(
+        list_glob(&store, "foo/c/[0-1]/9"),
+        ["foo/c/0/9", "foo/c/1/9"]
+    );
+    assert_eq!(list_glob(&store, "foo/**/[!0-8]").len(), 10);
+    assert_eq!(list_glob(&store, "foo/c/**").len(), 100);
+    // `*` does not match `/`
+    assert!(list_glob(&store, "foo/c/*").is_empty());
+    assert!(list_glob(&store, "bar/**").is_empty());
+    assert!(store.list_glob("foo/[").is_err());
+
+    let store = ZipStorageAdapterBuilder::new(storage, StoreKey::root())
+        .case_insensitive(true)
+        .build()?;
+    assert_eq!(
+        list_glob(&store, "FOO/C/0/[5-6]"),
+        ["foo/c/0/5", "foo/c/0/6"]
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_from_bytes() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::byte_range::ByteRange;
+
+    let path = Path::new("tests/zarr.zip");
+    let store = ZipStorageAdapter::new(Arc::new(FilesystemStore::new(path)?), StoreKey::root())?;
+    let bytes_store = ZipStorageAdapter::from_bytes(std::fs::read(path)?)?;
+    assert_eq!(bytes_store.list()?, store.list()?);
+    for key in store.list()? {
+        assert_eq!(bytes_store.get(&key)?, store.get(&key)?);
+        assert_eq!(
+            bytes_store.get_partial(&key, ByteRange::Suffix(1))?,
+            store.get_partial(&key, ByteRange::Suffix(1))?
+        );
+    }
+    assert!(bytes_store.get(&"missing".try_into()?)?.is_none());
+
+    // A value that is not a zip file is an error
+    assert!(ZipStorageAdapter::from_bytes(vec![0u8; 100]).is_err());
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_get_entire_stored_value() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::{Bytes, byte_range::ByteRange};
+
+    let data: Vec<u8> = (0..100_000u32).map(|i| i.to_le_bytes()[1]).collect();
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    zip.start_file("c/0", options)?;
+    zip.write_all(&data)?;
+    let zip_bytes = Bytes::from(zip.finish()?.into_inner());
+    let zip_range = zip_bytes.as_ptr_range();
+    let zip_store = ZipStorageAdapter::from_bytes(zip_bytes.clone())?;
+    let key = "c/0".try_into()?;
+
+    // The entire value is a single slice of the zip file, without copying
+    let size = data.len() as u64;
+    let value = zip_store.get(&key)?.unwrap();
+    assert_eq!(value, data);
+    assert!(zip_range.contains(&value.as_ptr()));
+    for byte_range in [
+        ByteRange::FromStart(0, None),
+        ByteRange::FromStart(0, Some(size)),
+        ByteRange::Suffix(size),
+    ] {
+        let values: Vec<Bytes> = zip_store
+            .get_partial_many(&key, Box::new(std::iter::once(byte_range)))?
+            .unwrap()
+            .collect::<Result<_, _>>()?;
+        assert_eq!(values.len(), 1);
+        assert_eq!(values[0], data);
+        assert!(zip_range.contains(&values[0].as_ptr()));
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_entries() -> Result<(), Box<dyn Error>> {
+    use zarrs_zip::{Method, ZipEntryInfo};
+
+    let data = vec![7u8; 10_000];
+    let tmp_dir = tempfile::TempDir::new()?;
+    let path = tmp_dir.path().join("test.zip");
+    {
+        let mut zip = zip::ZipWriter::new(File::create(&path)?);
+        let stored =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        let deflated =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        zip.start_file("c", deflated)?;
+        zip.write_all(&data)?;
+        zip.add_directory("a/", stored)?;
+        zip.start_file("a/b", stored)?;
+        zip.write_all(&data)?;
+        zip.finish()?;
+    }
+    let compressed_size = zip::ZipArchive::new(File::open(&path)?)?
+        .by_name("c")?
+        .compressed_size();
+
+    let store = FilesystemStore::new(tmp_dir.path())?;
+    let store = ZipStorageAdapter::new(store.into(), StoreKey::new("test.zip")?)?;
+    let entries: Vec<(String, ZipEntryInfo)> = store.entries().collect();
+    assert_eq!(
+        entries,
+        vec![
+            ("a/".to_string(), ZipEntryInfo::Directory),
+            (
+                "a/b".to_string(),
+                ZipEntryInfo::File {
+                    uncompressed_size: 10_000,
+                    compressed_size: 10_000,
+                    method: Method::Store
+                }
+            ),
+            (
+                "c".to_string(),
+                ZipEntryInfo::File {
+                    uncompressed_size: 10_000,
+                    compressed_size,
+                    method: Method::Deflate
+                }
+            ),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_validate() -> Result<(), Box<dyn Error>> {
+    use zarrs_zip::ZipValidationIssue;
+
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    for name in ["a", "b", "c"] {
+        zip.start_file(name, options)?;
+        zip.write_all(&[0; 100])?;
+    }
+    let mut bytes = zip.finish()?.into_inner();
+    let size = bytes.len() as u64;
+
+    let store = Arc::new(MemoryStore::new());
+    let zip_key = StoreKey::new("test.zip")?;
+    store.set(&zip_key, bytes.clone().into())?;
+    assert_eq!(
+        ZipStorageAdapter::new(store.clone(), zip_key.clone())?.validate(),
+        Ok(())
+    );
+
+    // The compressed size of `a` extends into `b`, and the local file header of `c` is beyond the end of the zip file
+    let central_headers: Vec<usize> = bytes
+        .windows(4)
+        .enumerate()
+        .filter(|(_, signature)| *signature == b"PK\x01\x02")
+        .map(|(offset, _)| offset)
+        .collect();
+    bytes[central_headers[0] + 20..central_headers[0] + 24].copy_from_slice(&150u32.to_le_bytes());
+    bytes[central_headers[2] + 42..central_headers[2] + 46]
+        .copy_from_slice(&1_000_000u32.to_le_bytes());
+    store.set(&zip_key, bytes.into())?;
+    let zip_store = ZipStorageAdapter::new(store, zip_key)?;
+    assert_eq!(zip_store.size_key(&"c".try_into()?)?, Some(100));
+    let issues = zip_store.validate().unwrap_err();
+    assert_eq!(
+        issues,
+        vec![
+            ZipValidationIssue::StoredSizeMismatch {
+                key: "a".try_into()?,
+                compressed_size: 150,
+                uncompressed_size: 100,
+            },
+            ZipValidationIssue::Overlap {
+                key: "b".try_into()?,
+                other: "a".try_into()?,
+            },
+            ZipValidationIssue::HeaderOutOfBounds {
+                key: "c".try_into()?,
+                header_offset: 1_000_000,
+                size,
+            },
+        ]
+    );
+    assert!(issues[2].to_string().contains("zip entry c"));
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_method() -> Result<(), Box<dyn Error>> {
+    use zarrs_zip::{Method, ZipCompressionMethod};
+
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let stored = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    let deflated =
+        SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    zip.add_directory("a/", stored)?;
+    zip.start_file("a/stored", stored)?;
+    zip.write_all(b"stored")?;
+    zip.start_file("a/deflated", deflated)?;
+    zip.write_all(b"deflated")?;
+    let memory_store = Arc::new(MemoryStore::new());
+    memory_store.set(
+        &StoreKey::new("test.zip")?,
+        zip.finish()?.into_inner().into(),
+    )?;
+
+    let store = ZipStorageAdapter::new(memory_store, StoreKey::new("test.zip")?)?;
+    assert_eq!(
+        store.method(&"a/stored".try_into()?),
+        Some(ZipCompressionMethod::Stored)
+    );
+    assert_eq!(
+        store.method(&"a/deflated".try_into()?),
+        Some(ZipCompressionMethod::Deflate)
+    );
+    assert_eq!(store.method(&"a".try_into()?), None);
+    assert_eq!(store.method(&"missing".try_into()?), None);
+
+    // Methods map from their codes, rather than the zip implementation
+    assert_eq!(
+        ZipCompressionMethod::from(Method::Bzip2),
+        ZipCompressionMethod::Bzip2
+    );
+    assert_eq!(
+        ZipCompressionMethod::from(Method::Unrecognized(95)),
+        ZipCompressionMethod::Other(95)
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_verify_crc32() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::byte_range::ByteRange;
+
+    let data: Vec<u8> = b"0123456789".repeat(10);
+    let tmp_dir = tempfile::TempDir::new()?;
+    let path = tmp_dir.path().join("test.zip");
+    {
+        let mut zip = zip::ZipWriter::new(File::create(&path)?);
+        let options =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        zip.start_file("data", options)?;
+        zip.write_all(&data)?;
+        zip.finish()?;
+    }
+
+    // Flip a byte of the stored data
+    let mut bytes = std::fs::read(&path)?;
+    let data_offset = bytes
+        .windows(data.len())
+        .position(|window| window == data)
+        .unwrap();
+    bytes[data_offset + 50] ^= 0xff;
+    std::fs::write(&path, bytes)?;
+
+    let fs_store: Arc<FilesystemStore> = FilesystemStore::new(tmp_dir.path())?.into();
+    let zip_key = StoreKey::new("test.zip")?;
+    let key = "data".try_into()?;
+
+    // Reads are not verified by default
+    let store = ZipStorageAdapter::new(fs_store.clone(), zip_key.clone())?;
+    assert_ne!(store.get(&key)?.unwrap(), data);
+
+    let store = ZipStorageAdapter::new_verified(fs_store, zip_key)?;
+    let err = store.get(&key).unwrap_err();
+    assert!(err.to_string().contains("CRC-32 mismatch"));
+    // Partial reads cannot be verified
+    assert_eq!(
+        store
+            .get_partial(&key, ByteRange::FromStart(0, Some(10)))?
+            .unwrap(),
+        &data[..10]
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_stored_data_offset() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::storage_adapter::performance_metrics::PerformanceMetricsStorageAdapter;
+
+    let path = tempfile::TempDir::new()?;
+    let mut path = path.path().to_path_buf();
+    path.push("test.zip");
+    zip_write(&path)?;
+
+    let store = Arc::new(PerformanceMetricsStorageAdapter::new(Arc::new(
+        FilesystemStore::new(path)?,
+    )));
+    let zip_store = ZipStorageAdapter::new(store.clone(), StoreKey::root())?;
+    let key = "a/b/zarr.json".try_into()?;
+
+    // The first read of an entry reads the local file header, later reads do not
+    let reads = store.reads();
+    assert_eq!(zip_store.get(&key)?.unwrap(), vec![0, 1, 2, 3]);
+    assert_eq!(store.reads() - reads, 2);
+    let reads = store.reads();
+    assert_eq!(zip_store.get(&key)?.unwrap(), vec![0, 1, 2, 3]);
+    assert_eq!(store.reads() - reads, 1);
+
+    // Ten reads of an entry from threads sharing a new adapter read its local file header once
+    let zip_store = Arc::new(ZipStorageAdapter::new(store.clone(), StoreKey::root())?);
+    let reads = store.reads();
+    std::thread::scope(|scope| {
+        for _ in 0..2 {
+            let zip_store = &zip_store;
+            let key = &key;
+            scope.spawn(move || {
+                for _ in 0..5 {
+                    assert_eq!(zip_store.get(key).unwrap().unwrap(), vec![0, 1, 2, 3]);
+                }
+            });
+        }
+    });
+    assert!(store.reads() - reads <= 12);
+    let reads = store.reads();
+    for _ in 0..10 {
+        zip_store.get(&key)?;
+    }
+    assert_eq!(store.reads() - reads, 10);
+
+    let zip_store = ZipStorageAdapter::new(store.clone(), StoreKey::root())?;
+    let reads = store.reads();
+    for _ in 0..10 {
+        zip_store.get(&key)?;
+    }
+    assert_eq!(store.reads() - reads, 11);
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_coalesce_byte_ranges() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::{
+        byte_range::ByteRange,
+        storage_adapter::performance_metrics::PerformanceMetricsStorageAdapter,
+    };
+
+    let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    let tmp_dir = tempfile::TempDir::new()?;
+    {
+        let file = File::create(tmp_dir.path().join("test.zip"))?;
+        let mut zip = zip::ZipWriter::new(file);
+        let options =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        zip.start_file("data", options)?;
+        zip.write_all(&data)?;
+        zip.finish()?;
+    }
+
+    // Nearby byte ranges out of order, overlapping and adjacent byte ranges, and a distant byte range
+    let mut byte_ranges: Vec<ByteRange> = (0..32)
+        .rev()
+        .map(|i| ByteRange::FromStart(i * 1000, Some(100)))
+        .collect();
+    byte_ranges.push(ByteRange::FromStart(50, Some(100)));
+    byte_ranges.push(ByteRange::FromStart(100, Some(900)));
+    byte_ranges.push(ByteRange::Suffix(10));
+    let expected: Vec<&[u8]> = byte_ranges
+        .iter()
+        .map(|range| &data[range.to_range_usize(data.len() as u64)])
+        .collect();
+
+    let store = Arc::new(PerformanceMetricsStorageAdapter::new(Arc::new(
+        FilesystemStore::new(tmp_dir.path())?,
+    )));
+    for (coalesce_gap, requests) in [(4096, 2), (0, 32)] {
+        let zip_store = ZipStorageAdapter::new(store.clone(), StoreKey::new("test.zip")?)?
+            .with_coalesce_gap(coalesce_gap);
+        let key = "data".try_into()?;
+        // Read the local file header of the entry
+        zip_store.get_partial(&key, ByteRange::FromStart(0, Some(1)))?;
+
+        let reads = store.reads();
+        let values = zip_store
+            .get_partial_many(&key, Box::new(byte_ranges.clone().into_iter()))?
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(store.reads() - reads, requests);
+        assert_eq!(values, expected);
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_eocd_prefetch_bytes() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::storage_adapter::performance_metrics::PerformanceMetricsStorageAdapter;
+    use zarrs_zip::ZipStorageAdapterBuilder;
+
+    // A zip file with a central directory of hundreds of KiB
+    let tmp_dir = tempfile::TempDir::new()?;
+    {
+        let file = File::create(tmp_dir.path().join("test.zip"))?;
+        let mut zip = zip::ZipWriter::new(file);
+        let options =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        for i in 0..5000 {
+            zip.start_file(format!("array/c/{i}/0/0/0"), options)?;
+            zip.write_all(&[0; 16])?;
+        }
+        zip.finish()?;
+    }
+
+    let store = Arc::new(PerformanceMetricsStorageAdapter::new(Arc::new(
+        FilesystemStore::new(tmp_dir.path())?,
+    )));
+    let key = StoreKey::new("test.zip")?;
+    let reads = store.reads();
+    let zip_store = ZipStorageAdapter::new(store.clone(), key.clone())?;
+    let reads_default = store.reads() - reads;
+    assert_eq!(zip_store.list()?.len(), 5000);
+
+    // The central directory is read from the prefetched suffix
+    let reads = store.reads();
+    let zip_store = ZipStorageAdapterBuilder::new(store.clone(), key.clone())
+        .eocd_prefetch_bytes(1 << 20)
+        .build()?;
+    assert_eq!(store.reads() - reads, 1);
+    assert!(reads_default > 1);
+    assert_eq!(zip_store.list()?.len(), 5000);
+    assert_eq!(
+        zip_store.get(&"array/c/4999/0/0/0".try_into()?)?.unwrap(),
+        &[0; 16][..]
+    );
+
+    // Bytes before a short prefetched suffix are read as needed
+    let zip_store = ZipStorageAdapterBuilder::new(store.clone(), key)
+        .eocd_prefetch_bytes(1024)
+        .build()?;
+    assert_eq!(zip_store.list()?.len(), 5000);
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_prefetch_headers() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::storage_adapter::performance_metrics::PerformanceMetricsStorageAdapter;
+    use zarrs_zip::ZipStorageAdapterBuilder;
+
+    let tmp_dir = tempfile::TempDir::new()?;
+    let path = tmp_dir.path().join("test.zip");
+    {
+        let mut zip = zip::ZipWriter::new(File::create(&path)?);
+        let options =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        for i in 0..1000u32 {
+            zip.start_file(format!("c/{i}"), options)?;
+            zip.write_all(&i.to_le_bytes())?;
+        }
+        zip.finish()?;
+    }
+
+    // The number of reads when opening the zip file, and when reading every key
+    let reads = |prefetch_headers| -> Result<(usize, usize), Box<dyn Error>> {
+        let store = Arc::new(PerformanceMetricsStorageAdapter::new(Arc::new(
+            FilesystemStore::new(&path)?,
+        )));
+        let zip_store = ZipStorageAdapterBuilder::new(store.clone(), StoreKey::root())
+            .prefetch_headers(prefetch_headers)
+            .build()?;
+        let reads_open = store.reads();
+        for i in 0..1000u32 {
+            let key = StoreKey::new(format!("c/{i}"))?;
+            assert_eq!(zip_store.get(&key)?.unwrap(), &i.to_le_bytes()[..]);
+        }
+        Ok((reads_open, store.reads() - reads_open))
+    };
+
+    let (reads_open, reads_get) = reads(false)?;
+    assert_eq!(reads_get, 2000);
+    // The headers are close together, so they are read in a single request
+    assert_eq!(reads(true)?, (reads_open + 1, 1000));
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_prefetch_headers_sparse() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::storage_adapter::performance_metrics::PerformanceMetricsStorageAdapter;
+    use zarrs_zip::ZipStorageAdapterBuilder;
+
+    // Entries larger than the maximum gap between prefetched headers, interleaved with small entries
+    let large = |i: u32| vec![(i % 251) as u8; 100_000];
+    let tmp_dir = tempfile::TempDir::new()?;
+    let path = tmp_dir.path().join("test.zip");
+    {
+        let mut zip = zip::ZipWriter::new(File::create(&path)?);
+        let options =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        for i in 0..10u32 {
+            zip.start_file(format!("large/{i}"), options)?;
+            zip.write_all(&large(i))?;
+            zip.start_file(format!("small/{i}"), options)?;
+            zip.write_all(&i.to_le_bytes())?;
+        }
+        zip.finish()?;
+    }
+
+    let store = Arc::new(PerformanceMetricsStorageAdapter::new(Arc::new(
+        FilesystemStore::new(&path)?,
+    )));
+    let zip_store = ZipStorageAdapterBuilder::new(store.clone(), StoreKey::root())
+        .prefetch_headers(true)
+        .build()?;
+
+    // Every data offset is known after opening, so each read is a single request
+    let reads = store.reads();
+    for i in 0..10u32 {
+        let key = StoreKey::new(format!("small/{i}"))?;
+        assert_eq!(zip_store.get(&key)?.unwrap(), &i.to_le_bytes()[..]);
+        let key = StoreKey::new(format!("large/{i}"))?;
+        assert_eq!(zip_store.get(&key)?.unwrap(), large(i));
+    }
+    assert_eq!(store.reads() - reads, 20);
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_get_many() -> Result<(), Box<dyn Error>> {
+    let tmp_dir = tempfile::TempDir::new()?;
+    let values: Vec<Vec<u8>> = (0..4u32)
+        .map(|i| (0..200_000u32).map(|j| ((i + j) % 251) as u8).collect())
+        .collect();
+    {
+        let file = File::create(tmp_dir.path().join("test.zip"))?;
+        let mut zip = zip::ZipWriter::new(file);
+        let deflated =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        let stored =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        for (i, value) in values.iter().enumerate() {
+            zip.start_file(format!("c/{i}"), if i == 3 { stored } else { deflated })?;
+            zip.write_all(value)?;
+        }
+        zip.finish()?;
+    }
+
+    let store = FilesystemStore::new(tmp_dir.path())?;
+    let zip_store =
+        ZipStorageAdapter::new(store.into(), StoreKey::new("test.zip")?)?.with_cache(250_000);
+    let keys = vec![
+        StoreKey::new("c/0")?,
+        StoreKey::new("missing")?,
+        StoreKey::new("c/1")?,
+        StoreKey::new("c/2")?,
+        StoreKey::new("c/3")?,
+        StoreKey::new("c/0")?,
+    ];
+    let expected = vec![
+        Some(&values[0]),
+        None,
+        Some(&values[1]),
+        Some(&values[2]),
+        Some(&values[3]),
+        Some(&values[0]),
+    ];
+
+    // Decompress in a thread pool with more than one thread, so that the parallel path is taken
+    #[cfg(feature = "rayon")]
+    let results = rayon::ThreadPoolBuilder::new()
+        .num_threads(4)
+        .build()?
+        .install(|| zip_store.get_many(&keys))?;
+    #[cfg(not(feature = "rayon"))]
+    let results = zip_store.get_many(&keys)?;
+    assert_eq!(results.len(), expected.len());
+    for (result, expected) in results.iter().zip(&expected) {
+        assert_eq!(result.as_deref(), expected.map(Vec::as_slice));
+    }
+
+    // Decompressed entries that fit are cached
+    assert_eq!(zip_store.cache_stats().unwrap().entries, 1);
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_cache() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::byte_range::ByteRange;
+
+    let tmp_dir = tempfile::TempDir::new()?;
+    {
+        let file = File::create(tmp_dir.path().join("test.zip"))?;
+        let mut zip = zip::ZipWriter::new(file);
+        let options =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        for name in ["a", "b", "c"] {
+            zip.start_file(name, options)?;
+            zip.write_all(&[name.as_bytes()[0]; 100])?;
+        }
+        zip.finish()?;
+    }
+
+    let store = FilesystemStore::new(tmp_dir.path())?;
+    let store = ZipStorageAdapter::new_with_cache(store.into(), StoreKey::new("test.zip")?, 200)?;
+    let (a, b, c) = ("a".try_into()?, "b".try_into()?, "c".try_into()?);
+    assert_eq!(
+        store
+            .get_partial(&a, ByteRange::FromStart(10, Some(5)))?
+            .unwrap(),
+        vec![b'a'; 5]
+    );
+    assert_eq!(store.get(&a)?.unwrap(), vec![b'a'; 100]);
+    assert_eq!(store.get(&b)?.unwrap(), vec![b'b'; 100]);
+    let stats = store.cache_stats().unwrap();
+    assert_eq!((stats.hits, stats.misses), (1, 2));
+    assert_eq!((stats.entries, stats.size), (2, 200));
+
+    // Reading c evicts the least recently used entry, a
+    assert_eq!(store.get(&c)?.unwrap(), vec![b'c'; 100]);
+    assert_eq!(store.get(&b)?.unwrap(), vec![b'b'; 100]);
+    assert_eq!(store.get(&a)?.unwrap(), vec![b'a'; 100]);
+    let stats = store.cache_stats().unwrap();
+    assert_eq!((stats.hits, stats.misses), (2, 4));
+    assert_eq!((stats.entries, stats.size), (2, 200));
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_cache_reads() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::{
+        byte_range::ByteRange,
+        storage_adapter::performance_metrics::PerformanceMetricsStorageAdapter,
+    };
+    use zarrs_zip::ZipCacheStats;
+
+    let tmp_dir = tempfile::TempDir::new()?;
+    let path = tmp_dir.path().join("test.zip");
+    {
+        let mut zip = zip::ZipWriter::new(File::create(&path)?);
+        let options =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        zip.start_file("a", options)?;
+        zip.write_all(&[b'a'; 1000])?;
+        zip.finish()?;
+    }
+
+    let store = Arc::new(PerformanceMetricsStorageAdapter::new(Arc::new(
+        FilesystemStore::new(path)?,
+    )));
+    let zip_store = ZipStorageAdapter::new(store.clone(), StoreKey::root())?.with_cache(1 << 20);
+    let key = "a".try_into()?;
+
+    // Sizes come from the central directory, and do not populate the cache
+    let reads = store.reads();
+    assert_eq!(zip_store.size_key(&key)?, Some(1000));
+    assert_eq!(store.reads(), reads);
+    assert_eq!(
+        zip_store.cache_stats().unwrap(),
+        ZipCacheStats {
+            capacity: 1 << 20,
+            ..ZipCacheStats::default()
+        }
+    );
+
+    // Later reads of a cached entry do not read the underlying storage
+    assert_eq!(zip_store.get(&key)?.unwrap(), vec![b'a'; 1000]);
+    assert!(store.reads() > reads);
+    let reads = store.reads();
+    assert_eq!(zip_store.get(&key)?.unwrap(), vec![b'a'; 1000]);
+    assert_eq!(
+        zip_store
+            .get_partial(&key, ByteRange::FromStart(10, Some(5)))?
+            .unwrap(),
+        vec![b'a'; 5]
+    );
+    assert_eq!(store.reads(), reads);
+    let stats = zip_store.cache_stats().unwrap();
+    assert_eq!((stats.hits, stats.misses, stats.size), (2, 1, 1000));
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_clone_shared_caches() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::storage_adapter::performance_metrics::PerformanceMetricsStorageAdapter;
+
+    let tmp_dir = tempfile::TempDir::new()?;
+    let path = tmp_dir.path().join("test.zip");
+    {
+        let mut zip = zip::ZipWriter::new(File::create(&path)?);
+        let options =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        for i in 0..64u8 {
+            zip.start_file(i.to_string(), options)?;
+            zip.write_all(&[i; 100])?;
+        }
+        zip.finish()?;
+    }
+    let keys: Vec<StoreKey> = (0..64)
+        .map(|i| StoreKey::new(i.to_string()))
+        .collect::<Result<_, _>>()?;
+    // Each of 8 readers first reads its own 8 keys, then 16 keys overlapping those of other readers
+    let own_keys = |reader: usize| (reader * 8..reader * 8 + 8).collect::<Vec<_>>();
+    let shared_keys = |reader: usize| {
+        (reader * 8 + 4..reader * 8 + 20)
+            .map(|i| i % 64)
+            .collect::<Vec<_>>()
+    };
+
+    let store = Arc::new(PerformanceMetricsStorageAdapter::new(Arc::new(
+        FilesystemStore::new(path)?,
+    )));
+
+    // A single handle read from one thread
+    let zip_store = ZipStorageAdapter::new(store.clone(), StoreKey::root())?.with_cache(1 << 20);
+    let reads = store.reads();
+    for reader in 0..8 {
+        for i in own_keys(reader).into_iter().chain(shared_keys(reader)) {
+            assert_eq!(
+                zip_store.get(&keys[i])?.unwrap(),
+                vec![u8::try_from(i)?; 100]
+            );
+        }
+    }
+    let single_threaded_reads = store.reads() - reads;
+
+    // A clone of a new handle per thread, with the index and cache shared between them
+    let zip_store = ZipStorageAdapter::new(store.clone(), StoreKey::root())?.with_cache(1 << 20);
+    let barrier = std::sync::Barrier::new(8);
+    let reads = store.reads();
+    std::thread::scope(|scope| {
+        for reader in 0..8 {
+            let zip_store = zip_store.clone();
+            let (keys, barrier) = (&keys, &barrier);
+            scope.spawn(move || {
+                for i in own_keys(reader) {
+                    assert_eq!(
+                        zip_store.get(&keys[i]).unwrap().unwrap(),
+                        vec![u8::try_from(i).unwrap(); 100]
+                    );
+                }
+                barrier.wait();
+                for i in shared_keys(reader) {
+                    assert_eq!(
+                        zip_store.get(&keys[i]).unwrap().unwrap(),
+                        vec![u8::try_from(i).unwrap(); 100]
+                    );
+                }
+            });
+        }
+    });
+    assert_eq!(store.reads() - reads, single_threaded_reads);
+    let stats = zip_store.cache_stats().unwrap();
+    assert_eq!((stats.hits, stats.misses, stats.entries), (128, 64, 64));
+
+    Ok(())
+}
+
+#[cfg(feature = "write")]
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_write_finish() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::byte_range::ByteRange;
+
+    let path = tempfile::TempDir::new()?;
+    let mut path = path.path().to_path_buf();
+    let fs_store = Arc::new(FilesystemStore::new(path.clone())?);
+    path.push("test.zip");
+    zip_write(&path)?;
+
+    let store = ZipStorageAdapter::new(fs_store.clone(), StoreKey::new("test.zip")?)?;
+    store.set(&"a/b/zarr.json".try_into()?, vec![4, 5].into())?;
+    store.set(&"d/zarr.json".try_into()?, vec![6, 7, 8].into())?;
+    store.erase(&"c/zarr.json".try_into()?)?;
+    store.set_partial(&"d/zarr.json".try_into()?, 2, vec![9, 10].into())?;
+
+    // Pending writes are visible before finishing
+    assert_eq!(
+        store.get(&"a/b/zarr.json".try_into()?)?.unwrap(),
+        vec![4, 5]
+    );
+    assert_eq!(
+        store.get(&"d/zarr.json".try_into()?)?.unwrap(),
+        vec![6, 7, 9, 10]
+    );
+    assert!(store.get(&"c/zarr.json".try_into()?)?.is_none());
+    store.finish()?;
+
+    let expected_keys = [
+        "a/b/zarr.json".try_into()?,
+        "a/c/zarr.json".try_into()?,
+        "a/d/e/zarr.json".try_into()?,
+        "a/f/g/zarr.json".try_into()?,
+        "a/f/h/zarr.json".try_into()?,
+        "b/c/d/zarr.json".try_into()?,
+        "b/zarr.json".try_into()?,
+        "d/zarr.json".try_into()?,
+    ];
+    assert_eq!(store.list()?, expected_keys);
+
+    let store = ZipStorageAdapter::new(fs_store, StoreKey::new("test.zip")?)?;
+    assert_eq!(store.list()?, expected_keys);
+    assert_eq!(
+        store.get(&"a/b/zarr.json".try_into()?)?.unwrap(),
+        vec![4, 5]
+    );
+    assert_eq!(
+        store.get_partial(&"d/zarr.json".try_into()?, ByteRange::FromStart(1, Some(2)))?,
+        Some(vec![7, 9].into())
+    );
+
+    // The rewritten archive is readable by other zip implementations
+    let mut zip = zip::ZipArchive::new(File::open(&path)?)?;
+    let mut value = Vec::new();
+    zip.by_name("d/zarr.json")?.read_to_end(&mut value)?;
+    assert_eq!(value, vec![6, 7, 9, 10]);
+    assert!(zip.by_name("c/zarr.json").is_err());
+
+    Ok(())
+}
+
+#[cfg(feature = "write")]
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_write_erase() -> Result<(), Box<dyn Error>> {
+    let path = tempfile::TempDir::new()?;
+    let mut path = path.path().to_path_buf();
+    let fs_store = Arc::new(FilesystemStore::new(path.clone())?);
+    path.push("test.zip");
+    zip_write(&path)?;
+    let zip_key = StoreKey::new("test.zip")?;
+    let key = "a/b/zarr.json".try_into()?;
+
+    let store = ZipStorageAdapter::new(fs_store.clone(), zip_key.clone())?;
+    assert_eq!(store.dead_bytes()?, 0);
+    store.erase(&key)?;
+    assert!(store.get(&key)?.is_none());
+    assert!(!store.list()?.contains(&key));
+
+    // The local header and data of the erased entry are dead
+    let dead_bytes = store.dead_bytes()?;
+    assert!(dead_bytes >= 30 + 13 + 4);
+    store.finish()?;
+    assert_eq!(store.dead_bytes()?, dead_bytes);
+
+    let store = ZipStorageAdapter::new(fs_store, zip_key)?;
+    assert!(store.get(&key)?.is_none());
+    assert_eq!(store.list()?.len(), 7);
+    assert_eq!(store.dead_bytes()?, dead_bytes);
+
+    Ok(())
+}
+
+#[cfg(feature = "write")]
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_write_read_interleaved() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::StorePrefix;
+    use zarrs_zip::ZipCompression;
+
+    let path = tempfile::TempDir::new()?;
+    let mut path = path.path().to_path_buf();
+    let fs_store = Arc::new(FilesystemStore::new(path.clone())?);
+    path.push("test.zip");
+    zip_write(&path)?;
+    let store = ZipStorageAdapter::new(fs_store, StoreKey::new("test.zip")?)?
+        .with_cache(1 << 20)
+        .with_write_options(ZipWriteOptions::default().compression(ZipCompression::Deflate(6)));
+    let dir = |prefix: &str| -> Result<(Vec<String>, Vec<String>), Box<dyn Error>> {
+        let keys_prefixes = store.list_dir(&StorePrefix::new(prefix)?)?;
+        Ok((
+            keys_prefixes
+                .keys()
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            keys_prefixes
+                .prefixes()
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+        ))
+    };
+
+    // A key under a new prefix is visible immediately
+    let key_new = StoreKey::new("a/x/y/zarr.json")?;
+    store.set(&key_new, vec![1, 2].into())?;
+    assert_eq!(store.get(&key_new)?.unwrap(), vec![1u8, 2]);
+    assert_eq!(
+        dir("a/")?.1,
+        ["a/b/", "a/c/", "a/d/", "a/f/", "a/x/"].map(String::from)
+    );
+    assert_eq!(dir("a/x/")?, (vec![], vec!["a/x/y/".to_string()]));
+
+    // Overwriting a key that has been read replaces its cached value
+    let key = StoreKey::new("a/b/zarr.json")?;
+    assert_eq!(store.get(&key)?.unwrap(), vec![0u8, 1, 2, 3]);
+    store.set(&key, vec![4, 5].into())?;
+    assert_eq!(store.get(&key)?.unwrap(), vec![4u8, 5]);
+    assert_eq!(store.size_key(&key)?, Some(2));
+
+    // Erasing the only key under a prefix removes the prefix
+    store.erase(&key_new)?;
+    assert!(store.get(&key_new)?.is_none());
+    assert!(!dir("a/")?.1.contains(&"a/x/".to_string()));
+
+    // Reads and writes continue after the zip file is written
+    store.finish()?;
+    assert_eq!(store.get(&key)?.unwrap(), vec![4u8, 5]);
+    store.set(&key, vec![6].into())?;
+    assert_eq!(store.get(&key)?.unwrap(), vec![6u8]);
+    store.set(&key_new, vec![7].into())?;
+    assert!(dir("a/")?.1.contains(&"a/x/".to_string()));
+    store.finish()?;
+    assert_eq!(store.get(&key)?.unwrap(), vec![6u8]);
+    assert_eq!(store.get(&key_new)?.unwrap(), vec![7u8]);
+
+    Ok(())
+}
+
+#[cfg(feature = "write")]
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_write_repack() -> Result<(), Box<dyn Error>> {
+    let data: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+    let mut archive = std::io::Cursor::new(Vec::new());
+    {
+        // Entries with data descriptors, which are copied with their local data
+        let mut zip = zip::ZipWriter::new_stream(&mut archive);
+        for (name, method) in [
+            ("a", zip::CompressionMethod::Stored),
+            ("b", zip::CompressionMethod::Deflated),
+            ("c", zip::CompressionMethod::Stored),
+        ] {
+            let options = SimpleFileOptions::default().compression_method(method);
+            zip.start_file(name, options)?;
+            zip.write_all(&data)?;
+        }
+        zip.finish()?;
+    }
+    let store = Arc::new(MemoryStore::default());
+    let zip_key = StoreKey::new("test.zip")?;
+    let repacked_key = StoreKey::new("repacked.zip")?;
+    store.set(&zip_key, archive.into_inner().into())?;
+
+    let zip_store = ZipStorageAdapter::new(store.clone(), zip_key.clone())?.with_write_options(
+        ZipWriteOptions::default().compression(zarrs_zip::ZipCompression::Deflate(6)),
+    );
+    zip_store.erase(&"a".try_into()?)?;
+    zip_store.set(&"c".try_into()?, vec![1, 2, 3].into())?;
+    zip_store.set(&"e".try_into()?, data.clone().into())?;
+
+    // Pending changes must be finished first
+    assert!(zip_store.repack(&*store, &repacked_key).is_err());
+    zip_store.finish()?;
+
+    let dead_bytes = zip_store.dead_bytes()?;
+    assert!(dead_bytes > 2 * data.len() as u64);
+    let summary = zip_store.repack(&*store, &repacked_key)?;
+    assert_eq!(summary.num_entries, 3);
+    assert_eq!(summary.bytes_in, store.size_key(&zip_key)?.unwrap());
+    assert_eq!(summary.bytes_out, store.size_key(&repacked_key)?.unwrap());
+    assert_eq!(summary.bytes_reclaimed, dead_bytes);
+    assert_eq!(store.list()?, vec![repacked_key.clone(), zip_key.clone()]);
+
+    // Entry order, compression methods, and CRC-32s are preserved
+    let zip_names = |key: &StoreKey| -> Result<Vec<_>, Box<dyn Error>> {
+        let archive = store.get(key)?.unwrap();
+        let mut zip = zip::ZipArchive::new(std::io::Cursor::new(archive.to_vec()))?;
+        let mut names = Vec::new();
+        for i in 0..zip.len() {
+            let mut file = zip.by_index(i)?;
+            let mut value = Vec::new();
+            file.read_to_end(&mut value)?;
+            names.push((file.name().to_string(), file.compression(), file.crc32()));
+        }
+        Ok(names)
+    };
+    assert_eq!(zip_names(&repacked_key)?, zip_names(&zip_key)?);
+
+    let repacked = ZipStorageAdapter::new(store.clone(), repacked_key)?;
+    assert_eq!(repacked.dead_bytes()?, 0);
+    assert_eq!(repacked.list()?, zip_store.list()?);
+    assert_eq!(repacked.get(&"b".try_into()?)?.unwrap(), data);
+    assert_eq!(repacked.get(&"c".try_into()?)?.unwrap(), vec![1, 2, 3]);
+    assert_eq!(repacked.get(&"e".try_into()?)?.unwrap(), data);
+
+    // Repacking in place
+    zip_store.repack(&*store, &zip_key)?;
+    let zip_store = ZipStorageAdapter::new(store.clone(), zip_key.clone())?;
+    assert_eq!(zip_store.dead_bytes()?, 0);
+    assert_eq!(zip_store.get(&"b".try_into()?)?.unwrap(), data);
+
+    Ok(())
+}
+
+#[cfg(feature = "write")]
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_overlay() -> Result<(), Box<dyn Error>> {
+    use zarrs_zip::ZipOverlayAdapter;
+
+    let path = Path::new("tests/zarr.zip");
+    let bytes = std::fs::read(path)?;
+    let base = ZipStorageAdapter::new(Arc::new(FilesystemStore::new(path)?), StoreKey::root())?;
+    let overlay = ZipOverlayAdapter::new(Arc::new(FilesystemStore::new(path)?), StoreKey::root())?;
+
+    let new_key = StoreKey::new("foo/new")?;
+    let set_key = StoreKey::new("foo/c/0/0")?;
+    let erased_key = StoreKey::new("foo/c/0/1")?;
+    overlay.set(&new_key, vec![1, 2, 3].into())?;
+    overlay.set(&set_key, vec![4, 5].into())?;
+    overlay.set_partial(&set_key, 1, vec![6, 7].into())?;
+    overlay.erase(&erased_key)?;
+
+    let expected_keys = |keys: Vec<StoreKey>| {
+        let mut keys: Vec<StoreKey> = keys
+            .into_iter()
+            .filter(|key| key != &erased_key)
+            .chain([new_key.clone()])
+            .collect();
+        keys.sort();
+        keys
+    };
+    assert_eq!(overlay.list()?, expected_keys(base.list()?));
+    assert_eq!(overlay.get(&new_key)?.unwrap(), vec![1u8, 2, 3]);
+    assert_eq!(overlay.get(&set_key)?.unwrap(), vec![4u8, 6, 7]);
+    assert!(overlay.get(&erased_key)?.is_none());
+    assert!(
+        overlay
+            .list_dir(&"foo/".try_into()?)?
+            .keys()
+            .contains(&new_key)
+    );
+
+    // The zip file is not modified by writes or commits
+    let dst_store = Arc::new(MemoryStore::new());
+    let dst_key = StoreKey::new("edited.zip")?;
+    overlay.commit(dst_store.as_ref(), &dst_key)?;
+    assert_eq!(std::fs::read(path)?, bytes);
+
+    let edited = ZipStorageAdapter::new_verified(dst_store, dst_key)?;
+    assert_eq!(edited.list()?, overlay.list()?);
+    for key in edited.list()? {
+        assert_eq!(edited.get(&key)?, overlay.get(&key)?);
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "write")]
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_stream_writer() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::byte_range::ByteRange;
+    use zarrs_zip::{ZipCompression, ZipStreamWriter};
+
+    let mut state = 1u32;
+    let data: Vec<u8> = (0..300_000)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            state.to_le_bytes()[2] % 16
+        })
+        .collect();
+    let compressions = [
+        ZipCompression::Stored,
+        ZipCompression::Deflate(6),
+        #[cfg(feature = "zstd")]
+        ZipCompression::Zstd(3),
+    ];
+
+    let store = Arc::new(MemoryStore::new());
+    let zip_key = StoreKey::new("streamed.zip")?;
+    for (compression, force_zip64) in compressions
+        .into_iter()
+        .flat_map(|compression| [(compression, false), (compression, true)])
+    {
+        let options = ZipWriteOptions::default()
+            .compression(compression)
+            .force_zip64(force_zip64);
+        let mut writer = ZipStreamWriter::new(Vec::new(), options);
+        writer.write_entry("a/b", data.as_slice())?;
+        writer.write_entry("c", &data[..10])?;
+        let bytes = writer.finish()?;
+        // General purpose flag bit 3 is set, and the CRC-32 and sizes in the local file header are zero, or saturated with ZIP64
+        assert_eq!(u16::from_le_bytes([bytes[6], bytes[7]]) & (1 << 3), 1 << 3);
+        assert_eq!(&bytes[14..18], &[0; 4]);
+        let sizes = if force_zip64 { [0xFF; 8] } else { [0; 8] };
+        assert_eq!(&bytes[18..26], &sizes);
+        let mut value = Vec::new();
+        zip::ZipArchive::new(std::io::Cursor::new(&bytes))?
+            .by_name("a/b")?
+            .read_to_end(&mut value)?;
+        assert_eq!(value, data);
+        store.set(&zip_key, bytes.into())?;
+
+        let zip_store = ZipStorageAdapter::new_verified(store.clone(), zip_key.clone())?;
+        let key = StoreKey::new("a/b")?;
+        assert_eq!(zip_store.get(&key)?.unwrap(), data);
+        assert_eq!(
+            zip_store
+                .get_partial(&key, ByteRange::FromStart(100_000, Some(10)))?
+                .unwrap(),
+            &data[100_000..100_010]
+        );
+        assert_eq!(
+            zip_store.get_partial(&key, ByteRange::Suffix(10))?.unwrap(),
+            &data[data.len() - 10..]
+        );
+        assert_eq!(zip_store.get(&"c".try_into()?)?.unwrap(), &data[..10]);
+        // The compressed data is decoded in more than one chunk with a small read buffer
+        let zip_store = zip_store.with_read_buffer_size(16 * 1024);
+        assert_eq!(zip_store.get(&key)?.unwrap(), data);
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "write")]
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_merge() -> Result<(), Box<dyn Error>> {
+    use zarrs_zip::{ZipCompression, merge_zips};
+
+    let store = Arc::new(MemoryStore::new());
+    let options = ZipWriteOptions::default().compression(ZipCompression::Deflate(6));
+    for (shard, keys) in [("0.zip", ["c/0/0", "c/0/1"]), ("1.zip", ["c/1/0", "c/1/1"])] {
+        let shard = ZipStorageAdapter::create(store.clone(), StoreKey::new(shard)?, false)?
+            .with_write_options(options.clone());
+        for key in keys {
+            shard.set(&key.try_into()?, key.as_bytes().repeat(100).into())?;
+        }
+        shard.finish()?;
+    }
+
+    let shard_0 = ZipStorageAdapter::new(store.clone(), StoreKey::new("0.zip")?)?;
+    let shard_1 = ZipStorageAdapter::new(store.clone(), StoreKey::new("1.zip")?)?;
+    let merged_key = StoreKey::new("merged.zip")?;
+    let summary = merge_zips(&[&shard_0, &shard_1], store.as_ref(), &merged_key)?;
+    assert_eq!(summary.num_entries, 4);
+    assert_eq!(summary.bytes_out, store.size_key(&merged_key)?.unwrap(),);
+    let merged = ZipStorageAdapter::new_verified(store.clone(), merged_key.clone())?;
+    assert_eq!(
+        merged.list()?,
+        ["c/0/0", "c/0/1", "c/1/0", "c/1/1"]
+            .into_iter()
+            .map(StoreKey::new)
+            .collect::<Result<Vec<_>, _>>()?
+    );
+    for key in merged.list()? {
+        assert_eq!(
+            merged.get(&key)?.unwrap(),
+            key.as_str().as_bytes().repeat(100)
+        );
+        // Entries are not recompressed
+        assert_eq!(
+            merged
+                .entry_metadata(&key)
+                .map(|metadata| metadata.compressed_size),
+            shard_0
+                .entry_metadata(&key)
+                .or_else(|| shard_1.entry_metadata(&key))
+                .map(|metadata| metadata.compressed_size)
+        );
+    }
+
+    // Keys in more than one source and sources with pending changes are rejected
+    assert!(merge_zips(&[&shard_0, &shard_0], store.as_ref(), &merged_key).is_err());
+    shard_1.erase(&"c/1/0".try_into()?)?;
+    assert!(merge_zips(&[&shard_0, &shard_1], store.as_ref(), &merged_key).is_err());
+    shard_1.finish()?;
+    let merged = merge_zips(&[&shard_0, &shard_1], store.as_ref(), &merged_key)?;
+    assert_eq!(merged.num_entries, 3);
+
+    Ok(())
+}
+
+#[cfg(feature = "write")]
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_write_erase_prefix() -> Result<(), Box<dyn Error>> {
+    let path = tempfile::TempDir::new()?;
+    let mut path = path.path().to_path_buf();
+    let fs_store = Arc::new(FilesystemStore::new(path.clone())?);
+    path.push("test.zip");
+    zip_write(&path)?;
+    let zip_key = StoreKey::new("test.zip")?;
+    let prefix = "a/d/".try_into()?;
+
+    let store = ZipStorageAdapter::new(fs_store.clone(), zip_key.clone())?;
+    assert!(
+        store
+            .list_dir(&"a/".try_into()?)?
+            .prefixes()
+            .contains(&prefix)
+    );
+    store.erase_prefix(&prefix)?;
+    assert!(store.list_prefix(&prefix)?.is_empty());
+    assert!(
+        !store
+            .list_dir(&"a/".try_into()?)?
+            .prefixes()
+            .contains(&prefix)
+    );
+    store.finish()?;
+
+    // The directory entries under the prefix are excluded from the central directory
+    let zip = zip::ZipArchive::new(File::open(&path)?)?;
+    assert!(!zip.file_names().any(|name| name.starts_with("a/d/")));
+    assert!(zip.file_names().any(|name| name == "a/f/"));
+
+    let store = ZipStorageAdapter::new(fs_store, zip_key)?;
+    assert_eq!(
+        store.list_dir(&"a/".try_into()?)?.prefixes(),
+        &["a/b/".try_into()?, "a/c/".try_into()?, "a/f/".try_into()?]
+    );
+    assert_eq!(store.list()?.len(), 7);
+
+    Ok(())
+}
+
+#[cfg(feature = "write")]
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_write_append() -> Result<(), Box<dyn Error>> {
+    use zarrs_zip::ZipOverwritePolicy;
+
+    let path = tempfile::TempDir::new()?;
+    let mut path = path.path().to_path_buf();
+    let fs_store = Arc::new(FilesystemStore::new(path.clone())?);
+    path.push("test.zip");
+    zip_write(&path)?;
+    let original = std::fs::read(&path)?;
+    let directory_offset = zip::ZipArchive::new(File::open(&path)?)?.central_directory_start();
+    let zip_key = StoreKey::new("test.zip")?;
+
+    let store = ZipStorageAdapter::open_append(fs_store.clone(), zip_key.clone())?
+        .with_overwrite_policy(ZipOverwritePolicy::Error);
+    store.set(&"d/zarr.json".try_into()?, vec![6, 7, 8].into())?;
+    assert!(
+        store
+            .set(&"b/zarr.json".try_into()?, vec![].into())
+            .is_err()
+    );
+    store.finish()?;
+    assert_eq!(store.list()?.len(), 9);
+
+    // New entries are written over the central directory, the local data of existing entries is untouched
+    let appended = std::fs::read(&path)?;
+    let directory_offset = usize::try_from(directory_offset)?;
+    assert_eq!(appended[..directory_offset], original[..directory_offset]);
+
+    let store = ZipStorageAdapter::open_append(fs_store.clone(), zip_key.clone())?;
+    store.set(&"a/b/zarr.json".try_into()?, vec![4, 5].into())?;
+    store.finish()?;
+
+    // Erasing entries shortens the central directory, so the new central directory is written after it
+    let size = std::fs::metadata(&path)?.len();
+    store.erase_prefix(&"a/".try_into()?)?;
+    store.finish()?;
+    assert!(std::fs::metadata(&path)?.len() > size);
+
+    let expected_keys = [
+        "b/c/d/zarr.json".try_into()?,
+        "b/zarr.json".try_into()?,
+        "c/zarr.json".try_into()?,
+        "d/zarr.json".try_into()?,
+    ];
+    assert_eq!(store.list()?, expected_keys);
+    let store = ZipStorageAdapter::new(fs_store, zip_key)?;
+    assert_eq!(store.list()?, expected_keys);
+    assert_eq!(
+        store.get(&"d/zarr.json".try_into()?)?.unwrap(),
+        vec![6, 7, 8]
+    );
+
+    // The appended archive is readable by other zip implementations
+    let mut zip = zip::ZipArchive::new(File::open(&path)?)?;
+    assert_eq!(
+        zip.file_names().filter(|name| !name.ends_with('/')).count(),
+        4
+    );
+    let mut value = Vec::new();
+    zip.by_name("d/zarr.json")?.read_to_end(&mut value)?;
+    assert_eq!(value, vec![6, 7, 8]);
+
+    Ok(())
+}
+
+#[cfg(feature = "write")]
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_write_partial_in_place() -> Result<(), Box<dyn Error>> {
+    let data: Vec<u8> = (0..100).collect();
+    let tmp_dir = tempfile::TempDir::new()?;
+    let path = tmp_dir.path().join("test.zip");
+    {
+        let mut zip = zip::ZipWriter::new(File::create(&path)?);
+        let options =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        zip.start_file("stored", options)?;
+        zip.write_all(&data)?;
+        let options =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        zip.start_file("deflated", options)?;
+        zip.write_all(&data)?;
+        zip.finish()?;
+    }
+    let size = std::fs::metadata(&path)?.len();
+
+    let fs_store = Arc::new(FilesystemStore::new(tmp_dir.path())?);
+    let zip_key = StoreKey::new("test.zip")?;
+    let store = ZipStorageAdapter::new(fs_store.clone(), zip_key.clone())?;
+    assert!(store.supports_set_partial());
+    let key = "stored".try_into()?;
+
+    // Partial writes at the start, middle, and end of a stored entry are written in place
+    store.set_partial(&key, 0, vec![200, 201].into())?;
+    store.set_partial(&key, 50, vec![202, 203, 204].into())?;
+    store.set_partial(&key, 98, vec![205, 206].into())?;
+    let mut expected = data.clone();
+    expected[0..2].copy_from_slice(&[200, 201]);
+    expected[50..53].copy_from_slice(&[202, 203, 204]);
+    expected[98..100].copy_from_slice(&[205, 206]);
+    assert_eq!(store.get(&key)?.unwrap(), expected);
+    assert_eq!(std::fs::metadata(&path)?.len(), size);
+    assert_eq!(store.dead_bytes()?, 0);
+
+    // Writes that would change the length of the entry or target a compressed entry fail
+    assert!(store.set_partial(&key, 99, vec![0, 0].into()).is_err());
+    assert!(matches!(
+        store.set_partial(&"deflated".try_into()?, 0, vec![0].into()),
+        Err(zarrs_storage::StorageError::Unsupported(_))
+    ));
+    assert_eq!(store.get(&key)?.unwrap(), expected);
+
+    // The CRC-32 is updated by finish, without rewriting the zip file
+    store.finish()?;
+    assert_eq!(std::fs::metadata(&path)?.len(), size);
+    let mut zip = zip::ZipArchive::new(File::open(&path)?)?;
+    let mut value = Vec::new();
+    zip.by_name("stored")?.read_to_end(&mut value)?;
+    assert_eq!(value, expected);
+
+    let store = ZipStorageAdapter::new(fs_store, zip_key)?;
+    assert_eq!(store.get(&key)?.unwrap(), expected);
+    assert_eq!(store.get(&"deflated".try_into()?)?.unwrap(), data);
+
+    Ok(())
+}
+
+#[cfg(feature = "write")]
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_write_overwrite_policy() -> Result<(), Box<dyn Error>> {
+    use zarrs_zip::ZipOverwritePolicy;
+
+    let path = tempfile::TempDir::new()?;
+    let mut path = path.path().to_path_buf();
+    let fs_store = Arc::new(FilesystemStore::new(path.clone())?);
+    path.push("test.zip");
+    zip_write(&path)?;
+
+    let store = ZipStorageAdapter::new(fs_store, StoreKey::new("test.zip")?)?
+        .with_overwrite_policy(ZipOverwritePolicy::Error);
+    assert!(
+        store
+            .set(&"b/zarr.json".try_into()?, vec![].into())
+            .is_err()
+    );
+    store.set(&"d/zarr.json".try_into()?, vec![0].into())?;
+    assert!(
+        store
+            .set(&"d/zarr.json".try_into()?, vec![1].into())
+            .is_err()
+    );
+    store.finish()?;
+    assert!(
+        store
+            .set(&"d/zarr.json".try_into()?, vec![1].into())
+            .is_err()
+    );
+    assert_eq!(store.get(&"d/zarr.json".try_into()?)?.unwrap(), vec![0]);
+
+    Ok(())
+}
+
+#[cfg(feature = "write")]
+#[test]
+fn zip_create() -> Result<(), Box<dyn Error>> {
+    use zarrs_zip::ZipStorageAdapterCreateError;
+
+    let memory_store = Arc::new(MemoryStore::default());
+    let zip_key = StoreKey::new("test.zip")?;
+    let store = Arc::new(ZipStorageAdapter::create(
+        memory_store.clone(),
+        zip_key.clone(),
+        false,
+    )?);
+    assert!(store.list()?.is_empty());
+    zarrs_storage::store_test::store_write(&store)?;
+    store.finish()?;
+
+    let store = Arc::new(ZipStorageAdapter::new(
+        memory_store.clone(),
+        zip_key.clone(),
+    )?);
+    zarrs_storage::store_test::store_read(&store)?;
+    zarrs_storage::store_test::store_list(&store)?;
+
+    assert!(matches!(
+        ZipStorageAdapter::create(memory_store.clone(), zip_key.clone(), false),
+        Err(ZipStorageAdapterCreateError::ExistingKey(_))
+    ));
+    let store = ZipStorageAdapter::create(memory_store, zip_key, true)?;
+    assert!(store.list()?.is_empty());
+
+    Ok(())
+}
+
+#[cfg(feature = "write")]
+#[test]
+fn zip_store_to_zip() -> Result<(), Box<dyn Error>> {
+    let memory_store = Arc::new(MemoryStore::default());
+    zarrs_storage::store_test::store_write(&memory_store)?;
+
+    let dst_store = Arc::new(MemoryStore::default());
+    let zip_key = StoreKey::new("test.zip")?;
+    let summary = zarrs_zip::store_to_zip(
+        &*memory_store,
+        &*dst_store,
+        &zip_key,
+        &ZipWriteOptions::default(),
+    )?;
+    assert_eq!(summary.num_entries, memory_store.list()?.len() as u64);
+    assert_eq!(summary.bytes_in, memory_store.size()?);
+    assert_eq!(Some(summary.bytes_out), dst_store.size_key(&zip_key)?);
+
+    let zip_store = Arc::new(ZipStorageAdapter::new(dst_store, zip_key)?);
+    assert_eq!(zip_store.list()?, memory_store.list()?);
+    zarrs_storage::store_test::store_read(&zip_store)?;
+    zarrs_storage::store_test::store_list(&zip_store)?;
+
+    Ok(())
+}
+
+#[cfg(feature = "write")]
+#[test]
+fn zip_write_options() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::{
+        byte_range::ByteRange,
+        storage_adapter::performance_metrics::PerformanceMetricsStorageAdapter,
+    };
+    use zarrs_zip::ZipCompression;
+
+    let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    let memory_store = Arc::new(MemoryStore::default());
+    memory_store.set(&"c/0".try_into()?, data.clone().into())?;
+    memory_store.set(&"zarr.json".try_into()?, b"{}".to_vec().into())?;
+    let key = "c/0".try_into()?;
+    let zip_key = StoreKey::new("test.zip")?;
+
+    // A deflated zip file is smaller, and is decompressed on read
+    let deflate = ZipWriteOptions::default().compression(ZipCompression::Deflate(6));
+    let dst_store = Arc::new(MemoryStore::default());
+    let summary = zarrs_zip::store_to_zip(&*memory_store, &*dst_store, &zip_key, &deflate)?;
+    assert!(summary.bytes_out < summary.bytes_in / 10);
+    let zip_store = ZipStorageAdapter::new(dst_store.clone(), zip_key.clone())?;
+    assert_eq!(zip_store.get(&key)?.unwrap(), data);
+    assert_eq!(
+        zip_store
+            .get_partial(&key, ByteRange::FromStart(50_000, Some(10)))?
+            .unwrap(),
+        &data[50_000..50_010]
+    );
+
+    #[cfg(feature = "zstd")]
+    {
+        let zstd = ZipWriteOptions::default().compression(ZipCompression::Zstd(3));
+        let dst_store = Arc::new(MemoryStore::default());
+        zarrs_zip::store_to_zip(&*memory_store, &*dst_store, &zip_key, &zstd)?;
+        let zip_store = ZipStorageAdapter::new(dst_store, zip_key.clone())?;
+        assert_eq!(zip_store.get(&key)?.unwrap(), data);
+    }
+
+    // Partial reads of a stored zip file only read the requested byte ranges
+    let dst_store = Arc::new(PerformanceMetricsStorageAdapter::new(Arc::new(
+        MemoryStore::default(),
+    )));
+    let summary = zarrs_zip::store_to_zip(
+        &*memory_store,
+        &*dst_store,
+        &zip_key,
+        &ZipWriteOptions::default(),
+    )?;
+    assert!(summary.bytes_out > summary.bytes_in);
+    let zip_store = ZipStorageAdapter::new(dst_store.clone(), zip_key.clone())?;
+    let bytes_read = dst_store.bytes_read();
+    assert_eq!(
+        zip_store
+            .get_partial(&key, ByteRange::FromStart(50_000, Some(10)))?
+            .unwrap(),
+        &data[50_000..50_010]
+    );
+    assert!(dst_store.bytes_read() - bytes_read < 100);
+
+    // The write options of the adapter can be overridden per value
+    let zip_store = ZipStorageAdapter::create(dst_store.clone(), zip_key.clone(), true)?
+        .with_write_options(deflate);
+    zip_store.set(&"a".try_into()?, data.clone().into())?;
+    zip_store.set_with_options(
+        &"b".try_into()?,
+        data.clone().into(),
+        &ZipWriteOptions::default(),
+    )?;
+    zip_store.finish()?;
+    assert_eq!(zip_store.get(&"a".try_into()?)?.unwrap(), data);
+    assert_eq!(zip_store.get(&"b".try_into()?)?.unwrap(), data);
+
+    let archive = dst_store.get(&zip_key)?.unwrap();
+    let mut zip = zip::ZipArchive::new(std::io::Cursor::new(archive.to_vec()))?;
+    assert_eq!(
+        zip.by_name("a")?.compression(),
+        zip::CompressionMethod::Deflated
+    );
+    assert_eq!(
+        zip.by_name("b")?.compression(),
+        zip::CompressionMethod::Stored
+    );
+    let mut value = Vec::new();
+    zip.by_name("a")?.read_to_end(&mut value)?;
+    assert_eq!(value, data);
+
+    Ok(())
+}
+
+#[cfg(feature = "write")]
+#[test]
+fn zip_write_compression_policy() -> Result<(), Box<dyn Error>> {
+    use zarrs_zip::{ZipCompression, zarr_compression_policy};
+
+    let chunk: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+    let metadata = br#"{"zarr_format": 3, "node_type": "group", "attributes": {}}"#.to_vec();
+    let memory_store = Arc::new(MemoryStore::default());
+    memory_store.set(&"zarr.json".try_into()?, metadata.clone().into())?;
+    memory_store.set(&"array/zarr.json".try_into()?, metadata.clone().into())?;
+    memory_store.set(&"array/c/0/0".try_into()?, chunk.clone().into())?;
+    memory_store.set(&"other".try_into()?, chunk.clone().into())?;
+    let zip_key = StoreKey::new("test.zip")?;
+
+    let compression_method = |archive: &[u8], name: &str| -> Result<_, Box<dyn Error>> {
+        let mut zip = zip::ZipArchive::new(std::io::Cursor::new(archive))?;
+        Ok(zip.by_name(name)?.compression())
+    };
+
+    // The policy is applied to each copied key
+    let options = ZipWriteOptions::default().compression_policy(zarr_compression_policy);
+    let dst_store = Arc::new(MemoryStore::default());
+    zarrs_zip::store_to_zip(&*memory_store, &*dst_store, &zip_key, &options)?;
+    let archive = dst_store.get(&zip_key)?.unwrap();
+    for (name, method) in [
+        ("zarr.json", zip::CompressionMethod::Deflated),
+        ("array/zarr.json", zip::CompressionMethod::Deflated),
+        ("array/c/0/0", zip::CompressionMethod::Stored),
+        ("other", zip::CompressionMethod::Stored),
+    ] {
+        assert_eq!(compression_method(&archive, name)?, method);
+    }
+    let zip_store = ZipStorageAdapter::new(dst_store, zip_key.clone())?;
+    assert_eq!(zip_store.get(&"array/c/0/0".try_into()?)?.unwrap(), chunk);
+    assert_eq!(zip_store.get(&"zarr.json".try_into()?)?.unwrap(), metadata);
+
+    // The policy is consulted on each set, with the size of the value
+    let options = ZipWriteOptions::default().compression_policy(|_key, size| {
+        if size > 1000 {
+            ZipCompression::Deflate(1)
+        } else {
+            ZipCompression::Stored
+        }
+    });
+    let dst_store = Arc::new(MemoryStore::default());
+    let zip_store = ZipStorageAdapter::create(dst_store.clone(), zip_key.clone(), false)?
+        .with_write_options(options);
+    zip_store.set(&"large".try_into()?, chunk.clone().into())?;
+    zip_store.set(&"small".try_into()?, metadata.clone().into())?;
+    zip_store.finish()?;
+    let archive = dst_store.get(&zip_key)?.unwrap();
+    assert_eq!(
+        compression_method(&archive, "large")?,
+        zip::CompressionMethod::Deflated
+    );
+    assert_eq!(
+        compression_method(&archive, "small")?,
+        zip::CompressionMethod::Stored
+    );
+    assert_eq!(zip_store.get(&"large".try_into()?)?.unwrap(), chunk);
+
+    Ok(())
+}
+
+#[cfg(feature = "write")]
+#[test]
+fn zip_write_alignment() -> Result<(), Box<dyn Error>> {
+    use zarrs_zip::ZipCompression;
+
+    let memory_store = Arc::new(MemoryStore::default());
+    for (i, name) in (0u8..).zip(["a", "bb/c/0", "ddd/c/0/1", "zarr.json"]) {
+        memory_store.set(&StoreKey::new(name)?, vec![i; 100 + usize::from(i)].into())?;
+    }
+    let zip_key = StoreKey::new("test.zip")?;
+
+    for alignment in [64, 4096] {
+        let options = ZipWriteOptions::default()
+            .alignment(alignment)
+            .compression_policy(|key, _| {
+                if key.as_str() == "zarr.json" {
+                    ZipCompression::Deflate(6)
+                } else {
+                    ZipCompression::Stored
+                }
+            });
+        let dst_store = Arc::new(MemoryStore::default());
+        zarrs_zip::store_to_zip(&*memory_store, &*dst_store, &zip_key, &options)?;
+
+        // Entries written by the adapter are also aligned
+        let zip_store = ZipStorageAdapter::open_append(dst_store.clone(), zip_key.clone())?
+            .with_write_options(options.force_zip64(true));
+        zip_store.set(&"eeeee".try_into()?, vec![5; 10].into())?;
+        zip_store.finish()?;
+
+        let archive = dst_store.get(&zip_key)?.unwrap();
+        let mut zip = zip::ZipArchive::new(std::io::Cursor::new(archive.to_vec()))?;
+        assert_eq!(zip.len(), 5);
+        for i in 0..zip.len() {
+            let mut file = zip.by_index(i)?;
+            if file.compression() == zip::CompressionMethod::Stored {
+                assert_eq!(file.data_start() % alignment, 0, "{}", file.name());
+            }
+            let mut value = Vec::new();
+            file.read_to_end(&mut value)?;
+        }
+
+        let zip_store = ZipStorageAdapter::new(dst_store, zip_key.clone())?;
+        assert_eq!(
+            zip_store.get(&"ddd/c/0/1".try_into()?)?.unwrap(),
+            vec![2; 102]
+        );
+        assert_eq!(zip_store.get(&"eeeee".try_into()?)?.unwrap(), vec![5; 10]);
+    }
+
+    Ok(())
+}
+
+/// A store that writes a truncated value and returns an error when `fail` is set, simulating a failure part way through a write.
+///
+/// If `fail_final` is set, only writes of keys other than the temporary keys of staged zip files fail.
+#[cfg(feature = "write")]
+#[derive(Default)]
+struct TruncatingStore {
+    inner: MemoryStore,
+    fail: std::sync::atomic::AtomicBool,
+    fail_final: std::sync::atomic::AtomicBool,
+}
+
+#[cfg(feature = "write")]
+impl ReadableStorageTraits for TruncatingStore {
+    fn get_partial_many<'a>(
+        &'a self,
+        key: &StoreKey,
+        byte_ranges: zarrs_storage::byte_range::ByteRangeIterator<'a>,
+    ) -> Result<zarrs_storage::MaybeBytesIterator<'a>, zarrs_storage::StorageError> {
+        self.inner.get_partial_many(key, byte_ranges)
+    }
+
+    fn size_key(&self, key: &StoreKey) -> Result<Option<u64>, zarrs_storage::StorageError> {
+        self.inner.size_key(key)
+    }
+
+    fn supports_get_partial(&self) -> bool {
+        self.inner.supports_get_partial()
+    }
+}
+
+#[cfg(feature = "write")]
+impl WritableStorageTraits for TruncatingStore {
+    fn set(
+        &self,
+        key: &StoreKey,
+        value: zarrs_storage::Bytes,
+    ) -> Result<(), zarrs_storage::StorageError> {
+        if self.fail.load(std::sync::atomic::Ordering::Relaxed)
+            || (self.fail_final.load(std::sync::atomic::Ordering::Relaxed)
+                && !key.as_str().contains(".tmp-"))
+        {
+            self.inner.set(key, value.slice(..value.len() / 2))?;
+            return Err(zarrs_storage::StorageError::Other(
+                "simulated failure".to_string(),
+            ));
+        }
+        self.inner.set(key, value)
+    }
+
+    fn set_partial_many<'a>(
+        &'a self,
+        key: &StoreKey,
+        offset_values: zarrs_storage::OffsetBytesIterator<'a>,
+    ) -> Result<(), zarrs_storage::StorageError> {
+        self.inner.set_partial_many(key, offset_values)
+    }
+
+    fn erase(&self, key: &StoreKey) -> Result<(), zarrs_storage::StorageError> {
+        self.inner.erase(key)
+    }
+
+    fn erase_prefix(
+        &self,
+        prefix: &zarrs_storage::StorePrefix,
+    ) -> Result<(), zarrs_storage::StorageError> {
+        self.inner.erase_prefix(prefix)
+    }
+
+    fn supports_set_partial(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(feature = "write")]
+#[test]
+fn zip_write_staged() -> Result<(), Box<dyn Error>> {
+    use std::sync::atomic::Ordering;
+
+    let store = Arc::new(TruncatingStore::default());
+    let zip_key = StoreKey::new("test.zip")?;
+    let key_a = StoreKey::new("a")?;
+    let key_b = StoreKey::new("b")?;
+    let zip_store = ZipStorageAdapter::create(store.clone(), zip_key.clone(), false)?;
+    zip_store.set(&key_a, vec![0, 1, 2, 3].into())?;
+    zip_store.finish()?;
+    let original = store.inner.get(&zip_key)?.unwrap();
+
+    // A failure while staging leaves the zip file untouched, and the temporary key is erased
+    zip_store.set(&key_b, vec![4, 5].into())?;
+    store.fail.store(true, Ordering::Relaxed);
+    assert!(zip_store.finish().is_err());
+    assert_eq!(store.inner.get(&zip_key)?.unwrap(), original);
+    assert_eq!(store.inner.list()?, vec![zip_key.clone()]);
+    assert_eq!(
+        ZipStorageAdapter::new(store.clone(), zip_key.clone())?.list()?,
+        vec![key_a.clone()]
+    );
+
+    // Pending changes are retained, so finish can be retried
+    store.fail.store(false, Ordering::Relaxed);
+    zip_store.finish()?;
+    assert_eq!(store.inner.list()?, vec![zip_key.clone()]);
+    let zip_store = ZipStorageAdapter::new(store.clone(), zip_key.clone())?;
+    assert_eq!(zip_store.list()?, vec![key_a.clone(), key_b.clone()]);
+
+    // Without staging, the zip file is written directly and a failure corrupts it
+    let zip_store = zip_store.with_write_options(ZipWriteOptions::default().staged(false));
+    zip_store.erase(&key_b)?;
+    store.fail.store(true, Ordering::Relaxed);
+    assert!(zip_store.finish().is_err());
+    assert!(ZipStorageAdapter::new(store.clone(), zip_key.clone()).is_err());
+    let corrupted = store.inner.get(&zip_key)?.unwrap();
+
+    // store_to_zip is also staged
+    let memory_store = MemoryStore::default();
+    memory_store.set(&key_a, vec![0, 1, 2, 3].into())?;
+    assert!(
+        zarrs_zip::store_to_zip(
+            &memory_store,
+            &*store,
+            &zip_key,
+            &ZipWriteOptions::default()
+        )
+        .is_err()
+    );
+    assert_eq!(store.inner.get(&zip_key)?.unwrap(), corrupted);
+    assert_eq!(store.inner.list()?, vec![zip_key]);
+
+    Ok(())
+}
+
+#[cfg(feature = "write")]
+#[test]
+fn zip_write_staged_final_copy() -> Result<(), Box<dyn Error>> {
+    use std::sync::atomic::Ordering;
+
+    let store = Arc::new(TruncatingStore::default());
+    let zip_key = StoreKey::new("test.zip")?;
+    let key_a = StoreKey::new("a")?;
+    let zip_store = ZipStorageAdapter::create(store.clone(), zip_key.clone(), false)?;
+    zip_store.set(&key_a, vec![0, 1, 2, 3].into())?;
+
+    // A failure while writing the zip file from the staged zip file keeps the temporary key, which is named in the error
+    store.fail_final.store(true, Ordering::Relaxed);
+    let err = zip_store.finish().unwrap_err().to_string();
+    let keys = store.inner.list()?;
+    assert_eq!(keys.len(), 2);
+    let temporary_key = keys.iter().find(|&key| *key != zip_key).unwrap();
+    assert!(err.contains(temporary_key.as_str()));
+
+    // The temporary key holds the complete zip file
+    let staged = ZipStorageAdapter::new(store.clone(), temporary_key.clone())?;
+    assert_eq!(staged.get(&key_a)?.unwrap(), vec![0, 1, 2, 3]);
+
+    Ok(())
+}
+
+#[cfg(all(feature = "async", feature = "write"))]
+#[tokio::test]
+async fn zip_async_write() -> Result<(), Box<dyn Error>> {
+    use object_store::memory::InMemory;
+    use zarrs_object_store::AsyncObjectStore;
+    use zarrs_storage::AsyncWritableStorageTraits;
+
+    let async_store = Arc::new(AsyncObjectStore::new(InMemory::new()));
+    let zip_key = StoreKey::new("test.zip")?;
+    let zip_store =
+        ZipStorageAdapter::create_async(async_store.clone(), zip_key.clone(), false).await?;
+
+    let key_a = StoreKey::new("a/b")?;
+    let key_c = StoreKey::new("c")?;
+    zip_store.set(&key_a, vec![0, 1, 2, 3].into()).await?;
+    zip_store.set(&key_c, vec![4, 5].into()).await?;
+    zip_store.set_partial(&key_c, 3, vec![6].into()).await?;
+    assert_eq!(zip_store.get(&key_c).await?.unwrap(), vec![4u8, 5, 0, 6]);
+    zip_store.finish_async().await?;
+
+    zip_store.erase(&key_a).await?;
+    zip_store.finish_async().await?;
+
+    let zip_store = ZipStorageAdapter::new_async(async_store, zip_key).await?;
+    assert_eq!(zip_store.list().await?, vec![key_c.clone()]);
+    assert_eq!(zip_store.get(&key_c).await?.unwrap(), vec![4u8, 5, 0, 6]);
+
+    Ok(())
+}
+
+#[cfg(all(feature = "async", feature = "write"))]
+#[tokio::test]
+async fn zip_async_write_blocking_spawner() -> Result<(), Box<dyn Error>> {
+    use object_store::memory::InMemory;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use zarrs_object_store::AsyncObjectStore;
+    use zarrs_storage::AsyncWritableStorageTraits;
+    use zarrs_zip::{ZipBlockingSpawner, ZipCompression};
+
+    let spawned = Arc::new(AtomicUsize::new(0));
+    let blocking_spawner: ZipBlockingSpawner = {
+        let spawned = spawned.clone();
+        Arc::new(move |task| {
+            spawned.fetch_add(1, Ordering::Relaxed);
+            tokio::task::spawn_blocking(task);
+        })
+    };
+    let async_store = Arc::new(AsyncObjectStore::new(InMemory::new()));
+    let zip_key = StoreKey::new("test.zip")?;
+    let zip_store = ZipStorageAdapter::create_async(async_store.clone(), zip_key.clone(), false)
+        .await?
+        .with_write_options(ZipWriteOptions::default().compression(ZipCompression::Deflate(6)))
+        .with_blocking_spawner(blocking_spawner);
+
+    // The zip file is rewritten with the compressed values on the blocking spawner
+    let key_a = StoreKey::new("a")?;
+    zip_store.set(&key_a, vec![7; 10_000].into()).await?;
+    zip_store.finish_async().await?;
+    assert_eq!(spawned.load(Ordering::Relaxed), 1);
+    assert_eq!(zip_store.get(&key_a).await?.unwrap(), vec![7; 10_000]);
+
+    // Without pending changes, nothing is spawned
+    zip_store.finish_async().await?;
+    assert_eq!(spawned.load(Ordering::Relaxed), 1);
+
+    let zip_store = ZipStorageAdapter::new_async(async_store, zip_key).await?;
+    assert_eq!(zip_store.get(&key_a).await?.unwrap(), vec![7; 10_000]);
+
+    Ok(())
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+#[cfg_attr(miri, ignore)]
+async fn zip_async_prefetch() -> Result<(), Box<dyn Error>> {
+    use object_store::memory::InMemory;
+    use zarrs_object_store::AsyncObjectStore;
+    use zarrs_storage::AsyncWritableStorageTraits;
+
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    for i in 0..10 {
+        zip.start_file(format!("c/{i}"), options)?;
+        zip.write_all(&[u8::try_from(i)?; 1000])?;
+    }
+    let bytes = zip.finish()?.into_inner();
+
+    let async_store = Arc::new(AsyncObjectStore::new(InMemory::new()));
+    let zip_key = StoreKey::new("test.zip")?;
+    async_store.set(&zip_key, bytes.into()).await?;
+    let zip_store =
+        ZipStorageAdapter::new_with_cache_async(async_store.clone(), zip_key, 1024 * 1024).await?;
+    let keys: Vec<StoreKey> = (0..10)
+        .map(|i| StoreKey::new(format!("c/{i}")))
+        .collect::<Result<_, _>>()?;
+    zip_store.prefetch_async(&keys).await?;
+
+    // The prefetched values are served from the cache
+    async_store.erase(&StoreKey::new("test.zip")?).await?;
+    for (i, key) in keys.iter().enumerate() {
+        assert_eq!(
+            zip_store.get(key).await?.unwrap(),
+            &[u8::try_from(i)?; 1000][..]
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+#[cfg_attr(miri, ignore)]
+async fn zip_async_missing_entry_data() -> Result<(), Box<dyn Error>> {
+    use object_store::memory::InMemory;
+    use zarrs_object_store::AsyncObjectStore;
+    use zarrs_storage::AsyncWritableStorageTraits;
+
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    zip.start_file("a", options)?;
+    zip.write_all(&[1; 100])?;
+    let bytes = zip.finish()?.into_inner();
+
+    let async_store = Arc::new(AsyncObjectStore::new(InMemory::new()));
+    let zip_key = StoreKey::new("test.zip")?;
+    async_store.set(&zip_key, bytes.into()).await?;
+    let zip_store = ZipStorageAdapter::new_async(async_store.clone(), zip_key.clone()).await?;
+    let key = StoreKey::new("a")?;
+    assert_eq!(zip_store.get(&key).await?.unwrap(), &[1; 100][..]);
+
+    // The data of an indexed entry that cannot be read is an error, as with the sync adapter, rather than a missing value
+    async_store.erase(&zip_key).await?;
+    assert!(zip_store.get(&key).await.is_err());
+
+    Ok(())
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+#[cfg_attr(miri, ignore)]
+async fn zip_async_compressed() -> Result<(), Box<dyn Error>> {
+    use object_store::memory::InMemory;
+    use zarrs_object_store::AsyncObjectStore;
+    use zarrs_storage::{AsyncWritableStorageTraits, byte_range::ByteRange};
+
+    // Poorly compressible data, so that the compressed data is read in multiple requests
+    let mut state = 1u32;
+    let data: Vec<u8> = (0..1_000_000)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            state.to_le_bytes()[2]
+        })
+        .collect();
+    let mut methods = vec![zip::CompressionMethod::Deflated];
+    if cfg!(feature = "zstd") {
+        methods.push(zip::CompressionMethod::Zstd);
+    }
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    for method in &methods {
+        let options = SimpleFileOptions::default().compression_method(*method);
+        zip.start_file(format!("{method:?}"), options)?;
+        zip.write_all(&data)?;
+    }
+    let bytes = zip.finish()?.into_inner();
+    // And with a data descriptor
+    let mut zip = zip::ZipWriter::new_stream(Vec::new());
+    for method in &methods {
+        let options = SimpleFileOptions::default().compression_method(*method);
+        zip.start_file(format!("{method:?}"), options)?;
+        zip.write_all(&data)?;
+    }
+    let bytes_stream = zip.finish()?.into_inner();
+
+    let async_store = Arc::new(AsyncObjectStore::new(InMemory::new()));
+    for (name, bytes) in [("test.zip", bytes), ("stream.zip", bytes_stream)] {
+        let zip_key = StoreKey::new(name)?;
+        async_store.set(&zip_key, bytes.into()).await?;
+        let zip_store = ZipStorageAdapter::new_async(async_store.clone(), zip_key).await?;
+        for method in &methods {
+            let key = StoreKey::new(format!("{method:?}"))?;
+            assert_eq!(zip_store.get(&key).await?.unwrap(), data);
+            for (byte_range, expected) in [
+                (ByteRange::FromStart(0, Some(10)), &data[..10]),
+                (
+                    ByteRange::FromStart(500_000, Some(10)),
+                    &data[500_000..500_010],
+                ),
+                (ByteRange::FromStart(10, None), &data[10..]),
+                (ByteRange::Suffix(10), &data[data.len() - 10..]),
+            ] {
+                assert_eq!(
+                    zip_store.get_partial(&key, byte_range).await?.unwrap(),
+                    expected
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+#[cfg_attr(miri, ignore)]
+async fn zip_async_remote() -> Result<(), Box<dyn Error>> {
+    use object_store::http::HttpBuilder;
+    use zarrs_object_store::AsyncObjectStore;
+
+    // Create an HTTP object store pointing to the raw GitHub URL
+    let http_store = HttpBuilder::new()
+        .with_url("https://github.com/zarrs/zarrs_zip/raw/refs/heads/main/tests/")
+        .build()?;
+    let async_store = Arc::new(AsyncObjectStore::new(http_store));
+
+    // Create the zip storage adapter asynchronously
+    let zip_store =
+        Arc::new(ZipStorageAdapter::new_async(async_store, StoreKey::new("zarr.zip")?).await?);
+
+    // Test list
+    let keys = zip_store.list().await?;
+    assert!(!keys.is_empty(), "Expected non-empty list of keys");
+
+    // Test list_prefix for the root
+    let root_keys = zip_store.list_prefix(&"".try_into()?).await?;
+    assert_eq!(keys, root_keys);
+
+    // Test list_dir at root
+    let root_dir = zip_store.list_dir(&"".try_into()?).await?;
+    assert!(
+        !root_dir.keys().is_empty() || !root_dir.prefixes().is_empty(),
+        "Expected non-empty root directory listing"
+    );
+
+    // Test reading zarr.json if it exists
+    if let Some(data) = zip_store.get(&"zarr.json".try_into()?).await? {
+        assert!(!data.is_empty(), "Expected non-empty zarr.json");
+    }
+
+    // Test size
+    let size = zip_store.size().await?;
+    assert!(size > 0, "Expected non-zero zip file size");
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_not_a_zip_file() -> Result<(), Box<dyn Error>> {
+    use zarrs_zip::ZipStorageAdapterCreateError;
+
+    let store = Arc::new(MemoryStore::new());
+    let json_key = StoreKey::new("zarr.json")?;
+    store.set(
+        &json_key,
+        br#"{"zarr_format":3,"node_type":"group"}"#.to_vec().into(),
+    )?;
+    let empty_key = StoreKey::new("empty.zip")?;
+    store.set(&empty_key, Vec::new().into())?;
+
+    for key in [json_key, empty_key] {
+        let Err(err) = ZipStorageAdapter::new(store.clone(), key.clone()) else {
+            panic!("expected an error");
+        };
+        assert!(matches!(
+            &err,
+            ZipStorageAdapterCreateError::NotAZipFile(not_a_zip) if not_a_zip == &key
+        ));
+        assert_eq!(err.to_string(), format!("{key} is not a zip file"));
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_seek_index() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::{
+        byte_range::ByteRange,
+        storage_adapter::performance_metrics::PerformanceMetricsStorageAdapter,
+    };
+    use zarrs_zip::ZipStorageAdapterBuilder;
+
+    // Partially compressible data, so that the entry has many deflate blocks
+    let mut state = 1u32;
+    let data: Vec<u8> = (0..16_000_000u32)
+        .map(|i| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            if i % 4 == 0 {
+                state.to_le_bytes()[2]
+            } else {
+                0
+            }
+        })
+        .collect();
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .large_file(true);
+    zip.start_file("data", options)?;
+    zip.write_all(&data)?;
+    let bytes = zip.finish()?.into_inner();
+    let size = bytes.len();
+
+    let memory_store = Arc::new(MemoryStore::new());
+    memory_store.set(&StoreKey::new("test.zip")?, bytes.into())?;
+    let store = Arc::new(PerformanceMetricsStorageAdapter::new(memory_store));
+    let zip_store = ZipStorageAdapterBuilder::new(store.clone(), StoreKey::new("test.zip")?)
+        .with_seek_index(256 * 1024)
+        .build()?;
+    let key = "data".try_into()?;
+
+    // The first read decompresses the entire entry and records the checkpoints
+    assert_eq!(
+        zip_store
+            .get_partial(&key, ByteRange::Suffix(1024))?
+            .unwrap(),
+        &data[data.len() - 1024..]
+    );
+
+    // Later reads resume from the nearest checkpoint
+    let bytes_read = store.bytes_read();
+    assert_eq!(
+        zip_store
+            .get_partial(&key, ByteRange::Suffix(1024))?
+            .unwrap(),
+        &data[data.len() - 1024..]
+    );
+    assert!(store.bytes_read() - bytes_read < size / 20);
+    for byte_ranges in [
+        vec![ByteRange::FromStart(8_000_000, Some(100))],
+        vec![
+            ByteRange::FromStart(12_000_000, Some(10)),
+            ByteRange::FromStart(11_000_000, Some(1_000_000)),
+        ],
+        vec![ByteRange::FromStart(0, Some(10))],
+    ] {
+        let bytes_read = store.bytes_read();
+        let values = zip_store
+            .get_partial_many(&key, Box::new(byte_ranges.clone().into_iter()))?
+            .unwrap();
+        assert!(store.bytes_read() - bytes_read < size / 10);
+        for (value, byte_range) in values.zip(&byte_ranges) {
+            assert_eq!(value?, &data[byte_range.to_range_usize(data.len() as u64)]);
+        }
+    }
+    assert_eq!(zip_store.get(&key)?.unwrap(), data);
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_seek_flush_points() -> Result<(), Box<dyn Error>> {
+    use miniz_oxide::deflate::core::{
+        CompressorOxide, TDEFLFlush, compress_to_output, create_comp_flags_from_zip_params,
+    };
+    use zarrs_storage::{
+        byte_range::ByteRange,
+        storage_adapter::performance_metrics::PerformanceMetricsStorageAdapter,
+    };
+    use zarrs_zip::ZipStorageAdapterBuilder;
+
+    let mut state = 1u32;
+    let data: Vec<u8> = (0..4_000_000u32)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            state.to_le_bytes()[2] % 16
+        })
+        .collect();
+
+    // Deflate the data with a flush every 64 KiB, and write it to a zip file by hand
+    let mut zip = Vec::new();
+    let mut central_directory = Vec::new();
+    for (name, flush) in [("full", TDEFLFlush::Full), ("sync", TDEFLFlush::Sync)] {
+        let mut compressor = CompressorOxide::new(create_comp_flags_from_zip_params(6, -15, 0));
+        let mut compressed = Vec::new();
+        for chunk in data.chunks(64 * 1024) {
+            compress_to_output(&mut compressor, chunk, flush, |output| {
+                compressed.extend_from_slice(output);
+                true
+            });
+        }
+        compress_to_output(&mut compressor, &[], TDEFLFlush::Finish, |output| {
+            compressed.extend_from_slice(output);
+            true
+        });
+        let fields = [
+            &20u16.to_le_bytes()[..],
+            &0u16.to_le_bytes(),
+            &8u16.to_le_bytes(),
+            &0u32.to_le_bytes(),
+            &crc32fast::hash(&data).to_le_bytes(),
+            &u32::try_from(compressed.len())?.to_le_bytes(),
+            &u32::try_from(data.len())?.to_le_bytes(),
+            &u16::try_from(name.len())?.to_le_bytes(),
+            &0u16.to_le_bytes(),
+        ]
+        .concat();
+        let header_offset = u32::try_from(zip.len())?;
+        zip.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        zip.extend_from_slice(&fields);
+        zip.extend_from_slice(name.as_bytes());
+        zip.extend_from_slice(&compressed);
+        central_directory.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        central_directory.extend_from_slice(&20u16.to_le_bytes());
+        central_directory.extend_from_slice(&fields);
+        central_directory.extend_from_slice(&[0; 10]);
+        central_directory.extend_from_slice(&header_offset.to_le_bytes());
+        central_directory.extend_from_slice(name.as_bytes());
+    }
+    let central_directory_offset = u32::try_from(zip.len())?;
+    zip.extend_from_slice(&central_directory);
+    zip.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+    zip.extend_from_slice(&[0, 0, 0, 0, 2, 0, 2, 0]);
+    zip.extend_from_slice(&u32::try_from(central_directory.len())?.to_le_bytes());
+    zip.extend_from_slice(&central_directory_offset.to_le_bytes());
+    zip.extend_from_slice(&[0, 0]);
+
+    let memory_store = Arc::new(MemoryStore::new());
+    memory_store.set(&StoreKey::new("test.zip")?, zip.into())?;
+    let store = Arc::new(PerformanceMetricsStorageAdapter::new(memory_store));
+    let zip_store = ZipStorageAdapterBuilder::new(store.clone(), StoreKey::new("test.zip")?)
+        .seek_flush_points(true)
+        .build()?;
+
+    for name in ["full", "sync"] {
+        let key = name.try_into()?;
+        let compressed_size = zip_store.entry_metadata(&key).unwrap().compressed_size;
+
+        // The first read decompresses the entire entry and records the full flush points
+        assert_eq!(zip_store.get(&key)?.unwrap(), data);
+        for byte_range in [
+            ByteRange::FromStart(3_000_000, Some(100)),
+            ByteRange::FromStart(1_000_000, Some(200_000)),
+            ByteRange::Suffix(1024),
+        ] {
+            let bytes_read = store.bytes_read();
+            assert_eq!(
+                zip_store.get_partial(&key, byte_range)?.unwrap(),
+                &data[byte_range.to_range_usize(data.len() as u64)]
+            );
+            let bytes_read = (store.bytes_read() - bytes_read) as u64;
+            if name == "full" {
+                // Later reads restart from the nearest flush point
+                assert!(bytes_read < compressed_size / 10);
+            } else {
+                // A sync flush does not reset the window, so decompression cannot restart from it
+                assert!(bytes_read > byte_range.end(data.len() as u64) / 4);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "encryption")]
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_aes_encrypted() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::byte_range::ByteRange;
+    use zip::AesMode;
+
+    let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    for (name, aes_mode, method) in [
+        ("aes128", AesMode::Aes128, zip::CompressionMethod::Deflated),
+        ("aes192", AesMode::Aes192, zip::CompressionMethod::Stored),
+        ("aes256", AesMode::Aes256, zip::CompressionMethod::Deflated),
+    ] {
+        let options = SimpleFileOptions::default()
+            .compression_method(method)
+            .with_aes_encryption(aes_mode, "password");
+        zip.start_file(name, options)?;
+        zip.write_all(&data)?;
+    }
+    zip.start_file("plain", SimpleFileOptions::default())?;
+    zip.write_all(b"plain")?;
+    let bytes = zip.finish()?.into_inner();
+
+    let store = Arc::new(MemoryStore::new());
+    let zip_key = StoreKey::new("test.zip")?;
+    store.set(&zip_key, bytes.clone().into())?;
+
+    let zip_store = ZipStorageAdapter::new_encrypted(store.clone(), zip_key.clone(), "password")?;
+    assert!(zip_store.unsupported_methods().is_empty());
+    for name in ["aes128", "aes192", "aes256"] {
+        let key = name.try_into()?;
+        assert_eq!(zip_store.get(&key)?.unwrap(), data);
+        assert_eq!(zip_store.size_key(&key)?, Some(data.len() as u64));
+        assert_eq!(
+            zip_store
+                .get_partial(&key, ByteRange::FromStart(1000, Some(10)))?
+                .unwrap(),
+            &data[1000..1010]
+        );
+    }
+    assert_eq!(
+        zip_store.get(&"plain".try_into()?)?.unwrap(),
+        b"plain".as_slice()
+    );
+
+    // A wrong or missing password is an error, and entries that are not encrypted are still readable
+    let zip_store = ZipStorageAdapter::new_encrypted(store.clone(), zip_key.clone(), "wrong")?;
+    let err = zip_store.get(&"aes256".try_into()?).unwrap_err();
+    assert!(err.to_string().contains("wrong password"));
+    let zip_store = ZipStorageAdapter::new(store.clone(), zip_key.clone())?;
+    let err = zip_store.get(&"aes256".try_into()?).unwrap_err();
+    assert!(err.to_string().contains("no password"));
+    assert_eq!(
+        zip_store.get(&"plain".try_into()?)?.unwrap(),
+        b"plain".as_slice()
+    );
+
+    // Encrypted data that has been tampered with fails authentication, even with the right password
+    let mut tampered = bytes;
+    let name = tampered
+        .windows(6)
+        .position(|window| window == b"aes256")
+        .unwrap();
+    let extra_len = usize::from(u16::from_le_bytes([tampered[name - 2], tampered[name - 1]]));
+    // After the salt and password verification value of an AES-256 entry
+    tampered[name + 6 + extra_len + 16 + 2 + 100] ^= 1;
+    store.set(&zip_key, tampered.into())?;
+    let zip_store = ZipStorageAdapter::new_encrypted(store, zip_key, "password")?;
+    let err = zip_store.get(&"aes256".try_into()?).unwrap_err();
+    assert!(err.to_string().contains("failed authentication"));
+    assert_eq!(zip_store.get(&"aes128".try_into()?)?.unwrap(), data);
+
+    Ok(())
+}
+
+#[cfg(feature = "encryption")]
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_zip_crypto_encrypted() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::byte_range::ByteRange;
+    use zarrs_zip::ZipStorageAdapterBuilder;
+
+    // Written by Info-ZIP with `zip -P password`, the encrypted entries have data descriptors
+    let path = Path::new("tests/zipcrypto.zip");
+    let storage = Arc::new(FilesystemStore::new(path)?);
+    let deflated: Vec<u8> = (0..2000)
+        .flat_map(|i| format!("line {i}\n").into_bytes())
+        .collect();
+
+    let store = ZipStorageAdapter::new_encrypted(storage.clone(), StoreKey::root(), "password")?;
+    assert_eq!(store.get(&"deflated".try_into()?)?.unwrap(), deflated);
+    assert_eq!(
+        store
+            .get_partial(&"deflated".try_into()?, ByteRange::FromStart(5, Some(4)))?
+            .unwrap(),
+        b"0\nli".as_slice()
+    );
+    assert_eq!(
+        store.get(&"stored".try_into()?)?.unwrap(),
+        b"stored value".as_slice()
+    );
+    assert_eq!(
+        store
+            .get_blocks(&"stored".try_into()?, 5)?
+            .unwrap()
+            .map(|block| block.map(|block| block.to_vec()))
+            .collect::<Result<Vec<_>, _>>()?,
+        [b"store".to_vec(), b"d val".to_vec(), b"ue".to_vec()]
+    );
+    assert_eq!(
+        store.get(&"plain".try_into()?)?.unwrap(),
+        b"plain".as_slice()
+    );
+
+    // A wrong or missing password is an error
+    let store = ZipStorageAdapterBuilder::new(storage.clone(), StoreKey::root())
+        .password("wrong")
+        .build()?;
+    for key in ["stored", "deflated"] {
+        let err = store.get(&key.try_into()?).unwrap_err();
+        assert!(err.to_string().contains("wrong password"));
+    }
+    let store = ZipStorageAdapter::new(storage, StoreKey::root())?;
+    let err = store.get(&"deflated".try_into()?).unwrap_err();
+    assert!(err.to_string().contains("no password"));
+    assert_eq!(
+        store.get(&"plain".try_into()?)?.unwrap(),
+        b"plain".as_slice()
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_read_progress() -> Result<(), Box<dyn Error>> {
+    use std::sync::Mutex;
+    use zarrs_storage::byte_range::ByteRange;
+    use zarrs_zip::{ZipReadProgress, ZipStorageAdapterBuilder};
+
+    // Incompressible data, so that the compressed data is read in more than one step
+    let mut state = 1u32;
+    let data: Vec<u8> = (0..1_000_000)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            state.to_le_bytes()[2]
+        })
+        .collect();
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    zip.start_file("data", options)?;
+    zip.write_all(&data)?;
+    let bytes = zip.finish()?.into_inner();
+    let size = bytes.len() as u64;
+
+    let store = Arc::new(MemoryStore::new());
+    store.set(&StoreKey::new("test.zip")?, bytes.into())?;
+    let progress: Arc<Mutex<Vec<(StoreKey, ZipReadProgress)>>> = Arc::default();
+    let zip_store = ZipStorageAdapterBuilder::new(store, StoreKey::new("test.zip")?)
+        .read_progress({
+            let progress = progress.clone();
+            Arc::new(move |key, read_progress| {
+                progress.lock().unwrap().push((key.clone(), read_progress));
+            })
+        })
+        .build()?;
+    let key: StoreKey = "data".try_into()?;
+
+    // Progress is reported as the entry is decompressed, and ends with the entire entry
+    assert_eq!(zip_store.get(&key)?.unwrap(), data);
+    let reports = std::mem::take(&mut *progress.lock().unwrap());
+    assert!(reports.len() > 1);
+    assert!(reports.iter().all(|(report_key, _)| report_key == &key));
+    assert!(reports.windows(2).all(|reports| {
+        reports[0].1.bytes_read <= reports[1].1.bytes_read
+            && reports[0].1.bytes_decompressed <= reports[1].1.bytes_decompressed
+    }));
+    let (_, last) = reports.last().unwrap();
+    assert_eq!(last.bytes_decompressed, data.len() as u64);
+    assert_eq!(last.uncompressed_size, data.len() as u64);
+    assert!(last.bytes_read >= last.compressed_size && last.bytes_read <= size);
+
+    // A partial read stops reporting at the end of its byte ranges
+    zip_store.get_partial(&key, ByteRange::FromStart(0, Some(10)))?;
+    let reports = std::mem::take(&mut *progress.lock().unwrap());
+    let (_, last) = reports.last().unwrap();
+    assert!(last.bytes_decompressed >= 10 && last.bytes_decompressed < data.len() as u64);
+
+    Ok(())
+}
+
+#[test]
+#[ignore = "writes and opens a zip file with 2 million entries"]
+fn zip_many_entries() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::StorePrefix;
+
+    const NUM_ENTRIES: u32 = 2_000_000;
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    for i in 0..NUM_ENTRIES {
+        zip.start_file(format!("array/c/{}/{}", i / 1000, i % 1000), options)?;
+        zip.write_all(&i.to_le_bytes())?;
+    }
+    let bytes = zip.finish()?.into_inner();
+
+    let store = Arc::new(MemoryStore::new());
+    store.set(&StoreKey::new("test.zip")?, bytes.into())?;
+    let zip_store = ZipStorageAdapter::new(store, StoreKey::new("test.zip")?)?;
+
+    assert_eq!(zip_store.list()?.len(), NUM_ENTRIES as usize);
+    let prefix = StorePrefix::new("array/c/1999/")?;
+    assert_eq!(zip_store.list_prefix(&prefix)?.len(), 1000);
+    assert_eq!(
+        zip_store
+            .list_dir(&StorePrefix::new("array/c/")?)?
+            .prefixes()
+            .len(),
+        NUM_ENTRIES as usize / 1000
+    );
+    for i in [0, 1, NUM_ENTRIES / 2, NUM_ENTRIES - 1] {
+        let key = StoreKey::new(format!("array/c/{}/{}", i / 1000, i % 1000))?;
+        assert_eq!(zip_store.get(&key)?.unwrap(), i.to_le_bytes().as_slice());
+    }
+    assert!(zip_store.get(&"array/c/2000/0".try_into()?)?.is_none());
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_from_archive() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::storage_adapter::performance_metrics::PerformanceMetricsStorageAdapter;
+
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    for (name, value) in [("a/x", b"ax"), ("a/y", b"ay"), ("b/x", b"bx")] {
+        zip.start_file(name, SimpleFileOptions::default())?;
+        zip.write_all(value)?;
+    }
+    let bytes = zip.finish()?.into_inner();
+
+    let memory_store = Arc::new(MemoryStore::new());
+    let zip_key = StoreKey::new("test.zip")?;
+    memory_store.set(&zip_key, bytes.into())?;
+    let store = Arc::new(PerformanceMetricsStorageAdapter::new(memory_store));
+
+    // The central directory is parsed once, and the adapters are created without reading the zip file
+    let archive = ZipStorageAdapter::parse(&store, &zip_key)?;
+    let reads = store.reads();
+    let a_store = ZipStorageAdapter::from_archive(store.clone(), zip_key.clone(), &archive, "a")?;
+    let b_store = ZipStorageAdapter::from_archive(store.clone(), zip_key.clone(), &archive, "b/")?;
+    assert_eq!(store.reads(), reads);
+
+    assert_eq!(a_store.list()?, &["x".try_into()?, "y".try_into()?]);
+    assert_eq!(b_store.list()?, &["x".try_into()?]);
+    assert_eq!(a_store.get(&"x".try_into()?)?.unwrap(), b"ax".as_slice());
+    assert_eq!(b_store.get(&"x".try_into()?)?.unwrap(), b"bx".as_slice());
+    assert!(b_store.get(&"y".try_into()?)?.is_none());
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_min_read_size() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::{
+        byte_range::ByteRange,
+        storage_adapter::performance_metrics::PerformanceMetricsStorageAdapter,
+    };
+    use zarrs_zip::ZipStorageAdapterBuilder;
+
+    // Incompressible data, so that the compressed data is as large as the entry
+    let mut state = 1u32;
+    let data: Vec<u8> = (0..4_000_000)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            state.to_le_bytes()[2]
+        })
+        .collect();
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    zip.start_file("data", options)?;
+    zip.write_all(&data)?;
+    zip.start_file("other", options)?;
+    zip.write_all(b"other")?;
+    let bytes = zip.finish()?.into_inner();
+    let size = bytes.len();
+
+    let memory_store = Arc::new(MemoryStore::new());
+    memory_store.set(&StoreKey::new("test.zip")?, bytes.into())?;
+    let store = Arc::new(PerformanceMetricsStorageAdapter::new(memory_store));
+    let key: StoreKey = "data".try_into()?;
+
+    // Read the entry incrementally rather than in a single request
+    let zip_store = ZipStorageAdapterBuilder::new(store.clone(), StoreKey::new("test.zip")?)
+        .single_read_threshold(0)
+        .build()?;
+    let reads = store.reads();
+    assert_eq!(zip_store.get(&key)?.unwrap(), data);
+    let default_reads = store.reads() - reads;
+
+    // The compressed data is read in requests of at least 1 MiB, which do not extend beyond the entry
+    let zip_store = ZipStorageAdapterBuilder::new(store.clone(), StoreKey::new("test.zip")?)
+        .single_read_threshold(0)
+        .min_read_size(1 << 20)
+        .build()?;
+    let (reads, bytes_read) = (store.reads(), store.bytes_read());
+    assert_eq!(zip_store.get(&key)?.unwrap(), data);
+    let min_read_size_reads = store.reads() - reads;
+    assert!(min_read_size_reads <= 6);
+    assert!(min_read_size_reads < default_reads);
+    assert!(store.bytes_read() - bytes_read < size);
+    assert_eq!(
+        zip_store
+            .get_partial(&key, ByteRange::FromStart(1000, Some(10)))?
+            .unwrap(),
+        &data[1000..1010]
+    );
+    assert_eq!(
+        zip_store.get(&"other".try_into()?)?.unwrap(),
+        b"other".as_slice()
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_read_buffer_size() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::{
+        byte_range::ByteRange,
+        storage_adapter::performance_metrics::PerformanceMetricsStorageAdapter,
+    };
+    use zarrs_zip::ZipStorageAdapterBuilder;
+
+    // Incompressible data, so that the compressed data is as large as the entry
+    let mut state = 1u32;
+    let data: Vec<u8> = (0..2_000_000)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            state.to_le_bytes()[2]
+        })
+        .collect();
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    zip.start_file("data", options)?;
+    zip.write_all(&data)?;
+    let bytes = zip.finish()?.into_inner();
+    let size = bytes.len();
+
+    let memory_store = Arc::new(MemoryStore::new());
+    memory_store.set(&StoreKey::new("test.zip")?, bytes.into())?;
+    let store = Arc::new(PerformanceMetricsStorageAdapter::new(memory_store));
+    let key: StoreKey = "data".try_into()?;
+
+    for (read_buffer_size, min_reads, max_reads) in [
+        // Requests of at most 8 KiB
+        (8 * 1024u64, size / (8 * 1024), usize::MAX),
+        // Requests of 1 MiB, with the minimum read size
+        (1 << 20, 1, 6),
+    ] {
+        let zip_store = ZipStorageAdapterBuilder::new(store.clone(), StoreKey::new("test.zip")?)
+            .single_read_threshold(0)
+            .min_read_size(1 << 20)
+            .read_buffer_size(read_buffer_size)
+            .build()?;
+        let reads = store.reads();
+        assert_eq!(zip_store.get(&key)?.unwrap(), data);
+        let reads = store.reads() - reads;
+        assert!((min_reads..=max_reads).contains(&reads));
+        assert_eq!(
+            zip_store
+                .get_partial(&key, ByteRange::FromStart(1_500_000, Some(10)))?
+                .unwrap(),
+            &data[1_500_000..1_500_010]
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_single_read() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::{
+        byte_range::ByteRange,
+        storage_adapter::performance_metrics::PerformanceMetricsStorageAdapter,
+    };
+    use zarrs_zip::ZipStorageAdapterBuilder;
+
+    // Incompressible data, so that the compressed data is as large as the entry
+    let mut state = 1u32;
+    let data: Vec<u8> = (0..4_000_000)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            state.to_le_bytes()[2]
+        })
+        .collect();
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    zip.start_file("data", options)?;
+    zip.write_all(&data)?;
+    zip.start_file("other", options)?;
+    zip.write_all(b"other")?;
+    let bytes = zip.finish()?.into_inner();
+
+    let memory_store = Arc::new(MemoryStore::new());
+    memory_store.set(&StoreKey::new("test.zip")?, bytes.into())?;
+    let store = Arc::new(PerformanceMetricsStorageAdapter::new(memory_store));
+    let key: StoreKey = "data".try_into()?;
+
+    // The local file header and compressed data are read in a single request
+    let zip_store = ZipStorageAdapter::new(store.clone(), StoreKey::new("test.zip")?)?;
+    let reads = store.reads();
+    assert_eq!(zip_store.get(&key)?.unwrap(), data);
+    assert_eq!(store.reads() - reads, 1);
+    let reads = store.reads();
+    assert_eq!(
+        zip_store.get(&"other".try_into()?)?.unwrap(),
+        b"other".as_slice()
+    );
+    assert_eq!(store.reads() - reads, 1);
+
+    // Partial reads that stop early do not read the entire entry
+    let bytes_read = store.bytes_read();
+    assert_eq!(
+        zip_store
+            .get_partial(&key, ByteRange::FromStart(1000, Some(10)))?
+            .unwrap(),
+        &data[1000..1010]
+    );
+    assert!(store.bytes_read() - bytes_read < data.len() / 4);
+
+    // Entries larger than the threshold are read incrementally
+    let zip_store = ZipStorageAdapterBuilder::new(store.clone(), StoreKey::new("test.zip")?)
+        .single_read_threshold(1000)
+        .build()?;
+    let reads = store.reads();
+    assert_eq!(zip_store.get(&key)?.unwrap(), data);
+    assert!(store.reads() - reads > 1);
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_case_insensitive() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::ListableStorageTraits;
+    use zarrs_zip::ZipStorageAdapterBuilder;
+
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    zip.start_file("Group/ZARR.JSON", options)?;
+    zip.write_all(b"metadata")?;
+    zip.start_file("group/c/0", options)?;
+    zip.write_all(b"chunk")?;
+    zip.start_file("a", options)?;
+    zip.write_all(b"first")?;
+    zip.start_file("A", options)?;
+    zip.write_all(b"last")?;
+    let bytes = zip.finish()?.into_inner();
+
+    let store = Arc::new(MemoryStore::new());
+    store.set(&StoreKey::new("test.zip")?, bytes.into())?;
+
+    // Keys are case-sensitive by default
+    let zip_store = ZipStorageAdapter::new(store.clone(), StoreKey::new("test.zip")?)?;
+    assert!(zip_store.get(&"group/zarr.json".try_into()?)?.is_none());
+    assert_eq!(
+        zip_store.get(&"a".try_into()?)?.unwrap(),
+        b"first".as_slice()
+    );
+
+    let zip_store = ZipStorageAdapterBuilder::new(store, StoreKey::new("test.zip")?)
+        .case_insensitive(true)
+        .build()?;
+    assert_eq!(
+        zip_store.get(&"group/zarr.json".try_into()?)?.unwrap(),
+        b"metadata".as_slice()
+    );
+    assert_eq!(
+        zip_store.get(&"GROUP/C/0".try_into()?)?.unwrap(),
+        b"chunk".as_slice()
+    );
+
+    // The last of the entries that differ only by case wins
+    assert_eq!(
+        zip_store.get(&"a".try_into()?)?.unwrap(),
+        b"last".as_slice()
+    );
+    assert_eq!(
+        zip_store.get(&"A".try_into()?)?.unwrap(),
+        b"last".as_slice()
+    );
+
+    // Prefixes match ignoring case
+    assert_eq!(
+        zip_store.list_prefix(&"GROUP/".try_into()?)?,
+        &["group/c/0".try_into()?, "Group/ZARR.JSON".try_into()?]
+    );
+    let list_dir = zip_store.list_dir(&"group/".try_into()?)?;
+    assert_eq!(list_dir.keys(), &["Group/ZARR.JSON".try_into()?]);
+    assert_eq!(list_dir.prefixes(), &["group/c/".try_into()?]);
+    assert_eq!(
+        zip_store.list()?,
+        &[
+            "A".try_into()?,
+            "group/c/0".try_into()?,
+            "Group/ZARR.JSON".try_into()?
+        ]
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+#[cfg_attr(miri, ignore)]
+async fn zip_async_concurrency() -> Result<(), Box<dyn Error>> {
+    use std::time::{Duration, Instant};
+
+    use futures::TryStreamExt;
+    use object_store::memory::InMemory;
+    use zarrs_object_store::AsyncObjectStore;
+    use zarrs_storage::{
+        AsyncMaybeBytesIterator, AsyncWritableStorageTraits, StorageError,
+        byte_range::{ByteRange, ByteRangeIterator},
+    };
+    use zarrs_zip::ZipStorageAdapterBuilder;
+
+    /// A store with a round trip of `delay` for each byte range.
+    struct DelayedStore {
+        storage: AsyncObjectStore<InMemory>,
+        delay: Duration,
+    }
+
+    #[async_trait::async_trait]
+    impl AsyncReadableStorageTraits for DelayedStore {
+        async fn get_partial_many<'a>(
+            &'a self,
+            key: &StoreKey,
+            byte_ranges: ByteRangeIterator<'a>,
+        ) -> Result<AsyncMaybeBytesIterator<'a>, StorageError> {
+            let byte_ranges: Vec<ByteRange> = byte_ranges.collect();
+            for _ in &byte_ranges {
+                let delay = self.delay;
+                tokio::task::spawn_blocking(move || std::thread::sleep(delay))
+                    .await
+                    .map_err(|err| StorageError::Other(err.to_string()))?;
+            }
+            self.storage
+                .get_partial_many(key, Box::new(byte_ranges.into_iter()))
+                .await
+        }
+
+        async fn size_key(&self, key: &StoreKey) -> Result<Option<u64>, StorageError> {
+            self.storage.size_key(key).await
+        }
+
+        fn supports_get_partial(&self) -> bool {
+            true
+        }
+    }
+
+    let data: Vec<u8> = (0..1_000_000u32).map(|i| i.to_le_bytes()[0]).collect();
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    zip.start_file("stored", options)?;
+    zip.write_all(&data)?;
+    let bytes = zip.finish()?.into_inner();
+
+    let delay = Duration::from_millis(50);
+    let storage = AsyncObjectStore::new(InMemory::new());
+    storage
+        .set(&StoreKey::new("test.zip")?, bytes.into())
+        .await?;
+    let store = Arc::new(DelayedStore { storage, delay });
+    let key = StoreKey::new("stored")?;
+
+    // Byte ranges far apart, so that they are not coalesced
+    let byte_ranges: Vec<ByteRange> = (0..8)
+        .map(|i| ByteRange::FromStart(i * 100_000, Some(10)))
+        .collect();
+    for concurrency in [8, 1] {
+        let zip_store = ZipStorageAdapterBuilder::new(store.clone(), StoreKey::new("test.zip")?)
+            .concurrency(concurrency)
+            .build_async()
+            .await?;
+        let start = Instant::now();
+        let values: Vec<_> = zip_store
+            .get_partial_many(&key, Box::new(byte_ranges.clone().into_iter()))
+            .await?
+            .unwrap()
+            .try_collect()
+            .await?;
+        let elapsed = start.elapsed();
+        for (value, byte_range) in values.iter().zip(&byte_ranges) {
+            assert_eq!(value, &data[byte_range.to_range_usize(data.len() as u64)]);
+        }
+
+        // The local file header is read first, then the byte ranges in a single round trip
+        if concurrency == 8 {
+            assert!(elapsed < delay * 5);
+        } else {
+            assert!(elapsed >= delay * 9);
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_preload_small_entries() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::{
+        byte_range::ByteRange,
+        storage_adapter::performance_metrics::PerformanceMetricsStorageAdapter,
+    };
+    use zarrs_zip::ZipStorageAdapterBuilder;
+
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let stored = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    let deflated =
+        SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    let metadata = |i: usize| format!(r#"{{"node_type":"group","attributes":{{"i":{i}}}}}"#);
+    for i in 0..10 {
+        zip.start_file(format!("g{i}/zarr.json"), deflated)?;
+        zip.write_all(metadata(i).as_bytes())?;
+        zip.start_file(format!("g{i}/c/0"), stored)?;
+        zip.write_all(&[u8::try_from(i)?; 10_000])?;
+    }
+    let bytes = zip.finish()?.into_inner();
+
+    let memory_store = Arc::new(MemoryStore::new());
+    memory_store.set(&StoreKey::new("test.zip")?, bytes.into())?;
+    let store = Arc::new(PerformanceMetricsStorageAdapter::new(memory_store));
+
+    let zip_store = ZipStorageAdapterBuilder::new(store.clone(), StoreKey::new("test.zip")?)
+        .preload_small_entries(1000)
+        .build()?;
+
+    // Small entries are served without reading the zip file
+    let reads = store.reads();
+    for i in 0..10 {
+        let key = StoreKey::new(format!("g{i}/zarr.json"))?;
+        assert_eq!(zip_store.get(&key)?.unwrap(), metadata(i).as_bytes());
+        assert_eq!(
+            zip_store
+                .get_partial(&key, ByteRange::FromStart(1, Some(4)))?
+                .unwrap(),
+            &metadata(i).as_bytes()[1..5]
+        );
+    }
+    assert_eq!(store.reads(), reads);
+
+    // Larger entries are read from the zip file
+    assert_eq!(
+        zip_store.get(&"g1/c/0".try_into()?)?.unwrap(),
+        &[1; 10_000][..]
+    );
+    assert!(store.reads() > reads);
+
+    // Preloaded values count against the capacity of the cache
+    let zip_store = ZipStorageAdapterBuilder::new(store.clone(), StoreKey::new("test.zip")?)
+        .cache_bytes(100)
+        .preload_small_entries(1000)
+        .build()?;
+    let capacity = zip_store.cache_stats().unwrap().capacity;
+    assert!(capacity < metadata(0).len());
+    let reads = store.reads();
+    for i in 0..10 {
+        let key = StoreKey::new(format!("g{i}/zarr.json"))?;
+        assert_eq!(zip_store.get(&key)?.unwrap(), metadata(i).as_bytes());
+    }
+    assert!(store.reads() > reads);
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_preload_all() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::{
+        byte_range::ByteRange,
+        storage_adapter::performance_metrics::PerformanceMetricsStorageAdapter,
+    };
+    use zarrs_zip::{ZipStorageAdapterBuilder, ZipStorageAdapterCreateError};
+
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let stored = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    let deflated =
+        SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    for i in 0..10 {
+        zip.start_file(format!("c/{i}/deflated"), deflated)?;
+        zip.write_all(&[u8::try_from(i)?; 10_000])?;
+        zip.start_file(format!("c/{i}/stored"), stored)?;
+        zip.write_all(&[u8::try_from(i)?; 1000])?;
+    }
+    let bytes = zip.finish()?.into_inner();
+
+    let memory_store = Arc::new(MemoryStore::new());
+    memory_store.set(&StoreKey::new("test.zip")?, bytes.into())?;
+    let store = Arc::new(PerformanceMetricsStorageAdapter::new(memory_store));
+
+    // All entries are served without reading the zip file, the preloaded values do not need the cache
+    let zip_store = ZipStorageAdapterBuilder::new(store.clone(), StoreKey::new("test.zip")?)
+        .cache_bytes(100)
+        .preload_all(110_000)
+        .build()?;
+    let reads = store.reads();
+    for i in 0..10 {
+        let value = u8::try_from(i)?;
+        let key = StoreKey::new(format!("c/{i}/deflated"))?;
+        assert_eq!(zip_store.get(&key)?.unwrap(), &[value; 10_000][..]);
+        assert_eq!(
+            zip_store.get_partial(&key, ByteRange::Suffix(10))?.unwrap(),
+            &[value; 10][..]
+        );
+        let key = StoreKey::new(format!("c/{i}/stored"))?;
+        assert_eq!(zip_store.get(&key)?.unwrap(), &[value; 1000][..]);
+    }
+    assert_eq!(store.reads(), reads);
+    assert_eq!(zip_store.cache_stats().unwrap().entries, 0);
+
+    // The total size of the values is limited
+    let result = ZipStorageAdapterBuilder::new(store.clone(), StoreKey::new("test.zip")?)
+        .preload_all(100_000)
+        .build();
+    let Err(err @ ZipStorageAdapterCreateError::PreloadTooLarge { size, max_size }) = result else {
+        panic!("expected the preload to be too large");
+    };
+    assert_eq!((size, max_size), (110_000, 100_000));
+    assert!(err.to_string().contains("110000 bytes"));
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_normalize_separators() -> Result<(), Box<dyn Error>> {
+    use zarrs_zip::ZipStorageAdapterBuilder;
+
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    zip.start_file("root\\group\\", options)?;
+    zip.start_file("root\\group\\zarr.json", options)?;
+    zip.write_all(b"metadata")?;
+    zip.start_file("root\\group\\c\\0", options)?;
+    zip.write_all(b"chunk")?;
+    let bytes = zip.finish()?.into_inner();
+
+    let store = Arc::new(MemoryStore::new());
+    store.set(&StoreKey::new("test.zip")?, bytes.into())?;
+
+    // Backslashes are part of the key by default
+    let zip_store = ZipStorageAdapter::new(store.clone(), StoreKey::new("test.zip")?)?;
+    assert!(
+        zip_store
+            .get(&"root/group/zarr.json".try_into()?)?
+            .is_none()
+    );
+
+    let zip_store = ZipStorageAdapterBuilder::new(store, StoreKey::new("test.zip")?)
+        .normalize_separators(true)
+        .path("root")
+        .build()?;
+    assert_eq!(
+        zip_store.get(&"group/zarr.json".try_into()?)?.unwrap(),
+        b"metadata".as_slice()
+    );
+    assert_eq!(
+        zip_store.get(&"group/c/0".try_into()?)?.unwrap(),
+        b"chunk".as_slice()
+    );
+    assert_eq!(
+        zip_store.list()?,
+        &["group/c/0".try_into()?, "group/zarr.json".try_into()?]
+    );
+    let list_dir = zip_store.list_dir(&"group/".try_into()?)?;
+    assert_eq!(list_dir.keys(), &["group/zarr.json".try_into()?]);
+    assert_eq!(list_dir.prefixes(), &["group/c/".try_into()?]);
+    assert_eq!(
+        zip_store.list_dir_prefixes(&"".try_into()?),
+        &["group/".try_into()?]
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_get_partial_many_keys() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::{
+        byte_range::ByteRange,
+        storage_adapter::performance_metrics::PerformanceMetricsStorageAdapter,
+    };
+
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let stored = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    let deflated =
+        SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    zip.start_file("array/zarr.json", deflated)?;
+    zip.write_all(br#"{"node_type":"array"}"#)?;
+    for i in 0..100 {
+        zip.start_file(format!("array/c/{i}"), stored)?;
+        zip.write_all(&[u8::try_from(i)?; 1000])?;
+    }
+    let bytes = zip.finish()?.into_inner();
+
+    let memory_store = Arc::new(MemoryStore::new());
+    memory_store.set(&StoreKey::new("test.zip")?, bytes.into())?;
+    let store = Arc::new(PerformanceMetricsStorageAdapter::new(memory_store));
+    let zip_store = ZipStorageAdapter::new(store.clone(), StoreKey::new("test.zip")?)?;
+
+    // Reading 100 sequential stored chunks reads their local file headers in one request and their data in another
+    let key_ranges: Vec<(StoreKey, Vec<ByteRange>)> = (0..100)
+        .map(|i| {
+            let ranges = vec![
+                ByteRange::FromStart(0, None),
+                ByteRange::FromStart(10, Some(5)),
+            ];
+            Ok((StoreKey::new(format!("array/c/{i}"))?, ranges))
+        })
+        .collect::<Result<_, Box<dyn Error>>>()?;
+    let reads = store.reads();
+    let values = zip_store.get_partial_many_keys(&key_ranges)?;
+    assert_eq!(store.reads() - reads, 2);
+    assert_eq!(values.len(), 100);
+    for (i, values) in values.into_iter().enumerate() {
+        let values = values.unwrap();
+        assert_eq!(values[0], &[u8::try_from(i)?; 1000][..]);
+        assert_eq!(values[1], &[u8::try_from(i)?; 5][..]);
+    }
+
+    // Compressed entries and missing keys are read individually, in the order requested
+    let key_ranges = vec![
+        (StoreKey::new("array/c/1")?, vec![ByteRange::Suffix(2)]),
+        (
+            StoreKey::new("array/zarr.json")?,
+            vec![ByteRange::FromStart(2, Some(9))],
+        ),
+        (
+            StoreKey::new("missing")?,
+            vec![ByteRange::FromStart(0, None)],
+        ),
+    ];
+    let values = zip_store.get_partial_many_keys(&key_ranges)?;
+    assert_eq!(values[0].as_deref(), Some(&[vec![1, 1].into()][..]));
+    assert_eq!(values[1].as_deref(), Some(&["node_type".into()][..]));
+    assert_eq!(values[2], None);
+
+    // An out of bounds byte range is an error
+    let key_ranges = vec![(StoreKey::new("array/c/1")?, vec![ByteRange::Suffix(1001)])];
+    assert!(zip_store.get_partial_many_keys(&key_ranges).is_err());
+
+    // `get_many` reads stored entries together
+    let zip_store = ZipStorageAdapter::new(store.clone(), StoreKey::new("test.zip")?)?;
+    let keys: Vec<StoreKey> = (0..100)
+        .map(|i| StoreKey::new(format!("array/c/{i}")))
+        .collect::<Result<_, _>>()?;
+    let reads = store.reads();
+    let values = zip_store.get_many(&keys)?;
+    assert_eq!(store.reads() - reads, 2);
+    assert_eq!(values[99].as_deref(), Some(&[99; 1000][..]));
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_prefetch() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::storage_adapter::performance_metrics::PerformanceMetricsStorageAdapter;
+
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let stored = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    let deflated =
+        SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    for i in 0..100 {
+        let options = if i % 2 == 0 { stored } else { deflated };
+        zip.start_file(format!("c/{i}"), options)?;
+        zip.write_all(&[u8::try_from(i)?; 1000])?;
+    }
+    let bytes = zip.finish()?.into_inner();
+
+    let memory_store = Arc::new(MemoryStore::new());
+    memory_store.set(&StoreKey::new("test.zip")?, bytes.into())?;
+    let store = Arc::new(PerformanceMetricsStorageAdapter::new(memory_store));
+    let keys: Vec<StoreKey> = (0..50)
+        .map(|i| StoreKey::new(format!("c/{i}")))
+        .collect::<Result<_, _>>()?;
+
+    // Prefetching reads the stored and compressed entries in a single request, and reads of them do not read storage
+    let zip_store =
+        ZipStorageAdapter::new_with_cache(store.clone(), StoreKey::new("test.zip")?, 1024 * 1024)?;
+    let reads = store.reads();
+    zip_store.prefetch(&keys)?;
+    assert_eq!(store.reads() - reads, 1);
+    let reads = store.reads();
+    for (i, key) in keys.iter().enumerate() {
+        assert_eq!(zip_store.get(key)?.unwrap(), &[u8::try_from(i)?; 1000][..]);
+    }
+    assert_eq!(store.reads(), reads);
+    zip_store.prefetch(&keys)?;
+    assert_eq!(store.reads(), reads);
+
+    // Values that do not fit in the cache are evicted as usual
+    let zip_store =
+        ZipStorageAdapter::new_with_cache(store.clone(), StoreKey::new("test.zip")?, 10_000)?;
+    zip_store.prefetch(&keys)?;
+    let reads = store.reads();
+    assert_eq!(zip_store.get(&keys[49])?.unwrap(), &[49; 1000][..]);
+    assert_eq!(store.reads(), reads);
+    assert_eq!(zip_store.get(&keys[0])?.unwrap(), &[0; 1000][..]);
+    assert_eq!(store.reads() - reads, 1);
+
+    // Without a cache, only the local file headers are read
+    let zip_store = ZipStorageAdapter::new(store.clone(), StoreKey::new("test.zip")?)?;
+    zip_store.prefetch(&keys)?;
+    let reads = store.reads();
+    assert_eq!(zip_store.get(&keys[0])?.unwrap(), &[0; 1000][..]);
+    assert_eq!(store.reads() - reads, 1);
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_unsafe_entry_name() -> Result<(), Box<dyn Error>> {
+    use zarrs_zip::{
+        UnsafeEntryNamePolicy, ZipStorageAdapterBuilder, ZipStorageAdapterCreateError,
+    };
+
+    let store = Arc::new(MemoryStore::new());
+    for (i, name) in ["../../etc/passwd", "a/../../b", "/etc/passwd", "C:/b"]
+        .into_iter()
+        .enumerate()
+    {
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        zip.start_file("zarr.json", SimpleFileOptions::default())?;
+        zip.write_all(b"{}")?;
+        zip.start_file(name, SimpleFileOptions::default())?;
+        zip.write_all(b"unsafe")?;
+        let key = StoreKey::new(format!("test{i}.zip"))?;
+        store.set(&key, zip.finish()?.into_inner().into())?;
+
+        // An entry with an unsafe name is rejected by default
+        let result = ZipStorageAdapter::new(store.clone(), key.clone());
+        assert!(
+            matches!(result, Err(ZipStorageAdapterCreateError::UnsafeEntryName(ref n)) if n == name)
+        );
+
+        // Or skipped
+        let zip_store = ZipStorageAdapterBuilder::new(store.clone(), key)
+            .unsafe_entry_name_policy(UnsafeEntryNamePolicy::Skip)
+            .build()?;
+        assert_eq!(zip_store.list()?, &["zarr.json".try_into()?]);
+    }
+
+    // Names with `..` within a component are safe
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    zip.start_file("a/..b/c..", SimpleFileOptions::default())?;
+    zip.write_all(b"safe")?;
+    let key = StoreKey::new("safe.zip")?;
+    store.set(&key, zip.finish()?.into_inner().into())?;
+    let zip_store = ZipStorageAdapter::new(store.clone(), key)?;
+    assert_eq!(
+        zip_store.get(&"a/..b/c..".try_into()?)?.unwrap(),
+        b"safe"[..]
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_large_index() -> Result<(), Box<dyn Error>> {
+    use zarrs_storage::Bytes;
+    use zarrs_zip::{ZipStorageAdapterBuilder, ZipStorageAdapterCreateError};
+
+    // Enough entries that the index is built in parallel with the `rayon` feature
+    const NUM_ENTRIES: usize = 70_000;
+    let name = |i: usize| format!("c/{}/{}", i % 100, i / 100);
+    let write_zip = |unsafe_names: &[(usize, &str)]| -> Result<Bytes, Box<dyn Error>> {
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let options =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        zip.add_directory("c/5/", options)?;
+        for i in 0..NUM_ENTRIES {
+            if let Some((_, unsafe_name)) = unsafe_names.iter().find(|(j, _)| *j == i) {
+                zip.start_file(*unsafe_name, options)?;
+            }
+            // Write the entries out of order
+            zip.start_file(name(i), options)?;
+        }
+        zip.start_file("C/0/1", options)?;
+        zip.write_all(b"upper")?;
+        Ok(zip.finish()?.into_inner().into())
+    };
+    let store = Arc::new(MemoryStore::new());
+    let key = StoreKey::new("test.zip")?;
+    store.set(&key, write_zip(&[])?)?;
+
+    let zip_store = ZipStorageAdapter::new(store.clone(), key.clone())?;
+    let mut expected: Vec<String> = (0..NUM_ENTRIES).map(name).collect();
+    expected.push("C/0/1".to_string());
+    expected.sort();
+    let keys = zip_store.list()?;
+    assert!(keys.iter().map(StoreKey::as_str).eq(expected.iter()));
+    assert_eq!(
+        zip_store.list_dir(&"c/5/".try_into()?)?.keys().len(),
+        NUM_ENTRIES / 100
+    );
+
+    // Keys that differ only by case are ambiguous, the last entry wins
+    let zip_store = ZipStorageAdapterBuilder::new(store.clone(), key.clone())
+        .case_insensitive(true)
+        .build()?;
+    assert_eq!(zip_store.list()?.len(), NUM_ENTRIES);
+    assert_eq!(zip_store.get(&"c/0/1".try_into()?)?.unwrap(), b"upper"[..]);
+
+    // The first invalid entry name is reported
+    store.set(&key, write_zip(&[(40_000, "../a"), (60_000, "/b")])?)?;
+    let result = ZipStorageAdapter::new(store.clone(), key);
+    assert!(
+        matches!(result, Err(ZipStorageAdapterCreateError::UnsafeEntryName(ref n)) if n == "../a")
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zip_truncated_archive() -> Result<(), Box<dyn Error>> {
+    use zarrs_zip::ZipStorageAdapterCreateError;
+
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    for i in 0..10 {
+        zip.start_file(format!("c/{i}"), options)?;
+        zip.write_all(&[u8::try_from(i).unwrap(); 1000])?;
+    }
+    let bytes = zip.finish()?.into_inner();
+
+    // A zip file cut short, as by an incomplete download, has no end of central directory record
+    let store = Arc::new(MemoryStore::new());
+    let key = StoreKey::new("test.zip")?;
+    let available = bytes.len() * 4 / 5;
+    store.set(&key, bytes[..available].to_vec().into())?;
+    let result = ZipStorageAdapter::new(store.clone(), key.clone());
+    let Err(ZipStorageAdapterCreateError::TruncatedArchive {
+        expected,
+        available: actual,
+    }) = result
+    else {
+        panic!("expected a truncated archive error");
+    };
+    assert_eq!(actual, available as u64);
+    assert!(expected > actual);
+
+    // A ZIP64 end of central directory record past the end of the file, which is requested from its offset
+    store.set(&key, zip64_end_records(0, 0, 0, 1000).into())?;
+    let result = ZipStorageAdapter::new(store.clone(), key);
+    assert!(matches!(
+        result,
+        Err(ZipStorageAdapterCreateError::TruncatedArchive {
+            expected,
+            available: 98
+        }) if expected > 1000
+    ));
 
     Ok(())
 }